@@ -0,0 +1,3 @@
+-- Optional gzip-compressed copy of the timekpr userinfo snapshot.
+-- When set it takes precedence over the plain-text last_config column.
+ALTER TABLE managed_users ADD COLUMN last_config_gz BLOB;
@@ -0,0 +1,21 @@
+-- One-off daily limit for a specific date (e.g. holidays).
+-- Replaces the weekly hours for that date.
+CREATE TABLE IF NOT EXISTS user_limit_override (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    date DATE NOT NULL,
+    hours REAL NOT NULL CHECK (hours >= 0 AND hours <= 24),
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE,
+    UNIQUE(user_id, date)
+);
+
+-- Extra minutes granted every week on a given day (timekpr numbering, 1 = Monday).
+-- Added on top of the weekly hours or a date override.
+CREATE TABLE IF NOT EXISTS user_recurring_bonus (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    day_of_week INTEGER NOT NULL CHECK (day_of_week BETWEEN 1 AND 7),
+    minutes INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE,
+    UNIQUE(user_id, day_of_week)
+);
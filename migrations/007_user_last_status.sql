@@ -0,0 +1,2 @@
+-- Outcome of the most recent validation: ok, offline, invalid_user or timekpr_missing
+ALTER TABLE managed_users ADD COLUMN last_status TEXT;
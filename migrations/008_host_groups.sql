@@ -0,0 +1,5 @@
+-- Additional host endpoints for the same logical user (e.g. a laptop seen on several IPs).
+-- Points at the primary managed_users row; NULL for primary/standalone users.
+ALTER TABLE managed_users ADD COLUMN host_group_id INTEGER REFERENCES managed_users (id);
+
+CREATE INDEX IF NOT EXISTS idx_managed_users_host_group ON managed_users (host_group_id);
@@ -0,0 +1,2 @@
+-- Optional note explaining a queued time adjustment ("homework finished", "sick day")
+ALTER TABLE managed_users ADD COLUMN pending_time_reason TEXT;
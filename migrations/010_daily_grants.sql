@@ -0,0 +1,9 @@
+-- Seconds added with manual '+' adjustments per user and local day, for the daily grant cap
+CREATE TABLE IF NOT EXISTS user_daily_grant (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    date DATE NOT NULL,
+    seconds INTEGER NOT NULL DEFAULT 0,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE,
+    UNIQUE(user_id, date)
+);
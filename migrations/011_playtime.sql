@@ -0,0 +1,8 @@
+-- timekpr PlayTime settings per user, stored as JSON and pushed by the scheduler
+CREATE TABLE IF NOT EXISTS user_playtime (
+    user_id INTEGER PRIMARY KEY,
+    settings TEXT NOT NULL,
+    is_synced BOOLEAN NOT NULL DEFAULT 0,
+    last_synced DATETIME,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE
+);
@@ -0,0 +1,2 @@
+-- Failed validations in a row; reset by the next successful check
+ALTER TABLE managed_users ADD COLUMN consecutive_failures INTEGER NOT NULL DEFAULT 0;
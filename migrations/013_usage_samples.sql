@@ -0,0 +1,11 @@
+-- Optional intraday usage: TIME_SPENT_DAY as seen on each scheduler pass
+CREATE TABLE IF NOT EXISTS user_usage_sample (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    sampled_at DATETIME NOT NULL,
+    time_spent INTEGER NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_usage_sample_user_time ON user_usage_sample (user_id, sampled_at);
+CREATE INDEX IF NOT EXISTS idx_user_usage_sample_time ON user_usage_sample (sampled_at);
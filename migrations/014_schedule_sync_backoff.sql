@@ -0,0 +1,3 @@
+-- Backoff for failed background schedule syncs; reset on success or when the schedule changes
+ALTER TABLE user_weekly_schedule ADD COLUMN sync_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_weekly_schedule ADD COLUMN next_sync_attempt TIMESTAMP;
@@ -0,0 +1,2 @@
+-- Where to find timekpra on the host when it is not on the SSH session's PATH; NULL means `timekpra`
+ALTER TABLE managed_users ADD COLUMN timekpr_path TEXT;
@@ -0,0 +1,4 @@
+-- Commands run instead of timekpra for hosts with a wrapped or patched install; NULL means the
+-- built-in `timekpra --userinfo` / `timekpra --settimeleft`
+ALTER TABLE managed_users ADD COLUMN validate_command_template TEXT;
+ALTER TABLE managed_users ADD COLUMN modify_command_template TEXT;
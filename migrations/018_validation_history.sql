@@ -0,0 +1,12 @@
+-- Outcome of each check of a host, newest kept up to a per-host cap
+CREATE TABLE IF NOT EXISTS user_validation_result (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    checked_at DATETIME NOT NULL,
+    status TEXT NOT NULL,
+    error TEXT,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_user_validation_result_user_time ON user_validation_result (user_id, checked_at);
+CREATE INDEX IF NOT EXISTS idx_user_validation_result_time ON user_validation_result (checked_at);
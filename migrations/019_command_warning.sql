@@ -0,0 +1,7 @@
+-- Latest text a successful timekpr command printed on stderr, e.g. a deprecation notice
+CREATE TABLE IF NOT EXISTS user_command_warning (
+    user_id INTEGER PRIMARY KEY,
+    warning TEXT NOT NULL,
+    recorded_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE
+);
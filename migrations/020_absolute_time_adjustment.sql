@@ -0,0 +1,5 @@
+-- Queued absolute sets ('='). pending_time_operation only allows '+' and '-', and widening
+-- that CHECK means rebuilding managed_users, which can't be done with foreign keys on
+-- without cascading to every table referencing it. A set is stored as '+' with this flag
+-- and read back as '='.
+ALTER TABLE managed_users ADD COLUMN pending_time_absolute BOOLEAN NOT NULL DEFAULT 0;
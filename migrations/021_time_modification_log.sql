@@ -0,0 +1,13 @@
+-- Every time modification as it was requested, whether applied right away or queued
+CREATE TABLE IF NOT EXISTS time_modification_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    operation TEXT NOT NULL,
+    seconds INTEGER NOT NULL,
+    reason TEXT,
+    applied BOOLEAN NOT NULL,
+    recorded_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_time_modification_log_user_time ON time_modification_log (user_id, recorded_at);
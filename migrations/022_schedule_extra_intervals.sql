@@ -0,0 +1,3 @@
+-- Allowed-hours intervals after each day's first one, as a JSON object of day name to
+-- intervals; NULL when every day has a single interval
+ALTER TABLE user_weekly_schedule ADD COLUMN extra_intervals TEXT;
@@ -0,0 +1,4 @@
+-- Day whose limit the last push gave that weekday, and the hours pushed for it, so the
+-- schedule is pushed again once that day's override or bonus no longer applies
+ALTER TABLE user_weekly_schedule ADD COLUMN synced_day DATE;
+ALTER TABLE user_weekly_schedule ADD COLUMN synced_day_hours REAL;
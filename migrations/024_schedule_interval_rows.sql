@@ -0,0 +1,75 @@
+-- One row per allowed-hours interval, replacing the first interval kept on
+-- user_weekly_schedule and the JSON of further ones. The old hour/minute table was never
+-- written, and its UNIQUE(user_id, day_of_week) allowed a single interval per day.
+DROP TABLE IF EXISTS user_daily_time_interval;
+
+CREATE TABLE user_daily_time_interval (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INTEGER NOT NULL,
+    day_of_week INTEGER NOT NULL CHECK(day_of_week >= 1 AND day_of_week <= 7),
+    start_time TEXT NOT NULL,
+    end_time TEXT NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES managed_users (id) ON DELETE CASCADE,
+    UNIQUE(user_id, day_of_week, start_time)
+);
+
+-- Each day's first interval, then the ones stored as JSON
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 1, COALESCE(monday_start_time, '00:00'), COALESCE(monday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 2, COALESCE(tuesday_start_time, '00:00'), COALESCE(tuesday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 3, COALESCE(wednesday_start_time, '00:00'), COALESCE(wednesday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 4, COALESCE(thursday_start_time, '00:00'), COALESCE(thursday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 5, COALESCE(friday_start_time, '00:00'), COALESCE(friday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 6, COALESCE(saturday_start_time, '00:00'), COALESCE(saturday_end_time, '23:59')
+FROM user_weekly_schedule;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT user_id, 7, COALESCE(sunday_start_time, '00:00'), COALESCE(sunday_end_time, '23:59')
+FROM user_weekly_schedule;
+
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 1, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.monday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 2, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.tuesday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 3, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.wednesday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 4, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.thursday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 5, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.friday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 6, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.saturday') i;
+INSERT OR IGNORE INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+SELECT s.user_id, 7, json_extract(i.value, '$.start_time'), json_extract(i.value, '$.end_time')
+FROM user_weekly_schedule s, json_each(s.extra_intervals, '$.sunday') i;
+
+ALTER TABLE user_weekly_schedule DROP COLUMN monday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN monday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN tuesday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN tuesday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN wednesday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN wednesday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN thursday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN thursday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN friday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN friday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN saturday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN saturday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN sunday_start_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN sunday_end_time;
+ALTER TABLE user_weekly_schedule DROP COLUMN extra_intervals;
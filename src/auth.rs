@@ -1,13 +1,28 @@
 use actix_web::{HttpRequest, Result as ActixResult};
+use argon2::password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
 use chrono::{Duration, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize};
 
+/// What a token may be used for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenScope {
+    /// Everything; also what tokens issued before scopes existed get
+    #[default]
+    Admin,
+    /// Reading the dashboard, stats and usage only, e.g. for a wall display
+    Readonly,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String, // Subject (username)
     pub exp: usize,  // Expiration time
     pub iat: usize,  // Issued at
+    #[serde(default)]
+    pub scope: TokenScope,
 }
 
 #[derive(Clone)]
@@ -25,13 +40,32 @@ impl JwtManager {
     }
 
     pub fn generate_token(&self, username: &str) -> Result<String, jsonwebtoken::errors::Error> {
+        // 24 hour expiration
+        self.issue(username, TokenScope::Admin, Duration::hours(24))
+    }
+
+    /// Long-lived token limited to the read-only routes
+    pub fn generate_readonly_token(
+        &self,
+        username: &str,
+        expires_in: Duration,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
+        self.issue(username, TokenScope::Readonly, expires_in)
+    }
+
+    fn issue(
+        &self,
+        username: &str,
+        scope: TokenScope,
+        expires_in: Duration,
+    ) -> Result<String, jsonwebtoken::errors::Error> {
         let now = Utc::now();
-        let expires_in = Duration::hours(24); // 24 hour expiration
 
         let claims = Claims {
             sub: username.to_string(),
             exp: (now + expires_in).timestamp() as usize,
             iat: now.timestamp() as usize,
+            scope,
         };
 
         encode(&Header::default(), &claims, &self.encoding_key)
@@ -46,19 +80,30 @@ impl JwtManager {
 }
 
 pub fn extract_token_from_header(req: &HttpRequest) -> Option<String> {
-    let auth_header = req
-        .headers()
-        .get("Authorization")?
-        .to_str()
-        .ok()?
-        .strip_prefix("Bearer ")?;
-
-    // Handle case where token accidentally starts with "bearer " due to Swagger UI bug
-    if auth_header.starts_with("bearer ") {
-        Some(auth_header.strip_prefix("bearer ")?.to_string())
-    } else {
-        Some(auth_header.to_string())
+    let auth_header = req.headers().get("Authorization")?.to_str().ok()?;
+    normalize_bearer_token(auth_header).map(str::to_string)
+}
+
+/// Token from an Authorization header value. The scheme is matched case-insensitively and
+/// repeated prefixes are removed, since Swagger UI sends "Bearer bearer <token>" when the
+/// token is pasted with its scheme. None when no token remains.
+pub fn normalize_bearer_token(header: &str) -> Option<&str> {
+    let mut rest = header.trim();
+    let mut has_scheme = false;
+
+    while let Some((scheme, token)) = rest.split_once(char::is_whitespace) {
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            break;
+        }
+        has_scheme = true;
+        rest = token.trim_start();
     }
+
+    // A bare token or one containing spaces is not a bearer credential
+    if !has_scheme || rest.is_empty() || rest.contains(char::is_whitespace) {
+        return None;
+    }
+    Some(rest)
 }
 
 pub fn verify_jwt(req: &HttpRequest, jwt_manager: &JwtManager) -> ActixResult<Claims> {
@@ -70,3 +115,50 @@ pub fn verify_jwt(req: &HttpRequest, jwt_manager: &JwtManager) -> ActixResult<Cl
         Err(_) => Err(actix_web::error::ErrorUnauthorized("Invalid token")),
     }
 }
+
+/// Target argon2 cost for admin password hashes. Defaults to the argon2 crate
+/// defaults; ARGON2_MEMORY_KIB, ARGON2_ITERATIONS and ARGON2_PARALLELISM raise it.
+pub fn password_hash_params() -> Params {
+    let cost = |name: &str, default: u32| {
+        std::env::var(name)
+            .ok()
+            .and_then(|value| value.parse::<u32>().ok())
+            .unwrap_or(default)
+    };
+
+    Params::new(
+        cost("ARGON2_MEMORY_KIB", Params::DEFAULT_M_COST),
+        cost("ARGON2_ITERATIONS", Params::DEFAULT_T_COST),
+        cost("ARGON2_PARALLELISM", Params::DEFAULT_P_COST),
+        None,
+    )
+    .unwrap_or_else(|e| {
+        eprintln!("Invalid argon2 cost configuration ({}), using defaults", e);
+        Params::default()
+    })
+}
+
+pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, password_hash_params());
+    Ok(argon2
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+/// Whether a stored hash was created with a weaker algorithm or cost than the current target
+pub fn needs_rehash(hash: &PasswordHash) -> bool {
+    if hash.algorithm != Algorithm::Argon2id.ident() {
+        return true;
+    }
+
+    let target = password_hash_params();
+    match Params::try_from(hash) {
+        Ok(params) => {
+            params.m_cost() < target.m_cost()
+                || params.t_cost() < target.t_cost()
+                || params.p_cost() < target.p_cost()
+        }
+        Err(_) => true,
+    }
+}
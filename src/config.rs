@@ -15,43 +15,145 @@ use utoipa::OpenApi;
         crate::handlers::auth::login_api,
         crate::handlers::auth::logout_api,
         crate::handlers::auth::change_password_api,
+        crate::handlers::auth::create_readonly_token,
         crate::handlers::dashboard::dashboard_api,
+        crate::handlers::dashboard::refresh_dashboard,
         crate::handlers::dashboard::admin_api,
+        crate::handlers::dashboard::get_stats,
         crate::handlers::users::add_user_api,
         crate::handlers::users::validate_user,
+        crate::handlers::users::update_user,
         crate::handlers::users::delete_user,
+        crate::handlers::users::bulk_delete_users,
+        crate::handlers::users::get_user_detail,
+        crate::handlers::users::attach_host,
+        crate::handlers::users::get_validation_history,
+        crate::handlers::users::set_timekpr_path,
+        crate::handlers::users::set_command_templates,
         crate::handlers::time::modify_time,
+        crate::handlers::time::set_time_limit,
+        crate::handlers::time::get_modification_history,
+        crate::handlers::time::adjustment_preview,
         crate::handlers::time::get_user_usage,
+        crate::handlers::time::snapshot_usage,
+        crate::handlers::time::get_intraday_usage,
+        crate::handlers::time::get_usage_aggregate,
         crate::handlers::schedule::update_schedule_api,
+        crate::handlers::schedule::validate_schedule,
         crate::handlers::schedule::get_schedule_sync_status,
+        crate::handlers::schedule::get_intervals_sync_status,
+        crate::handlers::schedule::sync_schedule,
+        crate::handlers::schedule::sync_schedule_aspect,
+        crate::handlers::schedule::clear_allowed_hours,
+        crate::handlers::schedule::timekpr_export,
+        crate::handlers::schedule::get_user_intervals,
+        crate::handlers::schedule::update_user_intervals,
+        crate::handlers::schedule::extend_today,
+        crate::handlers::schedule::import_timekpr,
+        crate::handlers::schedule::block_days,
+        crate::handlers::schedule::get_allowed_days,
+        crate::handlers::schedule::get_user_lockout,
+        crate::handlers::schedule::update_user_lockout,
+        crate::handlers::settings::update_week_start,
         crate::handlers::system::get_task_status,
-        crate::handlers::system::get_ssh_status
+        crate::handlers::system::run_scheduler_pass,
+        crate::handlers::system::get_capabilities,
+        crate::handlers::system::get_recent_events,
+        crate::handlers::system::get_ssh_status,
+        crate::handlers::system::get_ssh_key_candidates,
+        crate::handlers::system::fix_ssh_key_permissions,
+        crate::handlers::system::health_live,
+        crate::handlers::system::health_ready,
+        crate::handlers::system::health_detailed,
+        crate::handlers::settings::update_protect_observability,
+        crate::handlers::settings::update_default_schedule,
+        crate::handlers::settings::update_check_interval,
+        crate::handlers::settings::update_scheduler_window,
+        crate::handlers::settings::update_timezone,
+        crate::handlers::settings::list_timezones,
+        crate::handlers::settings::update_grant_cap,
+        crate::handlers::settings::update_revalidate_after_modify
     ),
     components(
         schemas(
             LoginForm,
+            ReadonlyTokenForm,
             AddUserForm,
+            UpdateUserForm,
+            UnreachableOnAdd,
+            AttachHostForm,
+            TimekprPathForm,
+            CommandTemplatesForm,
+            BulkDeleteForm,
+            PendingOnDelete,
             ModifyTimeForm,
+            SetTimeLimitForm,
             PasswordChangeForm,
             ScheduleUpdateForm,
+            IntervalsUpdateForm,
+            WeekStartForm,
+            ProtectObservabilityForm,
+            DefaultScheduleForm,
+            CheckIntervalForm,
+            SchedulerWindowForm,
+            TimezoneForm,
+            GrantCapForm,
+            RevalidateAfterModifyForm,
             ManagedUser,
             ApiResponse,
             LoginResponse,
             UserData,
+            DashboardStatus,
             DashboardResponse,
             AdminUserData,
+            UserStatus,
+            ValidationResult,
+            TimeModificationLogEntry,
             AdminResponse,
+            BulkDeleteResult,
+            BulkDeleteResponse,
             ModifyTimeResponse,
+            AddUserResponse,
+            AddUserValidation,
             UsageData,
+            UsageArrays,
+            UsageFormat,
             UsageResponse,
+            UsageUnit,
+            UsageGranularity,
+            UsageBucket,
+            AggregatedUsageResponse,
             TaskStatusData,
             TaskStatusResponse,
             ScheduleWithIntervals,
             WeeklyHours,
+            PlayTimeSettings,
+            LockoutAction,
+            LockoutSettings,
+            AllowedDay,
+            ExtendTodayForm,
+            TimekprImportForm,
+            BlockDaysForm,
+            PlayTimeActivity,
             WeeklyTimeIntervals,
+            FirstIntervals,
             TimeInterval,
+            DayOfWeek,
+            WeekStart,
+            SyncAspect,
+            DaySyncResult,
             ScheduleSyncResponse,
+            ScheduleValidationResponse,
+            IntervalsSyncResponse,
+            TimekprDayExport,
+            TimekprExport,
+            SchedulerPassSummary,
+            CapabilitiesResponse,
+            Event,
+            EventKind,
+            RecentEventsResponse,
             SshStatusResponse,
+            SshKeyCandidate,
             ErrorResponse
         )
     )
@@ -0,0 +1,34 @@
+use sqlx::sqlite::SqlitePoolOptions;
+use std::time::Duration;
+
+/// How long a connection may sit idle in the pool before it is closed
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+/// How long a connection is used before the pool replaces it
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(30 * 60);
+
+/// Seconds from the environment variable `name`: `default` when unset or invalid, no limit for 0
+fn duration_setting(name: &str, default: Duration) -> Option<Duration> {
+    match std::env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+    {
+        Some(0) => None,
+        Some(seconds) => Some(Duration::from_secs(seconds)),
+        None => Some(default),
+    }
+}
+
+/// Pool options that recycle connections, configured with `DB_IDLE_TIMEOUT_SECS` and
+/// `DB_MAX_LIFETIME_SECS`. A long-running instance then does not keep connections that
+/// went bad or still point at a database file that was replaced on disk.
+pub fn pool_options() -> SqlitePoolOptions {
+    SqlitePoolOptions::new()
+        .idle_timeout(duration_setting(
+            "DB_IDLE_TIMEOUT_SECS",
+            DEFAULT_IDLE_TIMEOUT,
+        ))
+        .max_lifetime(duration_setting(
+            "DB_MAX_LIFETIME_SECS",
+            DEFAULT_MAX_LIFETIME,
+        ))
+}
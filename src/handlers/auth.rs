@@ -1,11 +1,16 @@
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{http::header, web, HttpResponse, Result};
 use serde_json;
 use utoipa;
 
-use crate::auth::JwtManager;
-use crate::middleware::auth::authenticate_request;
-use crate::models::{ApiResponse, LoginForm, LoginResponse, PasswordChangeForm, ServiceError, SettingsEntry};
+use crate::auth::{hash_password, needs_rehash, JwtManager};
+use crate::http_cache::NO_STORE;
+use crate::middleware::auth::{authenticate_request, authenticated_claims};
+use crate::models::{
+    ApiResponse, LoginForm, LoginResponse, PasswordChangeForm, ReadonlyTokenForm, ServiceError,
+    SettingsEntry,
+};
 use crate::services::SettingsService;
+use chrono::Duration;
 
 #[utoipa::path(
     post,
@@ -26,35 +31,52 @@ pub async fn login_api(
         // Check admin password
         let admin_hash = settings_service.get_admin_password_hash().await;
 
-        match admin_hash {
-            Ok(Some(hash)) => {
-                use argon2::{Argon2, PasswordHash, PasswordVerifier};
-
-                if let Ok(parsed_hash) = PasswordHash::new(&hash) {
-                    if Argon2::default()
-                        .verify_password(form.password.as_bytes(), &parsed_hash)
-                        .is_ok()
-                    {
-                        // Generate JWT token
-                        match jwt_manager.generate_token(&form.username) {
-                            Ok(token) => {
-                                return Ok(HttpResponse::Ok().json(LoginResponse {
+        if let Ok(Some(hash)) = admin_hash {
+            use argon2::{Argon2, PasswordHash, PasswordVerifier};
+
+            if let Ok(parsed_hash) = PasswordHash::new(&hash) {
+                if Argon2::default()
+                    .verify_password(form.password.as_bytes(), &parsed_hash)
+                    .is_ok()
+                {
+                    // Upgrade hashes made with a weaker cost while the password is at hand
+                    if needs_rehash(&parsed_hash) {
+                        match hash_password(&form.password) {
+                            Ok(new_hash) => {
+                                if let Err(e) = settings_service
+                                    .set_value(SettingsEntry::ADMIN_PASSWORD_HASH, new_hash)
+                                    .await
+                                {
+                                    eprintln!("Failed to store upgraded password hash: {}", e);
+                                } else {
+                                    println!("Admin password hash upgraded to current argon2 cost");
+                                }
+                            }
+                            Err(e) => eprintln!("Failed to rehash admin password: {}", e),
+                        }
+                    }
+
+                    // Generate JWT token
+                    match jwt_manager.generate_token(&form.username) {
+                        Ok(token) => {
+                            // The body carries the token
+                            return Ok(HttpResponse::Ok()
+                                .insert_header((header::CACHE_CONTROL, NO_STORE))
+                                .json(LoginResponse {
                                     success: true,
                                     message: "Login successful".to_string(),
                                     token,
                                     expires_in: 24 * 3600, // 24 hours in seconds
                                 }));
-                            }
-                            Err(_) => {
-                                return Err(ServiceError::InternalError(
-                                    "Failed to generate token".to_string(),
-                                ));
-                            }
+                        }
+                        Err(_) => {
+                            return Err(ServiceError::InternalError(
+                                "Failed to generate token".to_string(),
+                            ));
                         }
                     }
                 }
             }
-            _ => {}
         }
     }
 
@@ -81,6 +103,56 @@ pub async fn logout_api() -> Result<HttpResponse, ServiceError> {
     }))
 }
 
+/// Lifetime of a read-only token when none is asked for
+const DEFAULT_READONLY_TOKEN_DAYS: i64 = 365;
+const MAX_READONLY_TOKEN_DAYS: i64 = 3650;
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/readonly-token",
+    request_body = ReadonlyTokenForm,
+    responses(
+        (status = 200, description = "Read-only token, limited to GET /api/dashboard, /api/stats and the usage endpoints", body = LoginResponse),
+        (status = 400, description = "Invalid lifetime", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn create_readonly_token(
+    form: web::Json<ReadonlyTokenForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Read-only tokens are turned away here, so they can't mint new ones
+    let claims = authenticated_claims(&req, &jwt_manager)?;
+
+    let days = form.days.unwrap_or(DEFAULT_READONLY_TOKEN_DAYS);
+    if !(1..=MAX_READONLY_TOKEN_DAYS).contains(&days) {
+        return Err(ServiceError::ValidationError(format!(
+            "days must be between 1 and {}",
+            MAX_READONLY_TOKEN_DAYS
+        )));
+    }
+
+    // There is no revocation list; changing JWT_SECRET invalidates every token
+    let token = jwt_manager
+        .generate_readonly_token(&claims.sub, Duration::days(days))
+        .map_err(|_| ServiceError::InternalError("Failed to generate token".to_string()))?;
+    println!(
+        "Read-only token issued by {} for {} day(s)",
+        claims.sub, days
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CACHE_CONTROL, NO_STORE))
+        .json(LoginResponse {
+            success: true,
+            message: format!("Read-only token valid for {} day(s)", days),
+            token,
+            expires_in: (days * 24 * 3600) as u64,
+        }))
+}
+
 #[utoipa::path(
     post,
     path = "/api/change-password",
@@ -88,7 +160,8 @@ pub async fn logout_api() -> Result<HttpResponse, ServiceError> {
     responses(
         (status = 200, description = "Password changed successfully", body = ApiResponse),
         (status = 400, description = "Invalid input", body = ErrorResponse),
-        (status = 401, description = "Authentication failed", body = ErrorResponse)
+        (status = 401, description = "Authentication failed", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn change_password_api(
@@ -97,11 +170,7 @@ pub async fn change_password_api(
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     // Validate inputs
     if form.current_password.is_empty()
@@ -138,25 +207,21 @@ pub async fn change_password_api(
                     .is_ok()
                 {
                     // Current password is correct, update to new password
-                    use argon2::password_hash::{rand_core::OsRng, SaltString};
-                    use argon2::PasswordHasher;
-
-                    let salt = SaltString::generate(&mut OsRng);
-                    let argon2 = Argon2::default();
-                    let new_password_hash =
-                        argon2.hash_password(form.new_password.as_bytes(), &salt);
+                    let new_password_hash = hash_password(&form.new_password);
 
                     match new_password_hash {
                         Ok(hash) => {
                             // Get the current admin password entry first
                             if let Ok(Some(admin_entry)) = settings_service
                                 .find_by_key(SettingsEntry::ADMIN_PASSWORD_HASH)
-                                .await 
+                                .await
                             {
-                                let result = settings_service.update_entry_value(
-                                    admin_entry.id,  // Use the actual ID
-                                    hash.to_string(),
-                                ).await;
+                                let result = settings_service
+                                    .update_entry_value(
+                                        admin_entry.id, // Use the actual ID
+                                        hash,
+                                    )
+                                    .await;
 
                                 match result {
                                     Ok(_) => {
@@ -2,36 +2,95 @@ use actix_web::{web, HttpResponse, Result};
 use utoipa;
 
 use crate::auth::JwtManager;
+use crate::http_cache::{cached_json, scheduler_max_age};
 use crate::middleware::auth::authenticate_request;
-use crate::models::{AdminResponse, DashboardResponse, ServiceError};
-use crate::services::UserService;
+use crate::models::{format_timestamp, AdminResponse, DashboardResponse, ServiceError};
+use crate::scheduler::BackgroundScheduler;
+use crate::services::{DashboardCache, ScheduleService, SettingsService, TimeService, UserService};
+use chrono::Utc;
 
 #[utoipa::path(
     get,
     path = "/api/dashboard",
     responses(
         (status = 200, description = "Dashboard data retrieved", body = DashboardResponse),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
         (status = 401, description = "Not authenticated", body = ErrorResponse)
     )
 )]
 pub async fn dashboard_api(
     user_service: web::Data<UserService>,
+    schedule_service: web::Data<ScheduleService>,
+    settings_service: web::Data<SettingsService>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated - valid JWT token required".to_string(),
-        ));
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Serve the scheduler's snapshot when there is one, otherwise query live
+    let (mut users, as_of) = match dashboard_cache.snapshot().await {
+        Some(snapshot) => (snapshot.users, snapshot.as_of),
+        None => (
+            DashboardCache::build(&user_service, &schedule_service).await?,
+            Utc::now(),
+        ),
+    };
+
+    // The countdown moves on between scheduler passes, so it is never cached
+    let seconds_until_reset = schedule_service.seconds_until_reset();
+    for user in &mut users {
+        user.seconds_until_reset = seconds_until_reset;
     }
 
+    cached_json(
+        &req,
+        &scheduler_max_age(settings_service.tick_interval().await),
+        &DashboardResponse {
+            success: true,
+            users,
+            as_of: format_timestamp(as_of),
+        },
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/dashboard/refresh",
+    responses(
+        (status = 200, description = "Every host checked live; rows whose host did not answer are marked stale", body = DashboardResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn refresh_dashboard(
+    scheduler: web::Data<BackgroundScheduler>,
+    schedule_service: web::Data<ScheduleService>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
     // Business logic delegation
-    let users = user_service.get_dashboard_users().await?;
+    let answered = scheduler.refresh_all().await?;
+    let snapshot = dashboard_cache.snapshot().await.ok_or_else(|| {
+        ServiceError::InternalError("Dashboard cache was not rebuilt".to_string())
+    })?;
+
+    let seconds_until_reset = schedule_service.seconds_until_reset();
+    let mut users = snapshot.users;
+    for user in &mut users {
+        user.seconds_until_reset = seconds_until_reset;
+        user.stale = !answered.contains(&user.id);
+    }
 
     Ok(HttpResponse::Ok().json(DashboardResponse {
         success: true,
         users,
+        as_of: format_timestamp(snapshot.as_of),
     }))
 }
 
@@ -40,7 +99,8 @@ pub async fn dashboard_api(
     path = "/api/admin",
     responses(
         (status = 200, description = "Admin user data retrieved", body = AdminResponse),
-        (status = 401, description = "Not authenticated", body = ErrorResponse)
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn admin_api(
@@ -49,11 +109,7 @@ pub async fn admin_api(
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated - valid JWT token required".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     // Business logic delegation
     let users = user_service.get_admin_users().await?;
@@ -63,3 +119,29 @@ pub async fn admin_api(
         users,
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/stats",
+    responses(
+        (status = 200, description = "User counts, screen time today and this week, busiest day and users close to their limit"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse)
+    )
+)]
+pub async fn get_stats(
+    time_service: web::Data<TimeService>,
+    settings_service: web::Data<SettingsService>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let week_start = settings_service.get_week_start().await?;
+    let stats = time_service.household_stats(week_start).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "stats": stats
+    })))
+}
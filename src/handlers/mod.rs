@@ -1,6 +1,7 @@
 pub mod auth;
 pub mod dashboard;
 pub mod schedule;
+pub mod settings;
 pub mod system;
 pub mod time;
 pub mod users;
@@ -9,6 +10,7 @@ pub mod users;
 pub use auth::*;
 pub use dashboard::*;
 pub use schedule::*;
+pub use settings::*;
 pub use system::*;
 pub use time::*;
 pub use users::*;
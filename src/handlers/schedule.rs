@@ -3,150 +3,134 @@ use serde_json;
 use utoipa;
 
 use crate::auth::JwtManager;
-use crate::middleware::auth::authenticate_request;
+use crate::middleware::auth::{authenticate_request, authenticated_claims};
 use crate::models::{
-    ScheduleUpdateForm, ServiceError, TimeInterval, WeeklyHours, WeeklyTimeIntervals,
+    format_timestamp, BlockDaysForm, DayOfWeek, ExtendTodayForm, FirstIntervals,
+    IntervalsSyncResponse, IntervalsUpdateForm, LockoutSettings, ScheduleUpdateForm,
+    ScheduleValidationResponse, ScheduleWithIntervals, ServiceError, SyncAspect, TimekprConfig,
+    TimekprImportForm,
 };
-use crate::services::ScheduleService;
+use crate::services::{DashboardCache, ScheduleService, SettingsService, UserService};
 
 #[utoipa::path(
     post,
     path = "/api/schedule/update",
     request_body = ScheduleUpdateForm,
     responses(
-        (status = 200, description = "Schedule updated successfully"),
+        (status = 200, description = "Schedule updated successfully; `warning` is set when the user has not passed validation, `warnings` lists days whose allowed hours are shorter than their limit"),
         (status = 400, description = "Invalid schedule values"),
-        (status = 401, description = "Not authenticated")
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Token is read-only"),
+        (status = 404, description = "User not found", body = ErrorResponse)
     )
 )]
 pub async fn update_schedule_api(
     schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
     form: web::Json<ScheduleUpdateForm>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication - only HTTP concern
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
+    let requested = form.hours();
     println!("Received schedule update: user_id={}, monday={}, tuesday={}, wednesday={}, thursday={}, friday={}, saturday={}, sunday={}",
-             form.user_id, form.monday, form.tuesday, form.wednesday, form.thursday, form.friday, form.saturday, form.sunday);
+             form.user_id, requested.monday, requested.tuesday, requested.wednesday, requested.thursday, requested.friday, requested.saturday, requested.sunday);
 
-    // Convert API model to domain model
-    let hours = WeeklyHours {
-        monday: form.monday,
-        tuesday: form.tuesday,
-        wednesday: form.wednesday,
-        thursday: form.thursday,
-        friday: form.friday,
-        saturday: form.saturday,
-        sunday: form.sunday,
-    };
+    // Reject bad PlayTime settings before anything is stored
+    if let Some(playtime) = &form.playtime {
+        playtime.validate().map_err(ServiceError::ValidationError)?;
+    }
+
+    // Missing days and minutes are checked here, the resulting hours by the service
+    let limit_errors = form.limit_errors();
+    if !limit_errors.is_empty() {
+        return Err(ServiceError::ValidationError(limit_errors.join("; ")));
+    }
 
-    // Check if time intervals are provided
-    let has_intervals = form.monday_start_time.is_some()
-        || form.tuesday_start_time.is_some()
-        || form.wednesday_start_time.is_some()
-        || form.thursday_start_time.is_some()
-        || form.friday_start_time.is_some()
-        || form.saturday_start_time.is_some()
-        || form.sunday_start_time.is_some();
-
-    if has_intervals {
-        // Build time intervals using TimeInterval::new for validation
-        let monday_interval = TimeInterval::new(
-            form.monday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.monday_end_time.clone().unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Monday interval: {}", e)))?;
-
-        let tuesday_interval = TimeInterval::new(
-            form.tuesday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.tuesday_end_time.clone().unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Tuesday interval: {}", e)))?;
-
-        let wednesday_interval = TimeInterval::new(
-            form.wednesday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.wednesday_end_time
-                .clone()
-                .unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Wednesday interval: {}", e)))?;
-
-        let thursday_interval = TimeInterval::new(
-            form.thursday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.thursday_end_time
-                .clone()
-                .unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Thursday interval: {}", e)))?;
-
-        let friday_interval = TimeInterval::new(
-            form.friday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.friday_end_time.clone().unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Friday interval: {}", e)))?;
-
-        let saturday_interval = TimeInterval::new(
-            form.saturday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.saturday_end_time
-                .clone()
-                .unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Saturday interval: {}", e)))?;
-
-        let sunday_interval = TimeInterval::new(
-            form.sunday_start_time
-                .clone()
-                .unwrap_or("00:00".to_string()),
-            form.sunday_end_time.clone().unwrap_or("23:59".to_string()),
-        )
-        .map_err(|e| ServiceError::ValidationError(format!("Sunday interval: {}", e)))?;
-
-        let intervals = WeeklyTimeIntervals {
-            monday: monday_interval,
-            tuesday: tuesday_interval,
-            wednesday: wednesday_interval,
-            thursday: thursday_interval,
-            friday: friday_interval,
-            saturday: saturday_interval,
-            sunday: sunday_interval,
-        };
+    // Saved anyway; a window shorter than the limit is valid for timekpr
+    let warnings = form.warnings();
 
+    // Convert API model to domain model
+    let hours = requested;
+    let intervals = form
+        .intervals()
+        .map_err(|errors| ServiceError::ValidationError(errors.join("; ")))?;
+
+    let warning = if let Some(intervals) = intervals {
         // Business logic delegation - service handles all business rules with intervals
         schedule_service
             .update_schedule_with_intervals(form.user_id, hours, intervals)
-            .await?;
+            .await?
     } else {
         // Business logic delegation - service handles all business rules (backward compatibility)
         schedule_service
             .update_schedule(form.user_id, hours)
+            .await?
+    };
+
+    let mut message = "Schedule updated successfully".to_string();
+    if let Some(playtime) = &form.playtime {
+        schedule_service
+            .update_playtime(form.user_id, playtime)
             .await?;
+
+        // Settings are kept either way; only hosts reporting PlayTime get them pushed
+        let supported = user_service
+            .find_by_id(form.user_id)
+            .await?
+            .and_then(|user| user.last_config)
+            .and_then(|config| config.parse::<TimekprConfig>().ok())
+            .is_none_or(|config| config.playtime_supported);
+        if !supported {
+            message.push_str(
+                ". PlayTime settings were saved, but timekpr on this host does not support PlayTime and they will not be applied until it is upgraded",
+            );
+        }
     }
 
+    dashboard_cache.invalidate().await;
+
     // Success response
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
-        "message": "Schedule updated successfully"
+        "message": message,
+        "warning": warning,
+        "warnings": warnings
     })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/schedule/validate",
+    request_body = ScheduleUpdateForm,
+    responses(
+        (status = 200, description = "Validation result; nothing is stored", body = ScheduleValidationResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn validate_schedule(
+    form: web::Json<ScheduleUpdateForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Same rules as /api/schedule/update, all reported at once
+    let errors = form.validation_errors();
+
+    Ok(HttpResponse::Ok().json(ScheduleValidationResponse {
+        success: true,
+        valid: errors.is_empty(),
+        errors,
+        warnings: form.warnings(),
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/api/schedule-sync-status/{id}",
@@ -155,26 +139,25 @@ pub async fn update_schedule_api(
     ),
     responses(
         (status = 200, description = "Schedule sync status retrieved", body = ScheduleSyncResponse),
-        (status = 401, description = "Not authenticated", body = ErrorResponse)
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn get_schedule_sync_status(
     schedule_service: web::Data<ScheduleService>,
+    settings_service: web::Data<SettingsService>,
     path: web::Path<i64>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     let user_id = path.into_inner();
 
     // Business logic delegation
     let sync_status = schedule_service.get_sync_status(user_id).await?;
+    let week_start = settings_service.get_week_start().await?;
 
     println!("Retrieved schedule sync status for user {}", user_id);
 
@@ -184,6 +167,630 @@ pub async fn get_schedule_sync_status(
         "is_synced": sync_status.is_synced,
         "schedule": sync_status.schedule,
         "last_synced": sync_status.last_synced,
-        "last_modified": sync_status.last_modified
+        "last_modified": sync_status.last_modified,
+        "sync_attempts": sync_status.sync_attempts,
+        "next_sync_attempt": sync_status.next_sync_attempt,
+        "needs_attention": sync_status.needs_attention,
+        "week_start": week_start,
+        "day_order": DayOfWeek::week(week_start)
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/intervals-sync-status/{id}",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Allowed-hours sync status retrieved", body = IntervalsSyncResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_intervals_sync_status(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    // Business logic delegation
+    let status = schedule_service.get_intervals_sync_status(user_id).await?;
+
+    Ok(HttpResponse::Ok().json(IntervalsSyncResponse {
+        success: true,
+        user_id,
+        username: user.username,
+        needs_sync: status.needs_sync,
+        last_synced: status.last_synced,
+        enabled_intervals: status.enabled_intervals,
+        total_intervals: status.total_intervals,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/sync/{aspect}",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("aspect" = SyncAspect, Path, description = "Schedule aspect to push: limits, hours, week or month")
+    ),
+    responses(
+        (status = 200, description = "Aspect pushed"),
+        (status = 202, description = "Host unreachable; the whole schedule was queued for the scheduler to push"),
+        (status = 400, description = "Invalid or unsupported aspect", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User or schedule not found", body = ErrorResponse)
+    )
+)]
+pub async fn sync_schedule_aspect(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<(i64, String)>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let (user_id, aspect) = path.into_inner();
+    let aspect: SyncAspect = aspect.parse().map_err(ServiceError::ValidationError)?;
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let outcome = schedule_service.sync_aspect(&user, aspect).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "aspect": aspect,
+        "message": outcome.message,
+        "days": outcome.days
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/sync",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Schedule pushed; a schedule that needed attention is synced again"),
+        (status = 202, description = "Host unreachable; the scheduler retries the schedule again"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User or schedule not found", body = ErrorResponse)
+    )
+)]
+pub async fn sync_schedule(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    dashboard_cache: web::Data<DashboardCache>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let result = schedule_service.sync_now(&user).await;
+    // Re-armed even when the push failed, so the dashboard flag goes away
+    dashboard_cache.invalidate().await;
+    let message = result?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": message
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/clear-hours",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "All days set to full-day access"),
+        (status = 202, description = "Host unreachable; the change was queued"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 409, description = "Host unreachable and no stored schedule to queue the change on", body = ErrorResponse)
+    )
+)]
+pub async fn clear_allowed_hours(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let outcome = schedule_service.clear_allowed_hours(&user).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": outcome.message,
+        "days": outcome.days
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/timekpr-export",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Stored schedule as timekpra arguments", body = TimekprExport),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User or schedule not found", body = ErrorResponse)
+    )
+)]
+pub async fn timekpr_export(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let export = schedule_service.timekpr_export(&user).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user_id": user.id,
+        "username": user.username,
+        "export": export
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/intervals",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Stored allowed hours: each day's first interval under `intervals`, every interval under `interval_lists`", body = WeeklyTimeIntervals),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_user_intervals(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let intervals = schedule_service.get_intervals(user.id).await?;
+    let status = schedule_service.get_intervals_sync_status(user.id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user_id": user.id,
+        "intervals": FirstIntervals::from(&intervals),
+        "interval_lists": intervals,
+        "needs_sync": status.needs_sync
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/intervals",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = IntervalsUpdateForm,
+    responses(
+        (status = 200, description = "Allowed hours stored and queued for sync; each day's first interval under `intervals`, every interval under `interval_lists`", body = WeeklyTimeIntervals),
+        (status = 400, description = "Invalid interval", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User or schedule not found", body = ErrorResponse)
+    )
+)]
+pub async fn update_user_intervals(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    form: web::Json<IntervalsUpdateForm>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+    let changes = form.changes().map_err(ServiceError::ValidationError)?;
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let updated_days = changes.len();
+    let intervals = schedule_service.update_intervals(&user, changes).await?;
+
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Allowed hours updated for {} day(s)", updated_days),
+        "user_id": user.id,
+        "intervals": FirstIntervals::from(&intervals),
+        "interval_lists": intervals,
+        "needs_sync": true
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/extend-today",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = ExtendTodayForm,
+    responses(
+        (status = 200, description = "Today's limit extended until the next daily reset and queued for sync"),
+        (status = 400, description = "Minutes out of range", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found or no schedule configured", body = ErrorResponse)
+    )
+)]
+pub async fn extend_today(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    dashboard_cache: web::Data<DashboardCache>,
+    path: web::Path<i64>,
+    form: web::Json<ExtendTodayForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication; the subject is kept for the audit log line
+    let claims = authenticated_claims(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    // Business logic delegation
+    let extension = schedule_service.extend_today(user.id, form.minutes).await?;
+    dashboard_cache.invalidate().await;
+
+    println!(
+        "AUDIT: {} extended today's limit of {} by {} minutes (until {})",
+        claims.sub,
+        user.username,
+        form.minutes,
+        format_timestamp(extension.expires_at)
+    );
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user_id": user.id,
+        "date": extension.date.to_string(),
+        "minutes": form.minutes,
+        "override_hours": extension.override_hours,
+        "limit_seconds": extension.limit_seconds,
+        "expires_at": format_timestamp(extension.expires_at),
+        "needs_sync": true
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/import-timekpr",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = TimekprImportForm,
+    responses(
+        (status = 200, description = "Schedule replaced by the imported one and marked synced; `warnings` lists what was not carried over exactly"),
+        (status = 400, description = "Config could not be parsed or read from the host", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn import_timekpr(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    dashboard_cache: web::Data<DashboardCache>,
+    path: web::Path<i64>,
+    form: web::Json<TimekprImportForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let (schedule, warnings) = schedule_service
+        .import_timekpr(&user, form.config.as_deref())
+        .await?;
+
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Imported timekpr schedule for {}", user.username),
+        "user_id": user.id,
+        "schedule": ScheduleWithIntervals::new(schedule.hours, schedule.intervals),
+        "warnings": warnings,
+        "is_synced": schedule.is_synced
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/block-days",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = BlockDaysForm,
+    responses(
+        (status = 200, description = "Days set to zero hours and queued for sync; returns the resulting schedule and allowed days"),
+        (status = 400, description = "No days given", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found or no schedule configured", body = ErrorResponse)
+    )
+)]
+pub async fn block_days(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    dashboard_cache: web::Data<DashboardCache>,
+    path: web::Path<i64>,
+    form: web::Json<BlockDaysForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    let schedule = schedule_service.block_days(&user, &form.days).await?;
+    // Allowed days as the next sync would push them
+    let days = schedule_service.allowed_days(user.id, None).await?;
+
+    dashboard_cache.invalidate().await;
+
+    let blocked = DayOfWeek::iter()
+        .filter(|day| form.days.contains(day))
+        .map(DayOfWeek::label)
+        .collect::<Vec<_>>();
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Blocked {} for {}", blocked.join(", "), user.username),
+        "user_id": user.id,
+        "schedule": ScheduleWithIntervals::new(schedule.hours, schedule.intervals),
+        "days": days,
+        "needs_sync": true
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/allowed-days",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Allowed and blocked days from the stored schedule, next to what the host last reported"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found or no schedule configured", body = ErrorResponse)
+    )
+)]
+pub async fn get_allowed_days(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+    let host_days = user
+        .last_config
+        .as_deref()
+        .and_then(|config| config.parse::<TimekprConfig>().ok())
+        .and_then(|config| config.allowed_weekdays());
+    let days = schedule_service.allowed_days(user.id, host_days).await?;
+
+    let names = |allowed: bool| {
+        days.iter()
+            .filter(|day| day.allowed == allowed)
+            .map(|day| day.day)
+            .collect::<Vec<DayOfWeek>>()
+    };
+    // Unknown until the host has reported its allowed days
+    let matches_host = days
+        .iter()
+        .map(|day| day.host_allowed.map(|host| host == day.allowed))
+        .collect::<Option<Vec<bool>>>()
+        .map(|matches| matches.into_iter().all(|matches| matches));
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user_id": user.id,
+        "username": user.username,
+        "allowed_days": names(true),
+        "blocked_days": names(false),
+        "matches_host": matches_host,
+        "days": days
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/lockout",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Stored lockout settings and what the host last reported"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_user_lockout(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+    let lockout = schedule_service.get_lockout(user.id).await?;
+    let config = user
+        .last_config
+        .as_deref()
+        .and_then(|config| config.parse::<TimekprConfig>().ok());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user_id": user.id,
+        "settings": lockout.as_ref().map(|lockout| &lockout.settings),
+        "is_synced": lockout.as_ref().map(|lockout| lockout.is_synced),
+        "last_synced": lockout.as_ref().and_then(|lockout| lockout.last_synced).map(format_timestamp),
+        "host_lockout_type": config.as_ref().and_then(|c| c.lockout_type.clone()),
+        "host_wakeup_hour_interval": config.as_ref().and_then(|c| c.wakeup_hour_interval.clone()),
+        // Unknown until the host has been checked once
+        "supported": config.as_ref().map(|c| c.lockout_supported())
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/lockout",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = LockoutSettings,
+    responses(
+        (status = 200, description = "Lockout settings stored and queued for sync"),
+        (status = 400, description = "Invalid lockout settings", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn update_user_lockout(
+    schedule_service: web::Data<ScheduleService>,
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    form: web::Json<LockoutSettings>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+    // Business logic delegation
+    schedule_service.update_lockout(user.id, &form).await?;
+
+    // Settings are kept either way; only hosts reporting a lockout type get them pushed
+    let mut message = "Lockout settings updated successfully".to_string();
+    let supported = user
+        .last_config
+        .as_deref()
+        .and_then(|config| config.parse::<TimekprConfig>().ok())
+        .is_none_or(|config| config.lockout_supported());
+    if !supported {
+        message.push_str(
+            ". timekpr on this host does not support lockout types; the settings will not be applied until it is upgraded",
+        );
+    }
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": message,
+        "user_id": user.id,
+        "settings": form.into_inner(),
+        "needs_sync": true
     })))
 }
@@ -2,9 +2,9 @@ use actix_web::{web, HttpResponse, Result};
 use serde_json;
 use utoipa;
 
-use crate::models::{ScheduleUpdateForm, WeeklyHours, ServiceError};
 use crate::auth::JwtManager;
 use crate::middleware::auth::authenticate_request;
+use crate::models::{ScheduleUpdateForm, ServiceError, WeeklyHours};
 use crate::services::ScheduleService;
 
 #[utoipa::path(
@@ -14,19 +14,18 @@ use crate::services::ScheduleService;
     responses(
         (status = 200, description = "Schedule updated successfully"),
         (status = 400, description = "Invalid schedule values"),
-        (status = 401, description = "Not authenticated")
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Token is read-only")
     )
 )]
 pub async fn update_schedule_api(
     schedule_service: web::Data<ScheduleService>,
     form: web::Json<ScheduleUpdateForm>,
-    req: actix_web::HttpRequest, 
+    req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication - only HTTP concern
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError("Not authenticated".to_string()));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     println!("Received schedule update: user_id={}, monday={}, tuesday={}, wednesday={}, thursday={}, friday={}, saturday={}, sunday={}",
              form.user_id, form.monday, form.tuesday, form.wednesday, form.thursday, form.friday, form.saturday, form.sunday);
@@ -43,7 +42,9 @@ pub async fn update_schedule_api(
     };
 
     // Business logic delegation - service handles all business rules
-    schedule_service.update_schedule(form.user_id, hours).await?;
+    schedule_service
+        .update_schedule(form.user_id, hours)
+        .await?;
 
     // Success response
     Ok(HttpResponse::Ok().json(serde_json::json!({
@@ -60,27 +61,26 @@ pub async fn update_schedule_api(
     ),
     responses(
         (status = 200, description = "Schedule sync status retrieved"),
-        (status = 401, description = "Not authenticated")
+        (status = 401, description = "Not authenticated"),
+        (status = 403, description = "Token is read-only")
     )
 )]
 pub async fn get_schedule_sync_status(
     schedule_service: web::Data<ScheduleService>,
     path: web::Path<i64>,
-    req: actix_web::HttpRequest, 
+    req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError("Not authenticated".to_string()));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     let user_id = path.into_inner();
-    
+
     // Business logic delegation
     let sync_status = schedule_service.get_sync_status(user_id).await?;
 
     println!("Retrieved schedule sync status for user {}", user_id);
-    
+
     // Response formatting
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
@@ -89,4 +89,4 @@ pub async fn get_schedule_sync_status(
         "last_synced": sync_status.last_synced,
         "last_modified": sync_status.last_modified
     })))
-}
\ No newline at end of file
+}
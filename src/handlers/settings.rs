@@ -0,0 +1,308 @@
+use actix_web::{web, HttpResponse, Result};
+use serde_json;
+use utoipa;
+
+use crate::auth::JwtManager;
+use crate::middleware::auth::authenticate_request;
+use crate::models::{
+    parse_timezone, timezone_names, CheckIntervalForm, DayOfWeek, DefaultScheduleForm,
+    GrantCapForm, ProtectObservabilityForm, RevalidateAfterModifyForm, SchedulerWindowForm,
+    ServiceError, TimezoneForm, WeekStart, WeekStartForm,
+};
+use crate::services::SettingsService;
+use chrono::NaiveTime;
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/week-start",
+    request_body = WeekStartForm,
+    responses(
+        (status = 200, description = "Week start updated successfully"),
+        (status = 400, description = "Invalid week start", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_week_start(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<WeekStartForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let week_start: WeekStart = form
+        .week_start
+        .parse()
+        .map_err(ServiceError::ValidationError)?;
+
+    settings_service.set_week_start(week_start).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Week start updated successfully",
+        "week_start": week_start,
+        "day_order": DayOfWeek::week(week_start)
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/protect-observability",
+    request_body = ProtectObservabilityForm,
+    responses(
+        (status = 200, description = "Observability protection updated"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_protect_observability(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<ProtectObservabilityForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    settings_service
+        .set_protect_observability(form.enabled)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Observability protection updated successfully",
+        "protect_observability": form.enabled
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/default-schedule",
+    request_body = DefaultScheduleForm,
+    responses(
+        (status = 200, description = "Default schedule updated"),
+        (status = 400, description = "Invalid hours", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_default_schedule(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<DefaultScheduleForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let form = form.into_inner();
+    settings_service
+        .set_default_schedule(form.hours.clone())
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Default schedule updated successfully",
+        "default_schedule": form.hours
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/check-interval",
+    request_body = CheckIntervalForm,
+    responses(
+        (status = 200, description = "Scheduler interval updated; applies from the next pass"),
+        (status = 400, description = "Interval out of range", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_check_interval(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<CheckIntervalForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    settings_service.set_check_interval(form.seconds).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Check interval updated successfully",
+        "check_interval": form.seconds
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/scheduler-window",
+    request_body = SchedulerWindowForm,
+    responses(
+        (status = 200, description = "Scheduler active window updated"),
+        (status = 400, description = "Invalid or incomplete window", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_scheduler_window(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<SchedulerWindowForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let parse = |value: &str| {
+        NaiveTime::parse_from_str(value, "%H:%M").map_err(|_| {
+            ServiceError::ValidationError(format!("Invalid time: {}. Expected HH:MM", value))
+        })
+    };
+
+    let window = match (&form.active_from, &form.active_to) {
+        (Some(from), Some(to)) => Some((parse(from)?, parse(to)?)),
+        (None, None) => None,
+        _ => {
+            return Err(ServiceError::ValidationError(
+                "active_from and active_to must be set together".to_string(),
+            ))
+        }
+    };
+
+    settings_service.set_scheduler_active_window(window).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Scheduler window updated successfully",
+        "active_from": window.map(|(from, _)| from.format("%H:%M").to_string()),
+        "active_to": window.map(|(_, to)| to.format("%H:%M").to_string())
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/timezones",
+    responses(
+        (status = 200, description = "Selectable IANA timezones and the configured one"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn list_timezones(
+    settings_service: web::Data<SettingsService>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let current = settings_service.get_timezone().await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "timezone": current.map(|tz| tz.name()),
+        "timezones": timezone_names()
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/timezone",
+    request_body = TimezoneForm,
+    responses(
+        (status = 200, description = "Timezone updated successfully"),
+        (status = 400, description = "Unknown timezone name", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_timezone(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<TimezoneForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let timezone = form
+        .timezone
+        .as_deref()
+        .map(parse_timezone)
+        .transpose()
+        .map_err(ServiceError::ValidationError)?;
+
+    settings_service.set_timezone(timezone).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Timezone updated successfully",
+        "timezone": timezone.map(|tz| tz.name())
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/grant-cap",
+    request_body = GrantCapForm,
+    responses(
+        (status = 200, description = "Daily grant cap updated successfully"),
+        (status = 400, description = "Cap is not positive", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_grant_cap(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<GrantCapForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    settings_service
+        .set_daily_grant_cap(form.max_daily_grant_seconds)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Daily grant cap updated successfully",
+        "max_daily_grant_seconds": form.max_daily_grant_seconds
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/settings/revalidate-after-modify",
+    request_body = RevalidateAfterModifyForm,
+    responses(
+        (status = 200, description = "Setting updated; when off, time adjustments skip the follow-up validation and the new time left shows after the next scheduler pass"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn update_revalidate_after_modify(
+    settings_service: web::Data<SettingsService>,
+    form: web::Json<RevalidateAfterModifyForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    settings_service
+        .set_revalidate_after_modify(form.enabled)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Revalidation after time adjustments updated successfully",
+        "revalidate_after_modify": form.enabled
+    })))
+}
@@ -4,34 +4,47 @@ use utoipa;
 
 use crate::auth::JwtManager;
 use crate::middleware::auth::authenticate_request;
-use crate::models::{ServiceError, SshStatusResponse};
+use crate::models::{
+    format_timestamp, CapabilitiesResponse, RecentEventsQuery, RecentEventsResponse, ServiceError,
+    SshStatusResponse, TimekprConfig,
+};
+use crate::readiness::Readiness;
 use crate::scheduler::BackgroundScheduler;
-use crate::ssh::SSHClient;
-use crate::services::UserService;
+use crate::services::{EventLog, SettingsService, UserService};
+use crate::ssh::{SSHClient, SSH_KEY_PERMISSIONS_INSECURE};
+use sqlx::SqlitePool;
 
 #[utoipa::path(
     get,
     path = "/api/task-status",
     responses(
         (status = 200, description = "Background task status retrieved", body = TaskStatusResponse),
-        (status = 401, description = "Not authenticated", body = ErrorResponse)
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn get_task_status(
     user_service: web::Data<UserService>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
-    scheduler: web::Data<std::sync::Arc<BackgroundScheduler>>,
+    scheduler: web::Data<BackgroundScheduler>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     // Get actual status
     let is_running = scheduler.is_running().await;
+    let healthy = scheduler.is_healthy().await;
+    let last_heartbeat = scheduler.last_heartbeat().await.map(format_timestamp);
+    let last_update = scheduler.last_success().await.map(format_timestamp);
+    let last_error = scheduler.last_error().await;
+
+    if is_running && !healthy {
+        eprintln!(
+            "Background scheduler heartbeat is stale (last: {})",
+            last_heartbeat.as_deref().unwrap_or("never")
+        );
+    }
 
     // Count managed users
     let user_count = user_service.get_valid_users().await?.len();
@@ -40,18 +53,129 @@ pub async fn get_task_status(
         "success": true,
         "status": {
             "running": is_running,
-            "last_update": chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            "healthy": healthy,
+            "last_heartbeat": last_heartbeat,
+            "last_update": last_update,
+            "last_error": last_error,
             "managed_users": user_count
         }
     })))
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/system/run-scheduler-pass",
+    responses(
+        (status = 200, description = "One scheduler pass ran to completion", body = SchedulerPassSummary),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 409, description = "A scheduler pass is already running", body = ErrorResponse)
+    )
+)]
+pub async fn run_scheduler_pass(
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+    scheduler: web::Data<BackgroundScheduler>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Never queued behind the timed loop; a pass in progress is reported instead
+    let summary = scheduler
+        .try_run_once()
+        .await
+        .ok_or_else(|| ServiceError::Conflict("A scheduler pass is already running".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "summary": summary
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/capabilities",
+    responses(
+        (status = 200, description = "Features this server supports", body = CapabilitiesResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn get_capabilities(
+    user_service: web::Data<UserService>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Hosts never checked yet are not counted
+    let playtime_supported_hosts = user_service
+        .find_all()
+        .await?
+        .into_iter()
+        .filter_map(|user| user.last_config)
+        .filter_map(|config| config.parse::<TimekprConfig>().ok())
+        .filter(|config| config.playtime_supported)
+        .count();
+
+    Ok(HttpResponse::Ok().json(CapabilitiesResponse {
+        success: true,
+        webhook: false,
+        email: false,
+        tls: false,
+        overnight_intervals: false,
+        multi_admin: false,
+        readonly_tokens: true,
+        multiple_intervals: true,
+        playtime: true,
+        playtime_supported_hosts,
+    }))
+}
+
+/// Events returned when no limit is given
+const DEFAULT_RECENT_EVENTS: usize = 50;
+
+#[utoipa::path(
+    get,
+    path = "/api/events/recent",
+    params(
+        ("limit" = Option<usize>, Query, description = "Most events to return; defaults to 50 and is capped at the log's capacity")
+    ),
+    responses(
+        (status = 200, description = "Recent events, newest first. Kept in memory only, so the log starts empty after a restart", body = RecentEventsResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn get_recent_events(
+    event_log: web::Data<EventLog>,
+    query: web::Query<RecentEventsQuery>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_RECENT_EVENTS)
+        .min(event_log.capacity());
+
+    Ok(HttpResponse::Ok().json(RecentEventsResponse {
+        success: true,
+        events: event_log.recent(limit),
+        capacity: event_log.capacity(),
+    }))
+}
+
 #[utoipa::path(
     get,
     path = "/api/ssh-status",
     responses(
         (status = 200, description = "SSH status retrieved", body = SshStatusResponse),
-        (status = 401, description = "Not authenticated", body = ErrorResponse)
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn get_ssh_status(
@@ -59,22 +183,179 @@ pub async fn get_ssh_status(
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     let ssh_key_exists = SSHClient::check_ssh_key_exists();
+    let ssh_agent_available = SSHClient::ssh_agent_available();
+    let ssh_key_mode =
+        SSHClient::find_ssh_key_path().and_then(|path| SSHClient::key_file_mode(&path));
 
     Ok(HttpResponse::Ok().json(SshStatusResponse {
         success: true,
         ssh_key_exists,
-        message: if ssh_key_exists {
-            "SSH keys are configured".to_string()
-        } else {
-            "SSH keys not found. Please configure SSH keys for passwordless authentication."
-                .to_string()
+        ssh_key_encrypted: SSHClient::ssh_key_encrypted(),
+        ssh_agent_available,
+        ssh_key_mode: ssh_key_mode.map(|mode| format!("{:04o}", mode)),
+        warning: SSHClient::insecure_key_mode().map(|_| SSH_KEY_PERMISSIONS_INSECURE.to_string()),
+        message: match SSHClient::key_diagnostic() {
+            Some(problem) => problem,
+            None if ssh_key_exists => "SSH keys are configured".to_string(),
+            None => "Using keys from ssh-agent".to_string(),
         },
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/ssh/key-candidates",
+    responses(
+        (status = 200, description = "Every path searched for the SSH key in order, which exist, which one is used and its permissions"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
+    )
+)]
+pub async fn get_ssh_key_candidates(
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Same list the SSH commands search, so this shows what they actually use
+    let candidates = SSHClient::describe_key_candidates(&SSHClient::ssh_key_candidates());
+    let selected = candidates
+        .iter()
+        .find(|candidate| candidate.selected)
+        .map(|candidate| candidate.path.clone());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "selected": selected,
+        "ssh_agent_available": SSHClient::ssh_agent_available(),
+        "candidates": candidates
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/ssh/fix-permissions",
+    responses(
+        (status = 200, description = "SSH key restricted to mode 0600"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "No SSH key found", body = ErrorResponse),
+        (status = 500, description = "Permissions could not be changed", body = ErrorResponse)
+    )
+)]
+pub async fn fix_ssh_key_permissions(
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let path = SSHClient::fix_key_permissions()
+        .map_err(|e| {
+            ServiceError::InternalError(format!("Failed to change key permissions: {}", e))
+        })?
+        .ok_or_else(|| ServiceError::NotFound("No SSH key found".to_string()))?;
+
+    println!("Restricted SSH key {} to mode 0600", path);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("SSH key {} permissions set to 0600", path),
+        "ssh_key_mode": "0600"
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/health/live",
+    responses(
+        (status = 200, description = "Service is alive"),
+        (status = 503, description = "Database unavailable")
+    ),
+    security()
+)]
+pub async fn health_live(pool: web::Data<SqlitePool>) -> HttpResponse {
+    // Deliberately minimal and unauthenticated - only the status code carries information
+    match sqlx::query("SELECT 1").execute(pool.get_ref()).await {
+        Ok(_) => HttpResponse::Ok().finish(),
+        Err(_) => HttpResponse::ServiceUnavailable().finish(),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/health/ready",
+    responses(
+        (status = 200, description = "Database pool warmed up, ready for traffic"),
+        (status = 503, description = "Still warming up")
+    ),
+    security()
+)]
+pub async fn health_ready(readiness: web::Data<Readiness>) -> HttpResponse {
+    // Unauthenticated like /api/health/live so orchestrators can gate traffic on it
+    if readiness.is_ready() {
+        HttpResponse::Ok().finish()
+    } else {
+        HttpResponse::ServiceUnavailable().finish()
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/health",
+    responses(
+        (status = 200, description = "All components healthy"),
+        (status = 401, description = "Not authenticated (when protect_observability is enabled)", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 503, description = "One or more components unhealthy")
+    ),
+    security((), ("bearer_auth" = []))
+)]
+pub async fn health_detailed(
+    pool: web::Data<SqlitePool>,
+    settings_service: web::Data<SettingsService>,
+    user_service: web::Data<UserService>,
+    scheduler: web::Data<BackgroundScheduler>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication only when the operator has asked for it
+    if settings_service.get_protect_observability().await? {
+        authenticate_request(&req, &jwt_manager)?;
+    }
+
+    let database_ok = sqlx::query("SELECT 1")
+        .execute(pool.get_ref())
+        .await
+        .is_ok();
+    let scheduler_running = scheduler.is_running().await;
+    let scheduler_healthy = scheduler.is_healthy().await;
+    let managed_users = if database_ok {
+        user_service.get_valid_users().await?.len()
+    } else {
+        0
+    };
+
+    let healthy = database_ok && scheduler_healthy;
+    let body = serde_json::json!({
+        "success": healthy,
+        "status": if healthy { "ok" } else { "degraded" },
+        "checks": {
+            "database": database_ok,
+            "scheduler_running": scheduler_running,
+            "scheduler_healthy": scheduler_healthy,
+            "ssh_key_exists": SSHClient::check_ssh_key_exists()
+        },
+        "managed_users": managed_users
+    });
+
+    if healthy {
+        Ok(HttpResponse::Ok().json(body))
+    } else {
+        Ok(HttpResponse::ServiceUnavailable().json(body))
+    }
+}
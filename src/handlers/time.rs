@@ -3,9 +3,14 @@ use serde_json;
 use utoipa;
 
 use crate::auth::JwtManager;
+use crate::http_cache::{cached_json, scheduler_max_age};
 use crate::middleware::auth::authenticate_request;
-use crate::models::{ModifyTimeForm, ServiceError, TimeModification};
-use crate::services::TimeService;
+use crate::models::{
+    AdjustmentPreviewQuery, AggregateUsageQuery, IntradayUsageQuery, ModificationHistoryQuery,
+    ModifyTimeForm, ServiceError, SetTimeLimitForm, TimeModification, UsageArrays, UsageFormat,
+    UsageQuery,
+};
+use crate::services::{DashboardCache, SettingsService, TimeService};
 
 #[utoipa::path(
     post,
@@ -13,73 +18,351 @@ use crate::services::TimeService;
     request_body = ModifyTimeForm,
     responses(
         (status = 200, description = "Time modified successfully", body = ModifyTimeResponse),
+        (status = 202, description = "Host unreachable; the adjustment was queued", body = ModifyTimeResponse),
         (status = 400, description = "Invalid operation", body = ErrorResponse),
         (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse)
     )
 )]
 pub async fn modify_time(
     time_service: web::Data<TimeService>,
     form: web::Json<ModifyTimeForm>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     // Create domain object with validation
     let modification = TimeModification::new(form.user_id, form.operation.clone(), form.seconds)
-        .map_err(|e| ServiceError::ValidationError(e))?;
+        .and_then(|modification| modification.with_reason(form.reason.as_deref()))
+        .map_err(ServiceError::ValidationError)?;
 
     // Business logic delegation
     let result = time_service.modify_time(modification).await?;
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
+    dashboard_cache.invalidate().await;
+
+    // 200 only when the host took the change; a queued one is accepted for later
+    let mut response = if result.pending {
+        HttpResponse::Accepted()
+    } else {
+        HttpResponse::Ok()
+    };
+    Ok(response.json(serde_json::json!({
+        "success": result.success,
+        "message": result.message,
+        "username": result.username,
+        "pending": result.pending,
+        "reason": result.reason,
+        "refresh": true
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/set-time-limit",
+    request_body = SetTimeLimitForm,
+    responses(
+        (status = 200, description = "Time left set", body = ModifyTimeResponse),
+        (status = 202, description = "Host unreachable; the new value was queued", body = ModifyTimeResponse),
+        (status = 400, description = "Seconds not positive or longer than a day", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn set_time_limit(
+    time_service: web::Data<TimeService>,
+    form: web::Json<SetTimeLimitForm>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let result = time_service
+        .set_time_limit(form.user_id, form.seconds)
+        .await?;
+
+    dashboard_cache.invalidate().await;
+
+    let mut response = if result.pending {
+        HttpResponse::Accepted()
+    } else {
+        HttpResponse::Ok()
+    };
+    Ok(response.json(serde_json::json!({
         "success": result.success,
         "message": result.message,
         "username": result.username,
         "pending": result.pending,
+        "reason": result.reason,
         "refresh": true
     })))
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/modification-history",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("limit" = Option<i64>, Query, description = "Number of entries to return (default 20, capped at 100)")
+    ),
+    responses(
+        (status = 200, description = "The user's last time modifications, newest first, each applied right away or queued"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_modification_history(
+    time_service: web::Data<TimeService>,
+    path: web::Path<i64>,
+    query: web::Query<ModificationHistoryQuery>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let (user, entries) = time_service
+        .modification_history(path.into_inner(), query.limit)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "username": user.username,
+        "entries": entries
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/adjustment-preview",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("operation" = String, Query, description = "'+' or '-'"),
+        ("seconds" = i64, Query, description = "Seconds to add or remove")
+    ),
+    responses(
+        (status = 200, description = "Current and projected time left, estimated from the last stored config without contacting the host"),
+        (status = 400, description = "Invalid operation or seconds", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn adjustment_preview(
+    time_service: web::Data<TimeService>,
+    path: web::Path<i64>,
+    query: web::Query<AdjustmentPreviewQuery>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    // Same validation as the real modification
+    let query = query.into_inner();
+    let modification = TimeModification::new(path.into_inner(), query.operation, query.seconds)
+        .map_err(ServiceError::ValidationError)?;
+
+    // Business logic delegation
+    let preview = time_service.preview_adjustment(&modification).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "estimate": true,
+        "username": preview.username,
+        "operation": preview.operation,
+        "seconds": preview.seconds,
+        "current_time_left": preview.current_time_left,
+        "projected_time_left": preview.projected_time_left,
+        "current_formatted": preview.current_formatted,
+        "projected_formatted": preview.projected_formatted,
+        "as_of": preview.as_of
+    })))
+}
+
 #[utoipa::path(
     get,
     path = "/api/user/{id}/usage",
     params(
-        ("id" = i64, Path, description = "User ID")
+        ("id" = i64, Path, description = "User ID"),
+        ("days" = Option<i32>, Query, description = "Number of days to return (default 7), clamped to 1..=365 or USAGE_MAX_DAYS when lower"),
+        ("unit" = Option<UsageUnit>, Query, description = "Unit for returned values: hours (default), minutes or seconds"),
+        ("format" = Option<UsageFormat>, Query, description = "Layout of data: objects (default, one {date, hours, value} per day) or arrays ({labels, hours, values}). Both contain every day of the range, oldest first, with zero for days without usage")
     ),
     responses(
         (status = 200, description = "User usage data retrieved", body = UsageResponse),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Unknown unit or format", body = ErrorResponse),
         (status = 401, description = "Not authenticated", body = ErrorResponse),
         (status = 404, description = "User not found", body = ErrorResponse)
     )
 )]
 pub async fn get_user_usage(
+    time_service: web::Data<TimeService>,
+    settings_service: web::Data<SettingsService>,
+    path: web::Path<i64>,
+    query: web::Query<UsageQuery>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    // Business logic delegation
+    let usage_data = time_service
+        .get_user_usage(user_id, query.days, query.unit.unwrap_or_default())
+        .await?;
+
+    let data = match query.format.unwrap_or_default() {
+        UsageFormat::Objects => serde_json::json!(usage_data.usage_data),
+        UsageFormat::Arrays => {
+            serde_json::json!(UsageArrays::from(usage_data.usage_data.as_slice()))
+        }
+    };
+
+    cached_json(
+        &req,
+        &scheduler_max_age(settings_service.tick_interval().await),
+        &serde_json::json!({
+            "success": true,
+            "data": data,
+            "format": query.format.unwrap_or_default(),
+            "username": usage_data.username,
+            "days": usage_data.days,
+            "unit": usage_data.unit
+        }),
+    )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/usage/aggregate",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("by" = Option<UsageGranularity>, Query, description = "Period to sum over: daily (default), weekly (ISO weeks) or monthly"),
+        ("periods" = Option<i32>, Query, description = "Periods to return, the current one included (default 7 days, 8 weeks or 6 months)")
+    ),
+    responses(
+        (status = 200, description = "Usage per period, oldest first", body = AggregatedUsageResponse),
+        (status = 304, description = "Unchanged since the ETag given in If-None-Match"),
+        (status = 400, description = "Invalid granularity or number of periods", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_usage_aggregate(
+    time_service: web::Data<TimeService>,
+    settings_service: web::Data<SettingsService>,
+    path: web::Path<i64>,
+    query: web::Query<AggregateUsageQuery>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    // Business logic delegation
+    let usage = time_service
+        .get_usage_aggregated(user_id, query.by.unwrap_or_default(), query.periods)
+        .await?;
+
+    cached_json(
+        &req,
+        &scheduler_max_age(settings_service.tick_interval().await),
+        &serde_json::json!({
+            "success": true,
+            "username": usage.username,
+            "by": usage.granularity,
+            "data": usage.buckets
+        }),
+    )
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/snapshot-usage",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Today's usage read from the host and stored; success is false when the host could not be read"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn snapshot_usage(
+    time_service: web::Data<TimeService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    // Business logic delegation
+    let snapshot = time_service.snapshot_usage(user_id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": snapshot.time_spent.is_some(),
+        "message": snapshot.message,
+        "username": snapshot.username,
+        "date": snapshot.date,
+        "time_spent": snapshot.time_spent,
+        "status": snapshot.status
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/usage/intraday",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("date" = Option<String>, Query, description = "Local usage day as YYYY-MM-DD (default today)")
+    ),
+    responses(
+        (status = 200, description = "Time spent samples for the day, oldest first; empty unless USAGE_SAMPLE_RETENTION_DAYS is set"),
+        (status = 400, description = "Invalid date", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_intraday_usage(
     time_service: web::Data<TimeService>,
     path: web::Path<i64>,
+    query: web::Query<IntradayUsageQuery>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     let user_id = path.into_inner();
 
     // Business logic delegation
-    let usage_data = time_service.get_user_usage(user_id).await?;
+    let usage = time_service
+        .get_intraday_usage(user_id, query.date.as_deref())
+        .await?;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
-        "data": usage_data.usage_data,
-        "username": usage_data.username
+        "username": usage.username,
+        "date": usage.date,
+        "enabled": usage.enabled,
+        "samples": usage.samples
     })))
 }
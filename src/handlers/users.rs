@@ -3,33 +3,36 @@ use serde_json;
 use utoipa;
 
 use crate::auth::JwtManager;
-use crate::middleware::auth::authenticate_request;
-use crate::models::{AddUserForm, ServiceError};
-use crate::services::UserService;
+use crate::middleware::auth::{authenticate_request, authenticated_claims};
+use crate::models::{
+    format_timestamp, AddUserForm, AddUserResponse, AddUserValidation, AttachHostForm,
+    BulkDeleteForm, BulkDeleteResponse, CommandTemplatesForm, DeleteUserQuery, PendingOnDelete,
+    ServiceError, TimekprConfig, TimekprPathForm, UpdateUserForm,
+};
+use crate::services::{DashboardCache, ScheduleService, UserService};
+use crate::ssh::DEFAULT_TIMEKPR_PATH;
 
 #[utoipa::path(
     post,
     path = "/api/users/add",
     request_body = AddUserForm,
     responses(
-        (status = 200, description = "User added successfully", body = ApiResponse),
-        (status = 400, description = "Invalid input", body = ErrorResponse),
+        (status = 200, description = "User added; `validation` tells whether the host was reachable and knows the user", body = AddUserResponse),
+        (status = 400, description = "Invalid input, or host unreachable with on_unreachable = reject", body = ErrorResponse),
         (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
         (status = 409, description = "User already exists", body = ErrorResponse)
     )
 )]
 pub async fn add_user_api(
     user_service: web::Data<UserService>,
     form: web::Json<AddUserForm>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     if form.username.is_empty() || form.system_ip.is_empty() {
         return Err(ServiceError::ValidationError(
@@ -38,14 +41,27 @@ pub async fn add_user_api(
     }
 
     // Business logic delegation
-    let message = user_service
-        .add_user(form.username.clone(), form.system_ip.clone())
+    let (user_id, message, status) = user_service
+        .add_user(
+            form.username.clone(),
+            form.system_ip.clone(),
+            form.validate,
+            form.on_unreachable,
+        )
         .await?;
 
-    Ok(HttpResponse::Ok().json(serde_json::json!({
-        "success": true,
-        "message": message
-    })))
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(AddUserResponse {
+        success: true,
+        message,
+        user_id,
+        validation: status.map(|status| AddUserValidation {
+            status,
+            reachable: status.is_reachable(),
+            user_missing: status.is_user_missing(),
+        }),
+    }))
 }
 
 #[utoipa::path(
@@ -56,27 +72,27 @@ pub async fn add_user_api(
     ),
     responses(
         (status = 200, description = "User validation completed", body = ApiResponse),
-        (status = 401, description = "Not authenticated", body = ErrorResponse)
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse)
     )
 )]
 pub async fn validate_user(
     user_service: web::Data<UserService>,
     path: web::Path<i64>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
-        ));
-    }
+    authenticate_request(&req, &jwt_manager)?;
 
     let user_id = path.into_inner();
 
     // Business logic delegation
     let message = user_service.validate_user(user_id).await?;
 
+    dashboard_cache.invalidate().await;
+
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
         "message": message
@@ -85,36 +101,429 @@ pub async fn validate_user(
 
 #[utoipa::path(
     post,
-    path = "/api/users/delete/{id}",
+    path = "/api/users/update/{id}",
     params(
         ("id" = i64, Path, description = "User ID")
     ),
+    request_body = UpdateUserForm,
+    responses(
+        (status = 200, description = "User updated and validated again; usage and schedules are kept, and pushed to the new host or account", body = ApiResponse),
+        (status = 400, description = "Missing field, or another user already has this username and IP", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn update_user(
+    user_service: web::Data<UserService>,
+    schedule_service: web::Data<ScheduleService>,
+    path: web::Path<i64>,
+    form: web::Json<UpdateUserForm>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let form = form.into_inner();
+    let username = form.username.trim().to_string();
+    let system_ip = form.system_ip.trim().to_string();
+    if username.is_empty() || system_ip.is_empty() {
+        return Err(ServiceError::ValidationError(
+            "Both username and system IP are required".to_string(),
+        ));
+    }
+
+    let user = user_service
+        .find_by_id(path.into_inner())
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+    let moved = user.username != username || user.system_ip != system_ip;
+
+    // Business logic delegation
+    let message = user_service
+        .update_user(user.id, username, system_ip)
+        .await?;
+
+    // What was synced went to the old host or account
+    if moved {
+        schedule_service.requeue_all(user.group_id()).await?;
+    }
+
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": message
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/delete/{id}",
+    params(
+        ("id" = i64, Path, description = "User ID"),
+        ("on_pending" = Option<PendingOnDelete>, Query, description = "With a queued adjustment or unsynced schedule: discard (default) deletes anyway and audits what was dropped, block refuses")
+    ),
     responses(
         (status = 200, description = "User deleted successfully", body = ApiResponse),
         (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse),
+        (status = 409, description = "Pending changes exist and on_pending=block", body = ErrorResponse),
         (status = 500, description = "Failed to delete user", body = ErrorResponse)
     )
 )]
 pub async fn delete_user(
+    user_service: web::Data<UserService>,
+    schedule_service: web::Data<ScheduleService>,
+    path: web::Path<i64>,
+    query: web::Query<DeleteUserQuery>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication; the subject is kept for the audit log line
+    let claims = authenticated_claims(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    // Changes that were never pushed to the host go away with the user
+    let mut discarded = user_service.pending_adjustments_on_delete(user_id).await?;
+    if !schedule_service.get_sync_status(user_id).await?.is_synced {
+        discarded.push("unsynced schedule".to_string());
+    }
+    if !discarded.is_empty() && query.on_pending.unwrap_or_default() == PendingOnDelete::Block {
+        return Err(ServiceError::Conflict(format!(
+            "User has pending changes: {}",
+            discarded.join(", ")
+        )));
+    }
+
+    // Business logic delegation
+    let message = user_service.delete_user(user_id).await?;
+
+    if !discarded.is_empty() {
+        println!(
+            "AUDIT: {} deleted user {} discarding pending changes: {}",
+            claims.sub,
+            user_id,
+            discarded.join(", ")
+        );
+    }
+
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": message,
+        "discarded": discarded
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/bulk-delete",
+    request_body = BulkDeleteForm,
+    responses(
+        (status = 200, description = "Per-user deletion results", body = BulkDeleteResponse),
+        (status = 400, description = "No user ids given", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 500, description = "Deletion failed, nothing was deleted", body = ErrorResponse)
+    )
+)]
+pub async fn bulk_delete_users(
+    user_service: web::Data<UserService>,
+    form: web::Json<BulkDeleteForm>,
+    dashboard_cache: web::Data<DashboardCache>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication; the subject is kept for the audit log line
+    let claims = authenticated_claims(&req, &jwt_manager)?;
+
+    // Business logic delegation
+    let results = user_service.bulk_delete_users(&form.user_ids).await?;
+    let deleted = results.iter().filter(|r| r.status == "deleted").count();
+    let not_found = results.len() - deleted;
+
+    println!(
+        "AUDIT: {} bulk-deleted users {:?} ({} deleted, {} not found)",
+        claims.sub,
+        results
+            .iter()
+            .filter(|r| r.status == "deleted")
+            .map(|r| r.user_id)
+            .collect::<Vec<_>>(),
+        deleted,
+        not_found
+    );
+
+    dashboard_cache.invalidate().await;
+
+    Ok(HttpResponse::Ok().json(BulkDeleteResponse {
+        success: true,
+        results,
+        deleted,
+        not_found,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "User details retrieved"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_user_detail(
+    user_service: web::Data<UserService>,
+    schedule_service: web::Data<ScheduleService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .find_by_id(user_id)
+        .await?
+        .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+    let sync_status = schedule_service.get_sync_status(user_id).await?;
+    let intervals_status = schedule_service.get_intervals_sync_status(user_id).await?;
+    let effective_limit_today = schedule_service.effective_limit_today(user_id).await?;
+    let playtime = schedule_service.get_playtime(user_id).await?;
+    let lockout = schedule_service.get_lockout(user_id).await?;
+    let command_warning = user_service.command_warning(user_id).await?;
+    // Single "needs attention" flag: nothing left for the scheduler to push
+    let fully_synced = sync_status.is_synced
+        && !intervals_status.needs_sync
+        && user.pending_time_adjustment.is_none()
+        && playtime.as_ref().is_none_or(|playtime| playtime.is_synced)
+        && lockout.as_ref().is_none_or(|lockout| lockout.is_synced);
+    let config = user
+        .last_config
+        .as_deref()
+        .and_then(|config| config.parse::<TimekprConfig>().ok());
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "user": {
+            "id": user.id,
+            "username": user.username,
+            "system_ip": user.system_ip,
+            "is_valid": user.is_valid,
+            "date_added": user.date_added.map(format_timestamp),
+            "last_checked": user.last_checked.map(format_timestamp),
+            "pending_time_adjustment": user.pending_time_adjustment,
+            "pending_time_operation": user.pending_time_operation,
+            "pending_time_reason": user.pending_time_reason,
+            "host_group_id": user.host_group_id,
+            "timekpr_path": user.timekpr_path,
+            "validate_command_template": user.validate_command_template,
+            "modify_command_template": user.modify_command_template,
+            "time_left_day": config.as_ref().and_then(|c| c.time_left_day),
+            "time_spent_day": config.as_ref().and_then(|c| c.time_spent_day),
+            "playtime_left_day": config.as_ref().and_then(|c| c.playtime_left_day)
+        },
+        "schedule": sync_status.schedule,
+        "playtime": {
+            "settings": playtime.as_ref().map(|playtime| &playtime.settings),
+            "is_synced": playtime.as_ref().map(|playtime| playtime.is_synced),
+            "last_synced": playtime.as_ref().and_then(|playtime| playtime.last_synced).map(format_timestamp),
+            // Unknown until the host has been checked once
+            "supported": config.as_ref().map(|c| c.playtime_supported)
+        },
+        "lockout": {
+            "settings": lockout.as_ref().map(|lockout| &lockout.settings),
+            "is_synced": lockout.as_ref().map(|lockout| lockout.is_synced),
+            "last_synced": lockout.as_ref().and_then(|lockout| lockout.last_synced).map(format_timestamp),
+            "host_lockout_type": config.as_ref().and_then(|c| c.lockout_type.clone()),
+            "supported": config.as_ref().map(|c| c.lockout_supported())
+        },
+        // Latest stderr of a timekpr command that still succeeded, e.g. a deprecation notice
+        "command_warning": command_warning.map(|(message, recorded_at)| serde_json::json!({
+            "message": message,
+            "recorded_at": format_timestamp(recorded_at)
+        })),
+        "is_synced": sync_status.is_synced,
+        "fully_synced": fully_synced,
+        "needs_attention": sync_status.needs_attention,
+        "effective_limit_today": effective_limit_today,
+        "seconds_until_reset": schedule_service.seconds_until_reset()
+    })))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/user/{id}/validation-history",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    responses(
+        (status = 200, description = "Checks of the user's hosts over the last day, newest first"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn get_validation_history(
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let user_id = path.into_inner();
+
+    // Business logic delegation
+    let (user, results) = user_service.validation_history(user_id).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "username": user.username,
+        "results": results
+    })))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/user/{id}/hosts",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = AttachHostForm,
+    responses(
+        (status = 200, description = "Host attached to the user", body = ApiResponse),
+        (status = 400, description = "Invalid input or host already present", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn attach_host(
     user_service: web::Data<UserService>,
     path: web::Path<i64>,
+    form: web::Json<AttachHostForm>,
+    dashboard_cache: web::Data<DashboardCache>,
     req: actix_web::HttpRequest,
     jwt_manager: web::Data<JwtManager>,
 ) -> Result<HttpResponse, ServiceError> {
     // Authentication
-    if let Err(_) = authenticate_request(&req, &jwt_manager) {
-        return Err(ServiceError::AuthenticationError(
-            "Not authenticated".to_string(),
+    authenticate_request(&req, &jwt_manager)?;
+
+    if form.system_ip.is_empty() {
+        return Err(ServiceError::ValidationError(
+            "System IP is required".to_string(),
         ));
     }
 
     let user_id = path.into_inner();
 
     // Business logic delegation
-    let message = user_service.delete_user(user_id).await?;
+    let message = user_service
+        .attach_host(user_id, form.system_ip.clone())
+        .await?;
+
+    dashboard_cache.invalidate().await;
 
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "success": true,
         "message": message
     })))
 }
+
+#[utoipa::path(
+    put,
+    path = "/api/user/{id}/timekpr-path",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = TimekprPathForm,
+    responses(
+        (status = 200, description = "timekpra path saved; used for every command sent to this user's host"),
+        (status = 400, description = "Path contains characters other than letters, digits, '/', '.', '_' and '-'", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn set_timekpr_path(
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    form: web::Json<TimekprPathForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let timekpr_path = form.path().map_err(ServiceError::ValidationError)?;
+    let user_id = path.into_inner();
+
+    let user = user_service.set_timekpr_path(user_id, timekpr_path).await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!(
+            "Commands for {} will run {}",
+            user.username,
+            user.timekpr_path.as_deref().unwrap_or(DEFAULT_TIMEKPR_PATH)
+        ),
+        "timekpr_path": user.timekpr_path
+    })))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/user/{id}/command-templates",
+    params(
+        ("id" = i64, Path, description = "User ID")
+    ),
+    request_body = CommandTemplatesForm,
+    responses(
+        (status = 200, description = "Templates saved; used to validate and modify time on this user's host"),
+        (status = 400, description = "A template lacks a required placeholder or uses an unknown one", body = ErrorResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Token is read-only", body = ErrorResponse),
+        (status = 404, description = "User not found", body = ErrorResponse)
+    )
+)]
+pub async fn set_command_templates(
+    user_service: web::Data<UserService>,
+    path: web::Path<i64>,
+    form: web::Json<CommandTemplatesForm>,
+    req: actix_web::HttpRequest,
+    jwt_manager: web::Data<JwtManager>,
+) -> Result<HttpResponse, ServiceError> {
+    // Authentication
+    authenticate_request(&req, &jwt_manager)?;
+
+    let (validate_command_template, modify_command_template) =
+        form.templates().map_err(ServiceError::ValidationError)?;
+    let user_id = path.into_inner();
+
+    let user = user_service
+        .set_command_templates(user_id, validate_command_template, modify_command_template)
+        .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": format!("Command templates for {} saved", user.username),
+        "validate_command_template": user.validate_command_template,
+        "modify_command_template": user.modify_command_template
+    })))
+}
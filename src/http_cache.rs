@@ -0,0 +1,57 @@
+use actix_web::http::header::{self, EntityTag, IfNoneMatch};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use serde::Serialize;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::models::ServiceError;
+use std::time::Duration;
+
+/// For responses carrying credentials; never stored by clients or proxies
+pub const NO_STORE: &str = "no-store";
+
+/// For data the scheduler refreshes: fresh for one `tick`, and only in the client's own
+/// cache since every response is tied to the caller's token
+pub fn scheduler_max_age(tick: Duration) -> String {
+    format!("private, max-age={}", tick.as_secs())
+}
+
+/// Strong ETag over a serialized body
+pub fn etag_for(body: &[u8]) -> EntityTag {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    EntityTag::new_strong(format!("{:016x}", hasher.finish()))
+}
+
+/// JSON response with `Cache-Control` and an `ETag` computed from the body. A request whose
+/// `If-None-Match` lists the same ETag gets `304 Not Modified` without a body.
+pub fn cached_json<T: Serialize>(
+    req: &HttpRequest,
+    cache_control: &str,
+    body: &T,
+) -> Result<HttpResponse, ServiceError> {
+    let body = serde_json::to_vec(body)
+        .map_err(|e| ServiceError::InternalError(format!("Failed to serialize response: {}", e)))?;
+    let etag = etag_for(&body);
+
+    let not_modified = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        None => false,
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .insert_header(header::ETag(etag));
+
+    if not_modified {
+        Ok(response.finish())
+    } else {
+        Ok(response.content_type("application/json").body(body))
+    }
+}
@@ -1,10 +1,14 @@
 pub mod auth;
 pub mod config;
+pub mod db;
 pub mod handlers;
+pub mod http_cache;
 pub mod middleware;
 pub mod models;
 pub mod openapi_config;
+pub mod readiness;
 pub mod repositories;
 pub mod scheduler;
 pub mod services;
 pub mod ssh;
+pub mod ssh_session;
@@ -1,87 +1,140 @@
 use actix_cors::Cors;
-use actix_web::{middleware::Logger, web, App, HttpServer};
-use sqlx::SqlitePool;
+use actix_web::{
+    middleware::{from_fn, Condition, Logger},
+    web, App, HttpServer,
+};
 use utoipa::OpenApi;
 
 mod auth;
 mod config;
+mod db;
 mod handlers;
+mod http_cache;
 mod middleware;
 mod models;
 mod openapi_config;
+mod readiness;
 mod repositories;
 mod scheduler;
 mod services;
 mod ssh;
+#[cfg_attr(feature = "process-ssh", allow(dead_code))]
+mod ssh_session;
 
 use auth::JwtManager;
 use config::ApiDoc;
+use middleware::error_format::negotiate_error_format;
+use middleware::request_log::{log_requests, request_log_enabled};
 use openapi_config::configure_openapi;
-use repositories::{SqliteScheduleRepository, SqliteUsageRepository, SqliteUserRepository, SqliteSettingsRepository};
+use readiness::Readiness;
+use repositories::{
+    SqliteScheduleRepository, SqliteSettingsRepository, SqliteUsageRepository, SqliteUserRepository,
+};
 use scheduler::BackgroundScheduler;
-use services::{ScheduleService, TimeService, UsageService, UserService, SettingsService};
+use services::{
+    DashboardCache, EventLog, LocalTime, ScheduleService, SettingsService, TimeService,
+    UsageService, UserLocks, UserService,
+};
 use std::sync::Arc;
-use crate::models::SettingsEntry;
-
 
 #[actix_web::main]
 async fn main() -> anyhow::Result<()> {
     // Load environment variables from .env file
     dotenvy::dotenv().ok();
 
+    // Send tracing events and actix's log records to stdout, filtered by RUST_LOG
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
+
     // Initialize database
     let database_url =
         std::env::var("DATABASE_URL").unwrap_or_else(|_| "sqlite:instance/timekpr.db".to_string());
-    let pool = SqlitePool::connect(&database_url).await?;
+    let pool = db::pool_options().connect(&database_url).await?;
 
     // Run migrations to ensure database is up to date
     sqlx::migrate!("./migrations").run(&pool).await?;
 
+    // Open the pool's connections before binding so the first requests don't race them
+    let warmup_connections = std::env::var("DB_WARMUP_CONNECTIONS")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|connections| *connections > 0)
+        .unwrap_or_else(|| pool.options().get_max_connections());
+    let readiness = Arc::new(Readiness::new());
+    readiness.warm_up(&pool, warmup_connections).await?;
+
     // Initialize repositories
     let schedule_repository = Arc::new(SqliteScheduleRepository::new(pool.clone()));
     let user_repository = Arc::new(SqliteUserRepository::new(pool.clone()));
     let usage_repository = Arc::new(SqliteUsageRepository::new(pool.clone()));
     let settings_repository = Arc::new(SqliteSettingsRepository::new(pool.clone()));
 
-    // Initialize services with dependency injection
-    let schedule_service_arc = Arc::new(ScheduleService::new(schedule_repository));
+    // Initialize services with dependency injection; all of them agree on what "today" is
+    let local_time = Arc::new(LocalTime::new());
+    let ssh_backend: Arc<dyn ssh::SshBackend> = Arc::new(ssh::SystemSsh::default());
+    let schedule_service_arc = Arc::new(
+        ScheduleService::new(schedule_repository.clone(), user_repository.clone())
+            .with_ssh_backend(ssh_backend.clone())
+            .with_local_time(local_time.clone()),
+    );
     let schedule_service = web::Data::from(schedule_service_arc.clone());
-    let user_service_arc = Arc::new(UserService::new(user_repository.clone()));
+    // One log for every service, served by /api/events/recent
+    let event_log = Arc::new(EventLog::new());
+    let user_service_arc = Arc::new(
+        UserService::new(user_repository.clone())
+            .with_default_schedule(schedule_repository.clone(), settings_repository.clone())
+            .with_settings(settings_repository.clone())
+            .with_event_log(event_log.clone()),
+    );
     let user_service = web::Data::from(user_service_arc.clone());
-    let usage_service_arc = Arc::new(UsageService::new(usage_repository.clone()));
-    let time_service = web::Data::new(TimeService::new(user_repository, usage_repository));
-    let settings_service_arc = Arc::new(SettingsService::new(settings_repository.clone()));
+    let usage_service_arc =
+        Arc::new(UsageService::new(usage_repository.clone()).with_local_time(local_time.clone()));
+    // Time modifications and the scheduler take turns per user
+    let user_locks = Arc::new(UserLocks::new());
+    let time_service = web::Data::new(
+        TimeService::new(user_repository, usage_repository)
+            .with_ssh_backend(ssh_backend.clone())
+            .with_grant_cap(settings_repository.clone())
+            .with_user_locks(user_locks.clone())
+            .with_event_log(event_log.clone())
+            .with_local_time(local_time.clone()),
+    );
+    let settings_service_arc = Arc::new(
+        SettingsService::new(settings_repository.clone()).with_local_time(local_time.clone()),
+    );
     let settings_service = web::Data::from(settings_service_arc.clone());
+    local_time.set_timezone(settings_service_arc.get_timezone().await?);
 
-    // Initialize admin password
-    let admin_hash = settings_service_arc
-        .find_by_key(SettingsEntry::ADMIN_PASSWORD_HASH)
+    // Hash any plaintext password from older versions, then create the credential if missing
+    settings_service_arc.migrate_legacy_admin_password().await?;
+    settings_service_arc
+        .initialize_admin_password(std::env::var("ADMIN_INITIAL_PASSWORD").ok())
         .await?;
 
-    if admin_hash.is_none() {
-        use argon2::password_hash::{rand_core::OsRng, SaltString};
-        use argon2::{Argon2, PasswordHasher};
-
-        let salt = SaltString::generate(&mut OsRng);
-        let argon2 = Argon2::default();
-        let password_hash = argon2
-            .hash_password("admin".as_bytes(), &salt)
-            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
-
-        let new_entry = models::SettingsEntry::new(
-            SettingsEntry::ADMIN_PASSWORD_HASH.to_string(),
-            password_hash.to_string(),
-        );
-        settings_service_arc.add_entry(new_entry.key, new_entry.value).await?;
-        println!("Initialized admin password to 'admin'. Please change it after first login.");
-    }    
+    // Surface SSH key problems once here instead of as an opaque error on every command
+    if let Some(problem) = ssh::SSHClient::key_diagnostic() {
+        eprintln!("Warning: {}", problem);
+    }
 
     // Initialize and start background scheduler
-    let scheduler = Arc::new(BackgroundScheduler::new(
-        user_service_arc.clone(),
-        usage_service_arc,
-        schedule_service_arc,
-    ));
+    let dashboard_cache = Arc::new(DashboardCache::new());
+    let scheduler = Arc::new(
+        BackgroundScheduler::new(
+            user_service_arc.clone(),
+            usage_service_arc,
+            schedule_service_arc,
+            settings_service_arc.clone(),
+            dashboard_cache.clone(),
+        )
+        .with_ssh_backend(ssh_backend)
+        .with_user_locks(user_locks)
+        .with_event_log(event_log.clone())
+        .with_local_time(local_time),
+    );
     scheduler.start().await;
 
     // Initialize JWT manager with secret key
@@ -93,18 +146,28 @@ async fn main() -> anyhow::Result<()> {
     println!("TimeKpr UI Server listening on http://localhost:5000");
     println!("📚 API Documentation: http://localhost:5000/swagger-ui/");
 
+    let request_log = request_log_enabled();
+    if request_log {
+        eprintln!("Warning: DEBUG_REQUEST_LOG is on, request and response bodies are logged");
+    }
+
     // Configure OpenAPI spec with Bearer auth (do this once, outside the closure)
     let openapi_spec = configure_openapi(ApiDoc::openapi());
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone()))
+            .app_data(models::path_config())
             .app_data(web::Data::from(scheduler.clone()))
+            .app_data(web::Data::from(dashboard_cache.clone()))
+            .app_data(web::Data::from(readiness.clone()))
+            .app_data(web::Data::from(event_log.clone()))
             .app_data(jwt_manager.clone())
             .app_data(schedule_service.clone())
             .app_data(user_service.clone())
             .app_data(time_service.clone())
             .app_data(settings_service.clone())
+            .wrap(from_fn(negotiate_error_format))
             .wrap(
                 Cors::default()
                     .allow_any_origin()
@@ -113,6 +176,7 @@ async fn main() -> anyhow::Result<()> {
                     .supports_credentials(),
             )
             .wrap(Logger::default())
+            .wrap(Condition::new(request_log, from_fn(log_requests)))
             // Swagger UI for API documentation
             .service(
                 utoipa_swagger_ui::SwaggerUi::new("/swagger-ui/{_:.*}")
@@ -122,35 +186,205 @@ async fn main() -> anyhow::Result<()> {
             .route("/api/login", web::post().to(handlers::login_api))
             .route("/api/logout", web::post().to(handlers::logout_api))
             .route("/api/dashboard", web::get().to(handlers::dashboard_api))
+            .route(
+                "/api/dashboard/refresh",
+                web::post().to(handlers::refresh_dashboard),
+            )
             .route("/api/admin", web::get().to(handlers::admin_api))
+            .route("/api/stats", web::get().to(handlers::get_stats))
             .route(
                 "/api/change-password",
                 web::post().to(handlers::change_password_api),
             )
+            .route(
+                "/api/auth/readonly-token",
+                web::post().to(handlers::create_readonly_token),
+            )
             .route("/api/users/add", web::post().to(handlers::add_user_api))
             .route(
                 "/api/users/validate/{id}",
                 web::get().to(handlers::validate_user),
             )
+            .route(
+                "/api/users/update/{id}",
+                web::post().to(handlers::update_user),
+            )
             .route(
                 "/api/users/delete/{id}",
                 web::post().to(handlers::delete_user),
             )
+            .route(
+                "/api/users/bulk-delete",
+                web::post().to(handlers::bulk_delete_users),
+            )
             .route("/api/modify-time", web::post().to(handlers::modify_time))
+            .route(
+                "/api/set-time-limit",
+                web::post().to(handlers::set_time_limit),
+            )
+            .route("/api/user/{id}", web::get().to(handlers::get_user_detail))
+            .route(
+                "/api/user/{id}/hosts",
+                web::post().to(handlers::attach_host),
+            )
+            .route(
+                "/api/user/{id}/validation-history",
+                web::get().to(handlers::get_validation_history),
+            )
+            .route(
+                "/api/user/{id}/modification-history",
+                web::get().to(handlers::get_modification_history),
+            )
+            .route(
+                "/api/user/{id}/timekpr-path",
+                web::put().to(handlers::set_timekpr_path),
+            )
+            .route(
+                "/api/user/{id}/command-templates",
+                web::put().to(handlers::set_command_templates),
+            )
+            .route(
+                "/api/user/{id}/adjustment-preview",
+                web::get().to(handlers::adjustment_preview),
+            )
             .route(
                 "/api/user/{id}/usage",
                 web::get().to(handlers::get_user_usage),
             )
+            .route(
+                "/api/user/{id}/snapshot-usage",
+                web::post().to(handlers::snapshot_usage),
+            )
+            .route(
+                "/api/user/{id}/usage/intraday",
+                web::get().to(handlers::get_intraday_usage),
+            )
+            .route(
+                "/api/user/{id}/usage/aggregate",
+                web::get().to(handlers::get_usage_aggregate),
+            )
             .route(
                 "/api/schedule-sync-status/{id}",
                 web::get().to(handlers::get_schedule_sync_status),
             )
+            .route(
+                "/api/intervals-sync-status/{id}",
+                web::get().to(handlers::get_intervals_sync_status),
+            )
             .route(
                 "/api/schedule/update",
                 web::post().to(handlers::update_schedule_api),
             )
+            .route(
+                "/api/schedule/validate",
+                web::post().to(handlers::validate_schedule),
+            )
+            .route(
+                "/api/user/{id}/sync",
+                web::post().to(handlers::sync_schedule),
+            )
+            .route(
+                "/api/user/{id}/sync/{aspect}",
+                web::post().to(handlers::sync_schedule_aspect),
+            )
+            .route(
+                "/api/user/{id}/clear-hours",
+                web::post().to(handlers::clear_allowed_hours),
+            )
+            .route(
+                "/api/user/{id}/timekpr-export",
+                web::get().to(handlers::timekpr_export),
+            )
+            .route(
+                "/api/user/{id}/extend-today",
+                web::post().to(handlers::extend_today),
+            )
+            .route(
+                "/api/user/{id}/import-timekpr",
+                web::post().to(handlers::import_timekpr),
+            )
+            .route(
+                "/api/user/{id}/block-days",
+                web::post().to(handlers::block_days),
+            )
+            .route(
+                "/api/user/{id}/allowed-days",
+                web::get().to(handlers::get_allowed_days),
+            )
+            .route(
+                "/api/user/{id}/lockout",
+                web::get().to(handlers::get_user_lockout),
+            )
+            .route(
+                "/api/user/{id}/lockout",
+                web::post().to(handlers::update_user_lockout),
+            )
+            .route(
+                "/api/user/{id}/intervals",
+                web::get().to(handlers::get_user_intervals),
+            )
+            .route(
+                "/api/user/{id}/intervals",
+                web::post().to(handlers::update_user_intervals),
+            )
+            .route(
+                "/api/settings/week-start",
+                web::post().to(handlers::update_week_start),
+            )
             .route("/api/task-status", web::get().to(handlers::get_task_status))
+            .route(
+                "/api/system/run-scheduler-pass",
+                web::post().to(handlers::run_scheduler_pass),
+            )
+            .route(
+                "/api/capabilities",
+                web::get().to(handlers::get_capabilities),
+            )
+            .route(
+                "/api/events/recent",
+                web::get().to(handlers::get_recent_events),
+            )
             .route("/api/ssh-status", web::get().to(handlers::get_ssh_status))
+            .route(
+                "/api/ssh/key-candidates",
+                web::get().to(handlers::get_ssh_key_candidates),
+            )
+            .route(
+                "/api/ssh/fix-permissions",
+                web::post().to(handlers::fix_ssh_key_permissions),
+            )
+            .route("/api/health/live", web::get().to(handlers::health_live))
+            .route("/api/health/ready", web::get().to(handlers::health_ready))
+            .route("/api/health", web::get().to(handlers::health_detailed))
+            .route(
+                "/api/settings/protect-observability",
+                web::post().to(handlers::update_protect_observability),
+            )
+            .route(
+                "/api/settings/default-schedule",
+                web::post().to(handlers::update_default_schedule),
+            )
+            .route(
+                "/api/settings/scheduler-window",
+                web::post().to(handlers::update_scheduler_window),
+            )
+            .route(
+                "/api/settings/check-interval",
+                web::post().to(handlers::update_check_interval),
+            )
+            .route(
+                "/api/settings/timezone",
+                web::post().to(handlers::update_timezone),
+            )
+            .route("/api/timezones", web::get().to(handlers::list_timezones))
+            .route(
+                "/api/settings/grant-cap",
+                web::post().to(handlers::update_grant_cap),
+            )
+            .route(
+                "/api/settings/revalidate-after-modify",
+                web::post().to(handlers::update_revalidate_after_modify),
+            )
     })
     .bind("0.0.0.0:5000")?
     .run()
@@ -1,12 +1,41 @@
-use crate::auth::{verify_jwt, JwtManager};
+use crate::auth::{verify_jwt, Claims, JwtManager, TokenScope};
+use crate::models::ServiceError;
 use actix_web;
+use actix_web::http::Method;
 
+/// Routes a read-only token may call, as registered; only with GET or HEAD
+pub const READONLY_ROUTES: &[&str] = &[
+    "/api/dashboard",
+    "/api/stats",
+    "/api/user/{id}/usage",
+    "/api/user/{id}/usage/intraday",
+    "/api/user/{id}/usage/aggregate",
+];
+
+/// 401 without a valid token, 403 when its scope doesn't cover the request
 pub fn authenticate_request(
     req: &actix_web::HttpRequest,
     jwt_manager: &JwtManager,
-) -> Result<(), actix_web::Error> {
-    match verify_jwt(req, jwt_manager) {
-        Ok(_claims) => Ok(()),
-        Err(e) => Err(e),
+) -> Result<(), ServiceError> {
+    authenticated_claims(req, jwt_manager).map(|_| ())
+}
+
+/// Claims of a valid token whose scope covers this request
+pub fn authenticated_claims(
+    req: &actix_web::HttpRequest,
+    jwt_manager: &JwtManager,
+) -> Result<Claims, ServiceError> {
+    let claims = verify_jwt(req, jwt_manager)
+        .map_err(|_| ServiceError::AuthenticationError("Not authenticated".to_string()))?;
+    if claims.scope == TokenScope::Readonly && !readonly_allows(req) {
+        return Err(ServiceError::Forbidden("Token is read-only".to_string()));
     }
+    Ok(claims)
+}
+
+fn readonly_allows(req: &actix_web::HttpRequest) -> bool {
+    (req.method() == Method::GET || req.method() == Method::HEAD)
+        && req
+            .match_pattern()
+            .is_some_and(|pattern| READONLY_ROUTES.contains(&pattern.as_str()))
 }
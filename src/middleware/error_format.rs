@@ -0,0 +1,99 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, Accept, ContentType};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage, HttpResponse};
+use serde_json::Value;
+
+/// Whether the client ranks HTML above JSON. No `Accept` header, `*/*` or anything else
+/// that doesn't name `text/html` first keeps the JSON envelope.
+pub fn prefers_html(accept: Option<&Accept>) -> bool {
+    let Some(accept) = accept else {
+        return false;
+    };
+    accept
+        .ranked()
+        .iter()
+        .find_map(|mime| match mime.essence_str() {
+            "text/html" | "application/xhtml+xml" => Some(true),
+            "application/json" | "application/*" | "*/*" => Some(false),
+            _ => None,
+        })
+        .unwrap_or(false)
+}
+
+/// Turn JSON error responses into a minimal HTML page for clients that prefer HTML,
+/// such as a browser opening an API URL directly. Successful responses are left as they are.
+pub async fn negotiate_error_format(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let html = prefers_html(req.get_header::<Accept>().as_ref());
+
+    let res = next.call(req).await?.map_into_boxed_body();
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if !html || !is_json || !(res.status().is_client_error() || res.status().is_server_error()) {
+        return Ok(res);
+    }
+
+    let (req, res) = res.into_parts();
+    let status = res.status();
+    let mut page = HttpResponse::build(status);
+    for (name, value) in res.headers() {
+        if name != header::CONTENT_TYPE && name != header::CONTENT_LENGTH {
+            page.append_header((name.clone(), value.clone()));
+        }
+    }
+
+    let response_body = body::to_bytes(res.into_body())
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    let message = serde_json::from_slice::<Value>(&response_body)
+        .ok()
+        .and_then(|json| {
+            json.get("message")
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        });
+
+    let res = page
+        .content_type(ContentType::html())
+        .body(render_error_page(
+            status.as_u16(),
+            status.canonical_reason(),
+            message.as_deref(),
+        ));
+    Ok(ServiceResponse::new(req, res))
+}
+
+/// HTML page for an error status, with the envelope's message when there is one
+pub fn render_error_page(code: u16, reason: Option<&str>, message: Option<&str>) -> String {
+    let title = format!("{} {}", code, reason.unwrap_or("Error"));
+    let message = message
+        .map(|message| format!("<p>{}</p>", escape_html(message)))
+        .unwrap_or_default();
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head><meta charset=\"utf-8\"><title>{title}</title></head>\n<body><h1>{title}</h1>{message}</body>\n</html>\n",
+        title = escape_html(&title),
+        message = message
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
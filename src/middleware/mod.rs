@@ -1 +1,3 @@
 pub mod auth;
+pub mod error_format;
+pub mod request_log;
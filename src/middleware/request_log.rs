@@ -0,0 +1,105 @@
+use actix_web::body::{self, BoxBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web::Bytes;
+use serde_json::Value;
+
+/// Bodies longer than this are cut off in the log
+pub const MAX_LOGGED_BODY_BYTES: usize = 4096;
+
+const REDACTED: &str = "[REDACTED]";
+
+/// Whether request logging was switched on with `DEBUG_REQUEST_LOG`; off by default
+pub fn request_log_enabled() -> bool {
+    std::env::var("DEBUG_REQUEST_LOG")
+        .map(|value| {
+            matches!(
+                value.trim().to_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Log method, path, status and both bodies of every request. Meant for debugging a
+/// frontend integration; enable it through `DEBUG_REQUEST_LOG` only while needed.
+pub async fn log_requests(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let path = req.path().to_string();
+
+    // Read the body and hand an identical copy back to the handler
+    let request_body = req.extract::<Bytes>().await?;
+    req.set_payload(Payload::from(request_body.clone()));
+
+    let res = next.call(req).await?;
+    let status = res.status();
+
+    let (req, res) = res.into_parts();
+    let (res, response_body) = res.into_parts();
+    let response_body = body::to_bytes(response_body)
+        .await
+        .map_err(|e| actix_web::error::ErrorInternalServerError(e.into()))?;
+
+    tracing::info!(
+        target: "request_log",
+        "{} {} -> {} request={} response={}",
+        method,
+        path,
+        status.as_u16(),
+        render_body(&request_body, MAX_LOGGED_BODY_BYTES),
+        render_body(&response_body, MAX_LOGGED_BODY_BYTES)
+    );
+
+    let res = res.set_body(BoxBody::new(response_body));
+    Ok(ServiceResponse::new(req, res))
+}
+
+/// Loggable form of a body: JSON with secrets redacted, capped at `max_bytes`.
+/// Anything that is not JSON is reported by size only, since it cannot be redacted.
+pub fn render_body(body: &[u8], max_bytes: usize) -> String {
+    if body.is_empty() {
+        return "-".to_string();
+    }
+
+    let mut json = match serde_json::from_slice::<Value>(body) {
+        Ok(json) => json,
+        Err(_) => return format!("<{} bytes, not JSON>", body.len()),
+    };
+    redact(&mut json);
+
+    let rendered = json.to_string();
+    if rendered.len() <= max_bytes {
+        return rendered;
+    }
+    let mut end = max_bytes;
+    while !rendered.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}... ({} bytes total)", &rendered[..end], rendered.len())
+}
+
+fn is_secret_key(key: &str) -> bool {
+    let key = key.to_lowercase();
+    ["password", "token", "secret"]
+        .iter()
+        .any(|secret| key.contains(secret))
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if is_secret_key(key) {
+                    *value = Value::String(REDACTED.to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
@@ -1,7 +1,26 @@
-use crate::models::schedule::{WeeklyHours, WeeklyTimeIntervals};
+use crate::models::event::Event;
+use crate::models::schedule::{
+    DayIntervals, DayOfWeek, DaySyncResult, FirstIntervals, PlayTimeSettings, TimeInterval,
+    WeekStart, WeeklyHours, WeeklyTimeIntervals,
+};
+use crate::models::user::{
+    check_command_template, ManagedUser, UserStatus, MODIFY_COMMAND_PLACEHOLDERS,
+    VALIDATE_COMMAND_PLACEHOLDERS,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+/// The one format timestamps leave the API in: RFC 3339 in UTC, e.g. "2024-05-01T17:30:00Z"
+pub fn format_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Seconds as the dashboard shows them, e.g. "2h 45m"
+pub fn format_hours_minutes(seconds: i64) -> String {
+    format!("{}h {}m", seconds / 3600, (seconds % 3600) / 60)
+}
+
 // =============================================================================
 // REQUEST TYPES
 // =============================================================================
@@ -9,13 +28,23 @@ use utoipa::ToSchema;
 #[derive(Deserialize, ToSchema)]
 pub struct ScheduleUpdateForm {
     pub user_id: i64,
-    pub monday: f64,
-    pub tuesday: f64,
-    pub wednesday: f64,
-    pub thursday: f64,
-    pub friday: f64,
-    pub saturday: f64,
-    pub sunday: f64,
+    /// Hours per day; each day needs either hours or minutes
+    pub monday: Option<f64>,
+    pub tuesday: Option<f64>,
+    pub wednesday: Option<f64>,
+    pub thursday: Option<f64>,
+    pub friday: Option<f64>,
+    pub saturday: Option<f64>,
+    pub sunday: Option<f64>,
+
+    /// Whole minutes per day (0-1440), taking precedence over the hours field
+    pub monday_minutes: Option<i64>,
+    pub tuesday_minutes: Option<i64>,
+    pub wednesday_minutes: Option<i64>,
+    pub thursday_minutes: Option<i64>,
+    pub friday_minutes: Option<i64>,
+    pub saturday_minutes: Option<i64>,
+    pub sunday_minutes: Option<i64>,
 
     // Time intervals for each day (format: "HH:MM")
     pub monday_start_time: Option<String>,
@@ -38,6 +67,205 @@ pub struct ScheduleUpdateForm {
 
     pub sunday_start_time: Option<String>,
     pub sunday_end_time: Option<String>,
+
+    /// Several allowed-hours intervals for a day, e.g. 07:00-09:00 and 15:00-18:00; takes
+    /// precedence over that day's start and end time
+    pub monday_intervals: Option<Vec<TimeInterval>>,
+    pub tuesday_intervals: Option<Vec<TimeInterval>>,
+    pub wednesday_intervals: Option<Vec<TimeInterval>>,
+    pub thursday_intervals: Option<Vec<TimeInterval>>,
+    pub friday_intervals: Option<Vec<TimeInterval>>,
+    pub saturday_intervals: Option<Vec<TimeInterval>>,
+    pub sunday_intervals: Option<Vec<TimeInterval>>,
+
+    /// PlayTime limits; left unchanged when omitted
+    pub playtime: Option<PlayTimeSettings>,
+}
+
+impl ScheduleUpdateForm {
+    /// Hours per day, using the minutes field where one is given
+    pub fn hours(&self) -> WeeklyHours {
+        let day = |day| {
+            let (hours, minutes) = self.limit_fields(day);
+            minutes.map_or(hours.unwrap_or(0.0), |minutes| minutes as f64 / 60.0)
+        };
+        WeeklyHours {
+            monday: day(DayOfWeek::Monday),
+            tuesday: day(DayOfWeek::Tuesday),
+            wednesday: day(DayOfWeek::Wednesday),
+            thursday: day(DayOfWeek::Thursday),
+            friday: day(DayOfWeek::Friday),
+            saturday: day(DayOfWeek::Saturday),
+            sunday: day(DayOfWeek::Sunday),
+        }
+    }
+
+    fn limit_fields(&self, day: DayOfWeek) -> (Option<f64>, Option<i64>) {
+        match day {
+            DayOfWeek::Monday => (self.monday, self.monday_minutes),
+            DayOfWeek::Tuesday => (self.tuesday, self.tuesday_minutes),
+            DayOfWeek::Wednesday => (self.wednesday, self.wednesday_minutes),
+            DayOfWeek::Thursday => (self.thursday, self.thursday_minutes),
+            DayOfWeek::Friday => (self.friday, self.friday_minutes),
+            DayOfWeek::Saturday => (self.saturday, self.saturday_minutes),
+            DayOfWeek::Sunday => (self.sunday, self.sunday_minutes),
+        }
+    }
+
+    /// Days with neither hours nor minutes, or with minutes outside 0-1440
+    pub fn limit_errors(&self) -> Vec<String> {
+        DayOfWeek::iter()
+            .filter_map(|day| match self.limit_fields(day) {
+                (None, None) => Some(format!("{} needs hours or minutes", day.label())),
+                (_, Some(minutes)) if !(0..=1440).contains(&minutes) => Some(format!(
+                    "{} minutes must be between 0 and 1440, got {}",
+                    day.label(),
+                    minutes
+                )),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn interval_fields(&self, day: DayOfWeek) -> (&Option<String>, &Option<String>) {
+        match day {
+            DayOfWeek::Monday => (&self.monday_start_time, &self.monday_end_time),
+            DayOfWeek::Tuesday => (&self.tuesday_start_time, &self.tuesday_end_time),
+            DayOfWeek::Wednesday => (&self.wednesday_start_time, &self.wednesday_end_time),
+            DayOfWeek::Thursday => (&self.thursday_start_time, &self.thursday_end_time),
+            DayOfWeek::Friday => (&self.friday_start_time, &self.friday_end_time),
+            DayOfWeek::Saturday => (&self.saturday_start_time, &self.saturday_end_time),
+            DayOfWeek::Sunday => (&self.sunday_start_time, &self.sunday_end_time),
+        }
+    }
+
+    fn interval_list(&self, day: DayOfWeek) -> &Option<Vec<TimeInterval>> {
+        match day {
+            DayOfWeek::Monday => &self.monday_intervals,
+            DayOfWeek::Tuesday => &self.tuesday_intervals,
+            DayOfWeek::Wednesday => &self.wednesday_intervals,
+            DayOfWeek::Thursday => &self.thursday_intervals,
+            DayOfWeek::Friday => &self.friday_intervals,
+            DayOfWeek::Saturday => &self.saturday_intervals,
+            DayOfWeek::Sunday => &self.sunday_intervals,
+        }
+    }
+
+    /// Intervals when any start time or interval list is given (missing times default to
+    /// the whole day); None for an hours-only update. Errors name the day, one per
+    /// invalid day.
+    pub fn intervals(&self) -> Result<Option<WeeklyTimeIntervals>, Vec<String>> {
+        let has_intervals = DayOfWeek::iter()
+            .any(|day| self.interval_fields(day).0.is_some() || self.interval_list(day).is_some());
+        if !has_intervals {
+            return Ok(None);
+        }
+
+        let mut intervals = WeeklyTimeIntervals::default();
+        let mut errors = Vec::new();
+        for day in DayOfWeek::iter() {
+            let day_intervals = match self.interval_list(day) {
+                Some(list) => check_intervals(list),
+                None => {
+                    let (start_time, end_time) = self.interval_fields(day);
+                    TimeInterval::new(
+                        start_time.clone().unwrap_or("00:00".to_string()),
+                        end_time.clone().unwrap_or("23:59".to_string()),
+                    )
+                    .map(|interval| vec![interval])
+                }
+            };
+            match day_intervals {
+                Ok(day_intervals) => intervals.set(day, day_intervals),
+                Err(e) => errors.push(format!("{} interval: {}", day.label(), e)),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Some(intervals))
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Everything an update with this form would reject, without stopping at the first
+    pub fn validation_errors(&self) -> Vec<String> {
+        // Missing or bad minutes make the hours meaningless; report only those first
+        let mut errors = self.limit_errors();
+        if errors.is_empty() {
+            errors = self.hours().errors();
+        }
+        if let Err(interval_errors) = self.intervals() {
+            errors.extend(interval_errors);
+        }
+        if let Some(Err(error)) = self.playtime.as_ref().map(PlayTimeSettings::validate) {
+            errors.push(error);
+        }
+        errors
+    }
+
+    /// Non-fatal problems with a schedule that would otherwise save: days whose allowed
+    /// hours are shorter than their limit
+    pub fn warnings(&self) -> Vec<String> {
+        if !self.limit_errors().is_empty() {
+            return Vec::new();
+        }
+        match self.intervals() {
+            Ok(Some(intervals)) => intervals.unreachable_limit_warnings(&self.hours()),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Re-validate intervals, since deserializing skips `TimeInterval::new`, and check they
+/// fit together on one day
+fn check_intervals(intervals: &[TimeInterval]) -> Result<Vec<TimeInterval>, String> {
+    let intervals = intervals
+        .iter()
+        .map(|interval| TimeInterval::new(interval.start_time.clone(), interval.end_time.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+    WeeklyTimeIntervals::check_day(intervals)
+}
+
+/// Allowed-hours changes for some days; days left out keep their stored intervals. Each
+/// day takes one interval or an array of them.
+#[derive(Deserialize, ToSchema)]
+pub struct IntervalsUpdateForm {
+    pub monday: Option<DayIntervals>,
+    pub tuesday: Option<DayIntervals>,
+    pub wednesday: Option<DayIntervals>,
+    pub thursday: Option<DayIntervals>,
+    pub friday: Option<DayIntervals>,
+    pub saturday: Option<DayIntervals>,
+    pub sunday: Option<DayIntervals>,
+}
+
+impl IntervalsUpdateForm {
+    /// Provided days, validated
+    pub fn changes(&self) -> Result<Vec<(DayOfWeek, Vec<TimeInterval>)>, String> {
+        let days = [
+            (DayOfWeek::Monday, &self.monday),
+            (DayOfWeek::Tuesday, &self.tuesday),
+            (DayOfWeek::Wednesday, &self.wednesday),
+            (DayOfWeek::Thursday, &self.thursday),
+            (DayOfWeek::Friday, &self.friday),
+            (DayOfWeek::Saturday, &self.saturday),
+            (DayOfWeek::Sunday, &self.sunday),
+        ];
+
+        days.into_iter()
+            .filter_map(|(day, intervals)| {
+                intervals
+                    .as_ref()
+                    .map(|intervals| (day, intervals.clone().into_vec()))
+            })
+            .map(|(day, intervals)| {
+                check_intervals(&intervals)
+                    .map(|intervals| (day, intervals))
+                    .map_err(|e| format!("{} interval: {}", day.label(), e))
+            })
+            .collect()
+    }
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -46,10 +274,148 @@ pub struct LoginForm {
     pub password: String,
 }
 
+#[derive(Deserialize, ToSchema)]
+pub struct ReadonlyTokenForm {
+    /// Days until the token expires; defaults to 365, at most 3650
+    pub days: Option<i64>,
+}
+
 #[derive(Deserialize, ToSchema)]
 pub struct AddUserForm {
     pub username: String,
     pub system_ip: String,
+    /// Check the user over SSH before returning (default). When false the user is saved
+    /// unchecked and validated by the background scheduler on its next pass.
+    #[serde(default = "default_validate")]
+    pub validate: bool,
+    /// What to do when validation cannot reach the host
+    #[serde(default)]
+    pub on_unreachable: UnreachableOnAdd,
+}
+
+/// New username or address for an existing user; both are required
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateUserForm {
+    pub username: String,
+    pub system_ip: String,
+}
+
+fn default_validate() -> bool {
+    true
+}
+
+/// What adding a user does when its host does not answer the validation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UnreachableOnAdd {
+    /// Save it as not valid, like any failed check
+    #[default]
+    Invalid,
+    /// Save it unchecked so the background scheduler validates it once the host is back
+    Pending,
+    /// Refuse with 400 and save nothing
+    Reject,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AttachHostForm {
+    pub system_ip: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TimekprPathForm {
+    /// Full path of timekpra on the host, e.g. /usr/local/bin/timekpra; empty or null
+    /// goes back to running plain `timekpra`
+    pub timekpr_path: Option<String>,
+}
+
+impl TimekprPathForm {
+    /// The trimmed path, None for the default. Only path characters are accepted since
+    /// the value becomes part of a remote shell command
+    pub fn path(&self) -> Result<Option<String>, String> {
+        let Some(path) = self
+            .timekpr_path
+            .as_deref()
+            .map(str::trim)
+            .filter(|path| !path.is_empty())
+        else {
+            return Ok(None);
+        };
+
+        if path
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '/' | '.' | '_' | '-'))
+        {
+            Ok(Some(path.to_string()))
+        } else {
+            Err(format!(
+                "timekpr path may only contain letters, digits, '/', '.', '_' and '-', got '{}'",
+                path
+            ))
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CommandTemplatesForm {
+    /// Run instead of `timekpra --userinfo {username}`; must contain {username}.
+    /// Empty or null goes back to the built-in command
+    pub validate_command_template: Option<String>,
+    /// Run instead of `timekpra --settimeleft {username} {op} {seconds}`; must contain all
+    /// three placeholders. Empty or null goes back to the built-in command
+    pub modify_command_template: Option<String>,
+}
+
+impl CommandTemplatesForm {
+    /// The trimmed (validate, modify) templates, None for the built-in commands
+    pub fn templates(&self) -> Result<(Option<String>, Option<String>), String> {
+        let check = |template: &Option<String>, placeholders: &[&str], which: &str| {
+            let Some(template) = template
+                .as_deref()
+                .map(str::trim)
+                .filter(|template| !template.is_empty())
+            else {
+                return Ok(None);
+            };
+            check_command_template(template, placeholders)
+                .map(|_| Some(template.to_string()))
+                .map_err(|e| format!("{} command: {}", which, e))
+        };
+
+        Ok((
+            check(
+                &self.validate_command_template,
+                VALIDATE_COMMAND_PLACEHOLDERS,
+                "Validate",
+            )?,
+            check(
+                &self.modify_command_template,
+                MODIFY_COMMAND_PLACEHOLDERS,
+                "Modify",
+            )?,
+        ))
+    }
+}
+
+/// What deleting a user does with changes that were never pushed to its hosts
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum PendingOnDelete {
+    /// Refuse with 409 Conflict
+    Block,
+    /// Delete anyway and record what was dropped in the audit log
+    #[default]
+    Discard,
+}
+
+#[derive(Deserialize)]
+pub struct DeleteUserQuery {
+    pub on_pending: Option<PendingOnDelete>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkDeleteForm {
+    pub user_ids: Vec<i64>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -57,6 +423,185 @@ pub struct ModifyTimeForm {
     pub user_id: i64,
     pub operation: String,
     pub seconds: i64,
+    /// Optional note such as "homework finished", up to 200 characters
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetTimeLimitForm {
+    pub user_id: i64,
+    /// Time left today, replacing whatever was left
+    pub seconds: i64,
+}
+
+#[derive(Deserialize)]
+pub struct ModificationHistoryQuery {
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct AdjustmentPreviewQuery {
+    pub operation: String,
+    pub seconds: i64,
+}
+
+#[derive(Deserialize)]
+pub struct UsageQuery {
+    pub days: Option<i32>,
+    pub unit: Option<UsageUnit>,
+    pub format: Option<UsageFormat>,
+}
+
+/// Layout of the usage `data` field
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageFormat {
+    /// `[{date, hours, value}]`, one object per day
+    #[default]
+    Objects,
+    /// `{labels, hours, values}`: parallel arrays, as most chart libraries take them
+    Arrays,
+}
+
+/// Length of the periods usage is summed over
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageGranularity {
+    #[default]
+    Daily,
+    /// ISO weeks, Monday to Sunday
+    Weekly,
+    /// Calendar months
+    Monthly,
+}
+
+impl UsageGranularity {
+    /// Periods returned unless asked for another number: a week of days, two months of
+    /// weeks or half a year of months
+    pub fn default_periods(self) -> i32 {
+        match self {
+            UsageGranularity::Daily => 7,
+            UsageGranularity::Weekly => 8,
+            UsageGranularity::Monthly => 6,
+        }
+    }
+
+    /// Longest period in days, for keeping a request within the usage range cap
+    pub fn max_days(self) -> i32 {
+        match self {
+            UsageGranularity::Daily => 1,
+            UsageGranularity::Weekly => 7,
+            UsageGranularity::Monthly => 31,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AggregateUsageQuery {
+    pub by: Option<UsageGranularity>,
+    /// Periods to return, the current one included
+    pub periods: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct IntradayUsageQuery {
+    /// Day to return as YYYY-MM-DD (UTC); defaults to today
+    pub date: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RecentEventsQuery {
+    /// Most events to return; defaults to 50, never more than the log keeps
+    pub limit: Option<usize>,
+}
+
+/// Unit for the numeric usage values returned to the client
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum UsageUnit {
+    #[default]
+    Hours,
+    Minutes,
+    Seconds,
+}
+
+impl UsageUnit {
+    pub fn convert(self, seconds: i64) -> f64 {
+        match self {
+            UsageUnit::Hours => seconds as f64 / 3600.0,
+            UsageUnit::Minutes => seconds as f64 / 60.0,
+            UsageUnit::Seconds => seconds as f64,
+        }
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct WeekStartForm {
+    pub week_start: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ProtectObservabilityForm {
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CheckIntervalForm {
+    /// Seconds between background scheduler passes, 5-3600
+    pub seconds: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SchedulerWindowForm {
+    /// Local time ("HH:MM") from which the scheduler contacts hosts; null together with
+    /// `active_to` removes the window
+    pub active_from: Option<String>,
+    pub active_to: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ExtendTodayForm {
+    /// Minutes added to today's limit (1-1440)
+    pub minutes: i64,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TimekprImportForm {
+    /// Contents of the user's timekpr config file; null reads the host's `--userinfo` output
+    /// over SSH instead
+    pub config: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BlockDaysForm {
+    /// Days to block, e.g. ["monday", "tuesday"]
+    pub days: Vec<DayOfWeek>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct GrantCapForm {
+    /// Most seconds one user may be granted per day with '+'; null removes the cap
+    pub max_daily_grant_seconds: Option<i64>,
+}
+
+/// Whether applying a time adjustment reads the host's config again right away (default).
+/// Turning it off saves one SSH round-trip per adjustment on slow links; the stored time
+/// left then stays as it was until the scheduler's next pass refreshes it.
+#[derive(Deserialize, ToSchema)]
+pub struct RevalidateAfterModifyForm {
+    pub enabled: bool,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TimezoneForm {
+    /// IANA timezone name such as "Europe/Berlin"; null removes the setting
+    pub timezone: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct DefaultScheduleForm {
+    /// Daily hours applied to newly added users; null disables the default
+    pub hours: Option<WeeklyHours>,
 }
 
 #[derive(Deserialize, ToSchema)]
@@ -93,21 +638,71 @@ pub struct LoginResponse {
 }
 
 // User management responses
-#[derive(Serialize, ToSchema)]
+#[derive(Clone, Serialize, ToSchema)]
 pub struct UserData {
     pub id: i64,
     pub username: String,
     pub system_ip: String,
     pub time_left: String,
-    pub last_checked: String,
+    /// RFC 3339; null until the user has been checked
+    pub last_checked: Option<String>,
     pub pending_adjustment: Option<String>,
     pub pending_schedule: bool,
+    /// Weekly and monthly figures in seconds from the host's last check; null when its
+    /// timekpr doesn't report them
+    pub time_left_week: Option<i64>,
+    pub time_spent_week: Option<i64>,
+    pub time_limit_week: Option<i64>,
+    pub time_left_month: Option<i64>,
+    pub time_spent_month: Option<i64>,
+    pub time_limit_month: Option<i64>,
+    /// Seconds allowed today after overrides and bonuses; None when no limit is configured
+    pub effective_limit_today: Option<i64>,
+    /// Seconds until the daily limit resets
+    pub seconds_until_reset: i64,
+    pub status: DashboardStatus,
+    /// The schedule stopped syncing after repeated failures and waits for a manual sync
+    pub needs_attention: bool,
+    /// Set by a live refresh when the host did not answer and the figures are from an earlier check
+    pub stale: bool,
+}
+
+/// Where a dashboard user stands, from the stored outcome of the last check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DashboardStatus {
+    Online,
+    /// Host unreachable or SSH authentication failed
+    Offline,
+    /// The host answered but has no timekpr account for the user (or no timekpr at all)
+    UserMissing,
+    NeverChecked,
+}
+
+impl DashboardStatus {
+    pub fn of(user: &ManagedUser) -> Self {
+        if user.last_checked.is_none() {
+            return DashboardStatus::NeverChecked;
+        }
+        match user.last_status.as_deref().map(str::parse::<UserStatus>) {
+            Some(Ok(UserStatus::Ok)) => DashboardStatus::Online,
+            // A command stuck on the host is as unusable as an unreachable host
+            Some(Ok(UserStatus::Offline | UserStatus::CommandTimeout)) => DashboardStatus::Offline,
+            Some(Ok(UserStatus::InvalidUser | UserStatus::TimekprMissing)) => {
+                DashboardStatus::UserMissing
+            }
+            // Checked before outcomes were stored
+            Some(Err(_)) | None => DashboardStatus::Offline,
+        }
+    }
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct DashboardResponse {
     pub success: bool,
     pub users: Vec<UserData>,
+    /// When the data was computed; older than now when served from the scheduler's cache
+    pub as_of: String,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -116,7 +711,15 @@ pub struct AdminUserData {
     pub username: String,
     pub system_ip: String,
     pub is_valid: bool,
-    pub last_checked: String,
+    /// RFC 3339 timestamps, null when unknown
+    pub date_added: Option<String>,
+    pub last_checked: Option<String>,
+    pub status: Option<UserStatus>,
+    pub status_label: String,
+    /// Primary user id when this row is an additional host
+    pub host_group_id: Option<i64>,
+    /// Failed checks in a row; `status` only turns offline past the configured threshold
+    pub consecutive_failures: i64,
 }
 
 #[derive(Serialize, ToSchema)]
@@ -125,6 +728,38 @@ pub struct AdminResponse {
     pub users: Vec<AdminUserData>,
 }
 
+#[derive(Serialize, ToSchema)]
+pub struct BulkDeleteResult {
+    pub user_id: i64,
+    /// "deleted" or "not_found"
+    pub status: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkDeleteResponse {
+    pub success: bool,
+    pub results: Vec<BulkDeleteResult>,
+    pub deleted: usize,
+    pub not_found: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AddUserResponse {
+    pub success: bool,
+    pub message: String,
+    /// Id of the new user, as taken by the other user endpoints
+    pub user_id: i64,
+    /// Outcome of the SSH check; null when the user was added without validation
+    pub validation: Option<AddUserValidation>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AddUserValidation {
+    pub status: UserStatus,
+    pub reachable: bool,
+    pub user_missing: bool,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct ModifyTimeResponse {
     pub success: bool,
@@ -132,6 +767,7 @@ pub struct ModifyTimeResponse {
     pub username: String,
     pub refresh: Option<bool>,
     pub pending: Option<bool>,
+    pub reason: Option<String>,
 }
 
 // Usage tracking responses
@@ -139,20 +775,78 @@ pub struct ModifyTimeResponse {
 pub struct UsageData {
     pub date: String,
     pub hours: f64,
+    /// Usage expressed in the requested unit
+    pub value: f64,
+}
+
+/// Usage days as parallel arrays (`?format=arrays`); index i of each array is the same day
+#[derive(Serialize, ToSchema)]
+pub struct UsageArrays {
+    pub labels: Vec<String>,
+    pub hours: Vec<f64>,
+    /// Usage expressed in the requested unit
+    pub values: Vec<f64>,
+}
+
+impl From<&[UsageData]> for UsageArrays {
+    fn from(days: &[UsageData]) -> Self {
+        Self {
+            labels: days.iter().map(|day| day.date.clone()).collect(),
+            hours: days.iter().map(|day| day.hours).collect(),
+            values: days.iter().map(|day| day.value).collect(),
+        }
+    }
+}
+
+/// Usage summed over one day, ISO week or calendar month
+#[derive(Serialize, ToSchema)]
+pub struct UsageBucket {
+    /// "2026-10-17", "2026-W42" or "2026-10"
+    pub period: String,
+    /// First and last day of the period
+    pub start: String,
+    pub end: String,
+    pub hours: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AggregatedUsageResponse {
+    pub success: bool,
+    pub username: String,
+    pub by: UsageGranularity,
+    /// Every period of the range, oldest first, with zero for periods without usage
+    pub data: Vec<UsageBucket>,
 }
 
 #[derive(Serialize, ToSchema)]
 pub struct UsageResponse {
     pub success: bool,
+    /// Every day of the range, oldest first; a `UsageArrays` object with `format=arrays`
     pub data: Vec<UsageData>,
+    pub format: UsageFormat,
     pub username: String,
+    pub days: i32,
+    pub unit: UsageUnit,
 }
 
 // Schedule management responses
 #[derive(Serialize, ToSchema)]
 pub struct ScheduleWithIntervals {
     pub hours: WeeklyHours,
-    pub intervals: WeeklyTimeIntervals,
+    /// Each day's first interval
+    pub intervals: FirstIntervals,
+    /// Every interval of each day, in order
+    pub interval_lists: WeeklyTimeIntervals,
+}
+
+impl ScheduleWithIntervals {
+    pub fn new(hours: WeeklyHours, intervals: WeeklyTimeIntervals) -> Self {
+        Self {
+            hours,
+            intervals: FirstIntervals::from(&intervals),
+            interval_lists: intervals,
+        }
+    }
 }
 
 #[derive(Serialize, ToSchema)]
@@ -162,6 +856,14 @@ pub struct ScheduleSyncResponse {
     pub schedule: Option<ScheduleWithIntervals>,
     pub last_synced: Option<String>,
     pub last_modified: Option<String>,
+    /// Failed background syncs in a row
+    pub sync_attempts: i64,
+    /// RFC 3339; when the scheduler retries a failed sync, null when no retry is waiting
+    pub next_sync_attempt: Option<String>,
+    /// Too many failed syncs in a row; the scheduler waits for `POST /api/user/{id}/sync`
+    pub needs_attention: bool,
+    pub week_start: WeekStart,
+    pub day_order: Vec<DayOfWeek>,
 }
 
 // Service status type (used by service layer)
@@ -171,13 +873,85 @@ pub struct ScheduleSyncStatus {
     pub schedule: Option<ScheduleWithIntervals>,
     pub last_synced: Option<String>,
     pub last_modified: Option<String>,
+    pub sync_attempts: i64,
+    pub next_sync_attempt: Option<String>,
+    pub needs_attention: bool,
+}
+
+/// Result of a manual push; `days` is set for aspects pushed day by day
+#[derive(Serialize)]
+pub struct AspectSyncOutcome {
+    pub message: String,
+    pub days: Option<Vec<DaySyncResult>>,
+}
+
+/// Allowed-hours sync state. Intervals are stored on the schedule row, so they
+/// share its sync flag and timestamps.
+#[derive(Serialize)]
+pub struct IntervalsSyncStatus {
+    pub needs_sync: bool,
+    pub last_synced: Option<String>,
+    /// Days with a restriction narrower than the full day
+    pub enabled_intervals: usize,
+    pub total_intervals: usize,
+}
+
+/// Outcome of a dry-run schedule validation
+#[derive(Serialize, ToSchema)]
+pub struct ScheduleValidationResponse {
+    pub success: bool,
+    pub valid: bool,
+    pub errors: Vec<String>,
+    /// Accepted as is, but probably not intended
+    pub warnings: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IntervalsSyncResponse {
+    pub success: bool,
+    pub user_id: i64,
+    pub username: String,
+    pub needs_sync: bool,
+    pub last_synced: Option<String>,
+    pub enabled_intervals: usize,
+    pub total_intervals: usize,
+}
+
+/// One day of the stored schedule in timekpr's own argument syntax
+#[derive(Serialize, ToSchema)]
+pub struct TimekprDayExport {
+    pub day: DayOfWeek,
+    /// timekpr day number, 1 = Monday
+    pub day_index: u8,
+    /// `--setallowedhours` argument, e.g. "16[30-59];17;18"; null when the day has no
+    /// allowed hours or its interval is malformed
+    pub allowed_hours: Option<String>,
+    /// Seconds passed to `--settimelimits`; null when the day is not allowed
+    pub time_limit: Option<i64>,
+}
+
+/// The stored schedule as the timekpra arguments a sync would send
+#[derive(Serialize, ToSchema)]
+pub struct TimekprExport {
+    /// `--setalloweddays` argument, e.g. "1;2;3;4;5"
+    pub allowed_days: String,
+    /// `--settimelimits` argument, one value per allowed day
+    pub time_limits: String,
+    pub days: Vec<TimekprDayExport>,
+    /// Full timekpra commands, in the order the scheduler runs them
+    pub commands: Vec<String>,
 }
 
 // System status responses
 #[derive(Serialize, ToSchema)]
 pub struct TaskStatusData {
     pub running: bool,
-    pub last_update: String,
+    pub healthy: bool,
+    pub last_heartbeat: Option<String>,
+    /// End of the latest background pass that completed without errors
+    pub last_update: Option<String>,
+    /// Why the latest background pass failed; null once a pass completes without errors
+    pub last_error: Option<String>,
     pub managed_users: i64,
 }
 
@@ -187,9 +961,75 @@ pub struct TaskStatusResponse {
     pub status: TaskStatusData,
 }
 
+/// Outcome of one background scheduler pass
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct SchedulerPassSummary {
+    /// False outside the configured active window, when no host is contacted
+    pub active: bool,
+    /// Hosts checked, i.e. `succeeded + failed`
+    pub users_processed: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub duration_ms: u64,
+}
+
+/// Optional features this server supports, so one frontend can adapt to differently
+/// configured backends
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CapabilitiesResponse {
+    pub success: bool,
+    /// Webhook notifications
+    pub webhook: bool,
+    /// Email notifications
+    pub email: bool,
+    /// Served over HTTPS by this process rather than a reverse proxy
+    pub tls: bool,
+    /// Intervals whose end is past midnight, e.g. 22:00-02:00
+    pub overnight_intervals: bool,
+    /// More than one admin account
+    pub multi_admin: bool,
+    /// Read-only API tokens for dashboards, from `/api/auth/readonly-token`
+    pub readonly_tokens: bool,
+    /// Several allowed-hours intervals per day, e.g. 07:00-09:00 and 15:00-18:00
+    pub multiple_intervals: bool,
+    /// PlayTime settings can be stored and synced
+    pub playtime: bool,
+    /// Hosts whose timekpr reported PlayTime on their last check
+    pub playtime_supported_hosts: usize,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct RecentEventsResponse {
+    pub success: bool,
+    /// Newest first
+    pub events: Vec<Event>,
+    /// Events the log keeps before dropping the oldest
+    pub capacity: usize,
+}
+
+/// One place searched for the SSH key, in search order
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SshKeyCandidate {
+    pub path: String,
+    pub exists: bool,
+    /// The key `ssh` is given: the first candidate that exists
+    pub selected: bool,
+    /// Octal mode of the file, e.g. "0600"; None when it doesn't exist
+    pub mode: Option<String>,
+    /// Readable by group or others, which makes `ssh` ignore the key
+    pub insecure: Option<bool>,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct SshStatusResponse {
     pub success: bool,
     pub ssh_key_exists: bool,
+    pub ssh_key_encrypted: bool,
+    pub ssh_agent_available: bool,
+    /// Octal mode of the key file, e.g. "0600"
+    pub ssh_key_mode: Option<String>,
+    /// Machine-readable warning code such as SSH_KEY_PERMISSIONS_INSECURE
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<String>,
     pub message: String,
 }
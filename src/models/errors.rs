@@ -1,16 +1,21 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::{error::InternalError, web, HttpResponse, ResponseError};
 use serde_json::json;
-use std::fmt;
 use std::error::Error as StdError;
+use std::fmt;
 
 #[derive(Debug)]
 pub enum ServiceError {
     ValidationError(String),
     DatabaseError(String),
-    #[allow(dead_code)]
-    SshError(String),
+    /// Host didn't take the change, and a retry was stored for the scheduler (202 Accepted).
+    /// Only for paths that persist that retry; a failure nothing retries is another error.
+    Queued(String),
     NotFound(String),
+    /// The request clashes with the current state, e.g. pending changes on delete (409)
+    Conflict(String),
     AuthenticationError(String),
+    /// Valid credentials that don't cover the request, e.g. a read-only token (403)
+    Forbidden(String),
     InternalError(String),
 }
 
@@ -19,9 +24,11 @@ impl fmt::Display for ServiceError {
         match self {
             ServiceError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
             ServiceError::DatabaseError(msg) => write!(f, "Database error: {}", msg),
-            ServiceError::SshError(msg) => write!(f, "SSH error: {}", msg),
+            ServiceError::Queued(msg) => write!(f, "Queued: {}", msg),
             ServiceError::NotFound(msg) => write!(f, "Not found: {}", msg),
+            ServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
             ServiceError::AuthenticationError(msg) => write!(f, "Authentication error: {}", msg),
+            ServiceError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             ServiceError::InternalError(msg) => write!(f, "Internal error: {}", msg),
         }
     }
@@ -40,10 +47,18 @@ impl ResponseError for ServiceError {
                 "success": false,
                 "message": msg
             })),
+            ServiceError::Conflict(msg) => HttpResponse::Conflict().json(json!({
+                "success": false,
+                "message": msg
+            })),
             ServiceError::AuthenticationError(msg) => HttpResponse::Unauthorized().json(json!({
                 "success": false,
                 "message": msg
             })),
+            ServiceError::Forbidden(msg) => HttpResponse::Forbidden().json(json!({
+                "success": false,
+                "message": msg
+            })),
             ServiceError::DatabaseError(msg) => {
                 eprintln!("Database error: {}", msg);
                 HttpResponse::InternalServerError().json(json!({
@@ -51,7 +66,7 @@ impl ResponseError for ServiceError {
                     "message": "Database error occurred"
                 }))
             }
-            ServiceError::SshError(msg) => HttpResponse::Ok().json(json!({
+            ServiceError::Queued(msg) => HttpResponse::Accepted().json(json!({
                 "success": true,
                 "message": format!("Queued for later sync: {}", msg),
                 "pending": true
@@ -73,3 +88,15 @@ impl From<sqlx::Error> for ServiceError {
         ServiceError::DatabaseError(err.to_string())
     }
 }
+
+/// Path extractor config so malformed ids get the API error envelope instead of actix's plain text
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default().error_handler(|err, _req| {
+        let response = HttpResponse::BadRequest().json(json!({
+            "success": false,
+            "code": "INVALID_PATH_PARAM",
+            "message": "id must be an integer"
+        }));
+        InternalError::from_response(err, response).into()
+    })
+}
@@ -0,0 +1,60 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// Something that happened to a user or host, as kept in the in-memory event log
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EventKind {
+    UserAdded {
+        username: String,
+        system_ip: String,
+        /// Whether the user passed validation when added; false also when it was skipped
+        is_valid: bool,
+    },
+    TimeModified {
+        user_id: i64,
+        username: String,
+        operation: String,
+        seconds: i64,
+        reason: Option<String>,
+        /// Queued because no host answered, rather than applied
+        pending: bool,
+    },
+    SyncSucceeded {
+        user_id: i64,
+        username: String,
+        system_ip: String,
+        /// What was pushed: "schedule", "playtime" or "lockout"
+        setting: String,
+    },
+    SyncFailed {
+        user_id: i64,
+        username: String,
+        system_ip: String,
+        setting: String,
+        error: String,
+    },
+    /// Syncs of the setting failed too often in a row; no more are attempted until a
+    /// manual sync
+    SyncHalted {
+        user_id: i64,
+        username: String,
+        setting: String,
+        failures: i64,
+    },
+    /// A host that was answering is now shown as offline
+    HostOffline {
+        user_id: i64,
+        username: String,
+        system_ip: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Event {
+    /// Increases by one per event, so clients can tell which ones they have seen
+    pub id: u64,
+    pub timestamp: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
@@ -1,13 +1,17 @@
 // Re-export all models organized by domain
 pub mod api;
 pub mod errors;
+pub mod event;
 pub mod schedule;
-pub mod user;
 pub mod settings;
+pub mod timekpr;
+pub mod user;
 
 // Re-export all structs for backward compatibility
 pub use api::*;
 pub use errors::*;
+pub use event::*;
 pub use schedule::*;
-pub use user::*;
 pub use settings::*;
+pub use timekpr::*;
+pub use user::*;
@@ -1,9 +1,202 @@
-use chrono::{DateTime, Utc};
+use crate::models::api::format_hours_minutes;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
+/// Day of the week using timekpr's numbering (1 = Monday ... 7 = Sunday)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DayOfWeek {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl DayOfWeek {
+    pub const ALL: [DayOfWeek; 7] = [
+        DayOfWeek::Monday,
+        DayOfWeek::Tuesday,
+        DayOfWeek::Wednesday,
+        DayOfWeek::Thursday,
+        DayOfWeek::Friday,
+        DayOfWeek::Saturday,
+        DayOfWeek::Sunday,
+    ];
+
+    /// Iterate over all days in timekpr order (Monday first)
+    pub fn iter() -> impl Iterator<Item = DayOfWeek> {
+        Self::ALL.into_iter()
+    }
+
+    /// Days in display order for the configured week start; timekpr indices are unaffected
+    pub fn week(week_start: WeekStart) -> [DayOfWeek; 7] {
+        let mut days = Self::ALL;
+        if week_start == WeekStart::Sunday {
+            days.rotate_right(1);
+        }
+        days
+    }
+
+    pub fn from_date(date: NaiveDate) -> Self {
+        Self::ALL[date.weekday().num_days_from_monday() as usize]
+    }
+
+    /// Build a day from timekpr's 1-based index (1 = Monday, 7 = Sunday)
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            1..=7 => Some(Self::ALL[(index - 1) as usize]),
+            _ => None,
+        }
+    }
+
+    /// Index used by timekpra on the wire (1 = Monday, 7 = Sunday)
+    pub fn to_timekpr_index(self) -> u8 {
+        match self {
+            DayOfWeek::Monday => 1,
+            DayOfWeek::Tuesday => 2,
+            DayOfWeek::Wednesday => 3,
+            DayOfWeek::Thursday => 4,
+            DayOfWeek::Friday => 5,
+            DayOfWeek::Saturday => 6,
+            DayOfWeek::Sunday => 7,
+        }
+    }
+
+    /// Lowercase name as used in API payloads and sync maps
+    pub fn name(self) -> &'static str {
+        match self {
+            DayOfWeek::Monday => "monday",
+            DayOfWeek::Tuesday => "tuesday",
+            DayOfWeek::Wednesday => "wednesday",
+            DayOfWeek::Thursday => "thursday",
+            DayOfWeek::Friday => "friday",
+            DayOfWeek::Saturday => "saturday",
+            DayOfWeek::Sunday => "sunday",
+        }
+    }
+
+    /// Capitalized name for user-facing messages
+    pub fn label(self) -> &'static str {
+        match self {
+            DayOfWeek::Monday => "Monday",
+            DayOfWeek::Tuesday => "Tuesday",
+            DayOfWeek::Wednesday => "Wednesday",
+            DayOfWeek::Thursday => "Thursday",
+            DayOfWeek::Friday => "Friday",
+            DayOfWeek::Saturday => "Saturday",
+            DayOfWeek::Sunday => "Sunday",
+        }
+    }
+}
+
+/// First day of the week as presented to the household (timekpr itself always starts on Monday)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WeekStart::Monday => "monday",
+            WeekStart::Sunday => "sunday",
+        }
+    }
+}
+
+impl std::str::FromStr for WeekStart {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "monday" => Ok(WeekStart::Monday),
+            "sunday" => Ok(WeekStart::Sunday),
+            other => Err(format!(
+                "Invalid week start: {}. Expected 'monday' or 'sunday'",
+                other
+            )),
+        }
+    }
+}
+
+/// Part of a schedule that can be pushed to timekpr on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncAspect {
+    /// Daily time limits (setTimeLimits)
+    Limits,
+    /// Allowed hours per day (setAllowedHours)
+    Hours,
+    /// Weekly time limit (setTimeLimitForWeek)
+    Week,
+    /// Monthly time limit (setTimeLimitForMonth)
+    Month,
+}
+
+/// Outcome of pushing one day's setting to the host
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct DaySyncResult {
+    pub day: DayOfWeek,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl DaySyncResult {
+    pub fn succeeded(day: DayOfWeek) -> Self {
+        Self {
+            day,
+            ok: true,
+            error: None,
+        }
+    }
+
+    pub fn failed(day: DayOfWeek, error: String) -> Self {
+        Self {
+            day,
+            ok: false,
+            error: Some(error),
+        }
+    }
+}
+
+impl SyncAspect {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SyncAspect::Limits => "limits",
+            SyncAspect::Hours => "hours",
+            SyncAspect::Week => "week",
+            SyncAspect::Month => "month",
+        }
+    }
+}
+
+impl std::str::FromStr for SyncAspect {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "limits" => Ok(SyncAspect::Limits),
+            "hours" => Ok(SyncAspect::Hours),
+            "week" => Ok(SyncAspect::Week),
+            "month" => Ok(SyncAspect::Month),
+            other => Err(format!(
+                "Invalid sync aspect: {}. Expected limits, hours, week or month",
+                other
+            )),
+        }
+    }
+}
+
 /// Value object representing a time interval (HH:MM format)
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
 pub struct TimeInterval {
     pub start_time: String, // Format: "HH:MM"
     pub end_time: String,   // Format: "HH:MM"
@@ -11,19 +204,9 @@ pub struct TimeInterval {
 
 impl TimeInterval {
     pub fn new(start_time: String, end_time: String) -> Result<Self, String> {
-        // Validate time format
-        if !Self::is_valid_time_format(&start_time) {
-            return Err(format!(
-                "Invalid start time format: {}. Expected HH:MM",
-                start_time
-            ));
-        }
-        if !Self::is_valid_time_format(&end_time) {
-            return Err(format!(
-                "Invalid end time format: {}. Expected HH:MM",
-                end_time
-            ));
-        }
+        // Validate time format and range
+        Self::validate_time("start", &start_time)?;
+        Self::validate_time("end", &end_time)?;
 
         // Validate start < end
         if start_time >= end_time {
@@ -36,32 +219,84 @@ impl TimeInterval {
         })
     }
 
-    pub fn default() -> Self {
-        Self {
-            start_time: "00:00".to_string(),
-            end_time: "23:59".to_string(),
-        }
-    }
-
-    #[allow(dead_code)]
     pub fn format_time(&self) -> String {
         format!("{}-{}", self.start_time, self.end_time)
     }
 
-    fn is_valid_time_format(time_str: &str) -> bool {
-        if time_str.len() != 5 || !time_str.chars().nth(2).map_or(false, |c| c == ':') {
-            return false;
-        }
+    /// Length of the window in minutes. An end of 23:59 is the end of the day, as in the
+    /// default 00:00-23:59 interval.
+    pub fn duration_minutes(&self) -> i64 {
+        let minutes = |time: &str| {
+            time.split_once(':')
+                .and_then(|(hour, minute)| {
+                    Some(hour.parse::<i64>().ok()? * 60 + minute.parse::<i64>().ok()?)
+                })
+                .unwrap_or(0)
+        };
+        let end = match minutes(&self.end_time) {
+            1439 => 1440,
+            end => end,
+        };
+        (end - minutes(&self.start_time)).max(0)
+    }
+
+    /// Hours of the day the interval reaches into, as `--setallowedhours` lists them: an
+    /// end on the full hour leaves that hour out, except 23:59 which covers all of 23
+    fn hours(&self) -> std::ops::RangeInclusive<u32> {
+        let parse = |time: &str| {
+            time.split_once(':')
+                .and_then(|(hour, minute)| {
+                    Some((hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?))
+                })
+                .unwrap_or((0, 0))
+        };
+        let (start_hour, _) = parse(&self.start_time);
+        let (end_hour, end_minute) = parse(&self.end_time);
+        let last = if end_minute == 0 {
+            end_hour.saturating_sub(1)
+        } else {
+            end_hour
+        };
+        start_hour..=last.max(start_hour)
+    }
+
+    /// timekpr's allowed hours only take hours 0-23 and minutes 0-59; anything else would
+    /// become a malformed argument such as `8[0-60]`
+    fn validate_time(which: &str, time_str: &str) -> Result<(), String> {
+        let parts = (time_str.len() == 5 && time_str.as_bytes()[2] == b':')
+            .then(|| time_str.split_once(':'))
+            .flatten()
+            .and_then(|(hour, minute)| {
+                Some((hour.parse::<u8>().ok()?, minute.parse::<u8>().ok()?))
+            });
+        let Some((hour, minute)) = parts else {
+            return Err(format!(
+                "Invalid {} time format: {}. Expected HH:MM",
+                which, time_str
+            ));
+        };
 
-        let parts: Vec<&str> = time_str.split(':').collect();
-        if parts.len() != 2 {
-            return false;
+        if hour > 23 {
+            return Err(format!(
+                "Invalid {} time {}: hour must be between 0 and 23",
+                which, time_str
+            ));
+        }
+        if minute > 59 {
+            return Err(format!(
+                "Invalid {} time {}: minutes must be between 0 and 59",
+                which, time_str
+            ));
         }
+        Ok(())
+    }
+}
 
-        if let (Ok(hour), Ok(minute)) = (parts[0].parse::<u8>(), parts[1].parse::<u8>()) {
-            hour <= 23 && minute <= 59
-        } else {
-            false
+impl Default for TimeInterval {
+    fn default() -> Self {
+        Self {
+            start_time: "00:00".to_string(),
+            end_time: "23:59".to_string(),
         }
     }
 }
@@ -79,30 +314,74 @@ pub struct WeeklyHours {
 }
 
 impl WeeklyHours {
+    pub fn get(&self, day: DayOfWeek) -> f64 {
+        match day {
+            DayOfWeek::Monday => self.monday,
+            DayOfWeek::Tuesday => self.tuesday,
+            DayOfWeek::Wednesday => self.wednesday,
+            DayOfWeek::Thursday => self.thursday,
+            DayOfWeek::Friday => self.friday,
+            DayOfWeek::Saturday => self.saturday,
+            DayOfWeek::Sunday => self.sunday,
+        }
+    }
+
+    pub fn set(&mut self, day: DayOfWeek, hours: f64) {
+        let slot = match day {
+            DayOfWeek::Monday => &mut self.monday,
+            DayOfWeek::Tuesday => &mut self.tuesday,
+            DayOfWeek::Wednesday => &mut self.wednesday,
+            DayOfWeek::Thursday => &mut self.thursday,
+            DayOfWeek::Friday => &mut self.friday,
+            DayOfWeek::Saturday => &mut self.saturday,
+            DayOfWeek::Sunday => &mut self.sunday,
+        };
+        *slot = hours;
+    }
+
     pub fn validate(&self) -> Result<(), String> {
-        for (day, hours) in [
-            ("Monday", self.monday),
-            ("Tuesday", self.tuesday),
-            ("Wednesday", self.wednesday),
-            ("Thursday", self.thursday),
-            ("Friday", self.friday),
-            ("Saturday", self.saturday),
-            ("Sunday", self.sunday),
-        ] {
-            if hours < 0.0 || hours > 24.0 {
-                return Err(format!(
+        match self.errors().into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Every out-of-range day, in timekpr order
+    pub fn errors(&self) -> Vec<String> {
+        DayOfWeek::iter()
+            .filter(|day| !(0.0..=24.0).contains(&self.get(*day)))
+            .map(|day| {
+                format!(
                     "{} hours must be between 0 and 24, got {}",
-                    day, hours
-                ));
-            }
+                    day.label(),
+                    self.get(day)
+                )
+            })
+            .collect()
+    }
+}
+
+/// A day's allowed hours as clients send them: a single interval, as before, or several
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(untagged)]
+pub enum DayIntervals {
+    One(TimeInterval),
+    Many(Vec<TimeInterval>),
+}
+
+impl DayIntervals {
+    pub fn into_vec(self) -> Vec<TimeInterval> {
+        match self {
+            DayIntervals::One(interval) => vec![interval],
+            DayIntervals::Many(intervals) => intervals,
         }
-        Ok(())
     }
 }
 
-/// Value object representing weekly time intervals
-#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
-pub struct WeeklyTimeIntervals {
+/// Each day's first interval, the response shape from before a day could have several;
+/// sent next to the full lists so existing clients keep working
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FirstIntervals {
     pub monday: TimeInterval,
     pub tuesday: TimeInterval,
     pub wednesday: TimeInterval,
@@ -112,17 +391,137 @@ pub struct WeeklyTimeIntervals {
     pub sunday: TimeInterval,
 }
 
-impl WeeklyTimeIntervals {
-    pub fn default() -> Self {
+impl From<&WeeklyTimeIntervals> for FirstIntervals {
+    fn from(intervals: &WeeklyTimeIntervals) -> Self {
+        let first = |day| intervals.get(day).first().cloned().unwrap_or_default();
         Self {
-            monday: TimeInterval::default(),
-            tuesday: TimeInterval::default(),
-            wednesday: TimeInterval::default(),
-            thursday: TimeInterval::default(),
-            friday: TimeInterval::default(),
-            saturday: TimeInterval::default(),
-            sunday: TimeInterval::default(),
+            monday: first(DayOfWeek::Monday),
+            tuesday: first(DayOfWeek::Tuesday),
+            wednesday: first(DayOfWeek::Wednesday),
+            thursday: first(DayOfWeek::Thursday),
+            friday: first(DayOfWeek::Friday),
+            saturday: first(DayOfWeek::Saturday),
+            sunday: first(DayOfWeek::Sunday),
+        }
+    }
+}
+
+/// Value object representing weekly time intervals: one or more disjoint intervals per
+/// day, in order
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WeeklyTimeIntervals {
+    pub monday: Vec<TimeInterval>,
+    pub tuesday: Vec<TimeInterval>,
+    pub wednesday: Vec<TimeInterval>,
+    pub thursday: Vec<TimeInterval>,
+    pub friday: Vec<TimeInterval>,
+    pub saturday: Vec<TimeInterval>,
+    pub sunday: Vec<TimeInterval>,
+}
+
+impl Default for WeeklyTimeIntervals {
+    /// The whole of every day
+    fn default() -> Self {
+        let full_day = || vec![TimeInterval::default()];
+        Self {
+            monday: full_day(),
+            tuesday: full_day(),
+            wednesday: full_day(),
+            thursday: full_day(),
+            friday: full_day(),
+            saturday: full_day(),
+            sunday: full_day(),
+        }
+    }
+}
+
+impl WeeklyTimeIntervals {
+    /// Order a day's intervals by start and check timekpr can take them together: at least
+    /// one, none overlapping, and no two within the same hour, since `--setallowedhours`
+    /// takes a single minute range per hour
+    pub fn check_day(mut intervals: Vec<TimeInterval>) -> Result<Vec<TimeInterval>, String> {
+        if intervals.is_empty() {
+            return Err("At least one interval is required".to_string());
+        }
+        intervals.sort_by(|a, b| a.start_time.cmp(&b.start_time));
+
+        for pair in intervals.windows(2) {
+            let (first, second) = (&pair[0], &pair[1]);
+            if second.start_time < first.end_time {
+                return Err(format!(
+                    "Intervals {} and {} overlap",
+                    first.format_time(),
+                    second.format_time()
+                ));
+            }
+            if first.hours().end() >= second.hours().start() {
+                return Err(format!(
+                    "Intervals {} and {} share hour {}; timekpr allows one range per hour, so merge them or leave a full hour between",
+                    first.format_time(),
+                    second.format_time(),
+                    second.hours().start()
+                ));
+            }
         }
+        Ok(intervals)
+    }
+
+    /// The day's intervals as text, e.g. "07:00-09:00, 15:00-18:00"
+    pub fn format_day(&self, day: DayOfWeek) -> String {
+        self.get(day)
+            .iter()
+            .map(TimeInterval::format_time)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    pub fn get(&self, day: DayOfWeek) -> &[TimeInterval] {
+        match day {
+            DayOfWeek::Monday => &self.monday,
+            DayOfWeek::Tuesday => &self.tuesday,
+            DayOfWeek::Wednesday => &self.wednesday,
+            DayOfWeek::Thursday => &self.thursday,
+            DayOfWeek::Friday => &self.friday,
+            DayOfWeek::Saturday => &self.saturday,
+            DayOfWeek::Sunday => &self.sunday,
+        }
+    }
+
+    pub fn set(&mut self, day: DayOfWeek, intervals: Vec<TimeInterval>) {
+        let slot = match day {
+            DayOfWeek::Monday => &mut self.monday,
+            DayOfWeek::Tuesday => &mut self.tuesday,
+            DayOfWeek::Wednesday => &mut self.wednesday,
+            DayOfWeek::Thursday => &mut self.thursday,
+            DayOfWeek::Friday => &mut self.friday,
+            DayOfWeek::Saturday => &mut self.saturday,
+            DayOfWeek::Sunday => &mut self.sunday,
+        };
+        *slot = intervals;
+    }
+
+    /// Days whose allowed hours are shorter than their time limit, so the limit can never
+    /// be used up. Valid for timekpr, hence warnings rather than errors.
+    pub fn unreachable_limit_warnings(&self, hours: &WeeklyHours) -> Vec<String> {
+        DayOfWeek::iter()
+            .filter_map(|day| {
+                let window: i64 = self
+                    .get(day)
+                    .iter()
+                    .map(|interval| interval.duration_minutes() * 60)
+                    .sum();
+                let limit = hours_to_seconds(hours.get(day));
+                (window < limit).then(|| {
+                    format!(
+                        "{}: allowed hours {} give only {}, less than the daily limit of {}; the limit can never be reached",
+                        day.label(),
+                        self.format_day(day),
+                        format_hours_minutes(window),
+                        format_hours_minutes(limit)
+                    )
+                })
+            })
+            .collect()
     }
 }
 
@@ -135,6 +534,14 @@ pub struct Schedule {
     pub is_synced: bool,
     pub last_synced: Option<DateTime<Utc>>,
     pub last_modified: DateTime<Utc>,
+    /// Failed background syncs since the last success or change
+    pub sync_attempts: i64,
+    /// The scheduler leaves the schedule alone until then
+    pub next_sync_attempt: Option<DateTime<Utc>>,
+    /// Day the last push was made for; its weekday got that day's effective limit
+    pub synced_day: Option<NaiveDate>,
+    /// Hours pushed for `synced_day`'s weekday, overrides and bonuses included
+    pub synced_day_hours: Option<f64>,
 }
 
 impl Schedule {
@@ -148,6 +555,10 @@ impl Schedule {
             is_synced: false, // New schedules always need sync
             last_synced: None,
             last_modified: Utc::now(),
+            sync_attempts: 0,
+            next_sync_attempt: None,
+            synced_day: None,
+            synced_day_hours: None,
         })
     }
 
@@ -165,6 +576,199 @@ impl Schedule {
             is_synced: false, // New schedules always need sync
             last_synced: None,
             last_modified: Utc::now(),
+            sync_attempts: 0,
+            next_sync_attempt: None,
+            synced_day: None,
+            synced_day_hours: None,
         })
     }
 }
+
+/// Whether timekpr lets the user log in on a day, as pushed with `--setalloweddays`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct AllowedDay {
+    pub day: DayOfWeek,
+    /// Days without time are sent as not allowed, i.e. blocked. When every day is without
+    /// time all of them are sent as allowed with a limit of zero.
+    pub allowed: bool,
+    /// Seconds pushed for the day, including today's override and bonus; None when blocked
+    pub time_limit: Option<i64>,
+    /// What the host last reported; None when it has not reported allowed days
+    pub host_allowed: Option<bool>,
+}
+
+/// Whole seconds for a limit stored in hours. Rounded, since minute values such as
+/// 50 minutes are not exact in hours and would otherwise lose a second.
+pub fn hours_to_seconds(hours: f64) -> i64 {
+    (hours * 3600.0).round() as i64
+}
+
+/// Seconds allowed on a single day once overrides and bonuses are applied.
+///
+/// Precedence: a date override replaces the weekly hours for that day, and a
+/// recurring bonus is added on top of whichever of the two applies. Without
+/// either a weekly schedule or an override there is no limit (`None`).
+pub fn effective_limit_seconds(
+    weekly_hours: Option<f64>,
+    override_hours: Option<f64>,
+    bonus_minutes: Option<i64>,
+) -> Option<i64> {
+    let base_hours = override_hours.or(weekly_hours)?;
+    let seconds = hours_to_seconds(base_hours) + bonus_minutes.unwrap_or(0) * 60;
+    Some(seconds.clamp(0, 24 * 3600))
+}
+
+/// Seconds from `now` until the next daily reset at `reset_hour`:00 (local wall clock).
+/// At exactly the reset time the full day until the following reset is returned.
+pub fn seconds_until_reset(now: NaiveDateTime, reset_hour: u32) -> i64 {
+    let reset_time = NaiveTime::from_hms_opt(reset_hour % 24, 0, 0).unwrap_or(NaiveTime::MIN);
+    let mut next_reset = now.date().and_time(reset_time);
+    if next_reset <= now {
+        next_reset += chrono::Duration::days(1);
+    }
+    (next_reset - now).num_seconds()
+}
+
+/// Day that `now` counts towards when the daily reset happens at `reset_hour`:00 instead
+/// of midnight: anything before the reset still belongs to the previous day.
+pub fn usage_day(now: NaiveDateTime, reset_hour: u32) -> NaiveDate {
+    (now - chrono::Duration::hours((reset_hour % 24) as i64)).date()
+}
+
+/// An application counted against the PlayTime allowance. `process` is matched by timekpr
+/// against running process names (it may be a regular expression).
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlayTimeActivity {
+    pub process: String,
+    pub description: Option<String>,
+}
+
+/// timekpr PlayTime: a separate daily allowance for the listed applications (games),
+/// counted on top of the overall time limit
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlayTimeSettings {
+    pub enabled: bool,
+    /// PlayTime hours per day
+    pub hours: WeeklyHours,
+    pub activities: Vec<PlayTimeActivity>,
+}
+
+impl PlayTimeSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        self.hours.validate()?;
+
+        // Sent inside a single-quoted timekpr argument with ';' and '[]' as separators
+        let is_safe = |text: &str| {
+            !text
+                .chars()
+                .any(|c| matches!(c, '\'' | ';' | '[' | ']') || c.is_control())
+        };
+        for activity in &self.activities {
+            if activity.process.trim().is_empty() {
+                return Err("PlayTime activity process must not be empty".to_string());
+            }
+            if !is_safe(&activity.process) || !activity.description.as_deref().is_none_or(is_safe) {
+                return Err(format!(
+                    "PlayTime activity '{}' contains one of the reserved characters ' ; [ ]",
+                    activity.process
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Stored PlayTime settings of a user and whether they have reached the host
+#[derive(Debug, Clone)]
+pub struct UserPlayTime {
+    pub user_id: i64,
+    pub settings: PlayTimeSettings,
+    pub is_synced: bool,
+    pub last_synced: Option<DateTime<Utc>>,
+}
+
+/// What timekpr does when a user runs out of time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LockoutAction {
+    /// Lock the screen
+    Lock,
+    Suspend,
+    /// Suspend, waking up again within the configured hours
+    SuspendWake,
+    /// End the user's session (log out)
+    Terminate,
+    /// Kill the session without a graceful logout
+    Kill,
+    Shutdown,
+}
+
+impl LockoutAction {
+    /// Name used by timekpra
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LockoutAction::Lock => "lock",
+            LockoutAction::Suspend => "suspend",
+            LockoutAction::SuspendWake => "suspendwake",
+            LockoutAction::Terminate => "terminate",
+            LockoutAction::Kill => "kill",
+            LockoutAction::Shutdown => "shutdown",
+        }
+    }
+}
+
+/// timekpr lockout policy. The wake-up hours only apply to `suspendwake`.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LockoutSettings {
+    pub action: LockoutAction,
+    /// First hour (0-23) the machine may be woken up in
+    pub wakeup_from: Option<u32>,
+    /// Last hour (0-23) the machine may be woken up in
+    pub wakeup_to: Option<u32>,
+}
+
+impl LockoutSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        match (self.action, self.wakeup_from, self.wakeup_to) {
+            (LockoutAction::SuspendWake, Some(from), Some(to)) => {
+                if from > 23 || to > 23 {
+                    return Err("Wake-up hours must be between 0 and 23".to_string());
+                }
+                if from > to {
+                    return Err("Wake-up hours must not end before they start".to_string());
+                }
+                Ok(())
+            }
+            (LockoutAction::SuspendWake, _, _) => {
+                Err("suspendwake needs wakeup_from and wakeup_to".to_string())
+            }
+            (_, None, None) => Ok(()),
+            (action, _, _) => Err(format!(
+                "Wake-up hours only apply to suspendwake, not {}",
+                action.as_str()
+            )),
+        }
+    }
+}
+
+/// Today's limit after `extend-today`; stored as an override for today only, so the next
+/// day falls back to the weekly schedule without anything having to be undone
+#[derive(Debug, Clone)]
+pub struct TodayExtension {
+    pub date: NaiveDate,
+    /// Hours stored for today, before the recurring bonus
+    pub override_hours: f64,
+    /// Effective limit in seconds for today, bonus included
+    pub limit_seconds: Option<i64>,
+    /// Next local daily reset, after which the extension no longer applies
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Stored lockout settings of a user and whether they have reached the host
+#[derive(Debug, Clone)]
+pub struct UserLockout {
+    pub user_id: i64,
+    pub settings: LockoutSettings,
+    pub is_synced: bool,
+    pub last_synced: Option<DateTime<Utc>>,
+}
@@ -1,3 +1,4 @@
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
@@ -23,14 +24,52 @@ impl SettingsEntry {
     pub fn with_id(id: i64, key: String, value: String) -> Self {
         Self { id, key, value }
     }
-
-
 }
 
 /// Helper constants for common setting keys
 #[allow(dead_code)]
 impl SettingsEntry {
     pub const ADMIN_PASSWORD_HASH: &'static str = "admin_password_hash";
+    /// Plaintext password stored by old versions; only read to migrate it at startup
+    pub const LEGACY_ADMIN_PASSWORD: &'static str = "admin_password";
     pub const JWT_SECRET: &'static str = "jwt_secret";
     pub const CHECK_INTERVAL: &'static str = "check_interval";
+    pub const WEEK_START: &'static str = "week_start";
+    pub const PROTECT_OBSERVABILITY: &'static str = "protect_observability";
+    pub const DEFAULT_SCHEDULE: &'static str = "default_schedule";
+    pub const SCHEDULER_ACTIVE_FROM: &'static str = "scheduler_active_from";
+    pub const SCHEDULER_ACTIVE_TO: &'static str = "scheduler_active_to";
+    pub const TIMEZONE: &'static str = "timezone";
+    pub const MAX_DAILY_GRANT_SECONDS: &'static str = "max_daily_grant_seconds";
+    pub const REVALIDATE_AFTER_MODIFY: &'static str = "revalidate_after_modify";
+}
+
+/// Region-based IANA names ("Europe/Berlin") plus UTC. Legacy zones such as "EST" are
+/// left out: they are fixed offsets without daylight saving and are rarely what is meant.
+fn is_selectable_timezone(name: &str) -> bool {
+    name == "UTC" || name.contains('/')
+}
+
+/// Timezones an admin can choose from, sorted by name
+pub fn timezone_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = chrono_tz::TZ_VARIANTS
+        .iter()
+        .map(|tz| tz.name())
+        .filter(|name| is_selectable_timezone(name))
+        .collect();
+    names.sort_unstable();
+    names
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    let name = name.trim();
+    name.parse::<Tz>()
+        .ok()
+        .filter(|_| is_selectable_timezone(name))
+        .ok_or_else(|| {
+            format!(
+                "Unknown timezone '{}'. Use an IANA name such as 'America/New_York' (see /api/timezones)",
+                name
+            )
+        })
 }
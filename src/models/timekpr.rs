@@ -0,0 +1,361 @@
+use crate::models::{DayOfWeek, TimeInterval, WeeklyHours, WeeklyTimeIntervals};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Typed view of a user's `timekpra --userinfo` output, stored as JSON in `last_config`.
+/// Field names on the wire match the keys used by earlier versions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimekprConfig {
+    #[serde(rename = "USERNAME")]
+    pub username: String,
+    #[serde(rename = "TIME_LEFT_DAY", default)]
+    pub time_left_day: Option<i64>,
+    #[serde(rename = "TIME_SPENT_DAY", default)]
+    pub time_spent_day: Option<i64>,
+    #[serde(rename = "PLAYTIME_LEFT_DAY", default)]
+    pub playtime_left_day: Option<i64>,
+    #[serde(rename = "TIME_LEFT_WEEK", default)]
+    pub time_left_week: Option<i64>,
+    #[serde(rename = "TIME_SPENT_WEEK", default)]
+    pub time_spent_week: Option<i64>,
+    #[serde(rename = "TIME_LIMIT_WEEK", default)]
+    pub time_limit_week: Option<i64>,
+    #[serde(rename = "TIME_LEFT_MONTH", default)]
+    pub time_left_month: Option<i64>,
+    #[serde(rename = "TIME_SPENT_MONTH", default)]
+    pub time_spent_month: Option<i64>,
+    #[serde(rename = "TIME_LIMIT_MONTH", default)]
+    pub time_limit_month: Option<i64>,
+    /// Whether the host's timekpr reports PlayTime (added in timekpr-nExT 0.5)
+    #[serde(default)]
+    pub playtime_supported: bool,
+    /// Lockout action as reported by the host, e.g. "terminate"
+    #[serde(rename = "LOCKOUT_TYPE", default)]
+    pub lockout_type: Option<String>,
+    /// Hours a `suspendwake` lockout may wake the machine in, as "from;to"
+    #[serde(rename = "WAKEUP_HOUR_INTERVAL", default)]
+    pub wakeup_hour_interval: Option<String>,
+    #[serde(default)]
+    pub raw_output: String,
+}
+
+impl TimekprConfig {
+    /// Build a config from raw `--userinfo` output (uses the ACTUAL_ values for current state)
+    pub fn parse(username: &str, output: &str) -> Self {
+        let mut config = Self {
+            username: username.to_string(),
+            time_left_day: None,
+            time_spent_day: None,
+            playtime_left_day: None,
+            time_left_week: None,
+            time_spent_week: None,
+            time_limit_week: None,
+            time_left_month: None,
+            time_spent_month: None,
+            time_limit_month: None,
+            playtime_supported: output.contains("PLAYTIME"),
+            lockout_type: None,
+            wakeup_hour_interval: None,
+            raw_output: output.trim().to_string(),
+        };
+
+        for line in output.lines() {
+            let value = line
+                .split(':')
+                .nth(1)
+                .and_then(|value| value.trim().parse::<i64>().ok());
+
+            if line.contains("ACTUAL_TIME_LEFT_DAY") {
+                config.time_left_day = value.or(config.time_left_day);
+            } else if line.contains("ACTUAL_TIME_SPENT_DAY") {
+                config.time_spent_day = value.or(config.time_spent_day);
+            } else if line.contains("ACTUAL_PLAYTIME_LEFT_DAY") {
+                config.playtime_left_day = value.or(config.playtime_left_day);
+            }
+            if let Some(text) = line.trim().strip_prefix("LOCKOUT_TYPE:") {
+                config.lockout_type = Some(text.trim().to_string());
+            } else if let Some(text) = line.trim().strip_prefix("WAKEUP_HOUR_INTERVAL:") {
+                config.wakeup_hour_interval = Some(text.trim().to_string());
+            }
+            // Add more parsing for other timekpr fields as needed
+        }
+
+        // Weekly and monthly figures stay None when the host's timekpr doesn't report them.
+        // Current values (`ACTUAL_`) win over the ones last saved by timekpr.
+        let number = |keys: &[&str]| {
+            keys.iter().find_map(|key| {
+                output.lines().find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    if name.trim() == *key {
+                        value.trim().parse::<i64>().ok()
+                    } else {
+                        None
+                    }
+                })
+            })
+        };
+        // Left and spent come from one snapshot so they add up to the limit. When only one of
+        // them is current, the saved other one moves by the time spent since it was saved.
+        let left_and_spent = |period: &str| {
+            let current = (
+                number(&[format!("ACTUAL_TIME_LEFT_{}", period).as_str()]),
+                number(&[format!("ACTUAL_TIME_SPENT_{}", period).as_str()]),
+            );
+            let saved = (
+                number(&[format!("TIME_LEFT_{}", period).as_str()]),
+                number(&[format!("TIME_SPENT_{}", period).as_str()]),
+            );
+            match (current, saved) {
+                ((Some(left), Some(spent)), _) => (Some(left), Some(spent)),
+                ((None, Some(spent)), (saved_left, saved_spent)) => (
+                    saved_left
+                        .zip(saved_spent)
+                        .map(|(left, saved_spent)| (left - (spent - saved_spent)).max(0)),
+                    Some(spent),
+                ),
+                ((Some(left), None), (saved_left, saved_spent)) => (
+                    Some(left),
+                    saved_spent
+                        .zip(saved_left)
+                        .map(|(spent, saved_left)| spent + (saved_left - left)),
+                ),
+                ((None, None), saved) => saved,
+            }
+        };
+        (config.time_left_week, config.time_spent_week) = left_and_spent("WEEK");
+        config.time_limit_week = number(&["TIME_LIMIT_WEEK", "LIMIT_PER_WEEK"]);
+        (config.time_left_month, config.time_spent_month) = left_and_spent("MONTH");
+        config.time_limit_month = number(&["TIME_LIMIT_MONTH", "LIMIT_PER_MONTH"]);
+
+        // If no time data was parsed, set defaults for testing
+        config.time_left_day.get_or_insert(7200); // 2 hours default
+        config.time_spent_day.get_or_insert(1800); // 30 minutes default
+
+        config
+    }
+
+    /// Whether the host's timekpr reports a lockout type and so accepts `--setlockouttype`
+    pub fn lockout_supported(&self) -> bool {
+        self.lockout_type.is_some()
+    }
+
+    /// Days the host reported as allowed (`ALLOWED_WEEKDAYS: 1;2;3`); None when the
+    /// output didn't include the line
+    pub fn allowed_weekdays(&self) -> Option<Vec<DayOfWeek>> {
+        let line = self
+            .raw_output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix("ALLOWED_WEEKDAYS:"))?;
+        Some(
+            line.split(';')
+                .filter_map(|index| index.trim().parse::<u8>().ok())
+                .filter_map(DayOfWeek::from_index)
+                .collect(),
+        )
+    }
+
+    /// Items of a `;`-separated line of the output, e.g. `LIMITS_PER_WEEKDAYS: 3600;3600`;
+    /// None when the output didn't include the line
+    pub fn list_value(&self, key: &str) -> Option<Vec<String>> {
+        let prefix = format!("{}:", key);
+        let line = self
+            .raw_output
+            .lines()
+            .find_map(|line| line.trim().strip_prefix(prefix.as_str()))?;
+        Some(split_list(line).map(str::to_string).collect())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+impl FromStr for TimekprConfig {
+    type Err = serde_json::Error;
+
+    /// Parse a stored `last_config` JSON document
+    fn from_str(json: &str) -> Result<Self, Self::Err> {
+        serde_json::from_str(json)
+    }
+}
+
+/// Weekly limits and allowed hours read from a timekpr per-user config file
+/// (`KEY = value`) or from `--userinfo` output (`KEY: value`)
+#[derive(Debug, Clone)]
+pub struct TimekprScheduleImport {
+    pub hours: WeeklyHours,
+    pub intervals: WeeklyTimeIntervals,
+    /// Parts that could not be carried over exactly, e.g. a limit on a day without allowed hours
+    pub warnings: Vec<String>,
+}
+
+impl TimekprScheduleImport {
+    /// Read `ALLOWED_WEEKDAYS`, `LIMITS_PER_WEEKDAYS` (or `LIMIT_PER_DAY`) and
+    /// `ALLOWED_HOURS_1`..`ALLOWED_HOURS_7`. Days not allowed get zero hours.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut allowed_days = None;
+        let mut limits = None;
+        let mut allowed_hours = std::collections::HashMap::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with('[') {
+                continue;
+            }
+            let Some((key, value)) = line.split_once(['=', ':']) else {
+                continue;
+            };
+            let (key, value) = (key.trim(), value.trim());
+
+            match key {
+                "ALLOWED_WEEKDAYS" => {
+                    let days = split_list(value)
+                        .map(|index| {
+                            index
+                                .parse::<u8>()
+                                .ok()
+                                .and_then(DayOfWeek::from_index)
+                                .ok_or_else(|| {
+                                    format!("Invalid day in ALLOWED_WEEKDAYS: {}", index)
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    allowed_days = Some(days);
+                }
+                "LIMITS_PER_WEEKDAYS" | "LIMIT_PER_DAY" => {
+                    let seconds = split_list(value)
+                        .map(|seconds| {
+                            seconds
+                                .parse::<i64>()
+                                .ok()
+                                .filter(|seconds| *seconds >= 0)
+                                .ok_or_else(|| {
+                                    format!("Invalid time limit in {}: {}", key, seconds)
+                                })
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    limits = Some(seconds);
+                }
+                _ => {
+                    let day = key
+                        .strip_prefix("ALLOWED_HOURS_")
+                        .and_then(|index| index.parse::<u8>().ok())
+                        .and_then(DayOfWeek::from_index);
+                    if let Some(day) = day {
+                        allowed_hours.insert(day, value.to_string());
+                    }
+                }
+            }
+        }
+
+        let allowed_days = allowed_days.ok_or("ALLOWED_WEEKDAYS not found")?;
+        let limits = limits.ok_or("LIMITS_PER_WEEKDAYS not found")?;
+        // Limits follow the allowed days in order; a single value applies to all of them
+        let limit_for = |position: usize| match limits.as_slice() {
+            [limit] => Some(*limit),
+            limits => limits.get(position).copied(),
+        };
+
+        let mut hours = WeeklyHours {
+            monday: 0.0,
+            tuesday: 0.0,
+            wednesday: 0.0,
+            thursday: 0.0,
+            friday: 0.0,
+            saturday: 0.0,
+            sunday: 0.0,
+        };
+        for (position, day) in allowed_days.iter().enumerate() {
+            let seconds = limit_for(position).ok_or_else(|| {
+                format!("No time limit in LIMITS_PER_WEEKDAYS for {}", day.label())
+            })?;
+            hours.set(*day, seconds as f64 / 3600.0);
+        }
+        hours.validate()?;
+
+        let mut intervals = WeeklyTimeIntervals::default();
+        let mut warnings = Vec::new();
+        for day in DayOfWeek::iter() {
+            let Some(value) = allowed_hours.get(&day) else {
+                continue;
+            };
+            let context = |e: String| format!("ALLOWED_HOURS_{}: {}", day.to_timekpr_index(), e);
+            let day_intervals = parse_allowed_hours(value).map_err(context)?;
+            if !day_intervals.is_empty() {
+                intervals.set(
+                    day,
+                    WeeklyTimeIntervals::check_day(day_intervals).map_err(context)?,
+                );
+            } else if hours.get(day) > 0.0 {
+                warnings.push(format!(
+                    "{}: no allowed hours; imported as blocked",
+                    day.label()
+                ));
+                hours.set(day, 0.0);
+            }
+        }
+
+        Ok(Self {
+            hours,
+            intervals,
+            warnings,
+        })
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+}
+
+/// timekpr's allowed hours (`7;8;9[0-30];15;16`) as intervals, one per run of adjoining
+/// minutes, in order. Empty when no hour is allowed.
+fn parse_allowed_hours(value: &str) -> Result<Vec<TimeInterval>, String> {
+    let mut ranges = split_list(value)
+        .map(|item| {
+            let (hour, minutes) = match item.split_once('[') {
+                Some((hour, minutes)) => {
+                    let (from, to) = minutes
+                        .strip_suffix(']')
+                        .and_then(|minutes| minutes.split_once('-'))
+                        .ok_or_else(|| format!("Invalid hour {}", item))?;
+                    (
+                        hour,
+                        (from.trim().parse::<u8>().ok(), to.trim().parse::<u8>().ok()),
+                    )
+                }
+                None => (item, (Some(0), Some(59))),
+            };
+            match (hour.trim().parse::<u8>().ok(), minutes) {
+                (Some(hour), (Some(from), Some(to))) if hour <= 23 && from <= to && to <= 59 => {
+                    Ok((hour, from, to))
+                }
+                _ => Err(format!("Invalid hour {}", item)),
+            }
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    ranges.sort_unstable();
+
+    // A run continues while each hour follows the last in full
+    let mut runs: Vec<((u8, u8), (u8, u8))> = Vec::new();
+    for (hour, from, to) in ranges {
+        match runs.last_mut() {
+            Some((_, end)) if end.0 + 1 == hour && end.1 == 59 && from == 0 => *end = (hour, to),
+            _ => runs.push(((hour, from), (hour, to))),
+        }
+    }
+
+    runs.into_iter()
+        .map(|((first_hour, first_from), (last_hour, last_to))| {
+            let start = format!("{:02}:{:02}", first_hour, first_from);
+            let end = match (last_hour, last_to) {
+                (23, 59) => "23:59".to_string(),
+                (hour, 59) => format!("{:02}:00", hour + 1),
+                (hour, to) => format!("{:02}:{:02}", hour, to),
+            };
+            TimeInterval::new(start, end)
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
 use utoipa::ToSchema;
 
 /// Database entity representing a managed user
@@ -14,30 +15,338 @@ pub struct ManagedUser {
     pub last_config: Option<String>,
     pub pending_time_adjustment: Option<i64>,
     pub pending_time_operation: Option<String>,
+    /// Why the queued adjustment was made, if the admin said
+    pub pending_time_reason: Option<String>,
+    /// Last validation outcome, see `UserStatus`
+    pub last_status: Option<String>,
+    /// Failed validations in a row, reset on the next successful one
+    pub consecutive_failures: i64,
+    /// Primary user this row is an additional host for; None for primary/standalone users
+    pub host_group_id: Option<i64>,
+    /// timekpra to run on this host, for installs outside the SSH session's PATH; None means `timekpra`
+    pub timekpr_path: Option<String>,
+    /// Replaces `timekpra --userinfo {username}` for hosts with a wrapped or patched timekpra
+    pub validate_command_template: Option<String>,
+    /// Replaces `timekpra --settimeleft {username} {op} {seconds}`
+    pub modify_command_template: Option<String>,
+}
+
+impl ManagedUser {
+    /// Id of the primary user of this row's host group (its own id for a primary)
+    pub fn group_id(&self) -> i64 {
+        self.host_group_id.unwrap_or(self.id)
+    }
+}
+
+/// Placeholders of a validate command template; each must appear
+pub const VALIDATE_COMMAND_PLACEHOLDERS: &[&str] = &["username"];
+
+/// Placeholders of a modify command template; each must appear
+pub const MODIFY_COMMAND_PLACEHOLDERS: &[&str] = &["username", "op", "seconds"];
+
+/// Check that a command template uses only `placeholders`, each at least once, so a
+/// rendered command never keeps a literal `{...}` or drops a value
+pub fn check_command_template(template: &str, placeholders: &[&str]) -> Result<(), String> {
+    if template.contains(['\n', '\r']) {
+        return Err("Command template must be a single line".to_string());
+    }
+
+    let mut used = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("Unclosed '{{' in command template '{}'", template));
+        };
+        let name = &rest[start + 1..start + end];
+        if !placeholders.contains(&name) {
+            return Err(format!(
+                "Unknown placeholder {{{}}} in command template; expected {}",
+                name,
+                placeholder_list(placeholders)
+            ));
+        }
+        used.push(name);
+        rest = &rest[start + end + 1..];
+    }
+
+    match placeholders.iter().find(|name| !used.contains(name)) {
+        Some(name) => Err(format!(
+            "Command template must contain {{{}}}; expected {}",
+            name,
+            placeholder_list(placeholders)
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Check that a host address is an IPv4 or IPv6 literal or a DNS name such as
+/// `kids-laptop.lan`, so a typo is rejected before any SSH attempt
+pub fn check_system_ip(system_ip: &str) -> Result<(), String> {
+    if system_ip.parse::<IpAddr>().is_ok() {
+        return Ok(());
+    }
+
+    let invalid = || {
+        Err(format!(
+            "'{}' is not a valid IP address or hostname",
+            system_ip
+        ))
+    };
+    let name = system_ip.strip_suffix('.').unwrap_or(system_ip);
+    if name.is_empty() || name.len() > 253 {
+        return invalid();
+    }
+    let labels: Vec<&str> = name.split('.').collect();
+    let label_ok = |label: &&str| {
+        !label.is_empty()
+            && label.len() <= 63
+            && !label.starts_with('-')
+            && !label.ends_with('-')
+            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    };
+    // All-numeric names such as 300.1.1.1 are malformed addresses, not hostnames
+    let numeric = labels
+        .iter()
+        .all(|label| label.chars().all(|c| c.is_ascii_digit()));
+    if labels.iter().all(label_ok) && !numeric {
+        Ok(())
+    } else {
+        invalid()
+    }
+}
+
+fn placeholder_list(placeholders: &[&str]) -> String {
+    placeholders
+        .iter()
+        .map(|name| format!("{{{}}}", name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Fill the `{name}` placeholders of a command template in one pass, so a value that
+/// itself looks like a placeholder is left alone
+pub fn render_command_template(template: &str, values: &[(&str, &str)]) -> String {
+    let mut command = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        command.push_str(&rest[..start]);
+        let value = rest[start..].find('}').and_then(|end| {
+            let name = &rest[start + 1..start + end];
+            values
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| (*value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                command.push_str(value);
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                command.push('{');
+                rest = &rest[start + 1..];
+            }
+        }
+    }
+    command.push_str(rest);
+    command
+}
+
+/// Result of checking a user on the remote host
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UserStatus {
+    Ok,
+    /// Host unreachable or SSH authentication failed
+    Offline,
+    /// timekpra ran but rejected the user
+    InvalidUser,
+    /// timekpra could not be run: not installed, or not on the SSH session's PATH
+    TimekprMissing,
+    /// The command did not finish within the command timeout and was killed
+    CommandTimeout,
+}
+
+impl UserStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UserStatus::Ok => "ok",
+            UserStatus::Offline => "offline",
+            UserStatus::InvalidUser => "invalid_user",
+            UserStatus::TimekprMissing => "timekpr_missing",
+            UserStatus::CommandTimeout => "command_timeout",
+        }
+    }
+
+    /// Human readable form for the admin view
+    pub fn label(self) -> &'static str {
+        match self {
+            UserStatus::Ok => "OK",
+            UserStatus::Offline => "Offline",
+            UserStatus::InvalidUser => "Invalid user",
+            UserStatus::TimekprMissing => "timekpra not found",
+            UserStatus::CommandTimeout => "Command timed out",
+        }
+    }
+
+    /// SSH reached the host, whatever timekpra said
+    pub fn is_reachable(self) -> bool {
+        self != UserStatus::Offline
+    }
+
+    /// timekpra ran but rejected the user
+    pub fn is_user_missing(self) -> bool {
+        self == UserStatus::InvalidUser
+    }
+}
+
+impl std::str::FromStr for UserStatus {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "ok" => Ok(UserStatus::Ok),
+            "offline" => Ok(UserStatus::Offline),
+            "invalid_user" => Ok(UserStatus::InvalidUser),
+            "timekpr_missing" => Ok(UserStatus::TimekprMissing),
+            "command_timeout" => Ok(UserStatus::CommandTimeout),
+            other => Err(format!("Unknown user status: {}", other)),
+        }
+    }
+}
+
+/// Outcome of one check of a host, as kept in the validation history
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ValidationResult {
+    pub checked_at: String,
+    pub host_id: i64,
+    pub status: String,
+    /// SSH reached the host, whatever timekpra said
+    pub reachable: bool,
+    /// timekpra ran but rejected the user
+    pub user_missing: bool,
+    /// Why the check failed; None when it succeeded
+    pub error: Option<String>,
+}
+
+impl ValidationResult {
+    pub fn new(checked_at: String, host_id: i64, status: String, error: Option<String>) -> Self {
+        let parsed = status.parse::<UserStatus>().ok();
+        Self {
+            checked_at,
+            host_id,
+            reachable: parsed.is_some_and(UserStatus::is_reachable),
+            user_missing: parsed.is_some_and(UserStatus::is_user_missing),
+            status,
+            error,
+        }
+    }
+}
+
+/// Users per state across the household; a user with additional hosts counts once
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, ToSchema)]
+pub struct UserCounts {
+    pub managed: i64,
+    pub valid: i64,
+    /// No host answered the last check and at least one was unreachable
+    pub offline: i64,
 }
 
 /// Business model for time modifications
 #[derive(Debug, Clone)]
 pub struct TimeModification {
     pub user_id: i64,
-    pub operation: String, // "+" or "-"
+    pub operation: String, // "+", "-", or "=" to set an absolute value
     pub seconds: i64,
+    pub reason: Option<String>,
 }
 
+/// One entry of a user's time modification history
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TimeModificationLogEntry {
+    pub recorded_at: String,
+    /// "+", "-", or "=" when time left was set to an absolute value
+    pub operation: String,
+    pub seconds: i64,
+    pub reason: Option<String>,
+    /// A host took it right away; false when it was queued until one came online
+    pub applied: bool,
+}
+
+/// Longest reason accepted for a time modification, in characters
+pub const MAX_REASON_LENGTH: usize = 200;
+
+/// Most time left that can be set for a day, in seconds
+pub const MAX_TIME_LIMIT_SECONDS: i64 = 24 * 60 * 60;
+
 impl TimeModification {
     pub fn new(user_id: i64, operation: String, seconds: i64) -> Result<Self, String> {
-        if operation != "+" && operation != "-" {
-            return Err("Operation must be '+' or '-'".to_string());
+        let operation = Self::normalize_operation(&operation)
+            .ok_or_else(|| "Operation must be '+' or '-'".to_string())?;
+
+        if seconds <= 0 {
+            return Err("Seconds must be positive".to_string());
         }
 
+        Ok(Self {
+            user_id,
+            operation: operation.to_string(),
+            seconds,
+            reason: None,
+        })
+    }
+
+    /// Set today's time left to exactly `seconds`, as `timekpra --settimeleft user = seconds`
+    pub fn absolute(user_id: i64, seconds: i64) -> Result<Self, String> {
         if seconds <= 0 {
             return Err("Seconds must be positive".to_string());
         }
+        if seconds > MAX_TIME_LIMIT_SECONDS {
+            return Err(format!(
+                "Seconds must be at most {} (one day)",
+                MAX_TIME_LIMIT_SECONDS
+            ));
+        }
 
         Ok(Self {
             user_id,
-            operation,
+            operation: "=".to_string(),
             seconds,
+            reason: None,
         })
     }
+
+    /// Attach an optional reason; control characters are dropped and blank reasons ignored
+    pub fn with_reason(mut self, reason: Option<&str>) -> Result<Self, String> {
+        let reason = reason
+            .map(|text| {
+                text.chars()
+                    .map(|c| if c.is_control() { ' ' } else { c })
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .filter(|text| !text.is_empty());
+
+        if let Some(text) = &reason {
+            if text.chars().count() > MAX_REASON_LENGTH {
+                return Err(format!(
+                    "Reason must be at most {} characters",
+                    MAX_REASON_LENGTH
+                ));
+            }
+        }
+
+        self.reason = reason;
+        Ok(self)
+    }
+
+    /// Map the spellings clients send ("add", " - ", "SUB", ...) to "+" or "-"
+    fn normalize_operation(operation: &str) -> Option<&'static str> {
+        match operation.trim().to_ascii_lowercase().as_str() {
+            "+" | "add" => Some("+"),
+            "-" | "sub" | "subtract" => Some("-"),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,33 @@
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Flipped once the database pool has been warmed up, backing `/api/health/ready`
+#[derive(Default)]
+pub struct Readiness {
+    ready: AtomicBool,
+}
+
+impl Readiness {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Open `connections` pool connections and run `SELECT 1` on each before marking ready.
+    /// Connections are held until all have answered so each one is a distinct connection.
+    pub async fn warm_up(&self, pool: &SqlitePool, connections: u32) -> Result<(), sqlx::Error> {
+        let mut warmed = Vec::new();
+        for _ in 0..connections.clamp(1, pool.options().get_max_connections()) {
+            let mut conn = pool.acquire().await?;
+            sqlx::query("SELECT 1").execute(&mut *conn).await?;
+            warmed.push(conn);
+        }
+        drop(warmed);
+
+        self.ready.store(true, Ordering::Release);
+        Ok(())
+    }
+}
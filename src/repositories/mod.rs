@@ -1,9 +1,9 @@
 pub mod schedule_repository;
+pub mod settings_repository;
 pub mod usage_repository;
 pub mod user_repository;
-pub mod settings_repository;
 
 pub use schedule_repository::*;
+pub use settings_repository::*;
 pub use usage_repository::*;
 pub use user_repository::*;
-pub use settings_repository::*;
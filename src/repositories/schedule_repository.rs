@@ -1,6 +1,9 @@
-use crate::models::{Schedule, ServiceError, TimeInterval, WeeklyHours, WeeklyTimeIntervals};
+use crate::models::{
+    DayOfWeek, LockoutSettings, PlayTimeSettings, Schedule, ServiceError, TimeInterval,
+    UserLockout, UserPlayTime, WeeklyHours, WeeklyTimeIntervals,
+};
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::SqlitePool;
 
 #[async_trait]
@@ -9,8 +12,93 @@ pub trait ScheduleRepository: Send + Sync {
     async fn find_by_user_id(&self, user_id: i64) -> Result<Option<Schedule>, ServiceError>;
     #[allow(dead_code)]
     async fn find_unsynced(&self) -> Result<Vec<Schedule>, ServiceError>;
-    #[allow(dead_code)]
-    async fn mark_as_synced(&self, user_id: i64) -> Result<(), ServiceError>;
+    /// Record a successful push made for `synced_day`, whose weekday got `day_hours`
+    async fn mark_as_synced(
+        &self,
+        user_id: i64,
+        synced_day: NaiveDate,
+        day_hours: f64,
+    ) -> Result<(), ServiceError>;
+    /// Synced schedules last pushed for a day before `day`, or before that was recorded
+    async fn find_synced_before(&self, day: NaiveDate) -> Result<Vec<Schedule>, ServiceError>;
+    /// Note that the last push still holds on `synced_day`, without touching the sync state
+    async fn update_synced_day(
+        &self,
+        user_id: i64,
+        synced_day: NaiveDate,
+        day_hours: f64,
+    ) -> Result<(), ServiceError>;
+    /// Queue the schedule for a push that is due right away
+    async fn mark_unsynced(&self, user_id: i64) -> Result<(), ServiceError>;
+    /// Queue the schedule, PlayTime and lockout settings for a push, e.g. to a new host
+    async fn mark_all_unsynced(&self, user_id: i64) -> Result<(), ServiceError>;
+    async fn record_sync_failure(
+        &self,
+        user_id: i64,
+        attempts: i64,
+        next_attempt: DateTime<Utc>,
+    ) -> Result<(), ServiceError>;
+    /// Forget earlier failed syncs so the scheduler tries again right away
+    async fn reset_sync_attempts(&self, user_id: i64) -> Result<(), ServiceError>;
+    /// Reset all stored intervals to full days; returns false when the user has no schedule
+    async fn clear_intervals(&self, user_id: i64, is_synced: bool) -> Result<bool, ServiceError>;
+    /// Hours set for a specific date, replacing the weekly value
+    async fn find_limit_override(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+    ) -> Result<Option<f64>, ServiceError>;
+    /// Set the hours for a specific date and queue the schedule for a push, so the host
+    /// gets the new limit for that day
+    async fn save_limit_override(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+        hours: f64,
+    ) -> Result<(), ServiceError>;
+    /// Extra minutes granted every week on the given day
+    async fn find_recurring_bonus(
+        &self,
+        user_id: i64,
+        day: DayOfWeek,
+    ) -> Result<Option<i64>, ServiceError>;
+    /// Drop the recurring bonuses on the given days, and the overrides for dates from `from`
+    /// on that fall on one of them
+    async fn clear_day_extras(
+        &self,
+        user_id: i64,
+        days: &[DayOfWeek],
+        from: NaiveDate,
+    ) -> Result<(), ServiceError>;
+    async fn find_playtime(&self, user_id: i64) -> Result<Option<UserPlayTime>, ServiceError>;
+    /// Store PlayTime settings as not yet synced
+    async fn save_playtime(
+        &self,
+        user_id: i64,
+        settings: &PlayTimeSettings,
+    ) -> Result<(), ServiceError>;
+    async fn find_unsynced_playtime(&self) -> Result<Vec<UserPlayTime>, ServiceError>;
+    async fn mark_playtime_synced(&self, user_id: i64) -> Result<(), ServiceError>;
+    async fn find_lockout(&self, user_id: i64) -> Result<Option<UserLockout>, ServiceError>;
+    /// Store lockout settings as not yet synced, replacing earlier ones
+    async fn save_lockout(
+        &self,
+        user_id: i64,
+        settings: &LockoutSettings,
+    ) -> Result<(), ServiceError>;
+    async fn find_unsynced_lockout(&self) -> Result<Vec<UserLockout>, ServiceError>;
+    async fn mark_lockout_synced(&self, user_id: i64) -> Result<(), ServiceError>;
+}
+
+fn parse_playtime_settings(settings: &str) -> Result<PlayTimeSettings, ServiceError> {
+    serde_json::from_str(settings).map_err(|e| {
+        ServiceError::InternalError(format!("Invalid stored PlayTime settings: {}", e))
+    })
+}
+
+fn parse_lockout_settings(settings: &str) -> Result<LockoutSettings, ServiceError> {
+    serde_json::from_str(settings)
+        .map_err(|e| ServiceError::InternalError(format!("Invalid stored lockout settings: {}", e)))
 }
 
 pub struct SqliteScheduleRepository {
@@ -28,32 +116,15 @@ impl ScheduleRepository for SqliteScheduleRepository {
     async fn save(&self, schedule: &Schedule) -> Result<(), ServiceError> {
         let last_modified = schedule.last_modified.naive_utc();
 
-        // Extract interval values to avoid borrowing issues
-        let mon_start = &schedule.intervals.monday.start_time;
-        let mon_end = &schedule.intervals.monday.end_time;
-        let tue_start = &schedule.intervals.tuesday.start_time;
-        let tue_end = &schedule.intervals.tuesday.end_time;
-        let wed_start = &schedule.intervals.wednesday.start_time;
-        let wed_end = &schedule.intervals.wednesday.end_time;
-        let thu_start = &schedule.intervals.thursday.start_time;
-        let thu_end = &schedule.intervals.thursday.end_time;
-        let fri_start = &schedule.intervals.friday.start_time;
-        let fri_end = &schedule.intervals.friday.end_time;
-        let sat_start = &schedule.intervals.saturday.start_time;
-        let sat_end = &schedule.intervals.saturday.end_time;
-        let sun_start = &schedule.intervals.sunday.start_time;
-        let sun_end = &schedule.intervals.sunday.end_time;
-
-        sqlx::query!(
-            "INSERT OR REPLACE INTO user_weekly_schedule 
-             (user_id, monday_hours, tuesday_hours, wednesday_hours, thursday_hours, 
-              friday_hours, saturday_hours, sunday_hours, is_synced, last_modified,
-              monday_start_time, monday_end_time, tuesday_start_time, tuesday_end_time,
-              wednesday_start_time, wednesday_end_time, thursday_start_time, thursday_end_time,
-              friday_start_time, friday_end_time, saturday_start_time, saturday_end_time,
-              sunday_start_time, sunday_end_time)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?,
-                     ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        // Hours and every day's interval are written together or not at all
+        let mut tx = self.pool.begin().await?;
+
+        // Replacing the row also resets the sync state and backoff to their defaults
+        sqlx::query!(
+            "INSERT OR REPLACE INTO user_weekly_schedule
+             (user_id, monday_hours, tuesday_hours, wednesday_hours, thursday_hours,
+              friday_hours, saturday_hours, sunday_hours, is_synced, last_modified)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             schedule.user_id,
             schedule.hours.monday,
             schedule.hours.tuesday,
@@ -63,25 +134,38 @@ impl ScheduleRepository for SqliteScheduleRepository {
             schedule.hours.saturday,
             schedule.hours.sunday,
             schedule.is_synced,
-            last_modified,
-            mon_start,
-            mon_end,
-            tue_start,
-            tue_end,
-            wed_start,
-            wed_end,
-            thu_start,
-            thu_end,
-            fri_start,
-            fri_end,
-            sat_start,
-            sat_end,
-            sun_start,
-            sun_end
+            last_modified
         )
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
 
+        // One row per interval, replacing the stored ones
+        sqlx::query!(
+            "DELETE FROM user_daily_time_interval WHERE user_id = ?",
+            schedule.user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        for day in DayOfWeek::iter() {
+            let day_index = day.to_timekpr_index() as i64;
+            for interval in schedule.intervals.get(day) {
+                sqlx::query!(
+                    "INSERT INTO user_daily_time_interval (user_id, day_of_week, start_time, end_time)
+                     VALUES (?, ?, ?, ?)",
+                    schedule.user_id,
+                    day_index,
+                    interval.start_time,
+                    interval.end_time
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        // Dropping the transaction on an earlier error rolls everything back
+        tx.commit().await?;
+
         Ok(())
     }
 
@@ -89,10 +173,7 @@ impl ScheduleRepository for SqliteScheduleRepository {
         let row = sqlx::query!(
             "SELECT user_id, monday_hours, tuesday_hours, wednesday_hours, thursday_hours,
                     friday_hours, saturday_hours, sunday_hours, is_synced, last_synced, last_modified,
-                    monday_start_time, monday_end_time, tuesday_start_time, tuesday_end_time,
-                    wednesday_start_time, wednesday_end_time, thursday_start_time, thursday_end_time,
-                    friday_start_time, friday_end_time, saturday_start_time, saturday_end_time,
-                    sunday_start_time, sunday_end_time
+                    sync_attempts, next_sync_attempt, synced_day, synced_day_hours
              FROM user_weekly_schedule WHERE user_id = ? ORDER BY last_modified DESC LIMIT 1",
             user_id
         )
@@ -100,6 +181,31 @@ impl ScheduleRepository for SqliteScheduleRepository {
         .await?;
 
         if let Some(row) = row {
+            let rows = sqlx::query!(
+                "SELECT day_of_week, start_time, end_time FROM user_daily_time_interval
+                 WHERE user_id = ? ORDER BY day_of_week, start_time",
+                user_id
+            )
+            .fetch_all(&self.pool)
+            .await?;
+
+            // Days without stored intervals keep the whole day
+            let mut intervals = WeeklyTimeIntervals::default();
+            for day in DayOfWeek::iter() {
+                let day_index = day.to_timekpr_index() as i64;
+                let day_intervals: Vec<TimeInterval> = rows
+                    .iter()
+                    .filter(|row| row.day_of_week == day_index)
+                    .map(|row| TimeInterval {
+                        start_time: row.start_time.clone(),
+                        end_time: row.end_time.clone(),
+                    })
+                    .collect();
+                if !day_intervals.is_empty() {
+                    intervals.set(day, day_intervals);
+                }
+            }
+
             let schedule = Schedule {
                 user_id: row.user_id,
                 hours: WeeklyHours {
@@ -111,42 +217,17 @@ impl ScheduleRepository for SqliteScheduleRepository {
                     saturday: row.saturday_hours.unwrap_or(0.0),
                     sunday: row.sunday_hours.unwrap_or(0.0),
                 },
-                intervals: WeeklyTimeIntervals {
-                    monday: TimeInterval {
-                        start_time: row.monday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.monday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    tuesday: TimeInterval {
-                        start_time: row.tuesday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.tuesday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    wednesday: TimeInterval {
-                        start_time: row.wednesday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.wednesday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    thursday: TimeInterval {
-                        start_time: row.thursday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.thursday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    friday: TimeInterval {
-                        start_time: row.friday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.friday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    saturday: TimeInterval {
-                        start_time: row.saturday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.saturday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                    sunday: TimeInterval {
-                        start_time: row.sunday_start_time.unwrap_or("00:00".to_string()),
-                        end_time: row.sunday_end_time.unwrap_or("23:59".to_string()),
-                    },
-                },
+                intervals,
                 is_synced: row.is_synced.unwrap_or(false),
                 last_synced: row.last_synced.map(|dt| dt.and_utc()),
                 last_modified: row
                     .last_modified
                     .map(|dt| dt.and_utc())
-                    .unwrap_or_else(|| Utc::now()),
+                    .unwrap_or_else(Utc::now),
+                sync_attempts: row.sync_attempts,
+                next_sync_attempt: row.next_sync_attempt.map(|dt| dt.and_utc()),
+                synced_day: row.synced_day,
+                synced_day_hours: row.synced_day_hours,
             };
             Ok(Some(schedule))
         } else {
@@ -157,7 +238,8 @@ impl ScheduleRepository for SqliteScheduleRepository {
     async fn find_unsynced(&self) -> Result<Vec<Schedule>, ServiceError> {
         let rows = sqlx::query!(
             "SELECT user_id, monday_hours, tuesday_hours, wednesday_hours, thursday_hours,
-                    friday_hours, saturday_hours, sunday_hours, is_synced, last_synced, last_modified
+                    friday_hours, saturday_hours, sunday_hours, is_synced, last_synced, last_modified,
+                    sync_attempts, next_sync_attempt, synced_day, synced_day_hours
              FROM user_weekly_schedule WHERE is_synced = 0"
         )
         .fetch_all(&self.pool)
@@ -182,17 +264,435 @@ impl ScheduleRepository for SqliteScheduleRepository {
                 last_modified: row
                     .last_modified
                     .map(|dt| dt.and_utc())
-                    .unwrap_or_else(|| Utc::now()),
+                    .unwrap_or_else(Utc::now),
+                sync_attempts: row.sync_attempts,
+                next_sync_attempt: row.next_sync_attempt.map(|dt| dt.and_utc()),
+                synced_day: row.synced_day,
+                synced_day_hours: row.synced_day_hours,
+            })
+            .collect();
+
+        Ok(schedules)
+    }
+
+    async fn mark_as_synced(
+        &self,
+        user_id: i64,
+        synced_day: NaiveDate,
+        day_hours: f64,
+    ) -> Result<(), ServiceError> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET is_synced = 1, last_synced = ?, sync_attempts = 0, next_sync_attempt = NULL,
+                    synced_day = ?, synced_day_hours = ?
+             WHERE user_id = ?",
+            now,
+            synced_day,
+            day_hours,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_synced_before(&self, day: NaiveDate) -> Result<Vec<Schedule>, ServiceError> {
+        // Only the limits are compared, so the intervals are left at their defaults
+        let rows = sqlx::query!(
+            "SELECT user_id, monday_hours, tuesday_hours, wednesday_hours, thursday_hours,
+                    friday_hours, saturday_hours, sunday_hours, is_synced, last_synced, last_modified,
+                    sync_attempts, next_sync_attempt, synced_day, synced_day_hours
+             FROM user_weekly_schedule WHERE is_synced = 1 AND (synced_day IS NULL OR synced_day < ?)",
+            day
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let schedules = rows
+            .into_iter()
+            .map(|row| Schedule {
+                user_id: row.user_id,
+                hours: WeeklyHours {
+                    monday: row.monday_hours.unwrap_or(0.0),
+                    tuesday: row.tuesday_hours.unwrap_or(0.0),
+                    wednesday: row.wednesday_hours.unwrap_or(0.0),
+                    thursday: row.thursday_hours.unwrap_or(0.0),
+                    friday: row.friday_hours.unwrap_or(0.0),
+                    saturday: row.saturday_hours.unwrap_or(0.0),
+                    sunday: row.sunday_hours.unwrap_or(0.0),
+                },
+                intervals: WeeklyTimeIntervals::default(),
+                is_synced: row.is_synced.unwrap_or(false),
+                last_synced: row.last_synced.map(|dt| dt.and_utc()),
+                last_modified: row
+                    .last_modified
+                    .map(|dt| dt.and_utc())
+                    .unwrap_or_else(Utc::now),
+                sync_attempts: row.sync_attempts,
+                next_sync_attempt: row.next_sync_attempt.map(|dt| dt.and_utc()),
+                synced_day: row.synced_day,
+                synced_day_hours: row.synced_day_hours,
             })
             .collect();
 
         Ok(schedules)
     }
 
-    async fn mark_as_synced(&self, user_id: i64) -> Result<(), ServiceError> {
+    async fn update_synced_day(
+        &self,
+        user_id: i64,
+        synced_day: NaiveDate,
+        day_hours: f64,
+    ) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET synced_day = ?, synced_day_hours = ? WHERE user_id = ?",
+            synced_day,
+            day_hours,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_unsynced(&self, user_id: i64) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET is_synced = 0, sync_attempts = 0, next_sync_attempt = NULL
+             WHERE user_id = ?",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn mark_all_unsynced(&self, user_id: i64) -> Result<(), ServiceError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET is_synced = 0, sync_attempts = 0, next_sync_attempt = NULL
+             WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE user_playtime SET is_synced = 0 WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE user_lockout SET is_synced = 0 WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn record_sync_failure(
+        &self,
+        user_id: i64,
+        attempts: i64,
+        next_attempt: DateTime<Utc>,
+    ) -> Result<(), ServiceError> {
+        let next_attempt = next_attempt.naive_utc();
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET sync_attempts = ?, next_sync_attempt = ? WHERE user_id = ?",
+            attempts,
+            next_attempt,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn reset_sync_attempts(&self, user_id: i64) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET sync_attempts = 0, next_sync_attempt = NULL WHERE user_id = ?",
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn clear_intervals(&self, user_id: i64, is_synced: bool) -> Result<bool, ServiceError> {
+        let now = Utc::now().naive_utc();
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE user_weekly_schedule SET
+                is_synced = ?, last_modified = ?, sync_attempts = 0, next_sync_attempt = NULL
+             WHERE user_id = ?",
+            is_synced,
+            now,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        // Days without stored intervals are read back as full days
+        sqlx::query!(
+            "DELETE FROM user_daily_time_interval WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn find_limit_override(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+    ) -> Result<Option<f64>, ServiceError> {
+        let row = sqlx::query!(
+            "SELECT hours FROM user_limit_override WHERE user_id = ? AND date = ?",
+            user_id,
+            date
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.hours))
+    }
+
+    async fn save_limit_override(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+        hours: f64,
+    ) -> Result<(), ServiceError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO user_limit_override (user_id, date, hours) VALUES (?, ?, ?)
+             ON CONFLICT(user_id, date) DO UPDATE SET hours = excluded.hours",
+            user_id,
+            date,
+            hours
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // A new change is due right away rather than after an earlier failure's backoff
+        sqlx::query!(
+            "UPDATE user_weekly_schedule SET is_synced = 0, sync_attempts = 0, next_sync_attempt = NULL
+             WHERE user_id = ?",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_recurring_bonus(
+        &self,
+        user_id: i64,
+        day: DayOfWeek,
+    ) -> Result<Option<i64>, ServiceError> {
+        let day_index = day.to_timekpr_index() as i64;
+        let row = sqlx::query!(
+            "SELECT minutes FROM user_recurring_bonus WHERE user_id = ? AND day_of_week = ?",
+            user_id,
+            day_index
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| row.minutes))
+    }
+
+    async fn clear_day_extras(
+        &self,
+        user_id: i64,
+        days: &[DayOfWeek],
+        from: NaiveDate,
+    ) -> Result<(), ServiceError> {
+        let mut tx = self.pool.begin().await?;
+
+        for day in days {
+            let day_index = day.to_timekpr_index() as i64;
+            sqlx::query!(
+                "DELETE FROM user_recurring_bonus WHERE user_id = ? AND day_of_week = ?",
+                user_id,
+                day_index
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        let dates = sqlx::query!(
+            "SELECT date FROM user_limit_override WHERE user_id = ? AND date >= ?",
+            user_id,
+            from
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in dates {
+            if days.contains(&DayOfWeek::from_date(row.date)) {
+                sqlx::query!(
+                    "DELETE FROM user_limit_override WHERE user_id = ? AND date = ?",
+                    user_id,
+                    row.date
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn find_playtime(&self, user_id: i64) -> Result<Option<UserPlayTime>, ServiceError> {
+        let row = sqlx::query!(
+            "SELECT user_id, settings, is_synced, last_synced FROM user_playtime WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| {
+            Ok(UserPlayTime {
+                user_id: row.user_id,
+                settings: parse_playtime_settings(&row.settings)?,
+                is_synced: row.is_synced,
+                last_synced: row.last_synced.map(|dt| dt.and_utc()),
+            })
+        })
+        .transpose()
+    }
+
+    async fn save_playtime(
+        &self,
+        user_id: i64,
+        settings: &PlayTimeSettings,
+    ) -> Result<(), ServiceError> {
+        let settings = serde_json::to_string(settings)
+            .map_err(|e| ServiceError::InternalError(e.to_string()))?;
+        sqlx::query!(
+            "INSERT INTO user_playtime (user_id, settings, is_synced) VALUES (?, ?, 0)
+             ON CONFLICT(user_id) DO UPDATE SET settings = excluded.settings, is_synced = 0",
+            user_id,
+            settings
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_unsynced_playtime(&self) -> Result<Vec<UserPlayTime>, ServiceError> {
+        let rows = sqlx::query!(
+            "SELECT user_id, settings, is_synced, last_synced FROM user_playtime WHERE is_synced = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(UserPlayTime {
+                    user_id: row.user_id,
+                    settings: parse_playtime_settings(&row.settings)?,
+                    is_synced: row.is_synced,
+                    last_synced: row.last_synced.map(|dt| dt.and_utc()),
+                })
+            })
+            .collect()
+    }
+
+    async fn mark_playtime_synced(&self, user_id: i64) -> Result<(), ServiceError> {
+        let now = Utc::now().naive_utc();
+        sqlx::query!(
+            "UPDATE user_playtime SET is_synced = 1, last_synced = ? WHERE user_id = ?",
+            now,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_lockout(&self, user_id: i64) -> Result<Option<UserLockout>, ServiceError> {
+        let row = sqlx::query!(
+            "SELECT user_id, settings, is_synced, last_synced FROM user_lockout WHERE user_id = ?",
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(|row| {
+            Ok(UserLockout {
+                user_id: row.user_id,
+                settings: parse_lockout_settings(&row.settings)?,
+                is_synced: row.is_synced,
+                last_synced: row.last_synced.map(|dt| dt.and_utc()),
+            })
+        })
+        .transpose()
+    }
+
+    async fn save_lockout(
+        &self,
+        user_id: i64,
+        settings: &LockoutSettings,
+    ) -> Result<(), ServiceError> {
+        let settings = serde_json::to_string(settings)
+            .map_err(|e| ServiceError::InternalError(e.to_string()))?;
+        sqlx::query!(
+            "INSERT INTO user_lockout (user_id, settings, is_synced) VALUES (?, ?, 0)
+             ON CONFLICT(user_id) DO UPDATE SET settings = excluded.settings, is_synced = 0",
+            user_id,
+            settings
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn find_unsynced_lockout(&self) -> Result<Vec<UserLockout>, ServiceError> {
+        let rows = sqlx::query!(
+            "SELECT user_id, settings, is_synced, last_synced FROM user_lockout WHERE is_synced = 0"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(UserLockout {
+                    user_id: row.user_id,
+                    settings: parse_lockout_settings(&row.settings)?,
+                    is_synced: row.is_synced,
+                    last_synced: row.last_synced.map(|dt| dt.and_utc()),
+                })
+            })
+            .collect()
+    }
+
+    async fn mark_lockout_synced(&self, user_id: i64) -> Result<(), ServiceError> {
         let now = Utc::now().naive_utc();
         sqlx::query!(
-            "UPDATE user_weekly_schedule SET is_synced = 1, last_synced = ? WHERE user_id = ?",
+            "UPDATE user_lockout SET is_synced = 1, last_synced = ? WHERE user_id = ?",
             now,
             user_id
         )
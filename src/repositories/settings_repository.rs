@@ -1,4 +1,4 @@
-use crate::models::{SettingsEntry, ServiceError};
+use crate::models::{ServiceError, SettingsEntry};
 use async_trait::async_trait;
 use sqlx::SqlitePool;
 
@@ -9,7 +9,6 @@ pub trait SettingsRepository: Send + Sync {
     async fn find_all(&self) -> Result<Vec<SettingsEntry>, ServiceError>;
     async fn save(&self, entry: &SettingsEntry) -> Result<(), ServiceError>;
     async fn delete(&self, id: i64) -> Result<(), ServiceError>;
-
 }
 
 pub struct SqliteSettingsRepository {
@@ -25,12 +24,9 @@ impl SqliteSettingsRepository {
 #[async_trait]
 impl SettingsRepository for SqliteSettingsRepository {
     async fn find_by_id(&self, id: i64) -> Result<Option<SettingsEntry>, ServiceError> {
-        let row = sqlx::query!(
-            "SELECT id, key, value FROM settings WHERE id = ?",
-            id
-        )
-        .fetch_optional(&self.pool)
-        .await?;
+        let row = sqlx::query!("SELECT id, key, value FROM settings WHERE id = ?", id)
+            .fetch_optional(&self.pool)
+            .await?;
 
         if let Some(row) = row {
             Ok(Some(SettingsEntry::with_id(row.id, row.key, row.value)))
@@ -38,31 +34,26 @@ impl SettingsRepository for SqliteSettingsRepository {
             Ok(None)
         }
     }
-    
+
     async fn find_by_key(&self, key: &str) -> Result<Option<SettingsEntry>, ServiceError> {
-        let row = sqlx::query!(
-            "SELECT id, key, value FROM settings WHERE key = ?",
-            key
-        )
-        .fetch_optional(&self.pool)
-        .await?;
+        let row = sqlx::query!("SELECT id, key, value FROM settings WHERE key = ?", key)
+            .fetch_optional(&self.pool)
+            .await?;
 
         if let Some(row) = row {
-            let id = row.id.ok_or_else(|| ServiceError::DatabaseError("Invalid settings row: missing ID".to_string()))?;
+            let id = row.id.ok_or_else(|| {
+                ServiceError::DatabaseError("Invalid settings row: missing ID".to_string())
+            })?;
             Ok(Some(SettingsEntry::with_id(id, row.key, row.value)))
-
         } else {
             Ok(None)
         }
     }
 
-
     async fn find_all(&self) -> Result<Vec<SettingsEntry>, ServiceError> {
-        let rows = sqlx::query!(
-            "SELECT id, key, value FROM settings ORDER BY id"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        let rows = sqlx::query!("SELECT id, key, value FROM settings ORDER BY id")
+            .fetch_all(&self.pool)
+            .await?;
 
         let settings = rows
             .into_iter()
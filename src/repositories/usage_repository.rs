@@ -1,8 +1,11 @@
-use crate::models::ServiceError;
+use crate::models::{DayOfWeek, ServiceError};
 use async_trait::async_trait;
-use chrono::NaiveDate;
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
 use sqlx::SqlitePool;
 
+/// Hard upper bound on the number of days a single usage query may span
+pub const MAX_USAGE_DAYS: i32 = 366;
+
 #[async_trait]
 pub trait UsageRepository: Send + Sync {
     #[allow(dead_code)]
@@ -11,9 +14,11 @@ pub trait UsageRepository: Send + Sync {
         user_id: i64,
         date: NaiveDate,
     ) -> Result<Option<i64>, ServiceError>;
+    /// Usage from `days` days before `to` up to and including `to`, the caller's local day
     async fn get_usage_data(
         &self,
         user_id: i64,
+        to: NaiveDate,
         days: i32,
     ) -> Result<Vec<(NaiveDate, i64)>, ServiceError>;
     async fn store_daily_usage(
@@ -22,6 +27,60 @@ pub trait UsageRepository: Send + Sync {
         date: NaiveDate,
         time_spent: i64,
     ) -> Result<(), ServiceError>;
+    /// Seconds granted with '+' adjustments on the given day
+    async fn get_granted_seconds(&self, user_id: i64, date: NaiveDate)
+        -> Result<i64, ServiceError>;
+    async fn add_granted_seconds(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+        seconds: i64,
+    ) -> Result<(), ServiceError>;
+    async fn store_usage_sample(
+        &self,
+        user_id: i64,
+        sampled_at: DateTime<Utc>,
+        time_spent: i64,
+    ) -> Result<(), ServiceError>;
+    /// Samples taken in [from, to), oldest first
+    async fn get_usage_samples(
+        &self,
+        user_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, i64)>, ServiceError>;
+    /// Delete samples taken before `before`; returns how many were removed
+    async fn prune_usage_samples(&self, before: DateTime<Utc>) -> Result<u64, ServiceError>;
+    /// Seconds used by everyone together on each day in [from, to], busiest day first
+    async fn get_household_usage(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, i64)>, ServiceError>;
+    /// Users (host groups) that used at least `fraction` of their limit on `date`.
+    /// Users without a limit that day are not counted.
+    async fn count_users_near_limit(
+        &self,
+        date: NaiveDate,
+        fraction: f64,
+    ) -> Result<i64, ServiceError>;
+    /// Record a time modification; `applied` is false when it was queued for later
+    async fn log_modification(
+        &self,
+        user_id: i64,
+        operation: &str,
+        seconds: i64,
+        reason: Option<&str>,
+        applied: bool,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ServiceError>;
+    /// The last `limit` modifications as (recorded_at, operation, seconds, reason, applied),
+    /// newest first
+    async fn get_modification_log(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(DateTime<Utc>, String, i64, Option<String>, bool)>, ServiceError>;
 }
 
 pub struct SqliteUsageRepository {
@@ -55,14 +114,23 @@ impl UsageRepository for SqliteUsageRepository {
     async fn get_usage_data(
         &self,
         user_id: i64,
+        to: NaiveDate,
         days: i32,
     ) -> Result<Vec<(NaiveDate, i64)>, ServiceError> {
+        if days <= 0 {
+            return Err(ServiceError::ValidationError(
+                "days must be a positive number".to_string(),
+            ));
+        }
+        let from = to - Duration::days(days.min(MAX_USAGE_DAYS) as i64);
+
         let rows = sqlx::query!(
-            "SELECT date, time_spent FROM user_time_usage 
-             WHERE user_id = ? AND date >= date('now', '-' || ? || ' days')
+            "SELECT date, time_spent FROM user_time_usage
+             WHERE user_id = ? AND date >= ? AND date <= ?
              ORDER BY date ASC",
             user_id,
-            days
+            from,
+            to
         )
         .fetch_all(&self.pool)
         .await?;
@@ -92,4 +160,217 @@ impl UsageRepository for SqliteUsageRepository {
 
         Ok(())
     }
+
+    async fn get_granted_seconds(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+    ) -> Result<i64, ServiceError> {
+        let seconds = sqlx::query_scalar!(
+            "SELECT seconds FROM user_daily_grant WHERE user_id = ? AND date = ?",
+            user_id,
+            date
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(seconds.unwrap_or(0))
+    }
+
+    async fn add_granted_seconds(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+        seconds: i64,
+    ) -> Result<(), ServiceError> {
+        sqlx::query!(
+            "INSERT INTO user_daily_grant (user_id, date, seconds) VALUES (?, ?, ?)
+             ON CONFLICT(user_id, date) DO UPDATE SET seconds = seconds + excluded.seconds",
+            user_id,
+            date,
+            seconds
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn store_usage_sample(
+        &self,
+        user_id: i64,
+        sampled_at: DateTime<Utc>,
+        time_spent: i64,
+    ) -> Result<(), ServiceError> {
+        let sampled_at = sampled_at.naive_utc();
+        sqlx::query!(
+            "INSERT INTO user_usage_sample (user_id, sampled_at, time_spent) VALUES (?, ?, ?)",
+            user_id,
+            sampled_at,
+            time_spent
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_usage_samples(
+        &self,
+        user_id: i64,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, i64)>, ServiceError> {
+        let from = from.naive_utc();
+        let to = to.naive_utc();
+        let rows = sqlx::query!(
+            r#"SELECT sampled_at as "sampled_at: NaiveDateTime", time_spent FROM user_usage_sample
+             WHERE user_id = ? AND sampled_at >= ? AND sampled_at < ?
+             ORDER BY sampled_at ASC"#,
+            user_id,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.sampled_at.and_utc(), row.time_spent))
+            .collect())
+    }
+
+    async fn prune_usage_samples(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        let before = before.naive_utc();
+        let result = sqlx::query!("DELETE FROM user_usage_sample WHERE sampled_at < ?", before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn get_household_usage(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<(NaiveDate, i64)>, ServiceError> {
+        let rows = sqlx::query!(
+            r#"SELECT date as "date!: NaiveDate", SUM(time_spent) as "time_spent!: i64"
+               FROM user_time_usage
+               WHERE date >= ? AND date <= ?
+               GROUP BY date
+               ORDER BY SUM(time_spent) DESC, date"#,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.date, row.time_spent))
+            .collect())
+    }
+
+    async fn count_users_near_limit(
+        &self,
+        date: NaiveDate,
+        fraction: f64,
+    ) -> Result<i64, ServiceError> {
+        let day_index = DayOfWeek::from_date(date).to_timekpr_index() as i64;
+        // Same precedence as effective_limit_seconds: override, else weekly hours, plus bonus
+        let row = sqlx::query!(
+            r#"WITH spent AS (
+                   SELECT COALESCE(m.host_group_id, m.id) AS user_id, SUM(u.time_spent) AS seconds
+                   FROM user_time_usage u JOIN managed_users m ON m.id = u.user_id
+                   WHERE u.date = ?
+                   GROUP BY COALESCE(m.host_group_id, m.id)
+               ),
+               limits AS (
+                   SELECT spent.user_id, spent.seconds,
+                          MIN(86400, MAX(0,
+                              COALESCE(o.hours, CASE ?
+                                  WHEN 1 THEN s.monday_hours
+                                  WHEN 2 THEN s.tuesday_hours
+                                  WHEN 3 THEN s.wednesday_hours
+                                  WHEN 4 THEN s.thursday_hours
+                                  WHEN 5 THEN s.friday_hours
+                                  WHEN 6 THEN s.saturday_hours
+                                  ELSE s.sunday_hours
+                              END) * 3600 + COALESCE(b.minutes, 0) * 60)) AS limit_seconds
+                   FROM spent
+                   LEFT JOIN user_weekly_schedule s ON s.user_id = spent.user_id
+                   LEFT JOIN user_limit_override o ON o.user_id = spent.user_id AND o.date = ?
+                   LEFT JOIN user_recurring_bonus b ON b.user_id = spent.user_id AND b.day_of_week = ?
+                   WHERE s.user_id IS NOT NULL OR o.user_id IS NOT NULL
+               )
+               SELECT COUNT(*) as "count!: i64" FROM limits
+               WHERE limit_seconds > 0 AND seconds >= limit_seconds * ?"#,
+            date,
+            day_index,
+            date,
+            day_index,
+            fraction
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.count)
+    }
+
+    async fn log_modification(
+        &self,
+        user_id: i64,
+        operation: &str,
+        seconds: i64,
+        reason: Option<&str>,
+        applied: bool,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(), ServiceError> {
+        let recorded_at = timestamp.naive_utc();
+        sqlx::query!(
+            "INSERT INTO time_modification_log (user_id, operation, seconds, reason, applied, recorded_at) VALUES (?, ?, ?, ?, ?, ?)",
+            user_id,
+            operation,
+            seconds,
+            reason,
+            applied,
+            recorded_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_modification_log(
+        &self,
+        user_id: i64,
+        limit: i64,
+    ) -> Result<Vec<(DateTime<Utc>, String, i64, Option<String>, bool)>, ServiceError> {
+        let rows = sqlx::query!(
+            r#"SELECT recorded_at as "recorded_at: NaiveDateTime", operation, seconds, reason, applied
+             FROM time_modification_log
+             WHERE user_id = ?
+             ORDER BY recorded_at DESC, id DESC
+             LIMIT ?"#,
+            user_id,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.recorded_at.and_utc(),
+                    row.operation,
+                    row.seconds,
+                    row.reason,
+                    row.applied,
+                )
+            })
+            .collect())
+    }
 }
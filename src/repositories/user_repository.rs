@@ -1,6 +1,44 @@
-use crate::models::{ManagedUser, ServiceError};
+use crate::models::{ManagedUser, ServiceError, UserCounts};
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
 use sqlx::SqlitePool;
+use std::io::{Read, Write};
+
+/// Gzip a timekpr config snapshot for storage in `last_config_gz`
+pub fn compress_config(config: &str) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    // Writing into a Vec cannot fail
+    encoder
+        .write_all(config.as_bytes())
+        .and_then(|_| encoder.finish())
+        .unwrap_or_default()
+}
+
+/// `pending_time_operation` only takes '+' and '-'; a queued '=' is stored as '+' with
+/// `pending_time_absolute` set
+fn encode_operation(operation: Option<&str>) -> (Option<&str>, bool) {
+    match operation {
+        Some("=") => (Some("+"), true),
+        operation => (operation, false),
+    }
+}
+
+fn decode_operation(operation: Option<String>, absolute: bool) -> Option<String> {
+    match operation {
+        Some(_) if absolute => Some("=".to_string()),
+        operation => operation,
+    }
+}
+
+/// Inverse of `compress_config`; returns None for corrupt data
+pub fn decompress_config(bytes: &[u8]) -> Option<String> {
+    let mut config = String::new();
+    GzDecoder::new(bytes).read_to_string(&mut config).ok()?;
+    Some(config)
+}
 
 #[async_trait]
 pub trait UserRepository: Send + Sync {
@@ -8,25 +46,91 @@ pub trait UserRepository: Send + Sync {
     async fn find_all_valid(&self) -> Result<Vec<ManagedUser>, ServiceError>;
     async fn find_all_pending(&self) -> Result<Vec<ManagedUser>, ServiceError>;
     async fn find_all(&self) -> Result<Vec<ManagedUser>, ServiceError>;
-    async fn save(&self, user: &ManagedUser) -> Result<(), ServiceError>;
+    /// The primary user and its additional hosts, most recently checked first
+    async fn find_group(&self, primary_id: i64) -> Result<Vec<ManagedUser>, ServiceError>;
+    async fn count_users(&self) -> Result<UserCounts, ServiceError>;
+    /// Insert the user when its id is 0, update it otherwise; returns the user's id
+    async fn save(&self, user: &ManagedUser) -> Result<i64, ServiceError>;
     async fn delete(&self, id: i64) -> Result<(), ServiceError>;
+    /// Delete the given users and their additional hosts in one transaction.
+    /// Returns the ids that existed and were deleted.
+    async fn delete_many(&self, ids: &[i64]) -> Result<Vec<i64>, ServiceError>;
     async fn update_pending_time_adjustment(
         &self,
         user_id: i64,
         operation: &str,
         seconds: i64,
+        reason: Option<&str>,
     ) -> Result<(), ServiceError>;
     #[allow(dead_code)]
     async fn clear_pending_time_adjustment(&self, user_id: i64) -> Result<(), ServiceError>;
+    /// Record the outcome of one check, dropping the oldest beyond `keep` for that user
+    async fn store_validation_result(
+        &self,
+        user_id: i64,
+        checked_at: DateTime<Utc>,
+        status: &str,
+        error: Option<&str>,
+        keep: i64,
+    ) -> Result<(), ServiceError>;
+    /// Checks of a user since `since` as (checked_at, status, error), newest first
+    async fn get_validation_results(
+        &self,
+        user_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String, Option<String>)>, ServiceError>;
+    /// Delete check results recorded before `before`; returns how many were removed
+    async fn prune_validation_results(&self, before: DateTime<Utc>) -> Result<u64, ServiceError>;
+    /// Replace the user's stored command warning
+    async fn store_command_warning(
+        &self,
+        user_id: i64,
+        warning: &str,
+        recorded_at: DateTime<Utc>,
+    ) -> Result<(), ServiceError>;
+    /// The user's latest command warning with when it was recorded
+    async fn get_command_warning(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<(String, DateTime<Utc>)>, ServiceError>;
 }
 
 pub struct SqliteUserRepository {
     pool: SqlitePool,
+    compress_config: bool,
 }
 
 impl SqliteUserRepository {
     pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+        // Plain text by default; set COMPRESS_LAST_CONFIG=true to store configs gzipped
+        let compress_config = std::env::var("COMPRESS_LAST_CONFIG")
+            .map(|value| value == "true" || value == "1")
+            .unwrap_or(false);
+
+        Self::with_config_compression(pool, compress_config)
+    }
+
+    pub fn with_config_compression(pool: SqlitePool, compress_config: bool) -> Self {
+        Self {
+            pool,
+            compress_config,
+        }
+    }
+
+    /// Split a config into the (plain, compressed) column pair according to the flag
+    fn encode_config(&self, config: Option<&str>) -> (Option<String>, Option<Vec<u8>>) {
+        match config {
+            Some(config) if self.compress_config => (None, Some(compress_config(config))),
+            Some(config) => (Some(config.to_string()), None),
+            None => (None, None),
+        }
+    }
+
+    /// Compressed data wins when present, so rows written with either setting stay readable
+    fn decode_config(plain: Option<String>, compressed: Option<Vec<u8>>) -> Option<String> {
+        compressed
+            .and_then(|bytes| decompress_config(&bytes))
+            .or(plain)
     }
 }
 
@@ -34,7 +138,7 @@ impl SqliteUserRepository {
 impl UserRepository for SqliteUserRepository {
     async fn find_by_id(&self, id: i64) -> Result<Option<ManagedUser>, ServiceError> {
         let row = sqlx::query!(
-            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, pending_time_adjustment, pending_time_operation FROM managed_users WHERE id = ?",
+            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template FROM managed_users WHERE id = ?",
             id
         )
         .fetch_optional(&self.pool)
@@ -48,9 +152,19 @@ impl UserRepository for SqliteUserRepository {
                 is_valid: row.is_valid.unwrap_or(false),
                 date_added: row.date_added.map(|dt| dt.and_utc()),
                 last_checked: row.last_checked.map(|dt| dt.and_utc()),
-                last_config: row.last_config,
+                last_config: Self::decode_config(row.last_config, row.last_config_gz),
                 pending_time_adjustment: row.pending_time_adjustment,
-                pending_time_operation: row.pending_time_operation,
+                pending_time_operation: decode_operation(
+                    row.pending_time_operation,
+                    row.pending_time_absolute,
+                ),
+                pending_time_reason: row.pending_time_reason,
+                last_status: row.last_status,
+                consecutive_failures: row.consecutive_failures,
+                host_group_id: row.host_group_id,
+                timekpr_path: row.timekpr_path,
+                validate_command_template: row.validate_command_template,
+                modify_command_template: row.modify_command_template,
             }))
         } else {
             Ok(None)
@@ -59,7 +173,7 @@ impl UserRepository for SqliteUserRepository {
 
     async fn find_all_valid(&self) -> Result<Vec<ManagedUser>, ServiceError> {
         let rows = sqlx::query!(
-            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, pending_time_adjustment, pending_time_operation FROM managed_users WHERE is_valid = 1 ORDER BY username"
+            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template FROM managed_users WHERE is_valid = 1 ORDER BY username"
         )
         .fetch_all(&self.pool)
         .await?;
@@ -73,9 +187,19 @@ impl UserRepository for SqliteUserRepository {
                 is_valid: row.is_valid.unwrap_or(false),
                 date_added: row.date_added.map(|dt| dt.and_utc()),
                 last_checked: row.last_checked.map(|dt| dt.and_utc()),
-                last_config: row.last_config,
+                last_config: Self::decode_config(row.last_config, row.last_config_gz),
                 pending_time_adjustment: row.pending_time_adjustment,
-                pending_time_operation: row.pending_time_operation,
+                pending_time_operation: decode_operation(
+                    row.pending_time_operation,
+                    row.pending_time_absolute,
+                ),
+                pending_time_reason: row.pending_time_reason,
+                last_status: row.last_status,
+                consecutive_failures: row.consecutive_failures,
+                host_group_id: row.host_group_id,
+                timekpr_path: row.timekpr_path,
+                validate_command_template: row.validate_command_template,
+                modify_command_template: row.modify_command_template,
             })
             .collect();
 
@@ -84,7 +208,7 @@ impl UserRepository for SqliteUserRepository {
 
     async fn find_all_pending(&self) -> Result<Vec<ManagedUser>, ServiceError> {
         let rows = sqlx::query!(
-            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, pending_time_adjustment, pending_time_operation FROM managed_users WHERE pending_time_adjustment IS NOT NULL AND pending_time_operation IS NOT NULL"
+            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template FROM managed_users WHERE pending_time_adjustment IS NOT NULL AND pending_time_operation IS NOT NULL"
         )
         .fetch_all(&self.pool)
         .await?;
@@ -98,9 +222,19 @@ impl UserRepository for SqliteUserRepository {
                 is_valid: row.is_valid.unwrap_or(false),
                 date_added: row.date_added.map(|dt| dt.and_utc()),
                 last_checked: row.last_checked.map(|dt| dt.and_utc()),
-                last_config: row.last_config,
+                last_config: Self::decode_config(row.last_config, row.last_config_gz),
                 pending_time_adjustment: row.pending_time_adjustment,
-                pending_time_operation: row.pending_time_operation,
+                pending_time_operation: decode_operation(
+                    row.pending_time_operation,
+                    row.pending_time_absolute,
+                ),
+                pending_time_reason: row.pending_time_reason,
+                last_status: row.last_status,
+                consecutive_failures: row.consecutive_failures,
+                host_group_id: row.host_group_id,
+                timekpr_path: row.timekpr_path,
+                validate_command_template: row.validate_command_template,
+                modify_command_template: row.modify_command_template,
             })
             .collect();
 
@@ -109,7 +243,7 @@ impl UserRepository for SqliteUserRepository {
 
     async fn find_all(&self) -> Result<Vec<ManagedUser>, ServiceError> {
         let rows = sqlx::query!(
-            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, pending_time_adjustment, pending_time_operation FROM managed_users ORDER BY username"
+            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template FROM managed_users ORDER BY username"
         )
         .fetch_all(&self.pool)
         .await?;
@@ -123,53 +257,147 @@ impl UserRepository for SqliteUserRepository {
                 is_valid: row.is_valid.unwrap_or(false),
                 date_added: row.date_added.map(|dt| dt.and_utc()),
                 last_checked: row.last_checked.map(|dt| dt.and_utc()),
-                last_config: row.last_config,
+                last_config: Self::decode_config(row.last_config, row.last_config_gz),
                 pending_time_adjustment: row.pending_time_adjustment,
-                pending_time_operation: row.pending_time_operation,
+                pending_time_operation: decode_operation(
+                    row.pending_time_operation,
+                    row.pending_time_absolute,
+                ),
+                pending_time_reason: row.pending_time_reason,
+                last_status: row.last_status,
+                consecutive_failures: row.consecutive_failures,
+                host_group_id: row.host_group_id,
+                timekpr_path: row.timekpr_path,
+                validate_command_template: row.validate_command_template,
+                modify_command_template: row.modify_command_template,
             })
             .collect();
 
         Ok(users)
     }
 
-    async fn save(&self, user: &ManagedUser) -> Result<(), ServiceError> {
+    async fn find_group(&self, primary_id: i64) -> Result<Vec<ManagedUser>, ServiceError> {
+        let rows = sqlx::query!(
+            "SELECT id, username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template FROM managed_users WHERE id = ? OR host_group_id = ? ORDER BY last_checked IS NULL, last_checked DESC, id",
+            primary_id,
+            primary_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let users = rows
+            .into_iter()
+            .map(|row| ManagedUser {
+                id: row.id,
+                username: row.username,
+                system_ip: row.system_ip,
+                is_valid: row.is_valid.unwrap_or(false),
+                date_added: row.date_added.map(|dt| dt.and_utc()),
+                last_checked: row.last_checked.map(|dt| dt.and_utc()),
+                last_config: Self::decode_config(row.last_config, row.last_config_gz),
+                pending_time_adjustment: row.pending_time_adjustment,
+                pending_time_operation: decode_operation(
+                    row.pending_time_operation,
+                    row.pending_time_absolute,
+                ),
+                pending_time_reason: row.pending_time_reason,
+                last_status: row.last_status,
+                consecutive_failures: row.consecutive_failures,
+                host_group_id: row.host_group_id,
+                timekpr_path: row.timekpr_path,
+                validate_command_template: row.validate_command_template,
+                modify_command_template: row.modify_command_template,
+            })
+            .collect();
+
+        Ok(users)
+    }
+
+    async fn count_users(&self) -> Result<UserCounts, ServiceError> {
+        // One row per host group first, so additional hosts don't inflate the totals
+        let row = sqlx::query!(
+            r#"SELECT COUNT(*) as "managed!: i64",
+                      COALESCE(SUM(is_valid), 0) as "valid!: i64",
+                      COALESCE(SUM(offline), 0) as "offline!: i64"
+               FROM (
+                   SELECT MAX(COALESCE(is_valid, 0)) AS is_valid,
+                          MAX(COALESCE(last_status = 'ok', 0)) = 0
+                              AND MAX(COALESCE(last_status = 'offline', 0)) = 1 AS offline
+                   FROM managed_users
+                   GROUP BY COALESCE(host_group_id, id)
+               )"#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(UserCounts {
+            managed: row.managed,
+            valid: row.valid,
+            offline: row.offline,
+        })
+    }
+
+    async fn save(&self, user: &ManagedUser) -> Result<i64, ServiceError> {
         if user.id == 0 {
             // Insert new user
+            let (last_config, last_config_gz) = self.encode_config(user.last_config.as_deref());
             let date_added = user.date_added.map(|dt| dt.naive_utc());
             let last_checked = user.last_checked.map(|dt| dt.naive_utc());
-            sqlx::query!(
-                "INSERT INTO managed_users (username, system_ip, is_valid, date_added, last_checked, last_config, pending_time_adjustment, pending_time_operation) 
-                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            let (operation, absolute) = encode_operation(user.pending_time_operation.as_deref());
+            let result = sqlx::query!(
+                "INSERT INTO managed_users (username, system_ip, is_valid, date_added, last_checked, last_config, last_config_gz, pending_time_adjustment, pending_time_operation, pending_time_reason, pending_time_absolute, last_status, consecutive_failures, host_group_id, timekpr_path, validate_command_template, modify_command_template) 
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 user.username,
                 user.system_ip,
                 user.is_valid,
                 date_added,
                 last_checked,
-                user.last_config,
+                last_config,
+                last_config_gz,
                 user.pending_time_adjustment,
-                user.pending_time_operation
+                operation,
+                user.pending_time_reason,
+                absolute,
+                user.last_status,
+                user.consecutive_failures,
+                user.host_group_id,
+                user.timekpr_path,
+                user.validate_command_template,
+                user.modify_command_template
             )
             .execute(&self.pool)
             .await?;
+
+            Ok(result.last_insert_rowid())
         } else {
             // Update existing user
+            let (last_config, last_config_gz) = self.encode_config(user.last_config.as_deref());
             let last_checked = user.last_checked.map(|dt| dt.naive_utc());
+            let (operation, absolute) = encode_operation(user.pending_time_operation.as_deref());
             sqlx::query!(
-                "UPDATE managed_users SET username = ?, system_ip = ?, is_valid = ?, last_checked = ?, last_config = ?, pending_time_adjustment = ?, pending_time_operation = ? WHERE id = ?",
+                "UPDATE managed_users SET username = ?, system_ip = ?, is_valid = ?, last_checked = ?, last_config = ?, last_config_gz = ?, pending_time_adjustment = ?, pending_time_operation = ?, pending_time_reason = ?, pending_time_absolute = ?, last_status = ?, consecutive_failures = ?, timekpr_path = ?, validate_command_template = ?, modify_command_template = ? WHERE id = ?",
                 user.username,
                 user.system_ip,
                 user.is_valid,
                 last_checked,
-                user.last_config,
+                last_config,
+                last_config_gz,
                 user.pending_time_adjustment,
-                user.pending_time_operation,
+                operation,
+                user.pending_time_reason,
+                absolute,
+                user.last_status,
+                user.consecutive_failures,
+                user.timekpr_path,
+                user.validate_command_template,
+                user.modify_command_template,
                 user.id
             )
             .execute(&self.pool)
             .await?;
-        }
 
-        Ok(())
+            Ok(user.id)
+        }
     }
 
     async fn delete(&self, id: i64) -> Result<(), ServiceError> {
@@ -180,16 +408,41 @@ impl UserRepository for SqliteUserRepository {
         Ok(())
     }
 
+    async fn delete_many(&self, ids: &[i64]) -> Result<Vec<i64>, ServiceError> {
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = Vec::new();
+
+        for &id in ids {
+            sqlx::query!("DELETE FROM managed_users WHERE host_group_id = ?", id)
+                .execute(&mut *tx)
+                .await?;
+            let result = sqlx::query!("DELETE FROM managed_users WHERE id = ?", id)
+                .execute(&mut *tx)
+                .await?;
+            if result.rows_affected() > 0 {
+                deleted.push(id);
+            }
+        }
+
+        // Dropping the transaction on an earlier error rolls everything back
+        tx.commit().await?;
+        Ok(deleted)
+    }
+
     async fn update_pending_time_adjustment(
         &self,
         user_id: i64,
         operation: &str,
         seconds: i64,
+        reason: Option<&str>,
     ) -> Result<(), ServiceError> {
+        let (operation, absolute) = encode_operation(Some(operation));
         sqlx::query!(
-            "UPDATE managed_users SET pending_time_adjustment = ?, pending_time_operation = ? WHERE id = ?",
+            "UPDATE managed_users SET pending_time_adjustment = ?, pending_time_operation = ?, pending_time_reason = ?, pending_time_absolute = ? WHERE id = ?",
             seconds,
             operation,
+            reason,
+            absolute,
             user_id
         )
         .execute(&self.pool)
@@ -200,7 +453,7 @@ impl UserRepository for SqliteUserRepository {
 
     async fn clear_pending_time_adjustment(&self, user_id: i64) -> Result<(), ServiceError> {
         sqlx::query!(
-            "UPDATE managed_users SET pending_time_adjustment = NULL, pending_time_operation = NULL WHERE id = ?",
+            "UPDATE managed_users SET pending_time_adjustment = NULL, pending_time_operation = NULL, pending_time_reason = NULL, pending_time_absolute = 0 WHERE id = ?",
             user_id
         )
         .execute(&self.pool)
@@ -208,4 +461,108 @@ impl UserRepository for SqliteUserRepository {
 
         Ok(())
     }
+
+    async fn store_validation_result(
+        &self,
+        user_id: i64,
+        checked_at: DateTime<Utc>,
+        status: &str,
+        error: Option<&str>,
+        keep: i64,
+    ) -> Result<(), ServiceError> {
+        let checked_at = checked_at.naive_utc();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO user_validation_result (user_id, checked_at, status, error) VALUES (?, ?, ?, ?)",
+            user_id,
+            checked_at,
+            status,
+            error
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            r#"DELETE FROM user_validation_result
+             WHERE user_id = ? AND id NOT IN (
+                 SELECT id FROM user_validation_result WHERE user_id = ? ORDER BY id DESC LIMIT ?
+             )"#,
+            user_id,
+            user_id,
+            keep
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn get_validation_results(
+        &self,
+        user_id: i64,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String, Option<String>)>, ServiceError> {
+        let since = since.naive_utc();
+        let rows = sqlx::query!(
+            r#"SELECT checked_at as "checked_at: NaiveDateTime", status, error FROM user_validation_result
+             WHERE user_id = ? AND checked_at >= ?
+             ORDER BY checked_at DESC, id DESC"#,
+            user_id,
+            since
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.checked_at.and_utc(), row.status, row.error))
+            .collect())
+    }
+
+    async fn prune_validation_results(&self, before: DateTime<Utc>) -> Result<u64, ServiceError> {
+        let before = before.naive_utc();
+        let result = sqlx::query!(
+            "DELETE FROM user_validation_result WHERE checked_at < ?",
+            before
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    async fn store_command_warning(
+        &self,
+        user_id: i64,
+        warning: &str,
+        recorded_at: DateTime<Utc>,
+    ) -> Result<(), ServiceError> {
+        let recorded_at = recorded_at.naive_utc();
+        sqlx::query!(
+            r#"INSERT INTO user_command_warning (user_id, warning, recorded_at) VALUES (?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET warning = excluded.warning, recorded_at = excluded.recorded_at"#,
+            user_id,
+            warning,
+            recorded_at
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn get_command_warning(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<(String, DateTime<Utc>)>, ServiceError> {
+        let row = sqlx::query!(
+            r#"SELECT warning, recorded_at as "recorded_at: NaiveDateTime" FROM user_command_warning WHERE user_id = ?"#,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| (row.warning, row.recorded_at.and_utc())))
+    }
 }
@@ -1,14 +1,73 @@
-use crate::services::{ScheduleService, UsageService, UserService};
-use crate::ssh::SSHClient;
+use crate::models::{
+    EventKind, ManagedUser, SchedulerPassSummary, ServiceError, TimekprConfig, UserStatus,
+};
+use crate::services::{
+    sync_retry_delay, DashboardCache, EventLog, LocalTime, ScheduleService, SettingsService,
+    UsageService, UserLocks, UserService,
+};
+use crate::ssh::{SSHClient, SshBackend, SystemSsh};
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveTime, Utc};
+use std::collections::HashSet;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::time::{interval, sleep};
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout};
+
+/// How often the background loop runs unless `check_interval` is set
+pub const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Shortest and longest `check_interval`, in seconds
+pub const MIN_CHECK_INTERVAL: i32 = 5;
+pub const MAX_CHECK_INTERVAL: i32 = 3600;
+
+/// Number of missed ticks after which the loop is considered dead
+pub const HEARTBEAT_STALE_TICKS: u32 = 3;
+
+/// Hosts checked at the same time by a dashboard refresh
+pub const REFRESH_CONCURRENCY: usize = 8;
+
+/// A host that hasn't answered a dashboard refresh by then is treated as offline
+pub const REFRESH_HOST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Waits between passes of the background loop, so it can run against a stand-in in tests
+#[async_trait]
+pub trait Clock: Send + Sync {
+    async fn sleep(&self, duration: Duration);
+}
+
+/// Waits in real time
+pub struct TokioClock;
+
+#[async_trait]
+impl Clock for TokioClock {
+    async fn sleep(&self, duration: Duration) {
+        sleep(duration).await;
+    }
+}
 
 pub struct BackgroundScheduler {
     user_service: Arc<UserService>,
     usage_service: Arc<UsageService>,
     schedule_service: Arc<ScheduleService>,
+    settings_service: Arc<SettingsService>,
+    dashboard_cache: Arc<DashboardCache>,
+    ssh: Arc<dyn SshBackend>,
+    user_locks: Arc<UserLocks>,
+    event_log: Arc<EventLog>,
+    local_time: Arc<LocalTime>,
     running: Arc<tokio::sync::RwLock<bool>>,
+    heartbeat: Arc<tokio::sync::RwLock<Option<DateTime<Utc>>>>,
+    /// Wait between passes as last read from the settings
+    tick: Arc<tokio::sync::RwLock<Duration>>,
+    clock: Arc<dyn Clock>,
+    /// Why the latest pass failed; cleared by the next pass that completes without errors
+    last_error: Arc<tokio::sync::RwLock<Option<String>>>,
+    /// End of the latest pass that completed without errors
+    last_success: Arc<tokio::sync::RwLock<Option<DateTime<Utc>>>>,
+    /// Held for the length of a pass, so the loop and passes run by hand never overlap
+    pass_lock: Arc<Mutex<()>>,
 }
 
 impl BackgroundScheduler {
@@ -16,32 +75,88 @@ impl BackgroundScheduler {
         user_service: Arc<UserService>,
         usage_service: Arc<UsageService>,
         schedule_service: Arc<ScheduleService>,
+        settings_service: Arc<SettingsService>,
+        dashboard_cache: Arc<DashboardCache>,
     ) -> Self {
         Self {
             user_service,
             usage_service,
             schedule_service,
+            settings_service,
+            dashboard_cache,
+            ssh: Arc::new(SystemSsh::default()),
+            user_locks: Arc::new(UserLocks::new()),
+            event_log: Arc::new(EventLog::new()),
+            local_time: Arc::new(LocalTime::new()),
             running: Arc::new(tokio::sync::RwLock::new(false)),
+            heartbeat: Arc::new(tokio::sync::RwLock::new(None)),
+            tick: Arc::new(tokio::sync::RwLock::new(TICK_INTERVAL)),
+            clock: Arc::new(TokioClock),
+            last_error: Arc::new(tokio::sync::RwLock::new(None)),
+            last_success: Arc::new(tokio::sync::RwLock::new(None)),
+            pass_lock: Arc::new(Mutex::new(())),
         }
     }
 
+    /// Validate users and apply queued adjustments through the given backend
+    pub fn with_ssh_backend(mut self, ssh: Arc<dyn SshBackend>) -> Self {
+        self.ssh = ssh;
+        self
+    }
+
+    /// Serialize pending adjustments with whoever else holds these locks (time modifications)
+    pub fn with_user_locks(mut self, user_locks: Arc<UserLocks>) -> Self {
+        self.user_locks = user_locks;
+        self
+    }
+
+    /// Record the outcome of pushing schedules, PlayTime and lockout settings in the given log
+    pub fn with_event_log(mut self, event_log: Arc<EventLog>) -> Self {
+        self.event_log = event_log;
+        self
+    }
+
+    /// Read the active window against the given local time, shared with the services
+    pub fn with_local_time(mut self, local_time: Arc<LocalTime>) -> Self {
+        self.local_time = local_time;
+        self
+    }
+
+    /// Wait between passes of the background loop with the given clock
+    #[allow(dead_code)]
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
     pub async fn start(&self) {
         let mut running = self.running.write().await;
         if *running {
             return;
         }
         *running = true;
+        *self.heartbeat.write().await = Some(Utc::now());
+        *self.tick.write().await = self.check_interval().await;
 
-        let user_service = Arc::clone(&self.user_service);
-        let usage_service = Arc::clone(&self.usage_service);
-        let schedule_service = Arc::clone(&self.schedule_service);
+        let scheduler = self.clone_handles();
         let running_flag = Arc::clone(&self.running);
+        let heartbeat = Arc::clone(&self.heartbeat);
 
         tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(30)); // Run every 30 seconds
+            let mut first = true;
 
             loop {
-                interval.tick().await;
+                // The first pass runs right away; the interval is read again before every
+                // wait, so a changed setting applies from the next tick
+                if !first {
+                    let tick = scheduler.check_interval().await;
+                    *scheduler.tick.write().await = tick;
+                    scheduler.clock.sleep(tick).await;
+                }
+                first = false;
+
+                // Record liveness before doing any work
+                *heartbeat.write().await = Some(Utc::now());
 
                 // Check if we should still be running
                 {
@@ -51,81 +166,386 @@ impl BackgroundScheduler {
                     }
                 }
 
-                // Update user data
-                Self::update_users_task(&user_service, &usage_service).await;
+                scheduler.run_once().await;
+            }
+        });
+    }
+
+    /// Same services and state, for moving into the background task
+    fn clone_handles(&self) -> Self {
+        Self {
+            user_service: Arc::clone(&self.user_service),
+            usage_service: Arc::clone(&self.usage_service),
+            schedule_service: Arc::clone(&self.schedule_service),
+            settings_service: Arc::clone(&self.settings_service),
+            dashboard_cache: Arc::clone(&self.dashboard_cache),
+            ssh: Arc::clone(&self.ssh),
+            user_locks: Arc::clone(&self.user_locks),
+            event_log: Arc::clone(&self.event_log),
+            local_time: Arc::clone(&self.local_time),
+            running: Arc::clone(&self.running),
+            heartbeat: Arc::clone(&self.heartbeat),
+            tick: Arc::clone(&self.tick),
+            clock: Arc::clone(&self.clock),
+            last_error: Arc::clone(&self.last_error),
+            last_success: Arc::clone(&self.last_success),
+            pass_lock: Arc::clone(&self.pass_lock),
+        }
+    }
+
+    /// One scheduler pass: refresh users, apply queued changes, then rebuild the dashboard cache.
+    /// Waits for a pass that is already running to finish first.
+    pub async fn run_once(&self) -> SchedulerPassSummary {
+        let _pass = self.pass_lock.lock().await;
+        self.run_pass().await
+    }
+
+    /// Run a pass now unless one is already running, in which case None is returned
+    pub async fn try_run_once(&self) -> Option<SchedulerPassSummary> {
+        let _pass = self.pass_lock.try_lock().ok()?;
+        Some(self.run_pass().await)
+    }
+
+    async fn run_pass(&self) -> SchedulerPassSummary {
+        let started = Instant::now();
 
-                // Process pending time adjustments
-                Self::process_pending_adjustments(&user_service).await;
+        // Outside the configured active window no hosts are contacted
+        let active = match self.settings_service.get_scheduler_active_window().await {
+            Ok(Some((from, to))) => is_within_active_window(self.local_time.now().time(), from, to),
+            _ => true,
+        };
+        let mut summary = SchedulerPassSummary {
+            active,
+            ..Default::default()
+        };
+        // Hosts that don't answer are expected; these are failures of the pass itself
+        let mut errors = Vec::new();
 
-                // Sync pending schedule changes
-                Self::sync_pending_schedules(&user_service, &schedule_service).await;
+        if summary.active {
+            // Update user data
+            let (succeeded, failed) = match Self::update_users_task(
+                &self.user_service,
+                &self.usage_service,
+                self.ssh.as_ref(),
+                &self.user_locks,
+            )
+            .await
+            {
+                Ok(counts) => counts,
+                Err(e) => {
+                    eprintln!("Failed to fetch users for background update: {}", e);
+                    errors.push(format!("Failed to fetch users: {}", e));
+                    (0, 0)
+                }
+            };
+            summary.succeeded = succeeded;
+            summary.failed = failed;
+            summary.users_processed = succeeded + failed;
+
+            // Process pending time adjustments
+            Self::process_pending_adjustments(
+                &self.user_service,
+                self.ssh.as_ref(),
+                &self.user_locks,
+            )
+            .await;
+
+            // Day limits pushed before the reset may have expired since
+            match self.schedule_service.requeue_expired_day_limits().await {
+                Ok(requeued) if !requeued.is_empty() => println!(
+                    "Queued schedule push for {} user(s) whose day limit changed at the reset",
+                    requeued.len()
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to check for expired day limits: {}", e),
             }
-        });
+
+            // Sync pending schedule changes
+            Self::sync_pending_schedules(
+                &self.user_service,
+                &self.schedule_service,
+                self.ssh.as_ref(),
+                &self.event_log,
+            )
+            .await;
+
+            // Sync pending PlayTime changes
+            Self::sync_pending_playtime(
+                &self.user_service,
+                &self.schedule_service,
+                self.ssh.as_ref(),
+                &self.event_log,
+            )
+            .await;
+
+            // Sync pending lockout changes
+            Self::sync_pending_lockout(
+                &self.user_service,
+                &self.schedule_service,
+                self.ssh.as_ref(),
+                &self.event_log,
+            )
+            .await;
+
+            // Deprecation notices and the like from commands that still succeeded
+            self.user_service
+                .record_command_warnings(self.ssh.take_warnings())
+                .await;
+        }
+
+        if let Err(e) = self
+            .dashboard_cache
+            .refresh(&self.user_service, &self.schedule_service)
+            .await
+        {
+            eprintln!("Failed to refresh dashboard cache: {}", e);
+            errors.push(format!("Failed to refresh dashboard cache: {}", e));
+        }
+
+        if errors.is_empty() {
+            *self.last_success.write().await = Some(Utc::now());
+            *self.last_error.write().await = None;
+        } else {
+            *self.last_error.write().await = Some(errors.join("; "));
+        }
+
+        summary.duration_ms = started.elapsed().as_millis() as u64;
+        summary
+    }
+
+    /// Configured `check_interval`, or the default when unset or out of range
+    async fn check_interval(&self) -> Duration {
+        self.settings_service.tick_interval().await
     }
 
     pub async fn is_running(&self) -> bool {
         *self.running.read().await
     }
 
-    async fn update_users_task(user_service: &UserService, usage_service: &UsageService) {
-        let users = user_service.get_valid_users().await;
+    pub async fn last_heartbeat(&self) -> Option<DateTime<Utc>> {
+        *self.heartbeat.read().await
+    }
+
+    /// Why the latest pass failed, None once a pass has completed without errors
+    pub async fn last_error(&self) -> Option<String> {
+        self.last_error.read().await.clone()
+    }
+
+    /// When the latest pass without errors ended
+    pub async fn last_success(&self) -> Option<DateTime<Utc>> {
+        *self.last_success.read().await
+    }
+
+    /// Running and the loop has ticked recently; catches a task that died while the flag stayed set
+    pub async fn is_healthy(&self) -> bool {
+        if !self.is_running().await {
+            return false;
+        }
+
+        let tick = *self.tick.read().await;
+        match self.last_heartbeat().await {
+            Some(last) => !Self::is_heartbeat_stale_for(last, Utc::now(), tick),
+            None => false,
+        }
+    }
+
+    /// Whether a loop waiting `tick` between passes has missed too many of them
+    pub fn is_heartbeat_stale_for(
+        last_heartbeat: DateTime<Utc>,
+        now: DateTime<Utc>,
+        tick: Duration,
+    ) -> bool {
+        let max_age = tick * HEARTBEAT_STALE_TICKS;
+        match (now - last_heartbeat).to_std() {
+            Ok(age) => age > max_age,
+            // Heartbeat in the future (clock adjustment) - treat as fresh
+            Err(_) => false,
+        }
+    }
+
+    /// Check every host, returning how many answered and how many did not; fails when the
+    /// users can't be read
+    async fn update_users_task(
+        user_service: &UserService,
+        usage_service: &UsageService,
+        ssh: &dyn SshBackend,
+        user_locks: &UserLocks,
+    ) -> Result<(usize, usize), ServiceError> {
+        let (mut succeeded, mut failed) = (0, 0);
+        let mut fetch_error = None;
+        // Valid users, plus users that haven't validated yet once their backoff has run out
+        let users = user_service.find_all().await;
+        let now = Utc::now();
 
         match users {
             Ok(users) => {
-                for user in users {
-                    let ssh_client = SSHClient::new(&user.system_ip);
-                    let (is_reachable, _message, config) =
-                        ssh_client.validate_user(&user.username).await;
-
-                    if is_reachable {
-                        // Update user data with config
-                        let config_json = config.as_ref().map(|c| c.to_string());
-                        let _ = user_service
-                            .update_background_data(user.id, config_json)
-                            .await;
-
-                        // Store usage data if available
-                        if let Some(config) = &config {
-                            if let Some(time_spent) =
-                                config.get("TIME_SPENT_DAY").and_then(|v| v.as_i64())
-                            {
-                                let _ = usage_service.store_daily_usage(user.id, time_spent).await;
-                            }
-                        }
+                for user in users.into_iter().filter(|user| is_due_for_check(user, now)) {
+                    let (status, message, config) = ssh.validate_user(&user).await;
+                    if status == UserStatus::Ok {
+                        succeeded += 1;
                     } else {
-                        // Just update last_checked timestamp
-                        let _ = user_service.update_last_checked(user.id).await;
+                        failed += 1;
                     }
+                    Self::store_check(
+                        user_service,
+                        usage_service,
+                        user_locks,
+                        user.id,
+                        status,
+                        &message,
+                        config,
+                    )
+                    .await;
 
                     sleep(Duration::from_millis(100)).await;
                 }
             }
-            Err(e) => {
-                eprintln!("Failed to fetch users for background update: {}", e);
+            Err(e) => fetch_error = Some(e),
+        }
+
+        if let Err(e) = usage_service.prune_samples().await {
+            eprintln!("Failed to prune usage samples: {}", e);
+        }
+        if let Err(e) = user_service.prune_validation_history().await {
+            eprintln!("Failed to prune validation history: {}", e);
+        }
+
+        match fetch_error {
+            Some(e) => Err(e),
+            None => Ok((succeeded, failed)),
+        }
+    }
+
+    /// Save the outcome of checking one host
+    async fn store_check(
+        user_service: &UserService,
+        usage_service: &UsageService,
+        user_locks: &UserLocks,
+        user_id: i64,
+        status: UserStatus,
+        message: &str,
+        config: Option<TimekprConfig>,
+    ) {
+        // The whole row is rewritten, so a pending adjustment cleared meanwhile must not come back
+        let _lock = user_locks.lock(user_id).await;
+        if let Err(e) = user_service
+            .record_validation(user_id, status, message)
+            .await
+        {
+            eprintln!(
+                "Failed to record validation result for user {}: {}",
+                user_id, e
+            );
+        }
+        if status == UserStatus::Ok {
+            // Update user data with config
+            let config_json = config.as_ref().map(|c| c.to_json());
+            let _ = user_service
+                .update_background_data(user_id, config_json)
+                .await;
+
+            // Store usage data if available
+            if let Some(config) = &config {
+                if let Some(time_spent) = config.time_spent_day {
+                    let _ = usage_service.store_daily_usage(user_id, time_spent).await;
+                    let _ = usage_service.record_sample(user_id, time_spent).await;
+                }
+            }
+        } else {
+            // Just update last_checked timestamp and why the check failed
+            let _ = user_service.update_last_checked(user_id, status).await;
+        }
+    }
+
+    /// Check every host now, a few at a time, and rebuild the dashboard cache.
+    /// Returns the ids of the users with a host that answered; the others keep their earlier data.
+    pub async fn refresh_all(&self) -> Result<HashSet<i64>, ServiceError> {
+        let users = self.user_service.find_all().await?;
+        let permits = Arc::new(Semaphore::new(REFRESH_CONCURRENCY));
+        let mut checks = JoinSet::new();
+
+        // Asked for by hand, so hosts that haven't validated yet are tried right away
+        for user in users {
+            let ssh = Arc::clone(&self.ssh);
+            let permits = Arc::clone(&permits);
+            checks.spawn(async move {
+                let _permit = permits.acquire_owned().await;
+                let check = timeout(REFRESH_HOST_TIMEOUT, ssh.validate_user(&user))
+                    .await
+                    .unwrap_or_else(|_| (UserStatus::Offline, "Timed out".to_string(), None));
+                (user.id, user.host_group_id.unwrap_or(user.id), check)
+            });
+        }
+
+        let mut answered = HashSet::new();
+        while let Some(result) = checks.join_next().await {
+            let Ok((user_id, group_id, (status, message, config))) = result else {
+                continue;
+            };
+            if status == UserStatus::Ok {
+                answered.insert(group_id);
             }
+            Self::store_check(
+                &self.user_service,
+                &self.usage_service,
+                &self.user_locks,
+                user_id,
+                status,
+                &message,
+                config,
+            )
+            .await;
         }
+
+        self.user_service
+            .record_command_warnings(self.ssh.take_warnings())
+            .await;
+
+        self.dashboard_cache
+            .refresh(&self.user_service, &self.schedule_service)
+            .await?;
+        Ok(answered)
     }
 
-    async fn process_pending_adjustments(user_service: &UserService) {
+    async fn process_pending_adjustments(
+        user_service: &UserService,
+        ssh: &dyn SshBackend,
+        user_locks: &UserLocks,
+    ) {
         // Get users with pending time adjustments
         let users = user_service.get_users_pending().await;
 
         match users {
             Ok(users) => {
                 for user in users {
+                    // A time modification may have applied or replaced the adjustment while
+                    // we waited, so read it again under the lock
+                    let lock = user_locks.lock(user.id).await;
+                    let user = match user_service.find_by_id(user.id).await {
+                        Ok(Some(user)) => user,
+                        _ => continue,
+                    };
+
                     if let (Some(adjustment), Some(operation)) =
                         (&user.pending_time_adjustment, &user.pending_time_operation)
                     {
-                        let ssh_client = SSHClient::new(&user.system_ip);
-                        let (success, _message) = ssh_client
-                            .modify_time_left(&user.username, operation, *adjustment)
-                            .await;
-
-                        if success {
-                            // Clear pending adjustment
-                            let _ = user_service.clear_pending_adjustements(user.id).await;
+                        // Any reachable host of the user's group can take the adjustment
+                        let hosts = user_service
+                            .find_group(user.id)
+                            .await
+                            .unwrap_or_else(|_| vec![user.clone()]);
+                        for host in hosts {
+                            let (success, _message) =
+                                ssh.modify_time_left(&host, operation, *adjustment).await;
+
+                            if success {
+                                // Clear pending adjustment
+                                let _ = user_service.clear_pending_adjustements(user.id).await;
+                                break;
+                            }
                         }
                     }
+                    drop(lock);
 
                     // Small delay between operations
                     sleep(Duration::from_millis(100)).await;
@@ -140,56 +560,133 @@ impl BackgroundScheduler {
     async fn sync_pending_schedules(
         user_service: &UserService,
         schedule_service: &ScheduleService,
+        ssh: &dyn SshBackend,
+        event_log: &EventLog,
     ) {
-        let unsynced_schedules = schedule_service.get_unsynced_schedules().await;
+        // Schedules that failed recently wait out their backoff instead of retrying every tick
+        let unsynced_schedules = schedule_service
+            .get_due_unsynced_schedules(Utc::now())
+            .await;
 
         match unsynced_schedules {
             Ok(schedules) => {
                 for schedule in schedules {
                     // Get user data for this schedule
                     if let Ok(Some(user)) = user_service.find_by_id(schedule.user_id).await {
-                        // Only sync for valid users
-                        if user.is_valid {
-                            let ssh_client = SSHClient::new(&user.system_ip);
+                        // Use service method to prepare sync data, including today's overrides and bonuses
+                        let (schedule_dict, intervals_dict) = match schedule_service
+                            .prepare_effective_sync_data(&schedule)
+                            .await
+                        {
+                            Ok(data) => data,
+                            Err(e) => {
+                                eprintln!(
+                                    "Failed to prepare schedule sync for {}: {}",
+                                    user.username, e
+                                );
+                                continue;
+                            }
+                        };
 
-                            // Use service method to prepare sync data
-                            let (schedule_dict, intervals_dict) =
-                                schedule_service.prepare_sync_data(&schedule);
+                        // Only sync to valid hosts, stopping at the first one that takes the schedule
+                        let hosts = user_service
+                            .find_group(user.id)
+                            .await
+                            .unwrap_or_else(|_| vec![user.clone()]);
+                        let mut synced = false;
+                        for host in hosts.into_iter().filter(|host| host.is_valid) {
+                            // A host that already enforces this schedule needs no writes
+                            if let (UserStatus::Ok, _, Some(config)) =
+                                ssh.validate_user(&host).await
+                            {
+                                if SSHClient::host_matches_schedule(
+                                    &config,
+                                    &schedule_dict,
+                                    &intervals_dict,
+                                ) {
+                                    println!(
+                                        "Schedule already applied for {} on {}, marking synced",
+                                        host.username, host.system_ip
+                                    );
+                                    let _ = schedule_service
+                                        .mark_as_synced(schedule.user_id, &schedule_dict)
+                                        .await;
+                                    Self::push_synced(event_log, &host, "schedule");
+                                    synced = true;
+                                    break;
+                                }
+                            }
 
                             // Sync operations
-                            let (limits_success, limits_message) = ssh_client
-                                .set_weekly_time_limits(&user.username, &schedule_dict)
-                                .await;
-                            let (hours_success, hours_message) = ssh_client
-                                .set_weekly_allowed_hours(&user.username, &intervals_dict)
-                                .await;
+                            let (limits_success, limits_message) =
+                                ssh.set_weekly_time_limits(&host, &schedule_dict).await;
+                            let (hours_success, hours_message) =
+                                ssh.set_weekly_allowed_hours(&host, &intervals_dict).await;
 
                             let success = limits_success && hours_success;
 
                             if success {
                                 println!(
-                                    "Schedule sync successful for {}: {}, {}",
-                                    user.username, limits_message, hours_message
-                                );
-                                let _ = schedule_service.mark_as_synced(schedule.user_id).await;
-                            } else {
-                                // Log what failed
-                                let mut error_parts = Vec::new();
-                                if !limits_success {
-                                    error_parts.push(format!("Time limits: {}", limits_message));
-                                }
-                                if !hours_success {
-                                    error_parts.push(format!("Allowed hours: {}", hours_message));
-                                }
-                                println!(
-                                    "Schedule sync failed for {}: {}",
-                                    user.username,
-                                    error_parts.join(", ")
+                                    "Schedule sync successful for {} on {}: {}, {}",
+                                    host.username, host.system_ip, limits_message, hours_message
                                 );
+                                let _ = schedule_service
+                                    .mark_as_synced(schedule.user_id, &schedule_dict)
+                                    .await;
+                                Self::push_synced(event_log, &host, "schedule");
+                                synced = true;
+                                break;
                             }
 
+                            // Log what failed
+                            let mut error_parts = Vec::new();
+                            if !limits_success {
+                                error_parts.push(format!("Time limits: {}", limits_message));
+                            }
+                            if !hours_success {
+                                error_parts.push(format!("Allowed hours: {}", hours_message));
+                            }
+                            println!(
+                                "Schedule sync failed for {} on {}: {}",
+                                host.username,
+                                host.system_ip,
+                                error_parts.join(", ")
+                            );
+                            Self::push_sync_failed(
+                                event_log,
+                                &host,
+                                "schedule",
+                                error_parts.join(", "),
+                            );
+
                             sleep(Duration::from_millis(100)).await;
                         }
+
+                        if !synced {
+                            match schedule_service.record_sync_failure(&schedule).await {
+                                Ok(Some(next_attempt)) => println!(
+                                    "Next schedule sync attempt for {} at {}",
+                                    user.username, next_attempt
+                                ),
+                                Ok(None) => {
+                                    println!(
+                                        "Schedule sync for {} failed {} times in a row; waiting for a manual sync",
+                                        user.username,
+                                        schedule.sync_attempts + 1
+                                    );
+                                    event_log.push(EventKind::SyncHalted {
+                                        user_id: user.id,
+                                        username: user.username.clone(),
+                                        setting: "schedule".to_string(),
+                                        failures: schedule.sync_attempts + 1,
+                                    });
+                                }
+                                Err(e) => eprintln!(
+                                    "Failed to record schedule sync failure for {}: {}",
+                                    user.username, e
+                                ),
+                            }
+                        }
                     }
                 }
             }
@@ -198,4 +695,165 @@ impl BackgroundScheduler {
             }
         }
     }
+
+    async fn sync_pending_playtime(
+        user_service: &UserService,
+        schedule_service: &ScheduleService,
+        ssh: &dyn SshBackend,
+        event_log: &EventLog,
+    ) {
+        let pending = match schedule_service.get_unsynced_playtime().await {
+            Ok(pending) => pending,
+            Err(e) => {
+                eprintln!("Failed to fetch unsynced PlayTime settings: {}", e);
+                return;
+            }
+        };
+
+        for playtime in pending {
+            let Ok(Some(user)) = user_service.find_by_id(playtime.user_id).await else {
+                continue;
+            };
+
+            let hosts = user_service
+                .find_group(user.id)
+                .await
+                .unwrap_or_else(|_| vec![user.clone()]);
+            for host in hosts.into_iter().filter(|host| host.is_valid) {
+                // Hosts known to run a timekpr without PlayTime are left alone; the settings
+                // stay pending in case the host is upgraded
+                let supported = host
+                    .last_config
+                    .as_deref()
+                    .and_then(|config| config.parse::<TimekprConfig>().ok())
+                    .is_none_or(|config| config.playtime_supported);
+                if !supported {
+                    println!(
+                        "Skipping PlayTime sync for {} on {}: timekpr on this host does not support PlayTime",
+                        host.username, host.system_ip
+                    );
+                    continue;
+                }
+
+                let (success, message) = ssh.set_playtime(&host, &playtime.settings).await;
+                if success {
+                    println!(
+                        "PlayTime sync successful for {} on {}: {}",
+                        host.username, host.system_ip, message
+                    );
+                    let _ = schedule_service
+                        .mark_playtime_synced(playtime.user_id)
+                        .await;
+                    Self::push_synced(event_log, &host, "playtime");
+                    break;
+                }
+
+                println!(
+                    "PlayTime sync failed for {} on {}: {}",
+                    host.username, host.system_ip, message
+                );
+                Self::push_sync_failed(event_log, &host, "playtime", message);
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    async fn sync_pending_lockout(
+        user_service: &UserService,
+        schedule_service: &ScheduleService,
+        ssh: &dyn SshBackend,
+        event_log: &EventLog,
+    ) {
+        let pending = match schedule_service.get_unsynced_lockout().await {
+            Ok(pending) => pending,
+            Err(e) => {
+                eprintln!("Failed to fetch unsynced lockout settings: {}", e);
+                return;
+            }
+        };
+
+        for lockout in pending {
+            let Ok(Some(user)) = user_service.find_by_id(lockout.user_id).await else {
+                continue;
+            };
+
+            let hosts = user_service
+                .find_group(user.id)
+                .await
+                .unwrap_or_else(|_| vec![user.clone()]);
+            for host in hosts.into_iter().filter(|host| host.is_valid) {
+                // Hosts known to run a timekpr without lockout types are left alone; the
+                // settings stay pending in case the host is upgraded
+                let supported = host
+                    .last_config
+                    .as_deref()
+                    .and_then(|config| config.parse::<TimekprConfig>().ok())
+                    .is_none_or(|config| config.lockout_supported());
+                if !supported {
+                    println!(
+                        "Skipping lockout sync for {} on {}: timekpr on this host does not support lockout types",
+                        host.username, host.system_ip
+                    );
+                    continue;
+                }
+
+                let (success, message) = ssh.set_lockout(&host, &lockout.settings).await;
+                if success {
+                    println!(
+                        "Lockout sync successful for {} on {}: {}",
+                        host.username, host.system_ip, message
+                    );
+                    let _ = schedule_service.mark_lockout_synced(lockout.user_id).await;
+                    Self::push_synced(event_log, &host, "lockout");
+                    break;
+                }
+
+                println!(
+                    "Lockout sync failed for {} on {}: {}",
+                    host.username, host.system_ip, message
+                );
+                Self::push_sync_failed(event_log, &host, "lockout", message);
+                sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    fn push_synced(event_log: &EventLog, host: &ManagedUser, setting: &str) {
+        event_log.push(EventKind::SyncSucceeded {
+            user_id: host.id,
+            username: host.username.clone(),
+            system_ip: host.system_ip.clone(),
+            setting: setting.to_string(),
+        });
+    }
+
+    fn push_sync_failed(event_log: &EventLog, host: &ManagedUser, setting: &str, error: String) {
+        event_log.push(EventKind::SyncFailed {
+            user_id: host.id,
+            username: host.username.clone(),
+            system_ip: host.system_ip.clone(),
+            setting: setting.to_string(),
+            error,
+        });
+    }
+}
+
+/// Whether the background loop checks `user` at `now`. Valid hosts are checked on every
+/// pass; hosts that haven't validated yet, e.g. added while offline, wait longer after each
+/// failed check, with the same backoff as schedule syncs.
+pub fn is_due_for_check(user: &ManagedUser, now: DateTime<Utc>) -> bool {
+    if user.is_valid {
+        return true;
+    }
+    user.last_checked
+        .is_none_or(|last| now - last >= sync_retry_delay(user.consecutive_failures))
+}
+
+/// Whether `now` falls in [from, to); a window with `from` after `to` spans midnight
+pub fn is_within_active_window(now: NaiveTime, from: NaiveTime, to: NaiveTime) -> bool {
+    if from <= to {
+        from <= now && now < to
+    } else {
+        now >= from || now < to
+    }
 }
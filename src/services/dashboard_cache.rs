@@ -0,0 +1,59 @@
+use crate::models::{ServiceError, UserData};
+use crate::services::{ScheduleService, UserService};
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+
+/// Dashboard rows as of a point in time
+#[derive(Clone)]
+pub struct DashboardSnapshot {
+    pub users: Vec<UserData>,
+    pub as_of: DateTime<Utc>,
+}
+
+/// Last dashboard computed by the background scheduler, so reads don't re-query
+/// and re-parse every user's config. Handlers that change users invalidate it.
+#[derive(Default)]
+pub struct DashboardCache {
+    snapshot: RwLock<Option<DashboardSnapshot>>,
+}
+
+impl DashboardCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compute the dashboard rows from the database
+    pub async fn build(
+        user_service: &UserService,
+        schedule_service: &ScheduleService,
+    ) -> Result<Vec<UserData>, ServiceError> {
+        let mut users = user_service.get_dashboard_users().await?;
+        for user in &mut users {
+            user.effective_limit_today = schedule_service.effective_limit_today(user.id).await?;
+            user.needs_attention = schedule_service.sync_needs_attention(user.id).await?;
+        }
+        Ok(users)
+    }
+
+    pub async fn refresh(
+        &self,
+        user_service: &UserService,
+        schedule_service: &ScheduleService,
+    ) -> Result<(), ServiceError> {
+        let users = Self::build(user_service, schedule_service).await?;
+        *self.snapshot.write().await = Some(DashboardSnapshot {
+            users,
+            as_of: Utc::now(),
+        });
+        Ok(())
+    }
+
+    pub async fn snapshot(&self) -> Option<DashboardSnapshot> {
+        self.snapshot.read().await.clone()
+    }
+
+    /// Drop the snapshot so the next read falls back to a live query
+    pub async fn invalidate(&self) {
+        *self.snapshot.write().await = None;
+    }
+}
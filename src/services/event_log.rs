@@ -0,0 +1,59 @@
+use crate::models::{format_timestamp, Event, EventKind};
+use chrono::Utc;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Events kept before the oldest are dropped
+pub const EVENT_LOG_CAPACITY: usize = 500;
+
+/// Recent events in memory only: lost on restart, and the oldest are dropped once the
+/// log is full. Meant for a quick "what happened" view, not as an audit trail.
+pub struct EventLog {
+    capacity: usize,
+    /// Newest last, with the id the next event gets
+    events: Mutex<(VecDeque<Event>, u64)>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::with_capacity(EVENT_LOG_CAPACITY)
+    }
+}
+
+impl EventLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            capacity,
+            events: Mutex::new((VecDeque::with_capacity(capacity), 1)),
+        }
+    }
+
+    pub fn push(&self, kind: EventKind) {
+        let mut guard = self.events.lock().unwrap();
+        let (events, next_id) = &mut *guard;
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(Event {
+            id: *next_id,
+            timestamp: format_timestamp(Utc::now()),
+            kind,
+        });
+        *next_id += 1;
+    }
+
+    /// Up to `limit` events, newest first
+    pub fn recent(&self, limit: usize) -> Vec<Event> {
+        let guard = self.events.lock().unwrap();
+        guard.0.iter().rev().take(limit).cloned().collect()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+}
@@ -0,0 +1,55 @@
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::sync::RwLock;
+
+/// Wall-clock time that every daily boundary is read from: usage days, date overrides,
+/// grant caps, the reset countdown and the scheduler's active window. Shared by the
+/// services so they agree on "today". Follows the `timezone` setting, or the server's
+/// local time while none is set.
+#[derive(Default)]
+pub struct LocalTime {
+    timezone: RwLock<Option<Tz>>,
+}
+
+impl LocalTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Switch to the given timezone; None goes back to the server's local time
+    pub fn set_timezone(&self, timezone: Option<Tz>) {
+        *self.timezone.write().unwrap() = timezone;
+    }
+
+    pub fn timezone(&self) -> Option<Tz> {
+        *self.timezone.read().unwrap()
+    }
+
+    /// Local wall-clock time at `at`
+    pub fn at(&self, at: DateTime<Utc>) -> NaiveDateTime {
+        match self.timezone() {
+            Some(timezone) => at.with_timezone(&timezone).naive_local(),
+            None => at.with_timezone(&Local).naive_local(),
+        }
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        self.at(Utc::now())
+    }
+
+    /// The instant a local wall-clock time refers to; the earlier one when a clock change
+    /// makes it ambiguous, and UTC as a fallback for times skipped by one
+    pub fn to_utc(&self, local: NaiveDateTime) -> DateTime<Utc> {
+        let at = match self.timezone() {
+            Some(timezone) => timezone
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+            None => Local
+                .from_local_datetime(&local)
+                .earliest()
+                .map(|at| at.with_timezone(&Utc)),
+        };
+        at.unwrap_or_else(|| local.and_utc())
+    }
+}
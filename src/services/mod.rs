@@ -1,11 +1,19 @@
+pub mod dashboard_cache;
+pub mod event_log;
+pub mod local_time;
 pub mod schedule_service;
+pub mod settings_service;
 pub mod time_service;
 pub mod usage_service;
+pub mod user_locks;
 pub mod user_service;
-pub mod settings_service;
 
+pub use dashboard_cache::*;
+pub use event_log::*;
+pub use local_time::*;
 pub use schedule_service::*;
+pub use settings_service::*;
 pub use time_service::*;
 pub use usage_service::*;
+pub use user_locks::*;
 pub use user_service::*;
-pub use settings_service::*;
\ No newline at end of file
@@ -1,27 +1,148 @@
 use crate::models::{
-    Schedule, ScheduleSyncStatus, ScheduleWithIntervals, ServiceError, WeeklyHours,
-    WeeklyTimeIntervals,
+    effective_limit_seconds, format_timestamp, seconds_until_reset, usage_day, AllowedDay,
+    AspectSyncOutcome, DayOfWeek, IntervalsSyncStatus, LockoutSettings, ManagedUser,
+    PlayTimeSettings, Schedule, ScheduleSyncStatus, ScheduleWithIntervals, ServiceError,
+    SyncAspect, TimeInterval, TimekprDayExport, TimekprExport, TimekprScheduleImport,
+    TodayExtension, UserLockout, UserPlayTime, UserStatus, WeeklyHours, WeeklyTimeIntervals,
 };
-use crate::repositories::ScheduleRepository;
+use crate::repositories::{ScheduleRepository, UserRepository};
+use crate::services::local_time::LocalTime;
+use crate::ssh::{SSHClient, SshBackend, SystemSsh};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::HashMap;
 use std::sync::Arc;
 
+/// Limit in hours per day name, as pushed to hosts
+pub type DayLimits = HashMap<String, f64>;
+/// Allowed-hours intervals per day name as ("HH:MM", "HH:MM") pairs, as pushed to hosts
+pub type DayIntervalsMap = HashMap<String, Vec<(String, String)>>;
+
+/// Wait before retrying a schedule sync after its first failure; doubles with each further one
+pub const SYNC_RETRY_BASE: Duration = Duration::seconds(30);
+/// Longest wait between two attempts at syncing a schedule
+pub const SYNC_RETRY_MAX: Duration = Duration::hours(1);
+
+/// Wait before the next attempt after `attempts` failed syncs in a row
+pub fn sync_retry_delay(attempts: i64) -> Duration {
+    // Past a dozen doublings the cap applies anyway
+    let doublings = attempts.clamp(1, 16) - 1;
+    SYNC_RETRY_BASE
+        .checked_mul(1 << doublings)
+        .map_or(SYNC_RETRY_MAX, |delay| delay.min(SYNC_RETRY_MAX))
+}
+
+/// Failed schedule syncs in a row after which the scheduler stops retrying a user
+pub const DEFAULT_MAX_SYNC_FAILURES: i64 = 10;
+
+/// Failed syncs before a user needs attention, from `SCHEDULE_SYNC_MAX_FAILURES`
+pub fn max_sync_failures() -> i64 {
+    std::env::var("SCHEDULE_SYNC_MAX_FAILURES")
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|failures| *failures > 0)
+        .unwrap_or(DEFAULT_MAX_SYNC_FAILURES)
+}
+
+/// Hour (0-23) at which timekpr starts a new day, from `DAILY_RESET_HOUR`; midnight by default
+pub fn daily_reset_hour() -> u32 {
+    std::env::var("DAILY_RESET_HOUR")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .filter(|hour| *hour < 24)
+        .unwrap_or(0)
+}
+
 pub struct ScheduleService {
     repository: Arc<dyn ScheduleRepository>,
+    user_repository: Arc<dyn UserRepository>,
+    reset_hour: u32,
+    local_time: Arc<LocalTime>,
+    ssh: Arc<dyn SshBackend>,
+    /// Failed background syncs in a row before the schedule waits for a manual sync
+    max_sync_failures: i64,
 }
 
 impl ScheduleService {
-    pub fn new(repository: Arc<dyn ScheduleRepository>) -> Self {
-        Self { repository }
+    pub fn new(
+        repository: Arc<dyn ScheduleRepository>,
+        user_repository: Arc<dyn UserRepository>,
+    ) -> Self {
+        // timekpr resets daily counters at local midnight unless configured otherwise
+        Self {
+            repository,
+            user_repository,
+            reset_hour: daily_reset_hour(),
+            local_time: Arc::new(LocalTime::new()),
+            ssh: Arc::new(SystemSsh::default()),
+            max_sync_failures: max_sync_failures(),
+        }
+    }
+
+    /// Push manual syncs through the given backend instead of the system `ssh` binary
+    pub fn with_ssh_backend(self, ssh: Arc<dyn SshBackend>) -> Self {
+        Self { ssh, ..self }
+    }
+
+    /// Take "today" from the given local time, shared with the other services
+    pub fn with_local_time(self, local_time: Arc<LocalTime>) -> Self {
+        Self { local_time, ..self }
+    }
+
+    /// Stop retrying a schedule in the background after this many failed syncs in a row
+    #[allow(dead_code)]
+    pub fn with_max_sync_failures(self, failures: i64) -> Self {
+        Self {
+            max_sync_failures: failures.max(1),
+            ..self
+        }
+    }
+
+    /// Whether background syncs of the schedule failed often enough that the scheduler
+    /// leaves it alone until a manual sync
+    pub fn needs_attention(&self, schedule: &Schedule) -> bool {
+        !schedule.is_synced && schedule.sync_attempts >= self.max_sync_failures
+    }
+
+    pub async fn sync_needs_attention(&self, user_id: i64) -> Result<bool, ServiceError> {
+        Ok(self
+            .repository
+            .find_by_user_id(user_id)
+            .await?
+            .is_some_and(|schedule| self.needs_attention(&schedule)))
+    }
+
+    /// The user a schedule is saved for must exist. One that failed validation still gets
+    /// the schedule, with a warning since it is only pushed once the host validates
+    async fn schedule_owner_warning(&self, user_id: i64) -> Result<Option<String>, ServiceError> {
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        if user.is_valid {
+            return Ok(None);
+        }
+        let status = user
+            .last_status
+            .as_deref()
+            .and_then(|status| status.parse::<UserStatus>().ok())
+            .map_or("not validated yet", UserStatus::label);
+        Ok(Some(format!(
+            "User {} on {} is not valid ({}); the schedule is saved and will be applied once the user validates",
+            user.username, user.system_ip, status
+        )))
     }
 
+    /// Save the schedule, returning a warning when the user has not passed validation
     pub async fn update_schedule(
         &self,
         user_id: i64,
         hours: WeeklyHours,
-    ) -> Result<(), ServiceError> {
+    ) -> Result<Option<String>, ServiceError> {
         // Business logic: Create and validate schedule (backward compatibility)
-        let schedule =
-            Schedule::new(user_id, hours).map_err(|e| ServiceError::ValidationError(e))?;
+        let schedule = Schedule::new(user_id, hours).map_err(ServiceError::ValidationError)?;
+        let warning = self.schedule_owner_warning(user_id).await?;
 
         // Persistence: Save through repository
         self.repository.save(&schedule).await?;
@@ -30,7 +151,7 @@ impl ScheduleService {
             "Schedule updated for user {}: is_synced={}",
             user_id, schedule.is_synced
         );
-        Ok(())
+        Ok(warning)
     }
 
     pub async fn update_schedule_with_intervals(
@@ -38,10 +159,11 @@ impl ScheduleService {
         user_id: i64,
         hours: WeeklyHours,
         intervals: WeeklyTimeIntervals,
-    ) -> Result<(), ServiceError> {
+    ) -> Result<Option<String>, ServiceError> {
         // Business logic: Create and validate schedule with intervals
         let schedule = Schedule::new_with_intervals(user_id, hours, intervals)
-            .map_err(|e| ServiceError::ValidationError(e))?;
+            .map_err(ServiceError::ValidationError)?;
+        let warning = self.schedule_owner_warning(user_id).await?;
 
         // Persistence: Save through repository
         self.repository.save(&schedule).await?;
@@ -50,127 +172,669 @@ impl ScheduleService {
             "Schedule with intervals updated for user {}: is_synced={}",
             user_id, schedule.is_synced
         );
-        Ok(())
+        Ok(warning)
     }
 
     pub async fn get_sync_status(&self, user_id: i64) -> Result<ScheduleSyncStatus, ServiceError> {
         match self.repository.find_by_user_id(user_id).await? {
-            Some(schedule) => Ok(ScheduleSyncStatus {
-                is_synced: schedule.is_synced,
-                schedule: Some(ScheduleWithIntervals {
-                    hours: schedule.hours,
-                    intervals: schedule.intervals,
-                }),
-                last_synced: schedule
-                    .last_synced
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string()),
-                last_modified: Some(schedule.last_modified.format("%Y-%m-%d %H:%M").to_string()),
-            }),
+            Some(schedule) => {
+                let needs_attention = self.needs_attention(&schedule);
+                Ok(ScheduleSyncStatus {
+                    is_synced: schedule.is_synced,
+                    schedule: Some(ScheduleWithIntervals::new(
+                        schedule.hours,
+                        schedule.intervals,
+                    )),
+                    last_synced: schedule.last_synced.map(format_timestamp),
+                    last_modified: Some(format_timestamp(schedule.last_modified)),
+                    sync_attempts: schedule.sync_attempts,
+                    next_sync_attempt: schedule
+                        .next_sync_attempt
+                        .filter(|_| !schedule.is_synced && !needs_attention)
+                        .map(format_timestamp),
+                    needs_attention,
+                })
+            }
             None => Ok(ScheduleSyncStatus {
                 is_synced: true, // No schedule means no sync needed
                 schedule: None,
                 last_synced: None,
                 last_modified: None,
+                sync_attempts: 0,
+                next_sync_attempt: None,
+                needs_attention: false,
             }),
         }
     }
 
-    pub async fn mark_as_synced(&self, user_id: i64) -> Result<(), ServiceError> {
-        self.repository.mark_as_synced(user_id).await
+    pub async fn get_intervals_sync_status(
+        &self,
+        user_id: i64,
+    ) -> Result<IntervalsSyncStatus, ServiceError> {
+        let schedule = self.repository.find_by_user_id(user_id).await?;
+        let full_day = TimeInterval::default();
+
+        Ok(match schedule {
+            Some(schedule) => IntervalsSyncStatus {
+                needs_sync: !schedule.is_synced,
+                last_synced: schedule.last_synced.map(format_timestamp),
+                enabled_intervals: DayOfWeek::iter()
+                    .filter(|day| schedule.intervals.get(*day) != std::slice::from_ref(&full_day))
+                    .count(),
+                total_intervals: DayOfWeek::ALL.len(),
+            },
+            // No schedule means no intervals to push
+            None => IntervalsSyncStatus {
+                needs_sync: false,
+                last_synced: None,
+                enabled_intervals: 0,
+                total_intervals: DayOfWeek::ALL.len(),
+            },
+        })
     }
 
-    pub async fn get_unsynced_schedules(&self) -> Result<Vec<Schedule>, ServiceError> {
-        self.repository.find_unsynced().await
+    /// Stored allowed hours; full days when the user has no schedule yet
+    pub async fn get_intervals(&self, user_id: i64) -> Result<WeeklyTimeIntervals, ServiceError> {
+        Ok(self
+            .repository
+            .find_by_user_id(user_id)
+            .await?
+            .map(|schedule| schedule.intervals)
+            .unwrap_or_default())
     }
 
-    // Helper method to prepare sync data for SSH operations
-    pub fn prepare_sync_data(
+    /// Replace the allowed hours of the given days, keeping the others and the weekly
+    /// limits. The schedule is marked unsynced for the scheduler to push.
+    pub async fn update_intervals(
         &self,
-        schedule: &Schedule,
-    ) -> (
-        std::collections::HashMap<String, f64>,
-        std::collections::HashMap<String, (String, String)>,
-    ) {
-        // Create time limits dict with non-null values only
-        let mut schedule_dict = std::collections::HashMap::new();
-        let hours = &schedule.hours;
+        user: &ManagedUser,
+        changes: Vec<(DayOfWeek, Vec<TimeInterval>)>,
+    ) -> Result<WeeklyTimeIntervals, ServiceError> {
+        let mut schedule = self
+            .repository
+            .find_by_user_id(user.id)
+            .await?
+            .ok_or_else(|| {
+                ServiceError::NotFound(format!(
+                    "No schedule configured for {}; save the weekly limits first",
+                    user.username
+                ))
+            })?;
 
-        if hours.monday > 0.0 {
-            schedule_dict.insert("monday".to_string(), hours.monday);
-        }
-        if hours.tuesday > 0.0 {
-            schedule_dict.insert("tuesday".to_string(), hours.tuesday);
-        }
-        if hours.wednesday > 0.0 {
-            schedule_dict.insert("wednesday".to_string(), hours.wednesday);
+        for (day, intervals) in changes {
+            schedule.intervals.set(day, intervals);
         }
-        if hours.thursday > 0.0 {
-            schedule_dict.insert("thursday".to_string(), hours.thursday);
-        }
-        if hours.friday > 0.0 {
-            schedule_dict.insert("friday".to_string(), hours.friday);
-        }
-        if hours.saturday > 0.0 {
-            schedule_dict.insert("saturday".to_string(), hours.saturday);
+        let schedule = Schedule::new_with_intervals(user.id, schedule.hours, schedule.intervals)
+            .map_err(ServiceError::ValidationError)?;
+        self.repository.save(&schedule).await?;
+
+        println!(
+            "Allowed hours updated for user {}: is_synced={}",
+            user.id, schedule.is_synced
+        );
+        Ok(schedule.intervals)
+    }
+
+    /// Set the given days to zero hours, which timekpr receives as not allowed. Recurring
+    /// bonuses on those days and overrides for upcoming dates on them are removed so they
+    /// can't allow the day again; other days and the allowed hours are kept. The schedule
+    /// is marked unsynced for the scheduler to push.
+    pub async fn block_days(
+        &self,
+        user: &ManagedUser,
+        days: &[DayOfWeek],
+    ) -> Result<Schedule, ServiceError> {
+        if days.is_empty() {
+            return Err(ServiceError::ValidationError(
+                "At least one day to block is required".to_string(),
+            ));
         }
-        if hours.sunday > 0.0 {
-            schedule_dict.insert("sunday".to_string(), hours.sunday);
+        let mut schedule = self
+            .repository
+            .find_by_user_id(user.id)
+            .await?
+            .ok_or_else(|| {
+                ServiceError::NotFound(format!(
+                    "No schedule configured for {}; save the weekly limits first",
+                    user.username
+                ))
+            })?;
+
+        for day in days {
+            schedule.hours.set(*day, 0.0);
         }
+        let schedule = Schedule::new_with_intervals(user.id, schedule.hours, schedule.intervals)
+            .map_err(ServiceError::ValidationError)?;
+        self.repository.save(&schedule).await?;
+        self.repository
+            .clear_day_extras(user.id, days, self.today())
+            .await?;
 
-        // Create time intervals dict
-        let mut intervals_dict = std::collections::HashMap::new();
-        let intervals = &schedule.intervals;
-
-        intervals_dict.insert(
-            "monday".to_string(),
-            (
-                intervals.monday.start_time.clone(),
-                intervals.monday.end_time.clone(),
-            ),
+        println!(
+            "Blocked {} day(s) for user {}: is_synced={}",
+            days.len(),
+            user.id,
+            schedule.is_synced
         );
-        intervals_dict.insert(
-            "tuesday".to_string(),
-            (
-                intervals.tuesday.start_time.clone(),
-                intervals.tuesday.end_time.clone(),
-            ),
+        Ok(schedule)
+    }
+
+    /// Replace the user's schedule with the limits and allowed hours of a timekpr config.
+    /// Without `config` the host's `--userinfo` output is read instead. The result is marked
+    /// synced since it describes what the host already enforces. Returns the saved schedule
+    /// and what could not be imported exactly.
+    pub async fn import_timekpr(
+        &self,
+        user: &ManagedUser,
+        config: Option<&str>,
+    ) -> Result<(Schedule, Vec<String>), ServiceError> {
+        let imported = match config {
+            Some(config) => TimekprScheduleImport::parse(config),
+            None => {
+                let (status, message, config) = SSHClient::for_user(user)
+                    .validate_user(&user.username)
+                    .await;
+                match config {
+                    Some(config) if status == UserStatus::Ok => {
+                        TimekprScheduleImport::parse(&config.raw_output)
+                    }
+                    _ => {
+                        return Err(ServiceError::ValidationError(format!(
+                            "Could not read the timekpr config of {} from {}: {}",
+                            user.username, user.system_ip, message
+                        )))
+                    }
+                }
+            }
+        }
+        .map_err(|e| ServiceError::ValidationError(format!("Invalid timekpr config: {}", e)))?;
+
+        let schedule = Schedule::new_with_intervals(user.id, imported.hours, imported.intervals)
+            .map_err(ServiceError::ValidationError)?;
+        self.repository.save(&schedule).await?;
+        let (schedule_dict, _) = self.prepare_sync_data(&schedule);
+        self.mark_as_synced(user.id, &schedule_dict).await?;
+
+        println!(
+            "Imported timekpr schedule for user {} ({} warning(s))",
+            user.id,
+            imported.warnings.len()
         );
-        intervals_dict.insert(
-            "wednesday".to_string(),
-            (
-                intervals.wednesday.start_time.clone(),
-                intervals.wednesday.end_time.clone(),
-            ),
+        Ok((
+            Schedule {
+                is_synced: true,
+                last_synced: Some(Utc::now()),
+                ..schedule
+            },
+            imported.warnings,
+        ))
+    }
+
+    /// Record a successful push of `day_limits`, keeping what today's weekday got so the
+    /// push can be redone once today's override or bonus no longer applies
+    pub async fn mark_as_synced(
+        &self,
+        user_id: i64,
+        day_limits: &DayLimits,
+    ) -> Result<(), ServiceError> {
+        let today = self.today();
+        let hours = day_limits
+            .get(DayOfWeek::from_date(today).name())
+            .copied()
+            .unwrap_or(0.0);
+        self.repository.mark_as_synced(user_id, today, hours).await
+    }
+
+    /// Queue a push for synced schedules whose last push, made on an earlier day, no longer
+    /// matches what today's push would send: an override or bonus has expired at the reset,
+    /// or one starts today. Returns the users whose schedule was queued.
+    pub async fn requeue_expired_day_limits(&self) -> Result<Vec<i64>, ServiceError> {
+        let today = self.today();
+        let today_name = DayOfWeek::from_date(today).name();
+        let mut requeued = Vec::new();
+
+        for schedule in self.repository.find_synced_before(today).await? {
+            let (wanted, _) = self.prepare_effective_sync_data(&schedule).await?;
+            let (mut pushed, _) = self.prepare_sync_data(&schedule);
+            if let (Some(day), Some(hours)) = (schedule.synced_day, schedule.synced_day_hours) {
+                pushed.insert(DayOfWeek::from_date(day).name().to_string(), hours);
+            }
+
+            if pushed == wanted {
+                let hours = wanted.get(today_name).copied().unwrap_or(0.0);
+                self.repository
+                    .update_synced_day(schedule.user_id, today, hours)
+                    .await?;
+            } else {
+                self.repository.mark_unsynced(schedule.user_id).await?;
+                requeued.push(schedule.user_id);
+            }
+        }
+        Ok(requeued)
+    }
+
+    /// Unsynced schedules whose backoff has run out by `now`, leaving out those that
+    /// need attention
+    pub async fn get_due_unsynced_schedules(
+        &self,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<Schedule>, ServiceError> {
+        Ok(self
+            .repository
+            .find_unsynced()
+            .await?
+            .into_iter()
+            .filter(|schedule| !self.needs_attention(schedule))
+            .filter(|schedule| schedule.next_sync_attempt.is_none_or(|next| next <= now))
+            .collect())
+    }
+
+    /// Push the next background attempt back exponentially; returns when it is due, or
+    /// None once this failure makes the schedule need attention
+    pub async fn record_sync_failure(
+        &self,
+        schedule: &Schedule,
+    ) -> Result<Option<DateTime<Utc>>, ServiceError> {
+        let attempts = schedule.sync_attempts + 1;
+        let next_attempt = Utc::now() + sync_retry_delay(attempts);
+        self.repository
+            .record_sync_failure(schedule.user_id, attempts, next_attempt)
+            .await?;
+        Ok((attempts < self.max_sync_failures).then_some(next_attempt))
+    }
+
+    /// Push the whole stored schedule now, e.g. once the host's config has been fixed.
+    /// Earlier failures are forgotten either way, so a schedule that needed attention is
+    /// retried by the scheduler again when this push fails too.
+    pub async fn sync_now(&self, user: &ManagedUser) -> Result<String, ServiceError> {
+        let schedule = self
+            .repository
+            .find_by_user_id(user.id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("No schedule configured for user".to_string()))?;
+        self.repository.reset_sync_attempts(user.id).await?;
+
+        let (schedule_dict, intervals_dict) = self.prepare_effective_sync_data(&schedule).await?;
+        let (limits_success, limits_message) =
+            self.ssh.set_weekly_time_limits(user, &schedule_dict).await;
+        let (hours_success, hours_message) = self
+            .ssh
+            .set_weekly_allowed_hours(user, &intervals_dict)
+            .await;
+
+        println!(
+            "Manual schedule sync for {}: limits={}, hours={}",
+            user.username, limits_success, hours_success
         );
-        intervals_dict.insert(
-            "thursday".to_string(),
-            (
-                intervals.thursday.start_time.clone(),
-                intervals.thursday.end_time.clone(),
-            ),
+
+        if limits_success && hours_success {
+            self.mark_as_synced(user.id, &schedule_dict).await?;
+            Ok(format!("{}; {}", limits_message, hours_message))
+        } else {
+            let mut error_parts = Vec::new();
+            if !limits_success {
+                error_parts.push(format!("Time limits: {}", limits_message));
+            }
+            if !hours_success {
+                error_parts.push(format!("Allowed hours: {}", hours_message));
+            }
+            Err(ServiceError::Queued(format!(
+                "{}; the scheduler will retry",
+                error_parts.join(", ")
+            )))
+        }
+    }
+
+    /// Push everything stored for the user again, since the host it was pushed to changed
+    pub async fn requeue_all(&self, user_id: i64) -> Result<(), ServiceError> {
+        self.repository.mark_all_unsynced(user_id).await
+    }
+
+    pub async fn update_playtime(
+        &self,
+        user_id: i64,
+        settings: &PlayTimeSettings,
+    ) -> Result<(), ServiceError> {
+        settings.validate().map_err(ServiceError::ValidationError)?;
+        self.repository.save_playtime(user_id, settings).await?;
+
+        println!(
+            "PlayTime settings updated for user {}: is_synced=false",
+            user_id
         );
-        intervals_dict.insert(
-            "friday".to_string(),
-            (
-                intervals.friday.start_time.clone(),
-                intervals.friday.end_time.clone(),
-            ),
+        Ok(())
+    }
+
+    pub async fn get_playtime(&self, user_id: i64) -> Result<Option<UserPlayTime>, ServiceError> {
+        self.repository.find_playtime(user_id).await
+    }
+
+    pub async fn get_unsynced_playtime(&self) -> Result<Vec<UserPlayTime>, ServiceError> {
+        self.repository.find_unsynced_playtime().await
+    }
+
+    pub async fn mark_playtime_synced(&self, user_id: i64) -> Result<(), ServiceError> {
+        self.repository.mark_playtime_synced(user_id).await
+    }
+
+    pub async fn update_lockout(
+        &self,
+        user_id: i64,
+        settings: &LockoutSettings,
+    ) -> Result<(), ServiceError> {
+        settings.validate().map_err(ServiceError::ValidationError)?;
+        self.repository.save_lockout(user_id, settings).await?;
+
+        println!(
+            "Lockout settings updated for user {}: is_synced=false",
+            user_id
         );
-        intervals_dict.insert(
-            "saturday".to_string(),
-            (
-                intervals.saturday.start_time.clone(),
-                intervals.saturday.end_time.clone(),
-            ),
+        Ok(())
+    }
+
+    pub async fn get_lockout(&self, user_id: i64) -> Result<Option<UserLockout>, ServiceError> {
+        self.repository.find_lockout(user_id).await
+    }
+
+    pub async fn get_unsynced_lockout(&self) -> Result<Vec<UserLockout>, ServiceError> {
+        self.repository.find_unsynced_lockout().await
+    }
+
+    pub async fn mark_lockout_synced(&self, user_id: i64) -> Result<(), ServiceError> {
+        self.repository.mark_lockout_synced(user_id).await
+    }
+
+    /// Local day the limits currently apply to, which starts at the reset hour
+    pub fn today(&self) -> NaiveDate {
+        usage_day(self.local_time.now(), self.reset_hour)
+    }
+
+    /// Seconds the user may use today (local date); None when no limit is configured
+    pub async fn effective_limit_today(&self, user_id: i64) -> Result<Option<i64>, ServiceError> {
+        self.effective_limit_on(user_id, self.today()).await
+    }
+
+    /// Add minutes to today's limit only. Builds on today's override when there is one,
+    /// otherwise on the weekly hours, and stays within a full day. The schedule is queued
+    /// for a push, and every push until the reset carries the extended limit; the scheduler
+    /// pushes the plain limit again after the reset.
+    pub async fn extend_today(
+        &self,
+        user_id: i64,
+        minutes: i64,
+    ) -> Result<TodayExtension, ServiceError> {
+        if !(1..=1440).contains(&minutes) {
+            return Err(ServiceError::ValidationError(
+                "minutes must be between 1 and 1440".to_string(),
+            ));
+        }
+        let schedule = self
+            .repository
+            .find_by_user_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("No schedule configured for user".to_string()))?;
+
+        let today = self.today();
+        let base_hours = match self.repository.find_limit_override(user_id, today).await? {
+            Some(hours) => hours,
+            None => schedule.hours.get(DayOfWeek::from_date(today)),
+        };
+        let override_hours = (base_hours + minutes as f64 / 60.0).min(24.0);
+        self.repository
+            .save_limit_override(user_id, today, override_hours)
+            .await?;
+
+        Ok(TodayExtension {
+            date: today,
+            override_hours,
+            limit_seconds: self.effective_limit_on(user_id, today).await?,
+            expires_at: Utc::now() + Duration::seconds(self.seconds_until_reset()),
+        })
+    }
+
+    /// Seconds until the daily limit resets
+    pub fn seconds_until_reset(&self) -> i64 {
+        seconds_until_reset(self.local_time.now(), self.reset_hour)
+    }
+
+    pub async fn effective_limit_on(
+        &self,
+        user_id: i64,
+        date: NaiveDate,
+    ) -> Result<Option<i64>, ServiceError> {
+        let day = DayOfWeek::from_date(date);
+        let weekly_hours = self
+            .repository
+            .find_by_user_id(user_id)
+            .await?
+            .map(|schedule| schedule.hours.get(day));
+        let override_hours = self.repository.find_limit_override(user_id, date).await?;
+        let bonus_minutes = self.repository.find_recurring_bonus(user_id, day).await?;
+
+        Ok(effective_limit_seconds(
+            weekly_hours,
+            override_hours,
+            bonus_minutes,
+        ))
+    }
+
+    /// Sync payload with today's weekday replaced by the composite effective limit
+    pub async fn prepare_effective_sync_data(
+        &self,
+        schedule: &Schedule,
+    ) -> Result<(DayLimits, DayIntervalsMap), ServiceError> {
+        let (mut schedule_dict, intervals_dict) = self.prepare_sync_data(schedule);
+
+        let today = self.today();
+        let day = DayOfWeek::from_date(today);
+        let hours = match self.effective_limit_on(schedule.user_id, today).await? {
+            Some(seconds) if seconds > 0 => seconds as f64 / 3600.0,
+            _ => 0.0,
+        };
+        schedule_dict.insert(day.name().to_string(), hours);
+
+        Ok((schedule_dict, intervals_dict))
+    }
+
+    /// Allowed and blocked days as the next sync would push them, next to the days the
+    /// host last reported (`host_days`) when known
+    pub async fn allowed_days(
+        &self,
+        user_id: i64,
+        host_days: Option<Vec<DayOfWeek>>,
+    ) -> Result<Vec<AllowedDay>, ServiceError> {
+        let schedule = self
+            .repository
+            .find_by_user_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("No schedule configured for user".to_string()))?;
+        let (schedule_dict, _) = self.prepare_effective_sync_data(&schedule).await?;
+        let pushed = SSHClient::allowed_day_limits(&schedule_dict);
+
+        Ok(DayOfWeek::iter()
+            .map(|day| {
+                let time_limit = pushed
+                    .iter()
+                    .find(|(pushed_day, _)| *pushed_day == day)
+                    .map(|(_, seconds)| *seconds);
+                AllowedDay {
+                    day,
+                    allowed: time_limit.is_some(),
+                    time_limit,
+                    host_allowed: host_days.as_ref().map(|days| days.contains(&day)),
+                }
+            })
+            .collect())
+    }
+
+    /// Push a single aspect of the stored schedule without touching the others.
+    /// A successful push leaves the schedule-wide sync flag as is, so the scheduler still
+    /// performs a full push for schedules that are out of sync; a failed one queues that
+    /// full push.
+    pub async fn sync_aspect(
+        &self,
+        user: &ManagedUser,
+        aspect: SyncAspect,
+    ) -> Result<AspectSyncOutcome, ServiceError> {
+        let schedule = self
+            .repository
+            .find_by_user_id(user.id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("No schedule configured for user".to_string()))?;
+
+        let (schedule_dict, intervals_dict) = self.prepare_effective_sync_data(&schedule).await?;
+        let ssh_client = SSHClient::for_user(user);
+
+        let (success, message, days) = match aspect {
+            SyncAspect::Limits => {
+                let (success, message) = ssh_client
+                    .set_weekly_time_limits(&user.username, &schedule_dict)
+                    .await;
+                (success, message, None)
+            }
+            SyncAspect::Hours => {
+                let days = ssh_client
+                    .set_weekly_allowed_hours_per_day(&user.username, &intervals_dict)
+                    .await;
+                let (success, message) = SSHClient::summarize_allowed_hours(&user.username, &days);
+                (success, message, Some(days))
+            }
+            SyncAspect::Week | SyncAspect::Month => {
+                return Err(ServiceError::ValidationError(format!(
+                    "The stored schedule has no {} limit to push",
+                    aspect.as_str()
+                )));
+            }
+        };
+
+        println!(
+            "Re-push of {} for {}: success={}, {}",
+            aspect.as_str(),
+            user.username,
+            success,
+            message
         );
-        intervals_dict.insert(
-            "sunday".to_string(),
-            (
-                intervals.sunday.start_time.clone(),
-                intervals.sunday.end_time.clone(),
-            ),
+
+        if success {
+            Ok(AspectSyncOutcome { message, days })
+        } else {
+            self.repository.mark_unsynced(user.id).await?;
+            Err(ServiceError::Queued(message))
+        }
+    }
+
+    /// Allow all 24 hours on every day, both on the host and in the stored schedule.
+    /// When the host is unreachable the cleared schedule is left unsynced for the scheduler;
+    /// without a stored schedule there is nothing to queue it on, which is a conflict.
+    pub async fn clear_allowed_hours(
+        &self,
+        user: &ManagedUser,
+    ) -> Result<AspectSyncOutcome, ServiceError> {
+        let ssh_client = SSHClient::for_user(user);
+        // No interval for a day means full-day access
+        let days = ssh_client
+            .set_weekly_allowed_hours_per_day(&user.username, &HashMap::new())
+            .await;
+        let (success, message) = SSHClient::summarize_allowed_hours(&user.username, &days);
+
+        let current = self.repository.find_by_user_id(user.id).await?;
+        let is_synced = success && current.as_ref().is_none_or(|schedule| schedule.is_synced);
+        let has_schedule = self.repository.clear_intervals(user.id, is_synced).await?;
+
+        println!(
+            "Cleared allowed hours for {}: pushed={}, {}",
+            user.username, success, message
         );
 
+        if success {
+            Ok(AspectSyncOutcome {
+                message,
+                days: Some(days),
+            })
+        } else if has_schedule {
+            Err(ServiceError::Queued(message))
+        } else {
+            Err(ServiceError::Conflict(format!(
+                "Host unreachable and no stored schedule to queue the change on; save the weekly limits first: {}",
+                message
+            )))
+        }
+    }
+
+    /// The stored schedule in timekpr's argument syntax. Overrides and bonuses for today
+    /// are not applied, so the export matches what is configured rather than today's push.
+    pub async fn timekpr_export(&self, user: &ManagedUser) -> Result<TimekprExport, ServiceError> {
+        let schedule = self
+            .repository
+            .find_by_user_id(user.id)
+            .await?
+            .ok_or_else(|| {
+                ServiceError::NotFound(format!("No schedule configured for {}", user.username))
+            })?;
+        let (schedule_dict, intervals_dict) = self.prepare_sync_data(&schedule);
+        let (allowed_days, time_limits) = SSHClient::time_limit_arguments(&schedule_dict);
+        let pushed = SSHClient::allowed_day_limits(&schedule_dict);
+
+        let days: Vec<TimekprDayExport> = DayOfWeek::iter()
+            .map(|day| TimekprDayExport {
+                day,
+                day_index: day.to_timekpr_index(),
+                allowed_hours: SSHClient::allowed_hours_for_day(&intervals_dict, day)
+                    .filter(|hours| !hours.is_empty())
+                    .map(|hours| hours.join(";")),
+                time_limit: pushed
+                    .iter()
+                    .find(|(pushed_day, _)| *pushed_day == day)
+                    .map(|(_, seconds)| *seconds),
+            })
+            .collect();
+
+        let mut commands = Vec::new();
+        if !allowed_days.is_empty() {
+            commands.push(format!(
+                "timekpra --setalloweddays {} '{}'",
+                user.username, allowed_days
+            ));
+            commands.push(format!(
+                "timekpra --settimelimits {} '{}'",
+                user.username, time_limits
+            ));
+        }
+        for day in &days {
+            if let Some(hours) = &day.allowed_hours {
+                commands.push(format!(
+                    "timekpra --setallowedhours {} {} '{}'",
+                    user.username, day.day_index, hours
+                ));
+            }
+        }
+
+        Ok(TimekprExport {
+            allowed_days,
+            time_limits,
+            days,
+            commands,
+        })
+    }
+
+    // Helper method to prepare sync data for SSH operations
+    pub fn prepare_sync_data(&self, schedule: &Schedule) -> (DayLimits, DayIntervalsMap) {
+        // Every day with its limit, zero for days without time, and the intervals dict
+        let mut schedule_dict = std::collections::HashMap::new();
+        let mut intervals_dict = std::collections::HashMap::new();
+
+        for day in DayOfWeek::iter() {
+            schedule_dict.insert(day.name().to_string(), schedule.hours.get(day));
+
+            let intervals = schedule
+                .intervals
+                .get(day)
+                .iter()
+                .map(|interval| (interval.start_time.clone(), interval.end_time.clone()))
+                .collect::<Vec<_>>();
+            intervals_dict.insert(day.name().to_string(), intervals);
+        }
+
         (schedule_dict, intervals_dict)
     }
 }
@@ -1,25 +1,39 @@
-use crate::models::{SettingsEntry, ServiceError};
+use crate::auth::hash_password;
+use crate::models::{parse_timezone, ServiceError, SettingsEntry, WeekStart, WeeklyHours};
 use crate::repositories::SettingsRepository;
+use crate::scheduler::{MAX_CHECK_INTERVAL, MIN_CHECK_INTERVAL, TICK_INTERVAL};
+use crate::services::LocalTime;
+use chrono::NaiveTime;
+use chrono_tz::Tz;
 use std::sync::Arc;
+use std::time::Duration;
 
 pub struct SettingsService {
     repository: Arc<dyn SettingsRepository>,
+    local_time: Arc<LocalTime>,
 }
 
 impl SettingsService {
     pub fn new(repository: Arc<dyn SettingsRepository>) -> Self {
-        Self { repository }
+        Self {
+            repository,
+            local_time: Arc::new(LocalTime::new()),
+        }
     }
 
-    pub async fn add_entry(
-        &self,
-        key: String,
-        value: String,
-    ) -> Result<String, ServiceError> {
+    /// Local time the other services read day boundaries from; kept on the timezone setting
+    pub fn with_local_time(mut self, local_time: Arc<LocalTime>) -> Self {
+        self.local_time = local_time;
+        self
+    }
 
+    #[allow(dead_code)]
+    pub async fn add_entry(&self, key: String, value: String) -> Result<String, ServiceError> {
         // Business logic: Check if entry already exists
         if self.repository.find_by_key(&key).await?.is_some() {
-            return Err(ServiceError::ValidationError("Setting key already exists".to_string()));
+            return Err(ServiceError::ValidationError(
+                "Setting key already exists".to_string(),
+            ));
         }
 
         // Create new entry
@@ -59,11 +73,7 @@ impl SettingsService {
         self.repository.find_all().await
     }
 
-    pub async fn update_entry_value(
-        &self,
-        id: i64,
-        value: String,
-    ) -> Result<String, ServiceError> {
+    pub async fn update_entry_value(&self, id: i64, value: String) -> Result<String, ServiceError> {
         let mut entry = self
             .repository
             .find_by_id(id)
@@ -78,24 +88,321 @@ impl SettingsService {
         Ok(format!("Entry {} updated successfully", id))
     }
 
+    /// Insert or overwrite the value stored under `key`
+    pub async fn set_value(&self, key: &str, value: String) -> Result<(), ServiceError> {
+        let entry = match self.repository.find_by_key(key).await? {
+            Some(entry) => SettingsEntry { value, ..entry },
+            None => SettingsEntry::new(key.to_string(), value),
+        };
+
+        self.repository.save(&entry).await?;
+
+        println!("Setting {} updated", key);
+        Ok(())
+    }
+
     // Convenience methods for common settings
     pub async fn get_admin_password_hash(&self) -> Result<Option<String>, ServiceError> {
-        Ok(self.find_by_key("admin_password_hash").await?.map(|entry| entry.value))
+        Ok(self
+            .find_by_key("admin_password_hash")
+            .await?
+            .map(|entry| entry.value))
+    }
+
+    /// Replace a plaintext admin password left by older versions with its hash and delete
+    /// the plaintext. An existing hash takes precedence. Returns whether anything changed.
+    pub async fn migrate_legacy_admin_password(&self) -> Result<bool, ServiceError> {
+        let legacy = match self
+            .repository
+            .find_by_key(SettingsEntry::LEGACY_ADMIN_PASSWORD)
+            .await?
+        {
+            Some(entry) => entry,
+            None => return Ok(false),
+        };
+
+        if self.get_admin_password_hash().await?.is_none() {
+            let hash = hash_password(&legacy.value).map_err(|e| {
+                ServiceError::InternalError(format!("Failed to hash password: {}", e))
+            })?;
+            self.set_value(SettingsEntry::ADMIN_PASSWORD_HASH, hash)
+                .await?;
+            println!("Migrated the plaintext admin password to a hash");
+        } else {
+            println!("Ignoring the plaintext admin password; a hash is already stored");
+        }
+
+        self.repository.delete(legacy.id).await?;
+        println!("Deleted the plaintext admin password");
+        Ok(true)
+    }
+
+    /// Create the admin credential when none is stored, from `initial_password` or the
+    /// documented default "admin". Login never falls back to a default on its own, so
+    /// this is the only place one gets created. Returns whether a credential was created.
+    pub async fn initialize_admin_password(
+        &self,
+        initial_password: Option<String>,
+    ) -> Result<bool, ServiceError> {
+        if self.get_admin_password_hash().await?.is_some() {
+            return Ok(false);
+        }
+
+        let (password, is_default) = match initial_password.filter(|p| !p.is_empty()) {
+            Some(password) => (password, false),
+            None => ("admin".to_string(), true),
+        };
+        let hash = hash_password(&password)
+            .map_err(|e| ServiceError::InternalError(format!("Failed to hash password: {}", e)))?;
+        self.set_value(SettingsEntry::ADMIN_PASSWORD_HASH, hash)
+            .await?;
+
+        if is_default {
+            eprintln!(
+                "Warning: no admin credential was stored; initialized the admin password to 'admin'. \
+                 Set ADMIN_INITIAL_PASSWORD to choose it, and change it after first login."
+            );
+        } else {
+            println!("Initialized the admin password from ADMIN_INITIAL_PASSWORD");
+        }
+        Ok(true)
     }
 
     #[allow(dead_code)]
     pub async fn get_jwt_secret(&self) -> Result<Option<String>, ServiceError> {
-        Ok(self.find_by_key("jwt_secret").await?.map(|entry| entry.value))
+        Ok(self
+            .find_by_key("jwt_secret")
+            .await?
+            .map(|entry| entry.value))
     }
 
+    /// Seconds between background scheduler passes, if configured
     #[allow(dead_code)]
     pub async fn get_check_interval(&self) -> Result<Option<i32>, ServiceError> {
-        if let Some(entry) = self.find_by_key("check_interval").await? {
-            entry.value.parse::<i32>()
-                .map(Some)
-                .map_err(|_| ServiceError::ValidationError("Invalid check_interval value".to_string()))
+        if let Some(entry) = self.find_by_key(SettingsEntry::CHECK_INTERVAL).await? {
+            entry.value.parse::<i32>().map(Some).map_err(|_| {
+                ServiceError::ValidationError("Invalid check_interval value".to_string())
+            })
         } else {
             Ok(None)
         }
     }
+
+    /// Time between scheduler passes in effect; see [`get_tick_interval`]
+    pub async fn tick_interval(&self) -> Duration {
+        get_tick_interval(self.repository.as_ref()).await
+    }
+
+    pub async fn set_check_interval(&self, seconds: i32) -> Result<(), ServiceError> {
+        if !(MIN_CHECK_INTERVAL..=MAX_CHECK_INTERVAL).contains(&seconds) {
+            return Err(ServiceError::ValidationError(format!(
+                "check_interval must be between {} and {} seconds, got {}",
+                MIN_CHECK_INTERVAL, MAX_CHECK_INTERVAL, seconds
+            )));
+        }
+        self.set_value(SettingsEntry::CHECK_INTERVAL, seconds.to_string())
+            .await
+    }
+
+    pub async fn get_week_start(&self) -> Result<WeekStart, ServiceError> {
+        Ok(self
+            .find_by_key(SettingsEntry::WEEK_START)
+            .await?
+            .and_then(|entry| entry.value.parse().ok())
+            .unwrap_or_default())
+    }
+
+    pub async fn set_week_start(&self, week_start: WeekStart) -> Result<(), ServiceError> {
+        self.set_value(SettingsEntry::WEEK_START, week_start.as_str().to_string())
+            .await
+    }
+
+    /// Whether the detailed health endpoint requires a bearer token (off by default)
+    pub async fn get_protect_observability(&self) -> Result<bool, ServiceError> {
+        Ok(self
+            .find_by_key(SettingsEntry::PROTECT_OBSERVABILITY)
+            .await?
+            .map(|entry| entry.value == "true")
+            .unwrap_or(false))
+    }
+
+    pub async fn set_protect_observability(&self, enabled: bool) -> Result<(), ServiceError> {
+        self.set_value(SettingsEntry::PROTECT_OBSERVABILITY, enabled.to_string())
+            .await
+    }
+
+    /// Local time window in which the scheduler contacts hosts; None means always
+    pub async fn get_scheduler_active_window(
+        &self,
+    ) -> Result<Option<(NaiveTime, NaiveTime)>, ServiceError> {
+        let from = self
+            .find_by_key(SettingsEntry::SCHEDULER_ACTIVE_FROM)
+            .await?;
+        let to = self.find_by_key(SettingsEntry::SCHEDULER_ACTIVE_TO).await?;
+
+        Ok(match (from, to) {
+            (Some(from), Some(to)) => NaiveTime::parse_from_str(&from.value, "%H:%M")
+                .ok()
+                .zip(NaiveTime::parse_from_str(&to.value, "%H:%M").ok()),
+            _ => None,
+        })
+    }
+
+    pub async fn set_scheduler_active_window(
+        &self,
+        window: Option<(NaiveTime, NaiveTime)>,
+    ) -> Result<(), ServiceError> {
+        match window {
+            Some((from, to)) => {
+                self.set_value(
+                    SettingsEntry::SCHEDULER_ACTIVE_FROM,
+                    from.format("%H:%M").to_string(),
+                )
+                .await?;
+                self.set_value(
+                    SettingsEntry::SCHEDULER_ACTIVE_TO,
+                    to.format("%H:%M").to_string(),
+                )
+                .await
+            }
+            None => {
+                for key in [
+                    SettingsEntry::SCHEDULER_ACTIVE_FROM,
+                    SettingsEntry::SCHEDULER_ACTIVE_TO,
+                ] {
+                    if let Some(entry) = self.find_by_key(key).await? {
+                        self.repository.delete(entry.id).await?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Configured timezone, if any; an unparseable stored value counts as unset
+    pub async fn get_timezone(&self) -> Result<Option<Tz>, ServiceError> {
+        Ok(self
+            .find_by_key(SettingsEntry::TIMEZONE)
+            .await?
+            .and_then(|entry| parse_timezone(&entry.value).ok()))
+    }
+
+    pub async fn set_timezone(&self, timezone: Option<Tz>) -> Result<(), ServiceError> {
+        match timezone {
+            Some(timezone) => {
+                self.set_value(SettingsEntry::TIMEZONE, timezone.name().to_string())
+                    .await?;
+            }
+            None => {
+                if let Some(entry) = self.find_by_key(SettingsEntry::TIMEZONE).await? {
+                    self.repository.delete(entry.id).await?;
+                }
+            }
+        }
+        self.local_time.set_timezone(timezone);
+        Ok(())
+    }
+
+    /// Cap on seconds granted per user and day with '+' adjustments; None removes it
+    pub async fn set_daily_grant_cap(&self, seconds: Option<i64>) -> Result<(), ServiceError> {
+        match seconds {
+            Some(seconds) if seconds <= 0 => Err(ServiceError::ValidationError(
+                "max_daily_grant_seconds must be positive".to_string(),
+            )),
+            Some(seconds) => {
+                self.set_value(SettingsEntry::MAX_DAILY_GRANT_SECONDS, seconds.to_string())
+                    .await
+            }
+            None => {
+                if let Some(entry) = self
+                    .find_by_key(SettingsEntry::MAX_DAILY_GRANT_SECONDS)
+                    .await?
+                {
+                    self.repository.delete(entry.id).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn set_revalidate_after_modify(&self, enabled: bool) -> Result<(), ServiceError> {
+        self.set_value(SettingsEntry::REVALIDATE_AFTER_MODIFY, enabled.to_string())
+            .await
+    }
+
+    /// Store the default schedule, or remove it when `hours` is None
+    pub async fn set_default_schedule(
+        &self,
+        hours: Option<WeeklyHours>,
+    ) -> Result<(), ServiceError> {
+        match hours {
+            Some(hours) => {
+                hours.validate().map_err(ServiceError::ValidationError)?;
+                let value = serde_json::to_string(&hours)
+                    .map_err(|e| ServiceError::InternalError(e.to_string()))?;
+                self.set_value(SettingsEntry::DEFAULT_SCHEDULE, value).await
+            }
+            None => {
+                if let Some(entry) = self.find_by_key(SettingsEntry::DEFAULT_SCHEDULE).await? {
+                    self.repository.delete(entry.id).await?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Hours applied to newly added users, if a default schedule is configured.
+/// A free function so `UserService` can read it from the repository directly.
+pub async fn get_default_schedule(
+    repository: &dyn SettingsRepository,
+) -> Result<Option<WeeklyHours>, ServiceError> {
+    match repository
+        .find_by_key(SettingsEntry::DEFAULT_SCHEDULE)
+        .await?
+    {
+        Some(entry) => serde_json::from_str(&entry.value)
+            .map(Some)
+            .map_err(|e| ServiceError::InternalError(format!("Invalid default schedule: {}", e))),
+        None => Ok(None),
+    }
+}
+
+/// Time between scheduler passes: the configured `check_interval`, or the default when it
+/// is unset, unreadable or out of range
+pub async fn get_tick_interval(repository: &dyn SettingsRepository) -> Duration {
+    let seconds = repository
+        .find_by_key(SettingsEntry::CHECK_INTERVAL)
+        .await
+        .ok()
+        .flatten()
+        .and_then(|entry| entry.value.parse::<i32>().ok());
+    match seconds {
+        Some(seconds) if (MIN_CHECK_INTERVAL..=MAX_CHECK_INTERVAL).contains(&seconds) => {
+            Duration::from_secs(seconds as u64)
+        }
+        _ => TICK_INTERVAL,
+    }
+}
+
+/// Daily grant cap in seconds, if one is configured
+pub async fn get_daily_grant_cap(
+    repository: &dyn SettingsRepository,
+) -> Result<Option<i64>, ServiceError> {
+    Ok(repository
+        .find_by_key(SettingsEntry::MAX_DAILY_GRANT_SECONDS)
+        .await?
+        .and_then(|entry| entry.value.parse().ok()))
+}
+
+/// Whether a successful time adjustment is followed by a fresh read of the host's config
+/// (on by default)
+pub async fn get_revalidate_after_modify(
+    repository: &dyn SettingsRepository,
+) -> Result<bool, ServiceError> {
+    Ok(repository
+        .find_by_key(SettingsEntry::REVALIDATE_AFTER_MODIFY)
+        .await?
+        .map(|entry| entry.value != "false")
+        .unwrap_or(true))
 }
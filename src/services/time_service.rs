@@ -1,13 +1,47 @@
-use crate::models::{ManagedUser, ServiceError, TimeModification};
-use crate::repositories::{UsageRepository, UserRepository};
-use crate::ssh::SSHClient;
-use chrono::Utc;
-use serde_json;
+use crate::models::{
+    format_hours_minutes, format_timestamp, usage_day, DayOfWeek, EventKind, ManagedUser,
+    ServiceError, TimeModification, TimeModificationLogEntry, TimekprConfig, UsageBucket,
+    UsageData as DailyUsage, UsageGranularity, UsageUnit, UserCounts, UserStatus, WeekStart,
+};
+use crate::repositories::{SettingsRepository, UsageRepository, UserRepository};
+use crate::services::event_log::EventLog;
+use crate::services::local_time::LocalTime;
+use crate::services::schedule_service::daily_reset_hour;
+use crate::services::settings_service::{get_daily_grant_cap, get_revalidate_after_modify};
+use crate::services::usage_service::usage_sample_retention_days;
+use crate::services::user_locks::UserLocks;
+use crate::ssh::{SshBackend, SystemSsh};
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, Utc};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
+pub const DEFAULT_USAGE_DAYS: i32 = 7;
+/// Longest range the usage endpoint returns. The repository allows `MAX_USAGE_DAYS`, a day
+/// more, so twelve monthly buckets always fit.
+pub const MAX_REQUESTED_USAGE_DAYS: i32 = 365;
+
+/// Entries the modification history returns unless asked for another number
+pub const DEFAULT_MODIFICATION_HISTORY: i64 = 20;
+/// Most entries one modification history request returns
+pub const MAX_MODIFICATION_HISTORY: i64 = 100;
+
+/// Share of the daily limit from which a user counts as close to it in the household stats
+pub const NEAR_LIMIT_FRACTION: f64 = 0.8;
+
 pub struct TimeService {
     user_repository: Arc<dyn UserRepository>,
     usage_repository: Arc<dyn UsageRepository>,
+    max_usage_days: i32,
+    ssh: Arc<dyn SshBackend>,
+    /// Source of the daily grant cap and `revalidate_after_modify`; without it grants are
+    /// tracked but not limited and every adjustment is followed by a validation
+    settings_repository: Option<Arc<dyn SettingsRepository>>,
+    /// Usage and grants before this hour still count towards the previous day
+    reset_hour: u32,
+    local_time: Arc<LocalTime>,
+    /// Shared with the scheduler so adjustments for one user never run concurrently
+    user_locks: Arc<UserLocks>,
+    event_log: Arc<EventLog>,
 }
 
 impl TimeService {
@@ -15,12 +49,58 @@ impl TimeService {
         user_repository: Arc<dyn UserRepository>,
         usage_repository: Arc<dyn UsageRepository>,
     ) -> Self {
+        // Optional override of the usage range cap, never above the endpoint's limit
+        let max_usage_days = std::env::var("USAGE_MAX_DAYS")
+            .ok()
+            .and_then(|value| value.parse::<i32>().ok())
+            .filter(|days| *days > 0)
+            .map_or(MAX_REQUESTED_USAGE_DAYS, |days| {
+                days.min(MAX_REQUESTED_USAGE_DAYS)
+            });
+
         Self {
             user_repository,
             usage_repository,
+            max_usage_days,
+            ssh: Arc::new(SystemSsh::default()),
+            settings_repository: None,
+            reset_hour: daily_reset_hour(),
+            local_time: Arc::new(LocalTime::new()),
+            user_locks: Arc::new(UserLocks::new()),
+            event_log: Arc::new(EventLog::new()),
+        }
+    }
+
+    /// Enforce the configured daily grant cap on adjustments that add time and follow the
+    /// `revalidate_after_modify` setting
+    pub fn with_grant_cap(self, settings_repository: Arc<dyn SettingsRepository>) -> Self {
+        Self {
+            settings_repository: Some(settings_repository),
+            ..self
         }
     }
 
+    /// Reach hosts through the given backend instead of the system `ssh` binary
+    pub fn with_ssh_backend(mut self, ssh: Arc<dyn SshBackend>) -> Self {
+        self.ssh = ssh;
+        self
+    }
+
+    /// Serialize adjustments with whoever else holds these locks (the scheduler)
+    pub fn with_user_locks(self, user_locks: Arc<UserLocks>) -> Self {
+        Self { user_locks, ..self }
+    }
+
+    /// Take usage days and grant days from the given local time, shared with the other services
+    pub fn with_local_time(self, local_time: Arc<LocalTime>) -> Self {
+        Self { local_time, ..self }
+    }
+
+    /// Record applied and queued adjustments in the given log
+    pub fn with_event_log(self, event_log: Arc<EventLog>) -> Self {
+        Self { event_log, ..self }
+    }
+
     pub async fn modify_time(
         &self,
         modification: TimeModification,
@@ -32,106 +112,692 @@ impl TimeService {
             .await?
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
-        // Try to apply the time modification via SSH
-        let ssh_client = SSHClient::new(&user.system_ip);
-        let (success, message) = ssh_client
-            .modify_time_left(
-                &user.username,
-                &modification.operation,
-                modification.seconds,
-            )
-            .await;
+        // Held until the adjustment is applied or queued, so the scheduler can't apply
+        // a pending adjustment for this user in between
+        let _lock = self.user_locks.lock(user.group_id()).await;
+
+        // Grants count per host group and local day; removing time is never limited.
+        // Setting time left counts for whatever it adds on top of the last known time
+        // left, or in full when that isn't known.
+        let grant = match modification.operation.as_str() {
+            "+" => modification.seconds,
+            "=" => match self.known_time_left(&user).await? {
+                Some((time_left, _)) => (modification.seconds - time_left).max(0),
+                None => modification.seconds,
+            },
+            _ => 0,
+        };
+
+        // Applied or queued, this adjustment replaces the one queued for the group, so
+        // what that one counted is given back
+        let hosts = self.user_repository.find_group(user.group_id()).await?;
+        let replaced = match hosts.iter().find(|host| host.id == user.group_id()) {
+            Some(primary) => self.queued_grant(primary).await?,
+            None => 0,
+        };
+        let today = self.usage_today();
+        let mut counted = grant;
+        if grant > 0 || replaced > 0 {
+            let granted = self
+                .usage_repository
+                .get_granted_seconds(user.group_id(), today)
+                .await?;
+            // Queued on an earlier day, it counted towards that day's grants instead
+            let refund = replaced.min(granted);
+            counted = grant - refund;
+            if grant > 0 {
+                if let Some(cap) = self.daily_grant_cap().await? {
+                    let remaining = (cap - granted + refund).max(0);
+                    if grant > remaining {
+                        return Err(ServiceError::ValidationError(format!(
+                            "Daily grant cap of {}s reached for {}: {}s can still be granted today",
+                            cap, user.username, remaining
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Try to apply the time modification via SSH, on whichever host of the group answers
+        let mut message = String::new();
+        let mut applied_host = None;
+        for host in hosts {
+            let (success, host_message) = self
+                .ssh
+                .modify_time_left(&host, &modification.operation, modification.seconds)
+                .await;
+            message = host_message;
+            if success {
+                applied_host = Some(host);
+                break;
+            }
+        }
 
-        if success {
-            // Command succeeded, update user info and clear pending adjustments
-            let ssh_client = SSHClient::new(&user.system_ip);
-            let (is_valid, _, config) = ssh_client.validate_user(&user.username).await;
+        if let Some(host) = applied_host {
+            if self.revalidate_after_modify().await? {
+                // Command succeeded, update host info
+                let (status, _, config) = self.ssh.validate_user(&host).await;
 
-            if is_valid {
-                let config_json = config.map(|c| c.to_string());
-                let updated_user = ManagedUser {
+                if status == UserStatus::Ok {
+                    let config_json = config.map(|c| c.to_json());
+                    let updated_host = ManagedUser {
+                        last_checked: Some(Utc::now()),
+                        last_config: config_json,
+                        pending_time_adjustment: None,
+                        pending_time_operation: None,
+                        pending_time_reason: None,
+                        last_status: Some(status.as_str().to_string()),
+                        consecutive_failures: 0,
+                        ..host
+                    };
+                    self.user_repository.save(&updated_host).await?;
+                }
+            } else {
+                // The stored config stays as it was until the scheduler's next pass
+                let updated_host = ManagedUser {
                     last_checked: Some(Utc::now()),
-                    last_config: config_json,
-                    pending_time_adjustment: None,
-                    pending_time_operation: None,
-                    ..user.clone()
+                    ..host
                 };
-                self.user_repository.save(&updated_user).await?;
+                self.user_repository.save(&updated_host).await?;
+            }
+            // Whatever was queued for the group is superseded by the adjustment just applied,
+            // also when the host couldn't be read back or is another one of the group
+            self.user_repository
+                .clear_pending_time_adjustment(user.group_id())
+                .await?;
+            self.count_grant(&user, today, counted).await?;
+            for (user_id, warning) in self.ssh.take_warnings() {
+                self.user_repository
+                    .store_command_warning(user_id, &warning, Utc::now())
+                    .await?;
             }
+            self.log_modification(&user, &modification, true).await?;
 
             println!(
-                "Applied time adjustment: {}{}s for user {} - {}",
-                modification.operation, modification.seconds, user.username, message
+                "Applied time adjustment: {}{}s for user {} ({}) - {}",
+                modification.operation,
+                modification.seconds,
+                user.username,
+                modification.reason.as_deref().unwrap_or("no reason given"),
+                message
             );
+            self.push_time_modified(&user, &modification, false);
 
             Ok(TimeModificationResult {
                 success: true,
                 message,
                 username: user.username,
                 pending: false,
+                reason: modification.reason,
             })
         } else {
-            // Command failed, store as pending adjustment
+            // Command failed, store as pending adjustment on the primary user
             self.user_repository
                 .update_pending_time_adjustment(
-                    modification.user_id,
+                    user.group_id(),
                     &modification.operation,
                     modification.seconds,
+                    modification.reason.as_deref(),
                 )
                 .await?;
+            self.count_grant(&user, today, counted).await?;
+            self.log_modification(&user, &modification, false).await?;
 
             println!(
-                "Queued time adjustment: {}{}s for user {} - SSH failed: {}",
-                modification.operation, modification.seconds, user.username, message
+                "Queued time adjustment: {}{}s for user {} ({}) - SSH failed: {}",
+                modification.operation,
+                modification.seconds,
+                user.username,
+                modification.reason.as_deref().unwrap_or("no reason given"),
+                message
             );
+            self.push_time_modified(&user, &modification, true);
 
             Ok(TimeModificationResult {
                 success: true,
-                message: format!("Computer seems to be offline. Time adjustment of {}{}s has been queued and will be applied when the computer comes online.", 
-                    modification.operation, modification.seconds),
+                message: if modification.operation == "=" {
+                    format!("Computer seems to be offline. Setting time left to {}s has been queued and will be applied when the computer comes online.",
+                        modification.seconds)
+                } else {
+                    format!("Computer seems to be offline. Time adjustment of {}{}s has been queued and will be applied when the computer comes online.", 
+                        modification.operation, modification.seconds)
+                },
                 username: user.username,
                 pending: true,
+                reason: modification.reason,
             })
         }
     }
 
-    pub async fn get_user_usage(&self, user_id: i64) -> Result<UsageData, ServiceError> {
+    /// Set the user's time left today to exactly `seconds`. Goes through the same path as
+    /// `modify_time`, so an offline host gets it queued; what it adds counts as a grant.
+    pub async fn set_time_limit(
+        &self,
+        user_id: i64,
+        seconds: i64,
+    ) -> Result<TimeModificationResult, ServiceError> {
+        let modification =
+            TimeModification::absolute(user_id, seconds).map_err(ServiceError::ValidationError)?;
+        self.modify_time(modification).await
+    }
+
+    /// Seconds the adjustment queued on the group's primary counted as granted
+    async fn queued_grant(&self, primary: &ManagedUser) -> Result<i64, ServiceError> {
+        Ok(
+            match (
+                primary.pending_time_adjustment,
+                primary.pending_time_operation.as_deref(),
+            ) {
+                (Some(seconds), Some("+")) => seconds,
+                (Some(seconds), Some("=")) => match self.known_time_left(primary).await? {
+                    Some((time_left, _)) => (seconds - time_left).max(0),
+                    None => seconds,
+                },
+                _ => 0,
+            },
+        )
+    }
+
+    /// Add `seconds` (negative when a replaced adjustment counted more) to today's grants
+    async fn count_grant(
+        &self,
+        user: &ManagedUser,
+        today: NaiveDate,
+        seconds: i64,
+    ) -> Result<(), ServiceError> {
+        if seconds != 0 {
+            self.usage_repository
+                .add_granted_seconds(user.group_id(), today, seconds)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn log_modification(
+        &self,
+        user: &ManagedUser,
+        modification: &TimeModification,
+        applied: bool,
+    ) -> Result<(), ServiceError> {
+        self.usage_repository
+            .log_modification(
+                user.group_id(),
+                &modification.operation,
+                modification.seconds,
+                modification.reason.as_deref(),
+                applied,
+                Utc::now(),
+            )
+            .await
+    }
+
+    /// The user's last time modifications, newest first. `limit` defaults to
+    /// `DEFAULT_MODIFICATION_HISTORY` and is kept within 1..=`MAX_MODIFICATION_HISTORY`.
+    pub async fn modification_history(
+        &self,
+        user_id: i64,
+        limit: Option<i64>,
+    ) -> Result<(ManagedUser, Vec<TimeModificationLogEntry>), ServiceError> {
         let user = self
             .user_repository
             .find_by_id(user_id)
             .await?
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
-        // Get usage data for the last 7 days efficiently in one query
-        let usage_pairs = self.usage_repository.get_usage_data(user_id, 7).await?;
+        let limit = limit
+            .unwrap_or(DEFAULT_MODIFICATION_HISTORY)
+            .clamp(1, MAX_MODIFICATION_HISTORY);
+        let entries = self
+            .usage_repository
+            .get_modification_log(user.group_id(), limit)
+            .await?
+            .into_iter()
+            .map(
+                |(recorded_at, operation, seconds, reason, applied)| TimeModificationLogEntry {
+                    recorded_at: format_timestamp(recorded_at),
+                    operation,
+                    seconds,
+                    reason,
+                    applied,
+                },
+            )
+            .collect();
+        Ok((user, entries))
+    }
+
+    fn push_time_modified(
+        &self,
+        user: &ManagedUser,
+        modification: &TimeModification,
+        pending: bool,
+    ) {
+        self.event_log.push(EventKind::TimeModified {
+            user_id: user.id,
+            username: user.username.clone(),
+            operation: modification.operation.clone(),
+            seconds: modification.seconds,
+            reason: modification.reason.clone(),
+            pending,
+        });
+    }
+
+    /// Read today's usage from the host and store it, without touching the user's status,
+    /// schedule or pending adjustments. Nothing is stored when the host doesn't answer.
+    pub async fn snapshot_usage(&self, user_id: i64) -> Result<UsageSnapshot, ServiceError> {
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let (status, message, config) = self.ssh.validate_user(&user).await;
+        let time_spent = config.and_then(|config| config.time_spent_day);
+        let date = self.usage_today();
+
+        if let (UserStatus::Ok, Some(time_spent)) = (status, time_spent) {
+            self.usage_repository
+                .store_daily_usage(user.id, date, time_spent)
+                .await?;
+        }
+
+        Ok(UsageSnapshot {
+            username: user.username,
+            date: date.to_string(),
+            time_spent: time_spent.filter(|_| status == UserStatus::Ok),
+            status,
+            message,
+        })
+    }
 
-        let usage_data = usage_pairs
+    /// Time left today from the group's last stored config, with when it was read
+    async fn known_time_left(
+        &self,
+        user: &ManagedUser,
+    ) -> Result<Option<(i64, Option<DateTime<Utc>>)>, ServiceError> {
+        // Hosts come most recently checked first; use the first one with a readable config
+        Ok(self
+            .user_repository
+            .find_group(user.group_id())
+            .await?
             .into_iter()
-            .map(|(date, time_spent)| {
-                serde_json::json!({
-                    "date": date.to_string(),
-                    "hours": (time_spent as f64) / 3600.0
-                })
+            .find_map(|host| {
+                let config = host.last_config.as_deref()?.parse::<TimekprConfig>().ok()?;
+                Some((config.time_left_day?, host.last_checked))
+            }))
+    }
+
+    /// Time left today if the modification were applied now, from the last stored config.
+    /// An estimate only: the host is not contacted and may have moved on since.
+    pub async fn preview_adjustment(
+        &self,
+        modification: &TimeModification,
+    ) -> Result<AdjustmentPreview, ServiceError> {
+        let user = self
+            .user_repository
+            .find_by_id(modification.user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let (current, as_of) = match self.known_time_left(&user).await? {
+            Some((time_left, checked)) => (Some(time_left), checked),
+            None => (None, None),
+        };
+        let projected = current.map(|time_left| match modification.operation.as_str() {
+            "+" => time_left + modification.seconds,
+            "=" => modification.seconds,
+            _ => (time_left - modification.seconds).max(0),
+        });
+
+        Ok(AdjustmentPreview {
+            username: user.username,
+            operation: modification.operation.clone(),
+            seconds: modification.seconds,
+            current_time_left: current,
+            projected_time_left: projected,
+            current_formatted: current.map(format_hours_minutes),
+            projected_formatted: projected.map(format_hours_minutes),
+            as_of: as_of.map(format_timestamp),
+        })
+    }
+
+    /// Usage day that has not been reset yet
+    fn usage_today(&self) -> NaiveDate {
+        usage_day(self.local_time.now(), self.reset_hour)
+    }
+
+    async fn daily_grant_cap(&self) -> Result<Option<i64>, ServiceError> {
+        match &self.settings_repository {
+            Some(repository) => get_daily_grant_cap(repository.as_ref()).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn revalidate_after_modify(&self) -> Result<bool, ServiceError> {
+        match &self.settings_repository {
+            Some(repository) => get_revalidate_after_modify(repository.as_ref()).await,
+            None => Ok(true),
+        }
+    }
+
+    pub async fn get_user_usage(
+        &self,
+        user_id: i64,
+        days: Option<i32>,
+        unit: UsageUnit,
+    ) -> Result<UsageData, ServiceError> {
+        // Clamp the range before touching the database: at least today, at most the cap
+        let days = days
+            .unwrap_or(DEFAULT_USAGE_DAYS)
+            .clamp(1, self.max_usage_days);
+
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+        // One entry per day of the range, oldest first; days without usage are zero
+        let today = self.usage_today();
+        let usage_by_date = self.group_usage_by_date(&user, today, days).await?;
+        let usage_data = (0..days as i64)
+            .rev()
+            .map(|days_ago| today - Duration::days(days_ago))
+            .map(|date| {
+                let time_spent = usage_by_date.get(&date).copied().unwrap_or(0);
+                DailyUsage {
+                    date: date.to_string(),
+                    hours: UsageUnit::Hours.convert(time_spent),
+                    value: unit.convert(time_spent),
+                }
             })
             .collect();
 
         Ok(UsageData {
             username: user.username,
             usage_data,
+            days,
+            unit,
+        })
+    }
+
+    /// Usage summed per day, ISO week or calendar month over the last `periods` periods,
+    /// the current one included, oldest first; periods without usage are zero
+    pub async fn get_usage_aggregated(
+        &self,
+        user_id: i64,
+        granularity: UsageGranularity,
+        periods: Option<i32>,
+    ) -> Result<AggregatedUsage, ServiceError> {
+        let periods = periods.unwrap_or(granularity.default_periods());
+        if periods <= 0 {
+            return Err(ServiceError::ValidationError(
+                "periods must be a positive number".to_string(),
+            ));
+        }
+        // Keep the range within the same cap as the daily series
+        let periods = periods.min((self.max_usage_days / granularity.max_days()).max(1));
+
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let period_start = |date: NaiveDate| match granularity {
+            UsageGranularity::Daily => date,
+            UsageGranularity::Weekly => {
+                date - Duration::days(date.weekday().num_days_from_monday() as i64)
+            }
+            UsageGranularity::Monthly => date.with_day(1).unwrap_or(date),
+        };
+        let next_start = |start: NaiveDate| match granularity {
+            UsageGranularity::Daily => start + Duration::days(1),
+            UsageGranularity::Weekly => start + Duration::days(7),
+            UsageGranularity::Monthly => start + Months::new(1),
+        };
+
+        let today = self.usage_today();
+        let mut starts = vec![period_start(today)];
+        for _ in 1..periods {
+            let previous = match granularity {
+                UsageGranularity::Daily => starts[0] - Duration::days(1),
+                UsageGranularity::Weekly => starts[0] - Duration::days(7),
+                UsageGranularity::Monthly => starts[0] - Months::new(1),
+            };
+            starts.insert(0, previous);
+        }
+
+        let days = ((today - starts[0]).num_days() + 1) as i32;
+        let mut seconds_by_start = BTreeMap::new();
+        for (date, time_spent) in self.group_usage_by_date(&user, today, days).await? {
+            *seconds_by_start.entry(period_start(date)).or_insert(0) += time_spent;
+        }
+
+        let buckets = starts
+            .into_iter()
+            .map(|start| {
+                let period = match granularity {
+                    UsageGranularity::Daily => start.to_string(),
+                    UsageGranularity::Weekly => {
+                        let week = start.iso_week();
+                        format!("{}-W{:02}", week.year(), week.week())
+                    }
+                    UsageGranularity::Monthly => start.format("%Y-%m").to_string(),
+                };
+                UsageBucket {
+                    period,
+                    start: start.to_string(),
+                    end: (next_start(start) - Duration::days(1)).to_string(),
+                    hours: UsageUnit::Hours
+                        .convert(seconds_by_start.get(&start).copied().unwrap_or(0)),
+                }
+            })
+            .collect();
+
+        Ok(AggregatedUsage {
+            username: user.username,
+            granularity,
+            buckets,
+        })
+    }
+
+    /// Each host of a group records its own usage; the per-day sum over the last `days` days
+    /// up to the local usage day `today`
+    async fn group_usage_by_date(
+        &self,
+        user: &ManagedUser,
+        today: NaiveDate,
+        days: i32,
+    ) -> Result<BTreeMap<NaiveDate, i64>, ServiceError> {
+        let mut usage_by_date = BTreeMap::new();
+        for host in self.user_repository.find_group(user.group_id()).await? {
+            for (date, time_spent) in self
+                .usage_repository
+                .get_usage_data(host.id, today, days)
+                .await?
+            {
+                *usage_by_date.entry(date).or_insert(0) += time_spent;
+            }
+        }
+        Ok(usage_by_date)
+    }
+
+    /// Intraday usage samples for one local usage day across the user's hosts, oldest first.
+    /// Empty unless sampling was enabled with `USAGE_SAMPLE_RETENTION_DAYS`.
+    pub async fn get_intraday_usage(
+        &self,
+        user_id: i64,
+        date: Option<&str>,
+    ) -> Result<IntradayUsage, ServiceError> {
+        let date = match date {
+            Some(date) => NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| {
+                ServiceError::ValidationError("date must be formatted as YYYY-MM-DD".to_string())
+            })?,
+            None => self.usage_today(),
+        };
+
+        let user = self
+            .user_repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        // The usage day runs from one reset to the next
+        let reset = NaiveTime::from_hms_opt(self.reset_hour, 0, 0).unwrap_or(NaiveTime::MIN);
+        let from = self.local_time.to_utc(date.and_time(reset));
+        let to = self
+            .local_time
+            .to_utc((date + Duration::days(1)).and_time(reset));
+
+        let mut samples = Vec::new();
+        for host in self.user_repository.find_group(user.group_id()).await? {
+            for (sampled_at, time_spent) in self
+                .usage_repository
+                .get_usage_samples(host.id, from, to)
+                .await?
+            {
+                samples.push((sampled_at, host.id, time_spent));
+            }
+        }
+        samples.sort_by_key(|(sampled_at, host_id, _)| (*sampled_at, *host_id));
+
+        Ok(IntradayUsage {
+            username: user.username,
+            date: date.to_string(),
+            enabled: usage_sample_retention_days().is_some(),
+            samples: samples
+                .into_iter()
+                .map(|(sampled_at, host_id, time_spent)| UsageSample {
+                    timestamp: format_timestamp(sampled_at),
+                    host_id,
+                    time_spent,
+                })
+                .collect(),
+        })
+    }
+
+    /// Household totals for today and the current week, which starts on `week_start`
+    pub async fn household_stats(
+        &self,
+        week_start: WeekStart,
+    ) -> Result<HouseholdStats, ServiceError> {
+        let today = self.usage_today();
+        let first_day = DayOfWeek::week(week_start)[0];
+        let days_into_week =
+            (DayOfWeek::from_date(today).to_timekpr_index() + 7 - first_day.to_timekpr_index()) % 7;
+        let week_from = today - Duration::days(days_into_week as i64);
+
+        let users = self.user_repository.count_users().await?;
+        // Busiest day first, so the first entry is the answer
+        let week = self
+            .usage_repository
+            .get_household_usage(week_from, today)
+            .await?;
+        let time_spent_today = week
+            .iter()
+            .find(|(date, _)| *date == today)
+            .map_or(0, |(_, seconds)| *seconds);
+        let users_near_limit = self
+            .usage_repository
+            .count_users_near_limit(today, NEAR_LIMIT_FRACTION)
+            .await?;
+
+        Ok(HouseholdStats {
+            users,
+            time_spent_today,
+            time_spent_week: week.iter().map(|(_, seconds)| seconds).sum(),
+            week_start: week_start.as_str().to_string(),
+            busiest_day: week.first().map(|(date, seconds)| BusiestDay {
+                date: date.to_string(),
+                time_spent: *seconds,
+                formatted: format_hours_minutes(*seconds),
+            }),
+            users_near_limit,
+            near_limit_percent: (NEAR_LIMIT_FRACTION * 100.0) as u8,
         })
     }
 }
 
+#[derive(serde::Serialize)]
+pub struct HouseholdStats {
+    pub users: UserCounts,
+    /// Seconds used by all users together on the current local usage day
+    pub time_spent_today: i64,
+    pub time_spent_week: i64,
+    pub week_start: String,
+    /// Day of the current week with the most combined usage; None before any usage is stored
+    pub busiest_day: Option<BusiestDay>,
+    pub users_near_limit: i64,
+    pub near_limit_percent: u8,
+}
+
+#[derive(serde::Serialize)]
+pub struct BusiestDay {
+    pub date: String,
+    pub time_spent: i64,
+    pub formatted: String,
+}
+
 #[derive(serde::Serialize)]
 pub struct TimeModificationResult {
     pub success: bool,
     pub message: String,
     pub username: String,
     pub pending: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct AdjustmentPreview {
+    pub username: String,
+    pub operation: String,
+    pub seconds: i64,
+    /// Last known TIME_LEFT_DAY in seconds; None when the user has no stored config or no limit
+    pub current_time_left: Option<i64>,
+    pub projected_time_left: Option<i64>,
+    pub current_formatted: Option<String>,
+    pub projected_formatted: Option<String>,
+    /// When the config the estimate is based on was read
+    pub as_of: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct UsageSnapshot {
+    pub username: String,
+    pub date: String,
+    /// Seconds used today as reported by the host; None when it could not be read
+    pub time_spent: Option<i64>,
+    pub status: UserStatus,
+    pub message: String,
 }
 
 #[derive(serde::Serialize)]
 pub struct UsageData {
     pub username: String,
-    pub usage_data: Vec<serde_json::Value>,
+    pub usage_data: Vec<DailyUsage>,
+    pub days: i32,
+    pub unit: UsageUnit,
+}
+
+#[derive(serde::Serialize)]
+pub struct AggregatedUsage {
+    pub username: String,
+    pub granularity: UsageGranularity,
+    pub buckets: Vec<UsageBucket>,
+}
+
+#[derive(serde::Serialize)]
+pub struct UsageSample {
+    pub timestamp: String,
+    pub host_id: i64,
+    /// TIME_SPENT_DAY on that host when the sample was taken, in seconds
+    pub time_spent: i64,
+}
+
+#[derive(serde::Serialize)]
+pub struct IntradayUsage {
+    pub username: String,
+    pub date: String,
+    /// Whether sampling is currently switched on
+    pub enabled: bool,
+    pub samples: Vec<UsageSample>,
 }
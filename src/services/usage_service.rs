@@ -1,15 +1,58 @@
-use crate::models::ServiceError;
+use crate::models::{usage_day, ServiceError};
 use crate::repositories::UsageRepository;
-use chrono::Utc;
+use crate::services::local_time::LocalTime;
+use crate::services::schedule_service::daily_reset_hour;
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
 
+/// Days intraday usage samples are kept, from `USAGE_SAMPLE_RETENTION_DAYS`.
+/// None (the default) disables sampling.
+pub fn usage_sample_retention_days() -> Option<i64> {
+    std::env::var("USAGE_SAMPLE_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse::<i64>().ok())
+        .filter(|days| *days > 0)
+}
+
 pub struct UsageService {
     repository: Arc<dyn UsageRepository>,
+    sample_retention_days: Option<i64>,
+    /// Usage before this hour still counts towards the previous day
+    reset_hour: u32,
+    local_time: Arc<LocalTime>,
 }
 
 impl UsageService {
     pub fn new(repository: Arc<dyn UsageRepository>) -> Self {
-        Self { repository }
+        Self {
+            repository,
+            sample_retention_days: usage_sample_retention_days(),
+            reset_hour: daily_reset_hour(),
+            local_time: Arc::new(LocalTime::new()),
+        }
+    }
+
+    /// Take usage days from the given local time, shared with the other services
+    pub fn with_local_time(self, local_time: Arc<LocalTime>) -> Self {
+        Self { local_time, ..self }
+    }
+
+    /// Start usage days at the given hour instead of `DAILY_RESET_HOUR`
+    #[allow(dead_code)]
+    pub fn with_reset_hour(self, reset_hour: u32) -> Self {
+        Self {
+            reset_hour: reset_hour % 24,
+            ..self
+        }
+    }
+
+    /// Keep intraday samples for the given number of days; None disables sampling
+    #[allow(dead_code)]
+    pub fn with_sample_retention(self, days: Option<i64>) -> Self {
+        Self {
+            sample_retention_days: days.filter(|days| *days > 0),
+            ..self
+        }
     }
 
     pub async fn store_daily_usage(
@@ -17,9 +60,42 @@ impl UsageService {
         user_id: i64,
         time_spent: i64,
     ) -> Result<(), ServiceError> {
-        let today = Utc::now().date_naive();
+        self.store_daily_usage_at(user_id, Utc::now(), time_spent)
+            .await
+    }
+
+    /// Store TIME_SPENT_DAY read at `at` under the usage day it belongs to
+    pub async fn store_daily_usage_at(
+        &self,
+        user_id: i64,
+        at: DateTime<Utc>,
+        time_spent: i64,
+    ) -> Result<(), ServiceError> {
+        let date = usage_day(self.local_time.at(at), self.reset_hour);
+        self.repository
+            .store_daily_usage(user_id, date, time_spent)
+            .await
+    }
+
+    /// Record the current TIME_SPENT_DAY as an intraday sample, when sampling is enabled
+    pub async fn record_sample(&self, user_id: i64, time_spent: i64) -> Result<(), ServiceError> {
+        if self.sample_retention_days.is_none() {
+            return Ok(());
+        }
         self.repository
-            .store_daily_usage(user_id, today, time_spent)
+            .store_usage_sample(user_id, Utc::now(), time_spent)
             .await
     }
+
+    /// Drop samples older than the retention period; a no-op when sampling is disabled
+    pub async fn prune_samples(&self) -> Result<u64, ServiceError> {
+        match self.sample_retention_days {
+            Some(days) => {
+                self.repository
+                    .prune_usage_samples(Utc::now() - Duration::days(days))
+                    .await
+            }
+            None => Ok(0),
+        }
+    }
 }
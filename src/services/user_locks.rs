@@ -0,0 +1,28 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// One lock per user, held by request handlers and the background scheduler while they
+/// change a user's time or pending adjustment, so those changes never interleave.
+/// Keyed by the primary user id of a host group, where pending adjustments are stored.
+#[derive(Default)]
+pub struct UserLocks {
+    locks: Mutex<HashMap<i64, Arc<AsyncMutex<()>>>>,
+}
+
+impl UserLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait for the user's lock; it is released when the guard is dropped
+    pub async fn lock(&self, user_id: i64) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap();
+            // Forget locks nobody holds or waits for, so removed users don't pile up
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            Arc::clone(locks.entry(user_id).or_default())
+        };
+        lock.lock_owned().await
+    }
+}
@@ -1,24 +1,111 @@
-use crate::models::{AdminUserData, ManagedUser, ServiceError, UserData};
-use crate::repositories::UserRepository;
+use crate::models::Schedule;
+use crate::models::{
+    check_system_ip, format_hours_minutes, format_timestamp, AdminUserData, BulkDeleteResult,
+    DashboardStatus, EventKind, ManagedUser, ServiceError, TimekprConfig, UnreachableOnAdd,
+    UserData, UserStatus, ValidationResult,
+};
+use crate::repositories::{ScheduleRepository, SettingsRepository, UserRepository};
+use crate::scheduler::TICK_INTERVAL;
+use crate::services::event_log::EventLog;
+use crate::services::settings_service::{get_default_schedule, get_tick_interval};
+use crate::services::usage_service::usage_sample_retention_days;
 use crate::ssh::SSHClient;
-use chrono::Utc;
-use serde_json;
+use chrono::{DateTime, Duration, Utc};
 use std::sync::Arc;
 
+/// Checks kept per host: a day of background passes `tick` apart
+pub fn validation_history_limit(tick: std::time::Duration) -> i64 {
+    (24 * 60 * 60 / tick.as_secs().max(1)) as i64
+}
+
+/// How far back `validation_history` looks
+pub const VALIDATION_HISTORY_WINDOW_HOURS: i64 = 24;
+
 pub struct UserService {
     repository: Arc<dyn UserRepository>,
+    default_schedule: Option<(Arc<dyn ScheduleRepository>, Arc<dyn SettingsRepository>)>,
+    /// Source of the check interval that sizes the validation history; without it the
+    /// default interval is assumed
+    settings_repository: Option<Arc<dyn SettingsRepository>>,
+    /// Consecutive failed background checks before a host is shown as offline
+    offline_after_failures: i64,
+    /// Days check results are kept besides the per-host cap; same setting as the usage samples
+    history_retention_days: Option<i64>,
+    event_log: Arc<EventLog>,
 }
 
 impl UserService {
     pub fn new(repository: Arc<dyn UserRepository>) -> Self {
-        Self { repository }
+        let offline_after_failures = std::env::var("OFFLINE_AFTER_FAILURES")
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .filter(|failures| *failures > 0)
+            .unwrap_or(1);
+
+        Self {
+            repository,
+            default_schedule: None,
+            settings_repository: None,
+            offline_after_failures,
+            history_retention_days: usage_sample_retention_days(),
+            event_log: Arc::new(EventLog::new()),
+        }
+    }
+
+    /// Require this many failed background checks in a row before showing a host offline
+    #[allow(dead_code)]
+    pub fn with_offline_after_failures(self, failures: i64) -> Self {
+        Self {
+            offline_after_failures: failures.max(1),
+            ..self
+        }
+    }
+
+    /// Drop check results older than the given number of days; None keeps them up to the cap
+    #[allow(dead_code)]
+    pub fn with_history_retention(self, days: Option<i64>) -> Self {
+        Self {
+            history_retention_days: days.filter(|days| *days > 0),
+            ..self
+        }
+    }
+
+    /// Keep a day of validation history at the check interval stored in these settings
+    pub fn with_settings(self, settings_repository: Arc<dyn SettingsRepository>) -> Self {
+        Self {
+            settings_repository: Some(settings_repository),
+            ..self
+        }
+    }
+
+    /// Record added users and hosts going offline in the given log
+    pub fn with_event_log(self, event_log: Arc<EventLog>) -> Self {
+        Self { event_log, ..self }
     }
 
+    /// Apply the `default_schedule` setting, when configured, to users created by `add_user`
+    pub fn with_default_schedule(
+        self,
+        schedule_repository: Arc<dyn ScheduleRepository>,
+        settings_repository: Arc<dyn SettingsRepository>,
+    ) -> Self {
+        Self {
+            default_schedule: Some((schedule_repository, settings_repository)),
+            ..self
+        }
+    }
+
+    /// Add a user, returning the message for the admin and the outcome of the validation
+    /// when one was run
     pub async fn add_user(
         &self,
         username: String,
         system_ip: String,
-    ) -> Result<String, ServiceError> {
+        validate: bool,
+        on_unreachable: UnreachableOnAdd,
+    ) -> Result<(i64, String, Option<UserStatus>), ServiceError> {
+        check_system_ip(&system_ip).map_err(ServiceError::ValidationError)?;
+
         // Business logic: Check if user already exists
         let existing_users = self.repository.find_all().await?;
         for user in &existing_users {
@@ -30,48 +117,263 @@ impl UserService {
             }
         }
 
-        // Validate user with SSH and timekpr
-        let ssh_client = SSHClient::new(&system_ip);
-        let (is_valid, message, config) = ssh_client.validate_user(&username).await;
-
-        let config_json = config.map(|c| c.to_string());
-
-        // Create new user
-        let new_user = ManagedUser {
+        let mut new_user = ManagedUser {
             id: 0, // Will be set by database
             username: username.clone(),
             system_ip: system_ip.clone(),
-            is_valid,
+            is_valid: false,
             date_added: Some(Utc::now()),
-            last_checked: Some(Utc::now()),
-            last_config: config_json,
+            last_checked: None,
+            last_config: None,
             pending_time_adjustment: None,
             pending_time_operation: None,
+            pending_time_reason: None,
+            last_status: None,
+            consecutive_failures: 0,
+            host_group_id: None,
+            timekpr_path: None,
+            validate_command_template: None,
+            modify_command_template: None,
         };
 
-        self.repository.save(&new_user).await?;
+        if !validate {
+            // Never checked; the scheduler picks it up on its next pass
+            let user_id = self.repository.save(&new_user).await?;
+            self.apply_default_schedule(user_id, &username).await?;
+            self.push_user_added(&new_user);
+            println!(
+                "Added user without validation: {} on {}",
+                username, system_ip
+            );
+            return Ok((
+                user_id,
+                format!(
+                    "User {} added; it will be validated on the next background check",
+                    username
+                ),
+                None,
+            ));
+        }
+
+        // Validate user with SSH and timekpr
+        let ssh_client = SSHClient::new(&system_ip);
+        let (status, message, config) = ssh_client.validate_user(&username).await;
+        let is_valid = status == UserStatus::Ok;
+
+        if !status.is_reachable() {
+            match on_unreachable {
+                UnreachableOnAdd::Invalid => {}
+                UnreachableOnAdd::Pending => {
+                    // Left unchecked, so the scheduler keeps trying it
+                    let user_id = self.repository.save(&new_user).await?;
+                    self.apply_default_schedule(user_id, &username).await?;
+                    self.push_user_added(&new_user);
+                    println!(
+                        "Added user: {} on {} as pending, host unreachable: {}",
+                        username, system_ip, message
+                    );
+                    return Ok((
+                        user_id,
+                        format!(
+                            "Host {} is unreachable; user {} added and will be validated on the next background check",
+                            system_ip, username
+                        ),
+                        Some(status),
+                    ));
+                }
+                UnreachableOnAdd::Reject => {
+                    return Err(ServiceError::ValidationError(format!(
+                        "Host {} is unreachable; user {} was not added: {}",
+                        system_ip, username, message
+                    )));
+                }
+            }
+        }
+
+        new_user.is_valid = is_valid;
+        new_user.last_checked = Some(Utc::now());
+        new_user.last_config = config.map(|c| c.to_json());
+        new_user.last_status = Some(status.as_str().to_string());
+        new_user.consecutive_failures = if is_valid { 0 } else { 1 };
+
+        let user_id = self.repository.save(&new_user).await?;
+        self.apply_default_schedule(user_id, &username).await?;
+        self.push_user_added(&new_user);
 
         if is_valid {
             println!(
                 "Added and validated user: {} on {} - {}",
                 username, system_ip, message
             );
-            Ok(format!(
-                "User {} added and validated successfully",
-                username
+            Ok((
+                user_id,
+                format!("User {} added and validated successfully", username),
+                Some(status),
             ))
         } else {
             println!(
                 "Added user: {} on {} but validation failed: {}",
                 username, system_ip, message
             );
-            Ok(format!(
-                "User {} added but validation failed: {}",
-                username, message
+            Ok((
+                user_id,
+                format!("User {} added but validation failed: {}", username, message),
+                Some(status),
             ))
         }
     }
 
+    fn push_user_added(&self, user: &ManagedUser) {
+        self.event_log.push(EventKind::UserAdded {
+            username: user.username.clone(),
+            system_ip: user.system_ip.clone(),
+            is_valid: user.is_valid,
+        });
+    }
+
+    /// Give a freshly added user the configured default schedule. It is saved unsynced,
+    /// so the scheduler pushes it once the host is reachable.
+    async fn apply_default_schedule(
+        &self,
+        user_id: i64,
+        username: &str,
+    ) -> Result<(), ServiceError> {
+        let Some((schedule_repository, settings_repository)) = &self.default_schedule else {
+            return Ok(());
+        };
+        let Some(hours) = get_default_schedule(settings_repository.as_ref()).await? else {
+            return Ok(());
+        };
+
+        let schedule = Schedule::new(user_id, hours).map_err(ServiceError::ValidationError)?;
+        schedule_repository.save(&schedule).await?;
+
+        println!("Applied default schedule to user {}", username);
+        Ok(())
+    }
+
+    /// Attach another host endpoint to an existing user, e.g. a laptop that roams between networks.
+    ///
+    /// The hosts of a group are merged as follows:
+    /// - each host records its own usage; usage is reported summed per day across the group
+    /// - the schedule and pending adjustments live on the primary user and are applied
+    ///   to whichever host is reachable, trying the most recently seen one first
+    /// - the dashboard shows only the primary, with the time left reported by the
+    ///   most recently checked host
+    pub async fn attach_host(
+        &self,
+        user_id: i64,
+        system_ip: String,
+    ) -> Result<String, ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+        let primary_id = user.group_id();
+        check_system_ip(&system_ip).map_err(ServiceError::ValidationError)?;
+
+        let existing_users = self.repository.find_all().await?;
+        if existing_users
+            .iter()
+            .any(|existing| existing.username == user.username && existing.system_ip == system_ip)
+        {
+            return Err(ServiceError::ValidationError(format!(
+                "User {} on {} already exists",
+                user.username, system_ip
+            )));
+        }
+
+        let ssh_client = SSHClient::new(&system_ip);
+        let (status, message, config) = ssh_client.validate_user(&user.username).await;
+
+        let host = ManagedUser {
+            id: 0,
+            username: user.username.clone(),
+            system_ip: system_ip.clone(),
+            is_valid: status == UserStatus::Ok,
+            date_added: Some(Utc::now()),
+            last_checked: Some(Utc::now()),
+            last_config: config.map(|c| c.to_json()),
+            pending_time_adjustment: None,
+            pending_time_operation: None,
+            pending_time_reason: None,
+            last_status: Some(status.as_str().to_string()),
+            consecutive_failures: if status == UserStatus::Ok { 0 } else { 1 },
+            host_group_id: Some(primary_id),
+            timekpr_path: None,
+            validate_command_template: None,
+            modify_command_template: None,
+        };
+
+        self.repository.save(&host).await?;
+
+        println!(
+            "Attached host {} to user {} (group {}): {}",
+            system_ip, user.username, primary_id, message
+        );
+        Ok(format!(
+            "Host {} attached to user {}: {}",
+            system_ip, user.username, message
+        ))
+    }
+
+    /// Set where timekpra lives on this user's host; None runs plain `timekpra`
+    pub async fn set_timekpr_path(
+        &self,
+        user_id: i64,
+        timekpr_path: Option<String>,
+    ) -> Result<ManagedUser, ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let updated_user = ManagedUser {
+            timekpr_path,
+            ..user
+        };
+        self.repository.save(&updated_user).await?;
+
+        Ok(updated_user)
+    }
+
+    /// Set the commands run instead of the built-in validate and modify commands on this
+    /// user's host; None goes back to the built-in one
+    pub async fn set_command_templates(
+        &self,
+        user_id: i64,
+        validate_command_template: Option<String>,
+        modify_command_template: Option<String>,
+    ) -> Result<ManagedUser, ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let updated_user = ManagedUser {
+            validate_command_template,
+            modify_command_template,
+            ..user
+        };
+        self.repository.save(&updated_user).await?;
+
+        Ok(updated_user)
+    }
+
+    /// Hosts of the group `user_id` belongs to, most recently checked first
+    pub async fn find_group(&self, user_id: i64) -> Result<Vec<ManagedUser>, ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        self.repository.find_group(user.group_id()).await
+    }
+
     pub async fn validate_user(&self, user_id: i64) -> Result<String, ServiceError> {
         let user = self
             .repository
@@ -80,19 +382,33 @@ impl UserService {
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
         // Validate with SSH and timekpr
-        let ssh_client = SSHClient::new(&user.system_ip);
-        let (is_valid, message, config) = ssh_client.validate_user(&user.username).await;
+        let ssh_client = SSHClient::for_user(&user);
+        let (status, message, config) = ssh_client.validate_user(&user.username).await;
+        let is_valid = status == UserStatus::Ok;
 
-        let config_json = config.map(|c| c.to_string());
+        let config_json = config.map(|c| c.to_json());
 
+        let consecutive_failures = if is_valid {
+            0
+        } else {
+            user.consecutive_failures + 1
+        };
         let updated_user = ManagedUser {
             is_valid,
             last_checked: Some(Utc::now()),
             last_config: config_json,
+            last_status: Some(status.as_str().to_string()),
+            consecutive_failures,
             ..user
         };
 
         self.repository.save(&updated_user).await?;
+        self.record_validation(updated_user.id, status, &message)
+            .await?;
+        if let Some(warning) = ssh_client.take_warning() {
+            self.record_command_warning(updated_user.id, &warning)
+                .await?;
+        }
 
         if is_valid {
             println!("Validated user: {} - {}", updated_user.username, message);
@@ -106,6 +422,116 @@ impl UserService {
         }
     }
 
+    /// Change the user's username or address in place, keeping its id and everything
+    /// recorded under it, then validate it again against the changed host
+    pub async fn update_user(
+        &self,
+        user_id: i64,
+        username: String,
+        system_ip: String,
+    ) -> Result<String, ServiceError> {
+        check_system_ip(&system_ip).map_err(ServiceError::ValidationError)?;
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        // Every host of a group runs the same account, so a new username applies to all
+        // of them; only the edited row moves to the new address
+        let renamed_hosts: Vec<ManagedUser> = if username != user.username {
+            self.repository
+                .find_group(user.group_id())
+                .await?
+                .into_iter()
+                .filter(|host| host.id != user_id)
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let existing_users = self.repository.find_all().await?;
+        let moved = |id: i64| id == user_id || renamed_hosts.iter().any(|host| host.id == id);
+        for target_ip in
+            std::iter::once(&system_ip).chain(renamed_hosts.iter().map(|host| &host.system_ip))
+        {
+            if existing_users.iter().any(|other| {
+                !moved(other.id) && other.username == username && other.system_ip == *target_ip
+            }) {
+                return Err(ServiceError::ValidationError(format!(
+                    "User {} on {} already exists",
+                    username, target_ip
+                )));
+            }
+        }
+
+        println!(
+            "Updating user {}: {} on {} -> {} on {}",
+            user_id, user.username, user.system_ip, username, system_ip
+        );
+        let updated_user = ManagedUser {
+            username,
+            system_ip,
+            ..user
+        };
+        self.repository.save(&updated_user).await?;
+        for host in renamed_hosts {
+            println!(
+                "Renaming {} on {} to {} with its host group",
+                host.username, host.system_ip, updated_user.username
+            );
+            let host_id = host.id;
+            self.repository
+                .save(&ManagedUser {
+                    username: updated_user.username.clone(),
+                    ..host
+                })
+                .await?;
+            self.validate_user(host_id).await?;
+        }
+
+        let message = self.validate_user(user_id).await?;
+        Ok(format!(
+            "User {} updated. {}",
+            updated_user.username, message
+        ))
+    }
+
+    /// Queued time adjustments that deleting `user_id` would drop, one line each
+    pub async fn pending_adjustments_on_delete(
+        &self,
+        user_id: i64,
+    ) -> Result<Vec<String>, ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        // Same hosts delete_user removes
+        let hosts = if user.host_group_id.is_none() {
+            self.repository.find_group(user_id).await?
+        } else {
+            vec![user]
+        };
+
+        Ok(hosts
+            .into_iter()
+            .filter_map(
+                |host| match (host.pending_time_adjustment, &host.pending_time_operation) {
+                    (Some(adjustment), Some(operation)) => Some(format!(
+                        "{}{} minutes for {} on {}",
+                        operation,
+                        adjustment / 60,
+                        host.username,
+                        host.system_ip
+                    )),
+                    _ => None,
+                },
+            )
+            .collect())
+    }
+
     pub async fn delete_user(&self, user_id: i64) -> Result<String, ServiceError> {
         let user = self
             .repository
@@ -114,38 +540,99 @@ impl UserService {
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
         let username = user.username.clone();
+        // Deleting a primary user removes its additional hosts as well
+        if user.host_group_id.is_none() {
+            for host in self.repository.find_group(user_id).await? {
+                if host.id != user_id {
+                    self.repository.delete(host.id).await?;
+                }
+            }
+        }
         self.repository.delete(user_id).await?;
 
         println!("Deleted user with id: {}", user_id);
         Ok(format!("User {} deleted successfully", username))
     }
 
+    /// Delete several users atomically; ids that do not exist are reported, not treated as errors
+    pub async fn bulk_delete_users(
+        &self,
+        user_ids: &[i64],
+    ) -> Result<Vec<BulkDeleteResult>, ServiceError> {
+        if user_ids.is_empty() {
+            return Err(ServiceError::ValidationError(
+                "user_ids must not be empty".to_string(),
+            ));
+        }
+
+        let mut unique_ids = user_ids.to_vec();
+        unique_ids.sort_unstable();
+        unique_ids.dedup();
+
+        let deleted = self.repository.delete_many(&unique_ids).await?;
+
+        Ok(unique_ids
+            .into_iter()
+            .map(|user_id| BulkDeleteResult {
+                user_id,
+                status: if deleted.contains(&user_id) {
+                    "deleted".to_string()
+                } else {
+                    "not_found".to_string()
+                },
+            })
+            .collect())
+    }
+
     pub async fn get_dashboard_users(&self) -> Result<Vec<UserData>, ServiceError> {
         let users = self.repository.find_all_valid().await?;
+        let all_users = self.repository.find_all().await?;
         let mut user_data = Vec::new();
 
-        for user in users {
-            let time_left_formatted = if let Some(config_str) = &user.last_config {
-                // Parse the JSON config to get actual time left
-                if let Ok(config) = serde_json::from_str::<serde_json::Value>(config_str) {
-                    if let Some(time_left) = config.get("TIME_LEFT_DAY").and_then(|v| v.as_i64()) {
-                        let hours = time_left / 3600;
-                        let minutes = (time_left % 3600) / 60;
-                        format!("{}h {}m", hours, minutes)
-                    } else {
-                        "No limit set".to_string()
-                    }
-                } else {
-                    "Unknown".to_string()
-                }
+        // Additional hosts are folded into their primary user
+        for user in users
+            .into_iter()
+            .filter(|user| user.host_group_id.is_none())
+        {
+            let freshest_config = all_users
+                .iter()
+                .filter(|host| host.group_id() == user.id && host.last_config.is_some())
+                .max_by_key(|host| host.last_checked)
+                .and_then(|host| host.last_config.clone());
+
+            // Online if any host answered; otherwise whatever the most recent check found
+            let hosts: Vec<&ManagedUser> = all_users
+                .iter()
+                .filter(|host| host.group_id() == user.id)
+                .collect();
+            let status = if hosts
+                .iter()
+                .any(|host| DashboardStatus::of(host) == DashboardStatus::Online)
+            {
+                DashboardStatus::Online
             } else {
-                "Unknown".to_string()
+                hosts
+                    .iter()
+                    .filter(|host| host.last_checked.is_some())
+                    .max_by_key(|host| host.last_checked)
+                    .map_or(DashboardStatus::NeverChecked, |host| {
+                        DashboardStatus::of(host)
+                    })
+            };
+
+            // Parse the JSON config to get actual time left
+            let config = freshest_config
+                .as_deref()
+                .and_then(|config_str| config_str.parse::<TimekprConfig>().ok());
+            let time_left_formatted = match &config {
+                Some(config) => match config.time_left_day {
+                    Some(time_left) => format_hours_minutes(time_left),
+                    None => "No limit set".to_string(),
+                },
+                None => "Unknown".to_string(),
             };
 
-            let last_checked_str = user
-                .last_checked
-                .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-                .unwrap_or_else(|| "Never".to_string());
+            let last_checked_str = user.last_checked.map(format_timestamp);
 
             let pending_adjustment = if let (Some(adjustment), Some(operation)) =
                 (&user.pending_time_adjustment, &user.pending_time_operation)
@@ -160,7 +647,7 @@ impl UserService {
 
             println!(
                 "User {}: time_left_formatted = '{}', config = {:?}",
-                user.username, time_left_formatted, user.last_config
+                user.username, time_left_formatted, freshest_config
             );
 
             user_data.push(UserData {
@@ -171,6 +658,19 @@ impl UserService {
                 last_checked: last_checked_str,
                 pending_adjustment,
                 pending_schedule,
+                time_left_week: config.as_ref().and_then(|c| c.time_left_week),
+                time_spent_week: config.as_ref().and_then(|c| c.time_spent_week),
+                time_limit_week: config.as_ref().and_then(|c| c.time_limit_week),
+                time_left_month: config.as_ref().and_then(|c| c.time_left_month),
+                time_spent_month: config.as_ref().and_then(|c| c.time_spent_month),
+                time_limit_month: config.as_ref().and_then(|c| c.time_limit_month),
+                // Filled in by the caller, which has access to the schedule service
+                effective_limit_today: None,
+                seconds_until_reset: 0,
+                status,
+                // Filled in by the caller as well
+                needs_attention: false,
+                stale: false,
             });
         }
 
@@ -182,17 +682,24 @@ impl UserService {
         let user_data = users
             .into_iter()
             .map(|user| {
-                let last_checked_str = user
-                    .last_checked
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-                    .unwrap_or_else(|| "Never".to_string());
+                let last_checked_str = user.last_checked.map(format_timestamp);
+                let date_added_str = user.date_added.map(format_timestamp);
+                let status = user
+                    .last_status
+                    .as_deref()
+                    .and_then(|status| status.parse::<UserStatus>().ok());
 
                 AdminUserData {
                     id: user.id,
                     username: user.username,
                     system_ip: user.system_ip,
                     is_valid: user.is_valid,
+                    date_added: date_added_str,
                     last_checked: last_checked_str,
+                    status,
+                    status_label: status.map_or("Unknown", UserStatus::label).to_string(),
+                    host_group_id: user.host_group_id,
+                    consecutive_failures: user.consecutive_failures,
                 }
             })
             .collect();
@@ -205,17 +712,24 @@ impl UserService {
         let user_data = users
             .into_iter()
             .map(|user| {
-                let last_checked_str = user
-                    .last_checked
-                    .map(|dt| dt.format("%Y-%m-%d %H:%M").to_string())
-                    .unwrap_or_else(|| "Never".to_string());
+                let last_checked_str = user.last_checked.map(format_timestamp);
+                let date_added_str = user.date_added.map(format_timestamp);
+                let status = user
+                    .last_status
+                    .as_deref()
+                    .and_then(|status| status.parse::<UserStatus>().ok());
 
                 AdminUserData {
                     id: user.id,
                     username: user.username,
                     system_ip: user.system_ip,
                     is_valid: user.is_valid,
+                    date_added: date_added_str,
                     last_checked: last_checked_str,
+                    status,
+                    status_label: status.map_or("Unknown", UserStatus::label).to_string(),
+                    host_group_id: user.host_group_id,
+                    consecutive_failures: user.consecutive_failures,
                 }
             })
             .collect();
@@ -223,6 +737,11 @@ impl UserService {
         Ok(user_data)
     }
 
+    /// Every host row, additional hosts included, as needed to run commands on them
+    pub async fn find_all(&self) -> Result<Vec<ManagedUser>, ServiceError> {
+        self.repository.find_all().await
+    }
+
     pub async fn get_users_pending(&self) -> Result<Vec<ManagedUser>, ServiceError> {
         self.repository.find_all_pending().await
     }
@@ -231,6 +750,106 @@ impl UserService {
         self.repository.find_by_id(user_id).await
     }
 
+    /// Add the outcome of a check to the host's validation history
+    pub async fn record_validation(
+        &self,
+        user_id: i64,
+        status: UserStatus,
+        message: &str,
+    ) -> Result<(), ServiceError> {
+        let error = (status != UserStatus::Ok).then_some(message);
+        let tick = match &self.settings_repository {
+            Some(settings_repository) => get_tick_interval(settings_repository.as_ref()).await,
+            None => TICK_INTERVAL,
+        };
+        self.repository
+            .store_validation_result(
+                user_id,
+                Utc::now(),
+                status.as_str(),
+                error,
+                validation_history_limit(tick),
+            )
+            .await
+    }
+
+    /// Keep the latest warning a successful command printed for this host, replacing the last
+    pub async fn record_command_warning(
+        &self,
+        user_id: i64,
+        warning: &str,
+    ) -> Result<(), ServiceError> {
+        self.repository
+            .store_command_warning(user_id, warning, Utc::now())
+            .await
+    }
+
+    /// Store the warnings collected by an SSH backend; a host removed meanwhile is skipped
+    pub async fn record_command_warnings(&self, warnings: Vec<(i64, String)>) {
+        for (user_id, warning) in warnings {
+            if let Err(e) = self.record_command_warning(user_id, &warning).await {
+                eprintln!(
+                    "Failed to record command warning for user {}: {}",
+                    user_id, e
+                );
+            }
+        }
+    }
+
+    /// The latest command warning of the user's host, with when it was recorded
+    pub async fn command_warning(
+        &self,
+        user_id: i64,
+    ) -> Result<Option<(String, DateTime<Utc>)>, ServiceError> {
+        self.repository.get_command_warning(user_id).await
+    }
+
+    /// Checks of the user's hosts over the last day, newest first
+    pub async fn validation_history(
+        &self,
+        user_id: i64,
+    ) -> Result<(ManagedUser, Vec<ValidationResult>), ServiceError> {
+        let user = self
+            .repository
+            .find_by_id(user_id)
+            .await?
+            .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
+
+        let since = Utc::now() - Duration::hours(VALIDATION_HISTORY_WINDOW_HOURS);
+        let mut results = Vec::new();
+        for host in self.repository.find_group(user.group_id()).await? {
+            for (checked_at, status, error) in self
+                .repository
+                .get_validation_results(host.id, since)
+                .await?
+            {
+                results.push((checked_at, host.id, status, error));
+            }
+        }
+        results
+            .sort_by_key(|(checked_at, host_id, _, _)| std::cmp::Reverse((*checked_at, *host_id)));
+
+        let results = results
+            .into_iter()
+            .map(|(checked_at, host_id, status, error)| {
+                ValidationResult::new(format_timestamp(checked_at), host_id, status, error)
+            })
+            .collect();
+        Ok((user, results))
+    }
+
+    /// Drop check results older than the retention period; a no-op when none is configured
+    pub async fn prune_validation_history(&self) -> Result<u64, ServiceError> {
+        match self.history_retention_days {
+            Some(days) => {
+                self.repository
+                    .prune_validation_results(Utc::now() - Duration::days(days))
+                    .await
+            }
+            None => Ok(0),
+        }
+    }
+
     // Background scheduler methods - don't change is_valid status
     pub async fn update_background_data(
         &self,
@@ -243,28 +862,65 @@ impl UserService {
             .await?
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
+        // Also completes validation of users added without it
         let updated_user = ManagedUser {
+            is_valid: true,
             last_checked: Some(Utc::now()),
             last_config: config,
+            last_status: Some(UserStatus::Ok.as_str().to_string()),
+            consecutive_failures: 0,
             ..user
         };
 
-        self.repository.save(&updated_user).await
+        self.repository.save(&updated_user).await?;
+        Ok(())
     }
 
-    pub async fn update_last_checked(&self, user_id: i64) -> Result<(), ServiceError> {
+    pub async fn update_last_checked(
+        &self,
+        user_id: i64,
+        status: UserStatus,
+    ) -> Result<(), ServiceError> {
         let user = self
             .repository
             .find_by_id(user_id)
             .await?
             .ok_or_else(|| ServiceError::NotFound("User not found".to_string()))?;
 
+        let consecutive_failures = if status == UserStatus::Ok {
+            0
+        } else {
+            user.consecutive_failures + 1
+        };
+        // Within the grace period a host keeps its last known status, so a single
+        // dropped connection does not flip it to offline
+        let last_status = if status == UserStatus::Offline
+            && consecutive_failures < self.offline_after_failures
+            && user.last_status.is_some()
+        {
+            user.last_status.clone()
+        } else {
+            Some(status.as_str().to_string())
+        };
+
+        let offline = UserStatus::Offline.as_str();
+        if last_status.as_deref() == Some(offline) && user.last_status.as_deref() != Some(offline) {
+            self.event_log.push(EventKind::HostOffline {
+                user_id: user.id,
+                username: user.username.clone(),
+                system_ip: user.system_ip.clone(),
+            });
+        }
+
         let updated_user = ManagedUser {
             last_checked: Some(Utc::now()),
+            last_status,
+            consecutive_failures,
             ..user
         };
 
-        self.repository.save(&updated_user).await
+        self.repository.save(&updated_user).await?;
+        Ok(())
     }
 
     pub async fn clear_pending_adjustements(&self, user_id: i64) -> Result<(), ServiceError> {
@@ -277,10 +933,12 @@ impl UserService {
         let updated_user = ManagedUser {
             pending_time_adjustment: None,
             pending_time_operation: None,
+            pending_time_reason: None,
             last_checked: Some(Utc::now()),
             ..user
         };
 
-        self.repository.save(&updated_user).await
+        self.repository.save(&updated_user).await?;
+        Ok(())
     }
 }
@@ -1,153 +1,981 @@
-use serde_json::Value;
+use crate::models::{
+    hours_to_seconds, render_command_template, DayOfWeek, DaySyncResult, LockoutAction,
+    LockoutSettings, ManagedUser, PlayTimeSettings, SshKeyCandidate, TimekprConfig, UserStatus,
+};
+use async_trait::async_trait;
+use base64::Engine;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
-use std::process::Command;
+use std::process::Output;
+use std::time::Duration;
+use tokio::process::Command;
+
+use crate::ssh_session::SSH_PORT;
+#[cfg(not(feature = "process-ssh"))]
+use crate::ssh_session::{run_command, run_commands, SessionTarget};
+
+/// Host operations behind the time adjustment flow, so it can run against a stand-in in tests
+#[async_trait]
+pub trait SshBackend: Send + Sync {
+    async fn validate_user(
+        &self,
+        host: &ManagedUser,
+    ) -> (UserStatus, String, Option<TimekprConfig>);
+
+    async fn modify_time_left(
+        &self,
+        host: &ManagedUser,
+        operation: &str,
+        seconds: i64,
+    ) -> (bool, String);
+
+    async fn set_weekly_time_limits(
+        &self,
+        host: &ManagedUser,
+        schedule: &std::collections::HashMap<String, f64>,
+    ) -> (bool, String);
+
+    async fn set_weekly_allowed_hours(
+        &self,
+        host: &ManagedUser,
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
+    ) -> (bool, String);
+
+    async fn set_playtime(&self, host: &ManagedUser, settings: &PlayTimeSettings)
+        -> (bool, String);
+
+    async fn set_lockout(&self, host: &ManagedUser, settings: &LockoutSettings) -> (bool, String);
+
+    /// Warnings printed by commands that succeeded since the last call, by user id
+    fn take_warnings(&self) -> Vec<(i64, String)>;
+}
+
+/// Runs commands through the system `ssh` binary
+#[derive(Default)]
+pub struct SystemSsh {
+    warnings: std::sync::Mutex<Vec<(i64, String)>>,
+}
+
+impl SystemSsh {
+    fn keep_warning(&self, host: &ManagedUser, client: &SSHClient) {
+        if let Some(warning) = client.take_warning() {
+            self.warnings.lock().unwrap().push((host.id, warning));
+        }
+    }
+}
+
+#[async_trait]
+impl SshBackend for SystemSsh {
+    async fn validate_user(
+        &self,
+        host: &ManagedUser,
+    ) -> (UserStatus, String, Option<TimekprConfig>) {
+        let client = SSHClient::for_user(host);
+        let result = client.validate_user(&host.username).await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    async fn modify_time_left(
+        &self,
+        host: &ManagedUser,
+        operation: &str,
+        seconds: i64,
+    ) -> (bool, String) {
+        let client = SSHClient::for_user(host);
+        let result = client
+            .modify_time_left(&host.username, operation, seconds)
+            .await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    async fn set_weekly_time_limits(
+        &self,
+        host: &ManagedUser,
+        schedule: &std::collections::HashMap<String, f64>,
+    ) -> (bool, String) {
+        let client = SSHClient::for_user(host);
+        let result = client
+            .set_weekly_time_limits(&host.username, schedule)
+            .await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    async fn set_weekly_allowed_hours(
+        &self,
+        host: &ManagedUser,
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
+    ) -> (bool, String) {
+        let client = SSHClient::for_user(host);
+        let result = client
+            .set_weekly_allowed_hours(&host.username, intervals)
+            .await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    async fn set_playtime(
+        &self,
+        host: &ManagedUser,
+        settings: &PlayTimeSettings,
+    ) -> (bool, String) {
+        let client = SSHClient::for_user(host);
+        let result = client.set_playtime(&host.username, settings).await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    async fn set_lockout(&self, host: &ManagedUser, settings: &LockoutSettings) -> (bool, String) {
+        let client = SSHClient::for_user(host);
+        let result = client.set_lockout(&host.username, settings).await;
+        self.keep_warning(host, &client);
+        result
+    }
+
+    fn take_warnings(&self) -> Vec<(i64, String)> {
+        std::mem::take(&mut *self.warnings.lock().unwrap())
+    }
+}
+
+/// What a successful command printed on stderr, such as a timekpr deprecation notice.
+/// The host-key notice `ssh` prints itself is left out; None when nothing else remains.
+pub fn command_warning(stderr: &str) -> Option<String> {
+    let warning = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("Warning: Permanently added"))
+        .collect::<Vec<_>>()
+        .join("\n");
+    (!warning.is_empty()).then_some(warning)
+}
+
+/// Warning code reported when the key file mode is broader than 0600
+pub const SSH_KEY_PERMISSIONS_INSECURE: &str = "SSH_KEY_PERMISSIONS_INSECURE";
+
+/// What timekpr commands are run as unless a user has its own path
+pub const DEFAULT_TIMEKPR_PATH: &str = "timekpra";
+
+/// Whether commands run through a login shell (`bash -lc`), switched on with `SSH_LOGIN_SHELL`.
+/// Non-interactive sessions skip the profile, so timekpra may be off their PATH; off by default
+pub fn ssh_login_shell() -> bool {
+    std::env::var("SSH_LOGIN_SHELL")
+        .map(|value| {
+            matches!(
+                value.trim().to_lowercase().as_str(),
+                "1" | "true" | "yes" | "on"
+            )
+        })
+        .unwrap_or(false)
+}
+
+/// Passphrase decrypting the key file, from `SSH_KEY_PASSPHRASE`. Only the russh transport
+/// uses it; `ssh` needs a protected key loaded into ssh-agent.
+pub fn ssh_key_passphrase() -> Option<String> {
+    std::env::var("SSH_KEY_PASSPHRASE")
+        .ok()
+        .filter(|passphrase| !passphrase.is_empty())
+}
+
+/// ssh-agent socket from `SSH_AUTH_SOCK`, when it exists
+pub fn ssh_agent_socket() -> Option<String> {
+    std::env::var("SSH_AUTH_SOCK")
+        .ok()
+        .filter(|socket| Path::new(socket).exists())
+}
+
+/// How long a remote command may run once started, unless `SSH_COMMAND_TIMEOUT` says otherwise
+pub const DEFAULT_SSH_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Limit on a whole remote command, connecting included, from `SSH_COMMAND_TIMEOUT` in seconds.
+/// `ConnectTimeout` only bounds the connection, so a timekpra waiting on a lock would hang forever
+pub fn ssh_command_timeout() -> Duration {
+    std::env::var("SSH_COMMAND_TIMEOUT")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|seconds| *seconds > 0)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SSH_COMMAND_TIMEOUT)
+}
+
+/// Why an `ssh` invocation produced no output to look at
+#[derive(Debug)]
+pub enum SshCommandError {
+    /// `ssh` could not be started
+    Spawn(std::io::Error),
+    /// The host could not be reached or the SSH handshake failed; worth another try
+    Connect(String),
+    /// The SSH session could not be used: key, authentication or channel errors
+    Session(String),
+    /// Connected, or still connecting, when the command timeout ran out; the process was killed
+    CommandTimeout(Duration),
+}
+
+impl std::fmt::Display for SshCommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SshCommandError::Spawn(e) => write!(f, "SSH connection failed: {}", e),
+            SshCommandError::Connect(e) | SshCommandError::Session(e) => {
+                write!(f, "SSH connection failed: {}", e)
+            }
+            SshCommandError::CommandTimeout(limit) => write!(
+                f,
+                "command_timeout: no result within {}s; the remote command may be stuck (e.g. waiting on a lock)",
+                limit.as_secs()
+            ),
+        }
+    }
+}
+
+impl SshCommandError {
+    /// Whether the command never reached the host, so running it again can't apply it twice
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SshCommandError::Connect(_))
+    }
+}
+
+/// How often and how patiently a command is retried when the host can't be reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first included
+    pub max_attempts: u32,
+    /// Wait before the second attempt; doubles before each further one
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Three attempts, 500ms and then 1s apart
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A single attempt
+    #[allow(dead_code)]
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Wait after the given failed attempt (1-based)
+    pub fn delay_after(&self, attempt: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+    }
+}
+
+/// Run `attempt` until it gives a result `is_transient` doesn't flag, or the policy's attempts
+/// are used up, in which case the last result is returned
+pub async fn retry_transient<T, F, Fut>(
+    policy: &RetryPolicy,
+    is_transient: impl Fn(&T) -> bool,
+    mut attempt: F,
+) -> T
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = T>,
+{
+    let mut attempts = 1;
+    loop {
+        let result = attempt().await;
+        if attempts >= policy.max_attempts || !is_transient(&result) {
+            return result;
+        }
+        let delay = policy.delay_after(attempts);
+        eprintln!(
+            "SSH attempt {} of {} could not reach the host; retrying in {}ms",
+            attempts,
+            policy.max_attempts,
+            delay.as_millis()
+        );
+        tokio::time::sleep(delay).await;
+        attempts += 1;
+    }
+}
+
+/// Whether a command's result says the host wasn't reached. `ssh` reports connection
+/// errors as exit code 255 with the reason on stderr rather than as an error of its own.
+pub fn is_transient_output(result: &Result<Output, SshCommandError>) -> bool {
+    match result {
+        Err(e) => e.is_transient(),
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            output.status.code() == Some(255)
+                && [
+                    "Connection refused",
+                    "Connection timed out",
+                    "Connection reset",
+                    "No route to host",
+                    "Could not resolve hostname",
+                ]
+                .iter()
+                .any(|reason| stderr.contains(reason))
+        }
+    }
+}
+
+/// Run a command to completion unless it takes longer than `limit`, in which case the child
+/// is killed
+#[cfg_attr(not(feature = "process-ssh"), allow(dead_code))]
+pub async fn output_within(
+    mut command: Command,
+    limit: Duration,
+) -> Result<Output, SshCommandError> {
+    command.kill_on_drop(true);
+    match tokio::time::timeout(limit, command.output()).await {
+        Ok(output) => output.map_err(SshCommandError::Spawn),
+        // Dropping the output future kills the child
+        Err(_) => Err(SshCommandError::CommandTimeout(limit)),
+    }
+}
 
 pub struct SSHClient {
     hostname: String,
+    timekpr_path: String,
+    login_shell: bool,
+    validate_template: Option<String>,
+    modify_template: Option<String>,
+    command_timeout: Duration,
+    port: u16,
+    /// Key used in place of the first of `ssh_key_candidates` that exists
+    key_path: Option<String>,
+    key_passphrase: Option<String>,
+    agent_socket: Option<String>,
+    /// Retries of commands that couldn't reach the host
+    pub retry_policy: RetryPolicy,
+    /// Latest `command_warning` of a command that succeeded
+    warning: std::sync::Mutex<Option<String>>,
 }
 
 impl SSHClient {
     pub fn new(hostname: &str) -> Self {
         Self {
             hostname: hostname.to_string(),
+            timekpr_path: DEFAULT_TIMEKPR_PATH.to_string(),
+            login_shell: ssh_login_shell(),
+            validate_template: None,
+            modify_template: None,
+            command_timeout: ssh_command_timeout(),
+            port: SSH_PORT,
+            key_path: None,
+            key_passphrase: ssh_key_passphrase(),
+            agent_socket: ssh_agent_socket(),
+            retry_policy: RetryPolicy::default(),
+            warning: std::sync::Mutex::new(None),
         }
     }
 
+    /// Client for a managed user's host, running timekpra from the user's path if one is set
+    /// and the user's command templates in place of the built-in validate and modify commands
+    pub fn for_user(user: &ManagedUser) -> Self {
+        Self::new(&user.system_ip)
+            .with_timekpr_path(user.timekpr_path.as_deref())
+            .with_command_templates(
+                user.validate_command_template.as_deref(),
+                user.modify_command_template.as_deref(),
+            )
+    }
+
+    pub fn with_timekpr_path(mut self, timekpr_path: Option<&str>) -> Self {
+        if let Some(path) = timekpr_path.map(str::trim).filter(|path| !path.is_empty()) {
+            self.timekpr_path = path.to_string();
+        }
+        self
+    }
+
+    pub fn with_command_templates(
+        mut self,
+        validate_template: Option<&str>,
+        modify_template: Option<&str>,
+    ) -> Self {
+        let template = |template: Option<&str>| {
+            template
+                .map(str::trim)
+                .filter(|template| !template.is_empty())
+                .map(str::to_string)
+        };
+        self.validate_template = template(validate_template);
+        self.modify_template = template(modify_template);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_command_timeout(mut self, command_timeout: Duration) -> Self {
+        self.command_timeout = command_timeout;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = port;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_key_path(mut self, key_path: &str) -> Self {
+        self.key_path = Some(key_path.to_string());
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_key_passphrase(mut self, passphrase: Option<&str>) -> Self {
+        self.key_passphrase = passphrase.map(str::to_string);
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_agent_socket(mut self, socket: Option<&str>) -> Self {
+        self.agent_socket = socket.map(str::to_string);
+        self
+    }
+
+    /// The key this client authenticates with
+    fn ssh_key_path(&self) -> Option<String> {
+        self.key_path.clone().or_else(Self::find_ssh_key_path)
+    }
+
+    /// A key file or an agent to authenticate with; an agent alone is enough
+    fn has_credentials(&self) -> bool {
+        self.agent_socket.is_some() || self.ssh_key_path().is_some()
+    }
+
+    #[allow(dead_code)]
+    pub fn with_login_shell(mut self, login_shell: bool) -> Self {
+        self.login_shell = login_shell;
+        self
+    }
+
+    /// The command as sent to the host: `timekpra` replaced by the configured path,
+    /// and wrapped in `bash -lc` when login shells are enabled
+    pub fn remote_command(&self, command: &str) -> String {
+        let command = match command.strip_prefix("timekpra ") {
+            Some(arguments) => format!("{} {}", self.timekpr_path, arguments),
+            None => command.to_string(),
+        };
+        if self.login_shell {
+            format!("bash -lc '{}'", command.replace('\'', "'\\''"))
+        } else {
+            command
+        }
+    }
+
+    /// Command reading the user's timekpr state, as sent to the host
+    pub fn validate_command(&self, username: &str) -> String {
+        self.remote_command(&match &self.validate_template {
+            Some(template) => render_command_template(template, &[("username", username)]),
+            None => format!("timekpra --userinfo {}", username),
+        })
+    }
+
+    /// Command adjusting the user's time left today, as sent to the host
+    pub fn modify_command(&self, username: &str, operation: &str, seconds: i64) -> String {
+        self.remote_command(&match &self.modify_template {
+            Some(template) => render_command_template(
+                template,
+                &[
+                    ("username", username),
+                    ("op", operation),
+                    ("seconds", &seconds.to_string()),
+                ],
+            ),
+            None => format!(
+                "timekpra --settimeleft {} {} {}",
+                username, operation, seconds
+            ),
+        })
+    }
+
+    /// Run `command` on the host over a russh session, bounded by `connect_timeout` seconds
+    /// for the connection and the client's command timeout overall
+    #[cfg(not(feature = "process-ssh"))]
+    async fn execute_ssh_command(
+        &self,
+        key_path: Option<&str>,
+        connect_timeout: u32,
+        command: &str,
+    ) -> Result<Output, SshCommandError> {
+        let target = SessionTarget {
+            host: &self.hostname,
+            port: self.port,
+            user: "timekpr-remote",
+            key_path,
+            passphrase: self.key_passphrase.as_deref(),
+            agent_socket: self.agent_socket.as_deref(),
+        };
+        let result = retry_transient(&self.retry_policy, is_transient_output, || {
+            run_command(
+                &target,
+                Duration::from_secs(connect_timeout.into()),
+                self.command_timeout,
+                command,
+            )
+        })
+        .await;
+        if let Err(e @ SshCommandError::CommandTimeout(_)) = &result {
+            eprintln!("SSH command on {} abandoned: {}", self.hostname, e);
+        }
+        result
+    }
+
+    /// Run `command` on the host over `ssh`, bounded by `connect_timeout` seconds for the
+    /// connection and the client's command timeout overall
+    #[cfg(feature = "process-ssh")]
+    async fn execute_ssh_command(
+        &self,
+        key_path: Option<&str>,
+        connect_timeout: u32,
+        command: &str,
+    ) -> Result<Output, SshCommandError> {
+        let result = retry_transient(&self.retry_policy, is_transient_output, || {
+            let mut ssh = Command::new("ssh");
+            ssh.args(["-p", &self.port.to_string()]);
+            // Without a key file, `ssh` falls back to the agent and its default keys
+            if let Some(key_path) = key_path {
+                ssh.args(["-i", key_path]);
+            }
+            ssh.args([
+                "-o",
+                &format!("ConnectTimeout={}", connect_timeout),
+                "-o",
+                "StrictHostKeyChecking=no",
+                "-o",
+                "BatchMode=yes",
+                "-o",
+                "PasswordAuthentication=no",
+                &format!("timekpr-remote@{}", self.hostname),
+                command,
+            ]);
+            output_within(ssh, self.command_timeout)
+        })
+        .await;
+        if let Err(e @ SshCommandError::CommandTimeout(_)) = &result {
+            eprintln!("SSH command on {} killed: {}", self.hostname, e);
+        }
+        result
+    }
+
+    /// Run `commands` over one russh session; a session that can't be set up fails every one
+    #[cfg(not(feature = "process-ssh"))]
+    async fn execute_ssh_batch(
+        &self,
+        key_path: Option<&str>,
+        connect_timeout: u32,
+        commands: &[String],
+    ) -> Vec<Result<Output, SshCommandError>> {
+        let target = SessionTarget {
+            host: &self.hostname,
+            port: self.port,
+            user: "timekpr-remote",
+            key_path,
+            passphrase: self.key_passphrase.as_deref(),
+            agent_socket: self.agent_socket.as_deref(),
+        };
+        // Only a session that couldn't be set up is retried, before any command ran
+        let result = retry_transient(
+            &self.retry_policy,
+            |result: &Result<_, SshCommandError>| {
+                result.as_ref().is_err_and(SshCommandError::is_transient)
+            },
+            || {
+                run_commands(
+                    &target,
+                    Duration::from_secs(connect_timeout.into()),
+                    self.command_timeout,
+                    commands,
+                )
+            },
+        )
+        .await;
+        match result {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                let message = e.to_string();
+                commands
+                    .iter()
+                    .map(|_| Err(SshCommandError::Session(message.clone())))
+                    .collect()
+            }
+        }
+    }
+
+    /// `ssh` runs one command per connection, so the batch is run command by command
+    #[cfg(feature = "process-ssh")]
+    async fn execute_ssh_batch(
+        &self,
+        key_path: Option<&str>,
+        connect_timeout: u32,
+        commands: &[String],
+    ) -> Vec<Result<Output, SshCommandError>> {
+        let mut outputs = Vec::with_capacity(commands.len());
+        for command in commands {
+            outputs.push(
+                self.execute_ssh_command(key_path, connect_timeout, command)
+                    .await,
+            );
+        }
+        outputs
+    }
+
+    /// Run several commands on the host over a single connection, returning the exit code,
+    /// stdout and stderr of each. Commands that could not be run report exit code 255 and
+    /// the reason on stderr, as `ssh` does.
+    pub async fn run_batch(&self, commands: &[String]) -> Vec<(i32, String, String)> {
+        if !self.has_credentials() {
+            return commands
+                .iter()
+                .map(|_| (255, String::new(), "SSH key not found".to_string()))
+                .collect();
+        }
+
+        self.execute_ssh_batch(self.ssh_key_path().as_deref(), 10, commands)
+            .await
+            .into_iter()
+            .map(|output| match output {
+                Ok(output) => (
+                    output.status.code().unwrap_or(255),
+                    String::from_utf8_lossy(&output.stdout).into_owned(),
+                    String::from_utf8_lossy(&output.stderr).into_owned(),
+                ),
+                Err(e) => (255, String::new(), e.to_string()),
+            })
+            .collect()
+    }
+
+    /// Keep what a command that succeeded printed on stderr
+    fn note_warning(&self, stderr: &str) {
+        if let Some(warning) = command_warning(stderr) {
+            println!(
+                "SSH command on {} succeeded with a warning: {}",
+                self.hostname, warning
+            );
+            *self.warning.lock().unwrap() = Some(warning);
+        }
+    }
+
+    /// The latest warning of a successful command run by this client, cleared once taken
+    pub fn take_warning(&self) -> Option<String> {
+        self.warning.lock().unwrap().take()
+    }
+
+    /// Explain that timekpra could not be run, pointing at the PATH settings before a reinstall
+    fn timekpr_missing_message(&self) -> String {
+        format!(
+            "{} was not found on {}. If timekpr is installed, the SSH session's PATH may not include it: set the user's timekpr path or enable SSH_LOGIN_SHELL",
+            self.timekpr_path, self.hostname
+        )
+    }
+
     pub fn check_ssh_key_exists() -> bool {
         Self::find_ssh_key_path().is_some()
     }
 
-    pub fn find_ssh_key_path() -> Option<String> {
-        let basic_paths = [
+    /// Key files tried in order: the app's own key, then the user's default keys
+    pub fn ssh_key_candidates() -> Vec<String> {
+        let mut candidates: Vec<String> = [
             "ssh/timekpr_ui_key",
             "./ssh/timekpr_ui_key",
             "/app/ssh/timekpr_ui_key",
-        ];
+        ]
+        .iter()
+        .map(|path| path.to_string())
+        .collect();
 
-        // Check basic paths first
-        for path in &basic_paths {
-            if Path::new(path).exists() {
-                return Some(path.to_string());
+        if let Some(home) = dirs::home_dir() {
+            for key in [".ssh/id_rsa", ".ssh/id_ed25519"] {
+                candidates.push(home.join(key).to_string_lossy().to_string());
             }
         }
 
-        // Check home directory keys
-        if let Some(home) = dirs::home_dir() {
-            let id_rsa = home.join(".ssh/id_rsa");
-            let id_ed25519 = home.join(".ssh/id_ed25519");
+        candidates
+    }
 
-            if id_rsa.exists() {
-                return Some(id_rsa.to_string_lossy().to_string());
-            }
-            if id_ed25519.exists() {
-                return Some(id_ed25519.to_string_lossy().to_string());
-            }
+    pub fn find_ssh_key_path() -> Option<String> {
+        Self::ssh_key_candidates()
+            .into_iter()
+            .find(|path| Path::new(path).exists())
+    }
+
+    /// Which of `candidates` exist and which one is used (the first that exists)
+    pub fn describe_key_candidates(candidates: &[String]) -> Vec<SshKeyCandidate> {
+        let mut selected = false;
+        candidates
+            .iter()
+            .map(|path| {
+                let exists = Path::new(path).exists();
+                let mode = if exists {
+                    Self::key_file_mode(path)
+                } else {
+                    None
+                };
+                let is_selected = exists && !selected;
+                selected |= exists;
+                SshKeyCandidate {
+                    path: path.clone(),
+                    exists,
+                    selected: is_selected,
+                    mode: mode.map(|mode| format!("{:04o}", mode)),
+                    insecure: mode.map(Self::is_mode_insecure),
+                }
+            })
+            .collect()
+    }
+
+    /// An agent is reachable through SSH_AUTH_SOCK; its keys are tried before the key file
+    pub fn ssh_agent_available() -> bool {
+        ssh_agent_socket().is_some()
+    }
+
+    /// A passphrase-protected key can be used: through the agent, or decrypted with
+    /// SSH_KEY_PASSPHRASE by the russh client
+    #[cfg(not(feature = "process-ssh"))]
+    fn key_unlockable() -> bool {
+        Self::ssh_agent_available() || ssh_key_passphrase().is_some()
+    }
+
+    /// `ssh` runs with BatchMode, so a passphrase-protected key only works through the agent
+    #[cfg(feature = "process-ssh")]
+    fn key_unlockable() -> bool {
+        Self::ssh_agent_available()
+    }
+
+    /// Whether the configured key file needs a passphrase; false when no key is found
+    pub fn ssh_key_encrypted() -> bool {
+        Self::find_ssh_key_path()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+            .map(|contents| Self::is_key_encrypted(&contents))
+            .unwrap_or(false)
+    }
+
+    /// Detect passphrase protection in both legacy PEM and OpenSSH private key formats
+    pub fn is_key_encrypted(contents: &str) -> bool {
+        if contents.contains("ENCRYPTED") {
+            return true;
+        }
+
+        if !contents.contains("BEGIN OPENSSH PRIVATE KEY") {
+            return false;
         }
 
+        // openssh-key-v1 layout: magic, then the cipher name as a length-prefixed string
+        let body: String = contents
+            .lines()
+            .filter(|line| !line.starts_with("-----"))
+            .collect();
+        let Ok(bytes) = base64::engine::general_purpose::STANDARD.decode(body.trim()) else {
+            return false;
+        };
+        let magic = b"openssh-key-v1\0";
+        let Some(rest) = bytes.strip_prefix(magic.as_slice()) else {
+            return false;
+        };
+        if rest.len() < 4 {
+            return false;
+        }
+        let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        rest.get(4..4 + len)
+            .map(|cipher| cipher != b"none")
+            .unwrap_or(false)
+    }
+
+    /// Permission bits of the key file, e.g. 0o600
+    pub fn key_file_mode(path: &str) -> Option<u32> {
+        std::fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions().mode() & 0o777)
+    }
+
+    /// Same rule `ssh` applies: any group or other access makes it ignore the key
+    pub fn is_mode_insecure(mode: u32) -> bool {
+        mode & 0o077 != 0
+    }
+
+    /// Mode of the configured key when it is too open for `ssh` to use
+    pub fn insecure_key_mode() -> Option<u32> {
+        Self::find_ssh_key_path()
+            .and_then(|path| Self::key_file_mode(&path))
+            .filter(|mode| Self::is_mode_insecure(*mode))
+    }
+
+    /// Mode of the configured key when it keeps the key from being used. russh loads a key
+    /// whatever its mode, so only `ssh` refuses one that is too open.
+    #[cfg(not(feature = "process-ssh"))]
+    fn blocking_key_mode() -> Option<u32> {
         None
     }
 
-    pub async fn validate_user(&self, username: &str) -> (bool, String, Option<Value>) {
-        // Find SSH key path
-        let key_path = match Self::find_ssh_key_path() {
-            Some(path) => {
-                println!("Using SSH key: {}", path);
-                path
-            }
-            None => {
-                return (
-                    false,
-                    "SSH key not found. Please configure SSH keys for passwordless authentication."
-                        .to_string(),
-                    None,
-                );
+    #[cfg(feature = "process-ssh")]
+    fn blocking_key_mode() -> Option<u32> {
+        Self::insecure_key_mode()
+    }
+
+    /// Restrict the configured key to 0600, returning its path
+    pub fn fix_key_permissions() -> Result<Option<String>, std::io::Error> {
+        let Some(path) = Self::find_ssh_key_path() else {
+            return Ok(None);
+        };
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+        Ok(Some(path))
+    }
+
+    /// Startup check: a problem that would make every SSH command fail, if any
+    pub fn key_diagnostic() -> Option<String> {
+        let agent = Self::ssh_agent_available();
+        match (Self::find_ssh_key_path(), Self::blocking_key_mode()) {
+            (None, _) if !agent => Some(
+                "No SSH key found and no ssh-agent available (SSH_AUTH_SOCK). SSH commands will fail."
+                    .to_string(),
+            ),
+            (Some(path), Some(mode)) => Some(format!(
+                "{}: SSH key {} has mode {:04o}; ssh ignores keys readable by group or others. \
+                 Run chmod 600 on it or POST /api/ssh/fix-permissions.",
+                SSH_KEY_PERMISSIONS_INSECURE, path, mode
+            )),
+            (Some(path), None) if !Self::key_unlockable() && Self::ssh_key_encrypted() => {
+                Some(format!(
+                    "SSH key {} is passphrase-protected and no ssh-agent is available. {}",
+                    path,
+                    Self::unlock_hint()
+                ))
             }
+            _ => None,
+        }
+    }
+
+    #[cfg(not(feature = "process-ssh"))]
+    fn unlock_hint() -> &'static str {
+        "Load the key into ssh-agent and set SSH_AUTH_SOCK, set SSH_KEY_PASSPHRASE, or use a key without a passphrase."
+    }
+
+    #[cfg(feature = "process-ssh")]
+    fn unlock_hint() -> &'static str {
+        "Load the key into ssh-agent and set SSH_AUTH_SOCK, or use a key without a passphrase."
+    }
+
+    /// Explain a publickey rejection caused by a locked key instead of a generic auth error
+    fn auth_failure_message() -> String {
+        if let Some(mode) = Self::blocking_key_mode() {
+            format!(
+                "SSH key permissions are too open ({:04o}); ssh requires 0600.",
+                mode
+            )
+        } else if Self::ssh_key_encrypted() && !Self::key_unlockable() {
+            format!(
+                "SSH key is passphrase-protected and no ssh-agent is available. {}",
+                Self::unlock_hint()
+            )
+        } else {
+            "SSH key authentication failed. Please ensure SSH keys are properly configured."
+                .to_string()
+        }
+    }
+
+    /// How a command is run, for the log: the `ssh` invocation or the russh session's target
+    #[cfg(feature = "process-ssh")]
+    fn describe_command(
+        &self,
+        key_path: Option<&str>,
+        connect_timeout: u32,
+        command: &str,
+    ) -> String {
+        let key = key_path
+            .map(|path| format!("-i {} ", path))
+            .unwrap_or_default();
+        format!(
+            "ssh -p {} {}-o ConnectTimeout={} -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no timekpr-remote@{} \"{}\"",
+            self.port, key, connect_timeout, self.hostname, command
+        )
+    }
+
+    #[cfg(not(feature = "process-ssh"))]
+    fn describe_command(
+        &self,
+        key_path: Option<&str>,
+        _connect_timeout: u32,
+        command: &str,
+    ) -> String {
+        let auth = match (self.agent_socket.as_deref(), key_path) {
+            (Some(socket), Some(path)) => format!("agent {} then key {}", socket, path),
+            (Some(socket), None) => format!("agent {}", socket),
+            (None, Some(path)) => format!("key {}", path),
+            (None, None) => "no key".to_string(),
         };
+        format!(
+            "timekpr-remote@{}:{} ({}) \"{}\"",
+            self.hostname, self.port, auth, command
+        )
+    }
 
-        // For now, use system SSH command instead of russh library for simplicity
-        let target_host = format!("timekpr-remote@{}", self.hostname);
-        let command = format!("timekpra --userinfo {}", username);
+    pub async fn validate_user(
+        &self,
+        username: &str,
+    ) -> (UserStatus, String, Option<TimekprConfig>) {
+        if !self.has_credentials() {
+            return (
+                UserStatus::Offline,
+                "SSH key not found. Please configure SSH keys for passwordless authentication."
+                    .to_string(),
+                None,
+            );
+        }
+        let key_path = self.ssh_key_path();
+        if let Some(path) = &key_path {
+            println!("Using SSH key: {}", path);
+        }
 
-        println!("Running SSH command: ssh -i {} -o ConnectTimeout=5 -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no {} {}", 
-                 key_path, target_host, command);
+        let command = self.validate_command(username);
 
-        let output = Command::new("ssh")
-            .args(&[
-                "-i",
-                &key_path,
-                "-o",
-                "ConnectTimeout=5",
-                "-o",
-                "StrictHostKeyChecking=no",
-                "-o",
-                "BatchMode=yes",
-                "-o",
-                "PasswordAuthentication=no",
-                &target_host,
-                &command,
-            ])
-            .output();
+        println!(
+            "Running SSH command: {}",
+            self.describe_command(key_path.as_deref(), 5, &command)
+        );
+
+        let output = self
+            .execute_ssh_command(key_path.as_deref(), 5, &command)
+            .await;
 
         match output {
             Ok(result) => {
                 if result.status.success() {
                     let stdout = String::from_utf8_lossy(&result.stdout);
+                    self.note_warning(&String::from_utf8_lossy(&result.stderr));
 
-                    // Parse actual timekpr output into structured data
-                    let mut config = serde_json::json!({
-                        "USERNAME": username,
-                        "raw_output": stdout.trim()
-                    });
-
-                    // Parse timekpr output for time values (use ACTUAL_ values for current state)
-                    let output_lines: Vec<&str> = stdout.lines().collect();
-                    for line in output_lines {
-                        if line.contains("ACTUAL_TIME_LEFT_DAY") {
-                            if let Some(value_str) = line.split(':').nth(1) {
-                                if let Ok(seconds) = value_str.trim().parse::<i64>() {
-                                    config["TIME_LEFT_DAY"] =
-                                        serde_json::Value::Number(seconds.into());
-                                }
-                            }
-                        } else if line.contains("ACTUAL_TIME_SPENT_DAY") {
-                            if let Some(value_str) = line.split(':').nth(1) {
-                                if let Ok(seconds) = value_str.trim().parse::<i64>() {
-                                    config["TIME_SPENT_DAY"] =
-                                        serde_json::Value::Number(seconds.into());
-                                }
-                            }
-                        }
-                        // Add more parsing for other timekpr fields as needed
-                    }
-
-                    // If no time data was parsed, set defaults for testing
-                    if !config.as_object().unwrap().contains_key("TIME_LEFT_DAY") {
-                        config["TIME_LEFT_DAY"] = serde_json::Value::Number(7200.into());
-                        // 2 hours default
-                    }
-                    if !config.as_object().unwrap().contains_key("TIME_SPENT_DAY") {
-                        config["TIME_SPENT_DAY"] = serde_json::Value::Number(1800.into());
-                        // 30 minutes default
-                    }
+                    let config = TimekprConfig::parse(username, &stdout);
 
                     (
-                        true,
+                        UserStatus::Ok,
                         format!("User {} validated successfully", username),
                         Some(config),
                     )
                 } else {
                     let stderr = String::from_utf8_lossy(&result.stderr);
                     println!("SSH validation failed - stderr: {}", stderr);
-                    let error_msg = if stderr.contains("Permission denied")
-                        || stderr.contains("publickey")
-                    {
-                        "SSH key authentication failed. Please ensure SSH keys are properly configured.".to_string()
+                    let status = Self::classify_failure(result.status.code(), &stderr);
+                    let error_msg = if status == UserStatus::TimekprMissing {
+                        self.timekpr_missing_message()
+                    } else if stderr.contains("Permission denied") || stderr.contains("publickey") {
+                        Self::auth_failure_message()
                     } else {
                         format!("Validation failed: {}", stderr.trim())
                     };
-                    (false, error_msg, None)
+                    (status, error_msg, None)
                 }
             }
+            Err(e @ SshCommandError::CommandTimeout(_)) => {
+                (UserStatus::CommandTimeout, e.to_string(), None)
+            }
             Err(e) => {
                 // Check if it's an SSH key issue
                 let error_msg = if e.to_string().contains("Permission denied")
@@ -157,56 +985,60 @@ impl SSHClient {
                     "SSH key authentication failed. Please ensure SSH keys are properly configured."
                         .to_string()
                 } else {
-                    format!("SSH connection failed: {}", e)
+                    e.to_string()
                 };
-                (false, error_msg, None)
+                (UserStatus::Offline, error_msg, None)
             }
         }
     }
 
+    /// Tell a missing timekpr install and an unreachable host apart from a rejected user
+    pub fn classify_failure(exit_code: Option<i32>, stderr: &str) -> UserStatus {
+        // 127 is the shell's "command not found"; ssh itself exits with 255 on connection errors
+        if exit_code == Some(127)
+            || stderr.contains("command not found")
+            || stderr.contains("timekpra: not found")
+        {
+            UserStatus::TimekprMissing
+        } else if exit_code == Some(255)
+            || stderr.contains("Permission denied")
+            || stderr.contains("publickey")
+            || stderr.contains("Connection refused")
+            || stderr.contains("Connection timed out")
+            || stderr.contains("No route to host")
+            || stderr.contains("Could not resolve hostname")
+        {
+            UserStatus::Offline
+        } else {
+            UserStatus::InvalidUser
+        }
+    }
+
     pub async fn modify_time_left(
         &self,
         username: &str,
         operation: &str,
         seconds: i64,
     ) -> (bool, String) {
-        // Find SSH key path
-        let key_path = match Self::find_ssh_key_path() {
-            Some(path) => path,
-            None => {
-                return (
-                    false,
-                    "SSH key not found. Please configure SSH keys for passwordless authentication."
-                        .to_string(),
-                );
-            }
-        };
+        if !self.has_credentials() {
+            return (
+                false,
+                "SSH key not found. Please configure SSH keys for passwordless authentication."
+                    .to_string(),
+            );
+        }
+        let key_path = self.ssh_key_path();
 
-        let target_host = format!("timekpr-remote@{}", self.hostname);
-        let command = format!(
-            "timekpra --settimeleft {} {} {}",
-            username, operation, seconds
-        );
+        let command = self.modify_command(username, operation, seconds);
 
-        println!("Running SSH command: ssh -i {} -o ConnectTimeout=5 -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no {} {}", 
-                 key_path, target_host, command);
+        println!(
+            "Running SSH command: {}",
+            self.describe_command(key_path.as_deref(), 5, &command)
+        );
 
-        let output = Command::new("ssh")
-            .args(&[
-                "-i",
-                &key_path,
-                "-o",
-                "ConnectTimeout=5",
-                "-o",
-                "StrictHostKeyChecking=no",
-                "-o",
-                "BatchMode=yes",
-                "-o",
-                "PasswordAuthentication=no",
-                &target_host,
-                &command,
-            ])
-            .output();
+        let output = self
+            .execute_ssh_command(key_path.as_deref(), 5, &command)
+            .await;
 
         match output {
             Ok(result) => {
@@ -220,6 +1052,7 @@ impl SSHClient {
                 }
 
                 if result.status.success() {
+                    self.note_warning(&stderr);
                     (
                         true,
                         format!(
@@ -227,6 +1060,10 @@ impl SSHClient {
                             operation, seconds, username
                         ),
                     )
+                } else if Self::classify_failure(result.status.code(), &stderr)
+                    == UserStatus::TimekprMissing
+                {
+                    (false, self.timekpr_missing_message())
                 } else {
                     (false, format!("Command failed: {}", stderr.trim()))
                 }
@@ -238,7 +1075,7 @@ impl SSHClient {
                     "SSH key authentication failed. Please ensure SSH keys are properly configured."
                         .to_string()
                 } else {
-                    format!("SSH connection failed: {}", e)
+                    e.to_string()
                 };
                 (false, error_msg)
             }
@@ -248,160 +1085,34 @@ impl SSHClient {
     pub async fn set_weekly_allowed_hours(
         &self,
         username: &str,
-        intervals: &std::collections::HashMap<String, (String, String)>,
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
     ) -> (bool, String) {
-        // Find SSH key path
-        let key_path = match Self::find_ssh_key_path() {
-            Some(path) => path,
-            None => {
-                return (
-                    false,
-                    "SSH key not found. Please configure SSH keys for passwordless authentication."
-                        .to_string(),
-                );
-            }
-        };
-
-        let target_host = format!("timekpr-remote@{}", self.hostname);
-
-        // Days: 1=Monday, 2=Tuesday, ..., 7=Sunday
-        let days = [
-            ("monday", 1),
-            ("tuesday", 2),
-            ("wednesday", 3),
-            ("thursday", 4),
-            ("friday", 5),
-            ("saturday", 6),
-            ("sunday", 7),
-        ];
-
-        let mut success_count = 0;
-        let mut errors = Vec::new();
-
-        for (day_name, day_num) in &days {
-            if let Some((start_time, end_time)) = intervals.get(*day_name) {
-                // Parse time format "HH:MM" to hours
-                if let (Ok(start_hour), Ok(end_hour)) = (
-                    Self::parse_time_to_hour(start_time),
-                    Self::parse_time_to_hour(end_time),
-                ) {
-                    // Create hour range (start inclusive, end exclusive)
-                    // For example: 7:00-17:00 means hours 7,8,9,10,11,12,13,14,15,16 (not including 17)
-                    let mut hours = Vec::new();
-                    let mut current = start_hour;
-                    while current < end_hour {
-                        hours.push(current.to_string());
-                        current += 1;
-                        if current > 23 {
-                            break;
-                        }
-                    }
-
-                    if !hours.is_empty() {
-                        let hours_string = hours.join(";");
-                        let command = format!(
-                            "timekpra --setallowedhours {} {} '{}'",
-                            username, day_num, hours_string
-                        );
-
-                        println!("Running SSH allowed hours command: ssh -i {} -o ConnectTimeout=10 -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no {} \"{}\"",
-                                 key_path, target_host, command);
-
-                        let output = Command::new("ssh")
-                            .args(&[
-                                "-i",
-                                &key_path,
-                                "-o",
-                                "ConnectTimeout=10",
-                                "-o",
-                                "StrictHostKeyChecking=no",
-                                "-o",
-                                "BatchMode=yes",
-                                "-o",
-                                "PasswordAuthentication=no",
-                                &target_host,
-                                &command,
-                            ])
-                            .output();
-
-                        match output {
-                            Ok(result) => {
-                                let stdout = String::from_utf8_lossy(&result.stdout);
-                                let stderr = String::from_utf8_lossy(&result.stderr);
-
-                                println!(
-                                    "SSH allowed hours command status for {}: {}",
-                                    day_name,
-                                    result.status.success()
-                                );
-                                println!("SSH stdout: {}", stdout.trim());
-                                if !stderr.is_empty() {
-                                    println!("SSH stderr: {}", stderr.trim());
-                                }
-
-                                if result.status.success() {
-                                    success_count += 1;
-                                    println!(
-                                        "Successfully set allowed hours for {}: {}-{}",
-                                        day_name, start_time, end_time
-                                    );
-                                } else {
-                                    errors.push(format!("{}: {}", day_name, stderr.trim()));
-                                }
-                            }
-                            Err(e) => {
-                                errors.push(format!("{}: SSH connection failed: {}", day_name, e));
-                            }
-                        }
-                    }
-                } else {
-                    errors.push(format!("{}: Invalid time format", day_name));
-                }
-            } else {
-                // Set full day access (0-23 hours) when no interval specified
-                let full_day_hours: Vec<String> = (0..24).map(|h| h.to_string()).collect();
-                let hours_string = full_day_hours.join(";");
-                let command = format!(
-                    "timekpra --setallowedhours {} {} '{}'",
-                    username, day_num, hours_string
-                );
+        if !self.has_credentials() {
+            return (
+                false,
+                "SSH key not found. Please configure SSH keys for passwordless authentication."
+                    .to_string(),
+            );
+        }
 
-                let output = Command::new("ssh")
-                    .args(&[
-                        "-i",
-                        &key_path,
-                        "-o",
-                        "ConnectTimeout=10",
-                        "-o",
-                        "StrictHostKeyChecking=no",
-                        "-o",
-                        "BatchMode=yes",
-                        "-o",
-                        "PasswordAuthentication=no",
-                        &target_host,
-                        &command,
-                    ])
-                    .output();
-
-                match output {
-                    Ok(result) => {
-                        if result.status.success() {
-                            success_count += 1;
-                            println!("Set full day access for {}", day_name);
-                        } else {
-                            let stderr = String::from_utf8_lossy(&result.stderr);
-                            errors.push(format!("{}: {}", day_name, stderr.trim()));
-                        }
-                    }
-                    Err(e) => {
-                        errors.push(format!("{}: SSH connection failed: {}", day_name, e));
-                    }
-                }
-            }
+        let results = self
+            .set_weekly_allowed_hours_per_day(username, intervals)
+            .await;
+        Self::summarize_allowed_hours(username, &results)
+    }
 
-            // Small delay between days to avoid overwhelming SSH connections
-            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-        }
+    /// Collapse per-day results into the (success, message) form used by the scheduler
+    pub fn summarize_allowed_hours(username: &str, results: &[DaySyncResult]) -> (bool, String) {
+        let success_count = results.iter().filter(|result| result.ok).count();
+        let errors: Vec<String> = results
+            .iter()
+            .filter_map(|result| {
+                result
+                    .error
+                    .as_ref()
+                    .map(|error| format!("{}: {}", result.day.name(), error))
+            })
+            .collect();
 
         if success_count > 0 {
             let message = if errors.is_empty() {
@@ -426,16 +1137,203 @@ impl SSHClient {
         }
     }
 
-    fn parse_time_to_hour(time_str: &str) -> Result<u8, ()> {
-        // Parse "HH:MM" format to just the hour
-        if let Some(hour_str) = time_str.split(':').next() {
-            if let Ok(hour) = hour_str.parse::<u8>() {
-                if hour <= 23 {
-                    return Ok(hour);
-                }
+    /// Push allowed hours day by day, reporting the outcome for each day. All days go over
+    /// one connection.
+    pub async fn set_weekly_allowed_hours_per_day(
+        &self,
+        username: &str,
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
+    ) -> Vec<DaySyncResult> {
+        if !self.has_credentials() {
+            return DayOfWeek::iter()
+                .map(|day| DaySyncResult::failed(day, "SSH key not found".to_string()))
+                .collect();
+        }
+
+        let mut results = Vec::new();
+        let mut pushed = Vec::new();
+        let mut commands = Vec::new();
+        for day in DayOfWeek::iter() {
+            let Some(hours) = Self::allowed_hours_for_day(intervals, day) else {
+                results.push(DaySyncResult::failed(
+                    day,
+                    "Invalid time format".to_string(),
+                ));
+                continue;
+            };
+
+            if hours.is_empty() {
+                // Nothing to allow; the day is left as it is on the host
+                continue;
+            }
+
+            pushed.push(day);
+            commands.push(self.remote_command(&format!(
+                "timekpra --setallowedhours {} {} '{}'",
+                username,
+                day.to_timekpr_index(),
+                hours.join(";")
+            )));
+        }
+
+        println!(
+            "Running {} SSH allowed hours command(s) on timekpr-remote@{}: {}",
+            commands.len(),
+            self.hostname,
+            commands.join("; ")
+        );
+
+        let outputs = self.run_batch(&commands).await;
+        for (day, (exit_code, stdout, stderr)) in pushed.into_iter().zip(outputs) {
+            println!(
+                "SSH allowed hours command status for {}: {}",
+                day.name(),
+                exit_code == 0
+            );
+            println!("SSH stdout: {}", stdout.trim());
+            if !stderr.is_empty() {
+                println!("SSH stderr: {}", stderr.trim());
+            }
+
+            if exit_code == 0 {
+                self.note_warning(&stderr);
+                results.push(DaySyncResult::succeeded(day));
+            } else {
+                results.push(DaySyncResult::failed(day, stderr.trim().to_string()));
             }
         }
-        Err(())
+
+        // Reported in week order, whichever days failed before anything was sent
+        results.sort_by_key(|result| result.day.to_timekpr_index());
+        results
+    }
+
+    /// Days pushed as allowed, with their limit in seconds: those with a limit above zero.
+    /// A schedule with every day at zero blocks the whole week on purpose; timekpr needs at
+    /// least one allowed day, so every day is allowed with a limit of zero instead.
+    pub fn allowed_day_limits(
+        schedule: &std::collections::HashMap<String, f64>,
+    ) -> Vec<(DayOfWeek, i64)> {
+        let limits: Vec<(DayOfWeek, i64)> = DayOfWeek::iter()
+            .filter_map(|day| {
+                schedule
+                    .get(day.name())
+                    .map(|hours| (day, hours_to_seconds(*hours)))
+            })
+            .collect();
+        if !limits.is_empty() && limits.iter().all(|(_, seconds)| *seconds <= 0) {
+            return DayOfWeek::iter().map(|day| (day, 0)).collect();
+        }
+        limits
+            .into_iter()
+            .filter(|(_, seconds)| *seconds > 0)
+            .collect()
+    }
+
+    /// `--setalloweddays` and `--settimelimits` arguments for `allowed_day_limits`
+    pub fn time_limit_arguments(
+        schedule: &std::collections::HashMap<String, f64>,
+    ) -> (String, String) {
+        let (allowed_days, time_limits): (Vec<String>, Vec<String>) =
+            Self::allowed_day_limits(schedule)
+                .into_iter()
+                .map(|(day, seconds)| (day.to_timekpr_index().to_string(), seconds.to_string()))
+                .unzip();
+
+        (allowed_days.join(";"), time_limits.join(";"))
+    }
+
+    /// Whether the host's `--userinfo` output already shows the limits and allowed hours a
+    /// sync of `schedule` and `intervals` would push. Days whose hours aren't pushed are not
+    /// compared; a line missing from the output counts as a difference.
+    pub fn host_matches_schedule(
+        config: &TimekprConfig,
+        schedule: &std::collections::HashMap<String, f64>,
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
+    ) -> bool {
+        let (allowed_days, time_limits) = Self::time_limit_arguments(schedule);
+        let joined = |key: &str| config.list_value(key).map(|items| items.join(";"));
+        if allowed_days.is_empty()
+            || joined("ALLOWED_WEEKDAYS").as_deref() != Some(allowed_days.as_str())
+            || joined("LIMITS_PER_WEEKDAYS").as_deref() != Some(time_limits.as_str())
+        {
+            return false;
+        }
+
+        DayOfWeek::iter().all(|day| match Self::allowed_hours_for_day(intervals, day) {
+            Some(hours) if hours.is_empty() => true,
+            Some(hours) => {
+                // A whole hour may be listed either bare or as `[0-59]`
+                let host_hours = config
+                    .list_value(&format!("ALLOWED_HOURS_{}", day.to_timekpr_index()))
+                    .map(|items| {
+                        items
+                            .into_iter()
+                            .map(|item| item.replace("[0-59]", ""))
+                            .collect::<Vec<_>>()
+                    });
+                host_hours == Some(hours)
+            }
+            None => false,
+        })
+    }
+
+    /// Hours passed to `--setallowedhours` for one day: every interval's hours in one list,
+    /// so 07:00-09:00 and 15:00-18:00 become `7;8;15;16;17`. The whole day when no interval
+    /// is stored, None when a stored interval is malformed.
+    pub fn allowed_hours_for_day(
+        intervals: &std::collections::HashMap<String, Vec<(String, String)>>,
+        day: DayOfWeek,
+    ) -> Option<Vec<String>> {
+        match intervals
+            .get(day.name())
+            .filter(|intervals| !intervals.is_empty())
+        {
+            Some(intervals) => intervals
+                .iter()
+                .map(|(start_time, end_time)| Self::expand_allowed_hours(start_time, end_time))
+                .collect::<Option<Vec<_>>>()
+                .map(|hours| hours.concat()),
+            None => Some((0..24).map(|hour| hour.to_string()).collect()),
+        }
+    }
+
+    /// Expand an "HH:MM"-"HH:MM" interval into timekpr's allowed-hours entries, keeping minutes.
+    /// Partial hours use the `hour[from-to]` syntax, so 16:30-19:45 becomes
+    /// `16[30-59]`, `17`, `18`, `19[0-45]`. An end on the full hour excludes that hour
+    /// (7:00-17:00 ends with 16), while the end-of-day value 23:59 covers all of 23.
+    pub fn expand_allowed_hours(start_time: &str, end_time: &str) -> Option<Vec<String>> {
+        let (start_hour, start_minute) = Self::parse_time(start_time)?;
+        let (end_hour, end_minute) = Self::parse_time(end_time)?;
+
+        let mut hours = Vec::new();
+        for hour in start_hour..=end_hour {
+            let from = if hour == start_hour { start_minute } else { 0 };
+            let to = match (hour == end_hour, end_minute) {
+                (true, 0) => continue,
+                (true, minute) => minute,
+                (false, _) => 59,
+            };
+            if from > to {
+                continue;
+            }
+
+            hours.push(if from == 0 && to == 59 {
+                hour.to_string()
+            } else {
+                format!("{}[{}-{}]", hour, from, to)
+            });
+        }
+
+        Some(hours)
+    }
+
+    /// Parse "HH:MM" into hour and minute
+    fn parse_time(time_str: &str) -> Option<(u8, u8)> {
+        let (hour, minute) = time_str.split_once(':')?;
+        let hour = hour.parse::<u8>().ok().filter(|hour| *hour <= 23)?;
+        let minute = minute.parse::<u8>().ok().filter(|minute| *minute <= 59)?;
+        Some((hour, minute))
     }
 
     pub async fn set_weekly_time_limits(
@@ -443,74 +1341,35 @@ impl SSHClient {
         username: &str,
         schedule: &std::collections::HashMap<String, f64>,
     ) -> (bool, String) {
-        // Find SSH key path
-        let key_path = match Self::find_ssh_key_path() {
-            Some(path) => path,
-            None => {
-                return (
-                    false,
-                    "SSH key not found. Please configure SSH keys for passwordless authentication."
-                        .to_string(),
-                );
-            }
-        };
-
-        let target_host = format!("timekpr-remote@{}", self.hostname);
-
-        let days = [
-            "monday",
-            "tuesday",
-            "wednesday",
-            "thursday",
-            "friday",
-            "saturday",
-            "sunday",
-        ];
-
-        // Step 1: Set allowed days (days with time limits > 0)
-        let mut allowed_days = Vec::new();
-        let mut time_limits = Vec::new();
-
-        for (i, day) in days.iter().enumerate() {
-            if let Some(hours) = schedule.get(*day) {
-                if *hours > 0.0 {
-                    allowed_days.push((i + 1).to_string()); // 1=Monday, 7=Sunday
-                    let seconds = (*hours * 3600.0) as i64;
-                    time_limits.push(seconds.to_string());
-                }
-            }
+        if !self.has_credentials() {
+            return (
+                false,
+                "SSH key not found. Please configure SSH keys for passwordless authentication."
+                    .to_string(),
+            );
         }
+        let key_path = self.ssh_key_path();
 
-        if allowed_days.is_empty() {
-            return (false, "No days with time limits > 0 configured".to_string());
+        // Step 1: Set allowed days (days with time limits > 0, or every day when all are 0)
+        let (allowed_days_str, time_limits_str) = Self::time_limit_arguments(schedule);
+        if allowed_days_str.is_empty() {
+            return (false, "No days with time limits configured".to_string());
         }
 
         // First set allowed days
-        let allowed_days_str = allowed_days.join(";");
-        let days_command = format!(
+        let days_command = self.remote_command(&format!(
             "timekpra --setalloweddays {} '{}'",
             username, allowed_days_str
-        );
+        ));
 
-        println!("Running SSH setalloweddays command: ssh -i {} -o ConnectTimeout=10 -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no {} \"{}\"",
-                 key_path, target_host, days_command);
+        println!(
+            "Running SSH setalloweddays command: {}",
+            self.describe_command(key_path.as_deref(), 10, &days_command)
+        );
 
-        let days_output = Command::new("ssh")
-            .args(&[
-                "-i",
-                &key_path,
-                "-o",
-                "ConnectTimeout=10",
-                "-o",
-                "StrictHostKeyChecking=no",
-                "-o",
-                "BatchMode=yes",
-                "-o",
-                "PasswordAuthentication=no",
-                &target_host,
-                &days_command,
-            ])
-            .output();
+        let days_output = self
+            .execute_ssh_command(key_path.as_deref(), 10, &days_command)
+            .await;
 
         match days_output {
             Ok(result) => {
@@ -532,41 +1391,27 @@ impl SSHClient {
                         format!("Failed to set allowed days: {}", stderr.trim()),
                     );
                 }
+                self.note_warning(&stderr);
             }
             Err(e) => {
-                return (
-                    false,
-                    format!("SSH connection failed for setalloweddays: {}", e),
-                );
+                return (false, format!("setalloweddays: {}", e));
             }
         }
 
         // Step 2: Set time limits for the allowed days
-        let time_limits_str = time_limits.join(";");
-        let full_command = format!(
+        let full_command = self.remote_command(&format!(
             "timekpra --settimelimits {} '{}'",
             username, time_limits_str
+        ));
+
+        println!(
+            "Running SSH schedule command: {}",
+            self.describe_command(key_path.as_deref(), 10, &full_command)
         );
 
-        println!("Running SSH schedule command: ssh -i {} -o ConnectTimeout=10 -o StrictHostKeyChecking=no -o BatchMode=yes -o PasswordAuthentication=no {} \"{}\"", 
-                 key_path, target_host, full_command);
-
-        let output = Command::new("ssh")
-            .args(&[
-                "-i",
-                &key_path,
-                "-o",
-                "ConnectTimeout=10",
-                "-o",
-                "StrictHostKeyChecking=no",
-                "-o",
-                "BatchMode=yes",
-                "-o",
-                "PasswordAuthentication=no",
-                &target_host,
-                &full_command,
-            ])
-            .output();
+        let output = self
+            .execute_ssh_command(key_path.as_deref(), 10, &full_command)
+            .await;
 
         match output {
             Ok(result) => {
@@ -580,6 +1425,7 @@ impl SSHClient {
                 }
 
                 if result.status.success() {
+                    self.note_warning(&stderr);
                     (
                         true,
                         format!(
@@ -601,10 +1447,147 @@ impl SSHClient {
                     "SSH key authentication failed. Please ensure SSH keys are properly configured."
                         .to_string()
                 } else {
-                    format!("SSH connection failed: {}", e)
+                    e.to_string()
                 };
                 (false, error_msg)
             }
         }
     }
+
+    /// timekpra commands applying PlayTime settings: enable flag, allowed days,
+    /// per-day limits (in seconds) and the list of counted activities
+    pub fn playtime_commands(username: &str, settings: &PlayTimeSettings) -> Vec<String> {
+        let mut days = Vec::new();
+        let mut limits = Vec::new();
+        for day in DayOfWeek::iter() {
+            let hours = settings.hours.get(day);
+            if hours > 0.0 {
+                days.push(day.to_timekpr_index().to_string());
+                limits.push(hours_to_seconds(hours).to_string());
+            }
+        }
+
+        let activities = settings
+            .activities
+            .iter()
+            .map(|activity| {
+                format!(
+                    "{}[{}]",
+                    activity.process.trim(),
+                    activity.description.as_deref().unwrap_or("").trim()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(";");
+
+        vec![
+            format!(
+                "timekpra --setplaytimeenabled {} {}",
+                username, settings.enabled
+            ),
+            format!(
+                "timekpra --setplaytimelimitdays {} '{}'",
+                username,
+                days.join(";")
+            ),
+            format!(
+                "timekpra --setplaytimelimits {} '{}'",
+                username,
+                limits.join(";")
+            ),
+            format!(
+                "timekpra --setplaytimeactivities {} '{}'",
+                username, activities
+            ),
+        ]
+    }
+
+    pub async fn set_playtime(
+        &self,
+        username: &str,
+        settings: &PlayTimeSettings,
+    ) -> (bool, String) {
+        for command in Self::playtime_commands(username, settings) {
+            if let Err(message) = self.run_command(&command).await {
+                return (false, format!("PlayTime command failed: {}", message));
+            }
+        }
+
+        (
+            true,
+            format!(
+                "PlayTime settings applied for {}: enabled={}, {} activities",
+                username,
+                settings.enabled,
+                settings.activities.len()
+            ),
+        )
+    }
+
+    /// timekpra command setting the lockout action; `suspendwake` carries its wake-up hours
+    pub fn lockout_command(username: &str, settings: &LockoutSettings) -> String {
+        let lockout = match (settings.wakeup_from, settings.wakeup_to) {
+            (Some(from), Some(to)) if settings.action == LockoutAction::SuspendWake => {
+                format!("{};{};{}", settings.action.as_str(), from, to)
+            }
+            _ => settings.action.as_str().to_string(),
+        };
+        format!("timekpra --setlockouttype {} '{}'", username, lockout)
+    }
+
+    pub async fn set_lockout(&self, username: &str, settings: &LockoutSettings) -> (bool, String) {
+        match self
+            .run_command(&Self::lockout_command(username, settings))
+            .await
+        {
+            Ok(_) => (
+                true,
+                format!(
+                    "Lockout action for {} set to {}",
+                    username,
+                    settings.action.as_str()
+                ),
+            ),
+            Err(message) => (false, format!("Lockout command failed: {}", message)),
+        }
+    }
+
+    /// Run a single command on the host, returning stderr (or the connection error) on failure
+    async fn run_command(&self, command: &str) -> Result<String, String> {
+        if !self.has_credentials() {
+            return Err(
+                "SSH key not found. Please configure SSH keys for passwordless authentication."
+                    .to_string(),
+            );
+        }
+        let key_path = self.ssh_key_path();
+        let command = &self.remote_command(command);
+
+        println!(
+            "Running SSH command: {}",
+            self.describe_command(key_path.as_deref(), 10, command)
+        );
+
+        let result = self
+            .execute_ssh_command(key_path.as_deref(), 10, command)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let stdout = String::from_utf8_lossy(&result.stdout);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        if !stderr.is_empty() {
+            println!("SSH stderr: {}", stderr.trim());
+        }
+
+        if result.status.success() {
+            self.note_warning(&stderr);
+            Ok(stdout.trim().to_string())
+        } else if Self::classify_failure(result.status.code(), &stderr)
+            == UserStatus::TimekprMissing
+        {
+            Err(self.timekpr_missing_message())
+        } else {
+            Err(stderr.trim().to_string())
+        }
+    }
 }
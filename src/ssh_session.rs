@@ -0,0 +1,198 @@
+use crate::ssh::SshCommandError;
+use async_trait::async_trait;
+use russh::client;
+use russh::ChannelMsg;
+use russh_keys::agent::client::AgentClient;
+use russh_keys::key::PublicKey;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Output};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Port managed hosts accept SSH connections on
+pub const SSH_PORT: u16 = 22;
+
+/// Exit code reported when the channel closed without an exit status, as `ssh` does on
+/// connection errors
+const NO_EXIT_STATUS: u32 = 255;
+
+/// Where a command runs and how it authenticates: the agent's keys first, then the key file
+#[derive(Clone, Copy)]
+pub struct SessionTarget<'a> {
+    pub host: &'a str,
+    pub port: u16,
+    pub user: &'a str,
+    pub key_path: Option<&'a str>,
+    /// Decrypts a passphrase-protected key file
+    pub passphrase: Option<&'a str>,
+    /// ssh-agent socket, as in SSH_AUTH_SOCK
+    pub agent_socket: Option<&'a str>,
+}
+
+/// Accepts any host key, as `ssh -o StrictHostKeyChecking=no` does
+struct AcceptHostKey;
+
+#[async_trait]
+impl client::Handler for AcceptHostKey {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        _server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+}
+
+/// Run `command` on the target over a russh session and collect what it printed, as
+/// `ssh` would. The connection and key exchange are bounded by `connect_timeout`, the
+/// whole run by `command_timeout`.
+pub async fn run_command(
+    target: &SessionTarget<'_>,
+    connect_timeout: Duration,
+    command_timeout: Duration,
+    command: &str,
+) -> Result<Output, SshCommandError> {
+    run_commands(
+        target,
+        connect_timeout,
+        command_timeout,
+        &[command.to_string()],
+    )
+    .await?
+    .pop()
+    .unwrap_or(Err(SshCommandError::CommandTimeout(command_timeout)))
+}
+
+/// Run `commands` one after another over a single session, each on its own channel.
+/// Fails as a whole when the session can't be set up; otherwise there is one result per
+/// command, each bounded by `command_timeout`.
+pub async fn run_commands(
+    target: &SessionTarget<'_>,
+    connect_timeout: Duration,
+    command_timeout: Duration,
+    commands: &[String],
+) -> Result<Vec<Result<Output, SshCommandError>>, SshCommandError> {
+    let mut session = tokio::time::timeout(command_timeout, connect(target, connect_timeout))
+        .await
+        .unwrap_or(Err(SshCommandError::CommandTimeout(command_timeout)))?;
+
+    let mut outputs = Vec::with_capacity(commands.len());
+    for command in commands {
+        outputs.push(
+            tokio::time::timeout(command_timeout, exec(&mut session, command))
+                .await
+                .unwrap_or(Err(SshCommandError::CommandTimeout(command_timeout))),
+        );
+    }
+
+    let _ = session
+        .disconnect(russh::Disconnect::ByApplication, "", "en")
+        .await;
+    Ok(outputs)
+}
+
+async fn connect(
+    target: &SessionTarget<'_>,
+    connect_timeout: Duration,
+) -> Result<client::Handle<AcceptHostKey>, SshCommandError> {
+    let config = Arc::new(client::Config::default());
+    let mut session = tokio::time::timeout(
+        connect_timeout,
+        client::connect(config, (target.host, target.port), AcceptHostKey),
+    )
+    .await
+    .map_err(|_| SshCommandError::Connect("Connection timed out".to_string()))?
+    .map_err(|e| SshCommandError::Connect(e.to_string()))?;
+
+    let mut authenticated = match target.agent_socket {
+        Some(socket) => authenticate_with_agent(&mut session, target.user, socket).await?,
+        None => false,
+    };
+    if !authenticated {
+        if let Some(key_path) = target.key_path {
+            let key = russh_keys::load_secret_key(key_path, target.passphrase).map_err(|e| {
+                SshCommandError::Session(format!("Could not load SSH key {}: {}", key_path, e))
+            })?;
+            authenticated = session
+                .authenticate_publickey(target.user, Arc::new(key))
+                .await
+                .map_err(|e| SshCommandError::Session(e.to_string()))?;
+        }
+    }
+    if !authenticated {
+        // Same wording as `ssh`, which the failure messages look for
+        return Err(SshCommandError::Session(format!(
+            "{}@{}: Permission denied (publickey)",
+            target.user, target.host
+        )));
+    }
+    Ok(session)
+}
+
+/// Offer each key the agent holds until the host accepts one. An agent that can't be
+/// reached is skipped, leaving the key file to try.
+async fn authenticate_with_agent(
+    session: &mut client::Handle<AcceptHostKey>,
+    user: &str,
+    socket: &str,
+) -> Result<bool, SshCommandError> {
+    let mut agent = match AgentClient::connect_uds(socket).await {
+        Ok(agent) => agent,
+        Err(e) => {
+            eprintln!("ssh-agent at {} not reachable: {}", socket, e);
+            return Ok(false);
+        }
+    };
+    let identities = match agent.request_identities().await {
+        Ok(identities) => identities,
+        Err(e) => {
+            eprintln!("ssh-agent at {} did not list its keys: {}", socket, e);
+            return Ok(false);
+        }
+    };
+
+    for key in identities {
+        let (returned, result) = session.authenticate_future(user, key, agent).await;
+        agent = returned;
+        if result.map_err(|e| SshCommandError::Session(e.to_string()))? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+async fn exec(
+    session: &mut client::Handle<AcceptHostKey>,
+    command: &str,
+) -> Result<Output, SshCommandError> {
+    let mut channel = session
+        .channel_open_session()
+        .await
+        .map_err(|e| SshCommandError::Session(e.to_string()))?;
+    channel
+        .exec(true, command)
+        .await
+        .map_err(|e| SshCommandError::Session(e.to_string()))?;
+
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut exit_status = None;
+    // The exit status may arrive before the last data, so read until the channel closes
+    while let Some(message) = channel.wait().await {
+        match message {
+            ChannelMsg::Data { data } => stdout.extend_from_slice(&data),
+            ChannelMsg::ExtendedData { data, ext: 1 } => stderr.extend_from_slice(&data),
+            ChannelMsg::ExitStatus { exit_status: code } => exit_status = Some(code),
+            _ => {}
+        }
+    }
+
+    let code = exit_status.unwrap_or(NO_EXIT_STATUS);
+    Ok(Output {
+        // Wait status layout: the exit code sits in the second byte
+        status: ExitStatus::from_raw((code as i32 & 0xff) << 8),
+        stdout,
+        stderr,
+    })
+}
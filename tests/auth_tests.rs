@@ -3,6 +3,9 @@ use serde_json::json;
 
 mod common;
 use common::TestApp;
+use std::sync::Arc;
+use timekpr_ui_rust::repositories::SqliteSettingsRepository;
+use timekpr_ui_rust::services::SettingsService;
 
 #[actix_web::test]
 async fn test_login_success() {
@@ -19,6 +22,7 @@ async fn test_login_success() {
 
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
 
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["success"], true);
@@ -111,3 +115,340 @@ async fn test_protected_endpoint_with_invalid_token() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
 }
+
+#[actix_web::test]
+async fn test_login_upgrades_weak_password_hash() {
+    use argon2::password_hash::{rand_core::OsRng, PasswordHash, SaltString};
+    use argon2::{Algorithm, Argon2, Params, PasswordHasher, Version};
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    // Store a hash with a much lower cost than the current target
+    let weak = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(8, 1, 1, None).unwrap(),
+    );
+    let salt = SaltString::generate(&mut OsRng);
+    let weak_hash = weak.hash_password(b"admin", &salt).unwrap().to_string();
+    sqlx::query("UPDATE settings SET value = ? WHERE key = 'admin_password_hash'")
+        .bind(&weak_hash)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({
+            "username": "admin",
+            "password": "admin"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let (stored,): (String,) =
+        sqlx::query_as("SELECT value FROM settings WHERE key = 'admin_password_hash'")
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_ne!(stored, weak_hash);
+
+    let params = Params::try_from(&PasswordHash::new(&stored).unwrap()).unwrap();
+    assert_eq!(params.m_cost(), Params::DEFAULT_M_COST);
+    assert_eq!(params.t_cost(), Params::DEFAULT_T_COST);
+
+    // The upgraded hash still accepts the same password
+    let req = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({
+            "username": "admin",
+            "password": "admin"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_bearer_prefix_variants() {
+    use timekpr_ui_rust::auth::normalize_bearer_token;
+
+    assert_eq!(normalize_bearer_token("Bearer abc.def"), Some("abc.def"));
+    assert_eq!(normalize_bearer_token("bearer abc.def"), Some("abc.def"));
+    assert_eq!(normalize_bearer_token("BEARER abc.def"), Some("abc.def"));
+    // Swagger UI doubles the scheme when the token is pasted with it
+    assert_eq!(
+        normalize_bearer_token("Bearer bearer abc.def"),
+        Some("abc.def")
+    );
+    assert_eq!(
+        normalize_bearer_token("Bearer Bearer abc.def"),
+        Some("abc.def")
+    );
+    assert_eq!(
+        normalize_bearer_token("  Bearer \t abc.def  "),
+        Some("abc.def")
+    );
+
+    assert_eq!(normalize_bearer_token("abc.def"), None);
+    assert_eq!(normalize_bearer_token("Bearer"), None);
+    assert_eq!(normalize_bearer_token("Bearer   "), None);
+    assert_eq!(normalize_bearer_token("Basic YWRtaW46YWRtaW4="), None);
+    assert_eq!(normalize_bearer_token("Bearer abc def"), None);
+}
+
+#[actix_web::test]
+async fn test_lowercase_bearer_prefix_is_accepted() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+
+    for header in [
+        format!("bearer {}", token),
+        format!("Bearer bearer {}", token),
+        format!(" Bearer  {} ", token),
+    ] {
+        let req = test::TestRequest::get()
+            .uri("/api/dashboard")
+            .insert_header(("Authorization", header))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/dashboard")
+        .insert_header(("Authorization", token))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+fn settings_service(test_app: &TestApp) -> SettingsService {
+    SettingsService::new(Arc::new(SqliteSettingsRepository::new(
+        test_app.pool.clone(),
+    )))
+}
+
+async fn login_status(test_app: &TestApp, password: &str) -> StatusCode {
+    let app = test::init_service(test_app.create_app()).await;
+    let req = test::TestRequest::post()
+        .uri("/api/login")
+        .set_json(json!({
+            "username": "admin",
+            "password": password
+        }))
+        .to_request();
+    test::call_service(&app, req).await.status()
+}
+
+#[actix_web::test]
+async fn test_login_fails_closed_without_credential() {
+    let test_app = TestApp::new().await;
+    sqlx::query("DELETE FROM settings WHERE key = 'admin_password_hash'")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    // No stored credential must not mean the default password works
+    assert_eq!(
+        login_status(&test_app, "admin").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Creating it is an explicit startup step
+    let settings = settings_service(&test_app);
+    assert!(settings
+        .initialize_admin_password(Some("s3cret".to_string()))
+        .await
+        .unwrap());
+    assert!(!settings.initialize_admin_password(None).await.unwrap());
+    assert_eq!(
+        login_status(&test_app, "admin").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(login_status(&test_app, "s3cret").await, StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn test_legacy_plaintext_password_is_migrated() {
+    let test_app = TestApp::new().await;
+    sqlx::query("DELETE FROM settings WHERE key = 'admin_password_hash'")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO settings (key, value) VALUES ('admin_password', 'legacy-pw')")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let settings = settings_service(&test_app);
+    assert!(settings.migrate_legacy_admin_password().await.unwrap());
+    assert!(!settings.migrate_legacy_admin_password().await.unwrap());
+
+    let (plaintext,): (i64,) =
+        sqlx::query_as("SELECT COUNT(*) FROM settings WHERE key = 'admin_password'")
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(plaintext, 0);
+
+    assert_eq!(login_status(&test_app, "legacy-pw").await, StatusCode::OK);
+    assert_eq!(
+        login_status(&test_app, "admin").await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn test_errors_follow_accept_header() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let dashboard = |accept: Option<&str>| {
+        let mut req = test::TestRequest::get().uri("/api/dashboard");
+        if let Some(accept) = accept {
+            req = req.insert_header(("Accept", accept));
+        }
+        req.to_request()
+    };
+
+    // Browsers get a page
+    let resp = test::call_service(
+        &app,
+        dashboard(Some(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8",
+        )),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        resp.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+    assert!(body.contains("<h1>401 Unauthorized</h1>"));
+    assert!(body.contains("Not authenticated"));
+
+    // API clients, and anyone not asking for HTML first, keep the JSON envelope
+    for accept in [
+        None,
+        Some("*/*"),
+        Some("application/json"),
+        Some("application/json, text/html;q=0.5"),
+    ] {
+        let resp = test::call_service(&app, dashboard(accept)).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["success"], false, "{:?}", accept);
+    }
+
+    // Successful responses are never rewritten
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::post()
+            .uri("/api/login")
+            .insert_header(("Accept", "text/html"))
+            .set_json(json!({ "username": "admin", "password": "admin" }))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["token"].is_string());
+}
+
+#[actix_web::test]
+async fn test_readonly_token_only_reads() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let with_token = |req: test::TestRequest, token: &str| {
+        req.insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/readonly-token")
+        .set_json(json!({}))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/readonly-token")
+        .set_json(json!({"days": 0}));
+    let resp = test::call_service(&app, with_token(req, &token)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/api/auth/readonly-token")
+        .set_json(json!({"days": 30}));
+    let resp = test::call_service(&app, with_token(req, &token)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["expires_in"], 30 * 24 * 3600);
+    let readonly = body["token"].as_str().unwrap().to_string();
+
+    for uri in [
+        "/api/dashboard".to_string(),
+        "/api/stats".to_string(),
+        format!("/api/user/{}/usage", user_id),
+        format!("/api/user/{}/usage/intraday", user_id),
+        format!("/api/user/{}/usage/aggregate", user_id),
+    ] {
+        let resp = test::call_service(
+            &app,
+            with_token(test::TestRequest::get().uri(&uri), &readonly),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+    }
+
+    // Anything else, reading or not, is forbidden rather than unauthenticated
+    let resp = test::call_service(
+        &app,
+        with_token(test::TestRequest::get().uri("/api/admin"), &readonly),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["message"], "Token is read-only");
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .set_json(json!({
+            "user_id": user_id,
+            "operation": "+",
+            "seconds": 600
+        }));
+    let resp = test::call_service(&app, with_token(req, &readonly)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post().uri(&format!("/api/users/delete/{}", user_id));
+    let resp = test::call_service(&app, with_token(req, &readonly)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let req = test::TestRequest::post()
+        .uri("/api/auth/readonly-token")
+        .set_json(json!({}));
+    let resp = test::call_service(&app, with_token(req, &readonly)).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // A bad token is still unauthenticated
+    let req = test::TestRequest::post().uri(&format!("/api/users/delete/{}", user_id));
+    let resp = test::call_service(&app, with_token(req, "not-a-token")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    // The user is still there
+    let resp = test::call_service(
+        &app,
+        with_token(
+            test::TestRequest::get().uri(&format!("/api/user/{}", user_id)),
+            &token,
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -1,22 +1,223 @@
+use actix_web::middleware::from_fn;
 use actix_web::{test, web, App};
-use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
-use std::sync::Arc;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 use timekpr_ui_rust::{
     auth::JwtManager,
-    handlers,
-    models::ManagedUser,
+    db, handlers,
+    middleware::error_format::negotiate_error_format,
+    models::{
+        path_config, LockoutSettings, ManagedUser, PlayTimeSettings, TimekprConfig, UserStatus,
+    },
+    readiness::Readiness,
     repositories::{
-        schedule_repository::SqliteScheduleRepository, user_repository::SqliteUserRepository,
+        schedule_repository::SqliteScheduleRepository,
+        settings_repository::SqliteSettingsRepository, usage_repository::SqliteUsageRepository,
+        user_repository::SqliteUserRepository,
     },
+    scheduler::BackgroundScheduler,
     services::{
-        schedule_service::ScheduleService, time_service::TimeService, user_service::UserService,
+        dashboard_cache::DashboardCache, event_log::EventLog, local_time::LocalTime,
+        schedule_service::ScheduleService, settings_service::SettingsService,
+        time_service::TimeService, usage_service::UsageService, user_locks::UserLocks,
+        user_service::UserService,
     },
+    ssh::{SSHClient, SshBackend},
 };
 
+/// Stand-in for the remote hosts: offline until told otherwise, records applied adjustments
+/// and pushed time limits.
+/// Online hosts report 1200s used and 3600s left today.
+#[derive(Default)]
+pub struct MockSsh {
+    online: AtomicBool,
+    /// Milliseconds each time adjustment takes, to let tests overlap them
+    adjust_delay_ms: AtomicU64,
+    pub applied: Mutex<Vec<(String, String, i64)>>,
+    /// Username with the `--setalloweddays` and `--settimelimits` arguments
+    pub time_limits: Mutex<Vec<(String, String, String)>>,
+    /// Usernames allowed hours were pushed for, once per sync
+    pub allowed_hours: Mutex<Vec<String>>,
+    /// Usernames with the PlayTime settings pushed for them
+    pub playtime: Mutex<Vec<(String, PlayTimeSettings)>>,
+    /// Usernames with the lockout settings pushed for them
+    pub lockout: Mutex<Vec<(String, LockoutSettings)>>,
+    /// Extra `--userinfo` lines online hosts report, such as their current limits
+    userinfo: Mutex<String>,
+    /// Stderr each successful command reports, as a deprecation notice would
+    warning: Mutex<Option<String>>,
+    pending_warnings: Mutex<Vec<(i64, String)>>,
+}
+
+impl MockSsh {
+    #[allow(dead_code)]
+    pub fn set_online(&self, online: bool) {
+        self.online.store(online, Ordering::SeqCst);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_adjust_delay(&self, delay: std::time::Duration) {
+        self.adjust_delay_ms
+            .store(delay.as_millis() as u64, Ordering::SeqCst);
+    }
+
+    #[allow(dead_code)]
+    pub fn set_userinfo(&self, userinfo: &str) {
+        *self.userinfo.lock().unwrap() = userinfo.to_string();
+    }
+
+    #[allow(dead_code)]
+    pub fn set_warning(&self, warning: Option<&str>) {
+        *self.warning.lock().unwrap() = warning.map(str::to_string);
+    }
+
+    fn is_online(&self) -> bool {
+        self.online.load(Ordering::SeqCst)
+    }
+
+    fn keep_warning(&self, host: &ManagedUser) {
+        if let Some(warning) = self.warning.lock().unwrap().clone() {
+            self.pending_warnings
+                .lock()
+                .unwrap()
+                .push((host.id, warning));
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl SshBackend for MockSsh {
+    async fn validate_user(
+        &self,
+        host: &ManagedUser,
+    ) -> (UserStatus, String, Option<TimekprConfig>) {
+        if self.is_online() {
+            let config = TimekprConfig::parse(
+                &host.username,
+                &format!(
+                    "ACTUAL_TIME_LEFT_DAY: 3600\nACTUAL_TIME_SPENT_DAY: 1200\n{}",
+                    self.userinfo.lock().unwrap()
+                ),
+            );
+            self.keep_warning(host);
+            (UserStatus::Ok, "User is valid".to_string(), Some(config))
+        } else {
+            (
+                UserStatus::Offline,
+                "Connection timed out".to_string(),
+                None,
+            )
+        }
+    }
+
+    async fn modify_time_left(
+        &self,
+        host: &ManagedUser,
+        operation: &str,
+        seconds: i64,
+    ) -> (bool, String) {
+        let delay = self.adjust_delay_ms.load(Ordering::SeqCst);
+        if delay > 0 {
+            tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+        }
+        if !self.is_online() {
+            return (false, "Connection timed out".to_string());
+        }
+        self.applied
+            .lock()
+            .unwrap()
+            .push((host.username.clone(), operation.to_string(), seconds));
+        self.keep_warning(host);
+        (true, "Time adjusted".to_string())
+    }
+
+    async fn set_weekly_time_limits(
+        &self,
+        host: &ManagedUser,
+        schedule: &HashMap<String, f64>,
+    ) -> (bool, String) {
+        if !self.is_online() {
+            return (false, "Connection timed out".to_string());
+        }
+        let (allowed_days, time_limits) = SSHClient::time_limit_arguments(schedule);
+        if allowed_days.is_empty() {
+            return (false, "No days with time limits configured".to_string());
+        }
+        self.time_limits
+            .lock()
+            .unwrap()
+            .push((host.username.clone(), allowed_days, time_limits));
+        self.keep_warning(host);
+        (true, "Time limits set".to_string())
+    }
+
+    async fn set_weekly_allowed_hours(
+        &self,
+        host: &ManagedUser,
+        _intervals: &HashMap<String, Vec<(String, String)>>,
+    ) -> (bool, String) {
+        if !self.is_online() {
+            return (false, "Connection timed out".to_string());
+        }
+        self.allowed_hours
+            .lock()
+            .unwrap()
+            .push(host.username.clone());
+        self.keep_warning(host);
+        (true, "Allowed hours set".to_string())
+    }
+
+    async fn set_playtime(
+        &self,
+        host: &ManagedUser,
+        settings: &PlayTimeSettings,
+    ) -> (bool, String) {
+        if !self.is_online() {
+            return (false, "Connection timed out".to_string());
+        }
+        self.playtime
+            .lock()
+            .unwrap()
+            .push((host.username.clone(), settings.clone()));
+        self.keep_warning(host);
+        (true, "PlayTime set".to_string())
+    }
+
+    async fn set_lockout(&self, host: &ManagedUser, settings: &LockoutSettings) -> (bool, String) {
+        if !self.is_online() {
+            return (false, "Connection timed out".to_string());
+        }
+        self.lockout
+            .lock()
+            .unwrap()
+            .push((host.username.clone(), settings.clone()));
+        self.keep_warning(host);
+        (true, "Lockout set".to_string())
+    }
+
+    fn take_warnings(&self) -> Vec<(i64, String)> {
+        std::mem::take(&mut *self.pending_warnings.lock().unwrap())
+    }
+}
+
 pub struct TestApp {
     pub pool: SqlitePool,
     pub jwt_manager: JwtManager,
+    /// Shared by every app instance so a snapshot outlives a single `create_app`
+    pub dashboard_cache: Arc<DashboardCache>,
+    /// Left cold so tests can observe the warm-up transition
+    pub readiness: Arc<Readiness>,
+    /// Used by the time service and scheduler in place of real SSH
+    pub ssh: Arc<MockSsh>,
+    /// Shared by the time service and every scheduler, as in the real app
+    pub user_locks: Arc<UserLocks>,
+    /// Shared by every service and scheduler, as in the real app
+    pub event_log: Arc<EventLog>,
+    /// Shared by every service and scheduler, as in the real app
+    pub local_time: Arc<LocalTime>,
     #[allow(dead_code)]
     pub temp_dir: TempDir,
 }
@@ -47,7 +248,7 @@ impl TestApp {
         let database_url = format!("sqlite://{}?mode=rwc", db_path.display());
 
         // Create connection pool
-        let pool = SqlitePoolOptions::new()
+        let pool = db::pool_options()
             .max_connections(1)
             .connect(&database_url)
             .await
@@ -67,6 +268,12 @@ impl TestApp {
         Self {
             pool,
             jwt_manager,
+            dashboard_cache: Arc::new(DashboardCache::new()),
+            readiness: Arc::new(Readiness::new()),
+            ssh: Arc::new(MockSsh::default()),
+            user_locks: Arc::new(UserLocks::new()),
+            event_log: Arc::new(EventLog::new()),
+            local_time: Arc::new(LocalTime::new()),
             temp_dir,
         }
     }
@@ -85,46 +292,329 @@ impl TestApp {
         // Initialize repositories
         let user_repository = Arc::new(SqliteUserRepository::new(self.pool.clone()));
         let schedule_repository = Arc::new(SqliteScheduleRepository::new(self.pool.clone()));
+        let usage_repository = Arc::new(SqliteUsageRepository::new(self.pool.clone()));
+        let settings_repository = Arc::new(SqliteSettingsRepository::new(self.pool.clone()));
 
         // Initialize services
-        let user_service = web::Data::new(UserService::new(user_repository.clone()));
-        let schedule_service = web::Data::new(ScheduleService::new(schedule_repository));
-        let time_service = web::Data::new(TimeService::new(user_repository));
+        let user_service_arc = Arc::new(
+            UserService::new(user_repository.clone())
+                .with_default_schedule(schedule_repository.clone(), settings_repository.clone())
+                .with_settings(settings_repository.clone())
+                .with_event_log(self.event_log.clone()),
+        );
+        let user_service = web::Data::from(user_service_arc.clone());
+        let schedule_service_arc = Arc::new(
+            ScheduleService::new(schedule_repository.clone(), user_repository.clone())
+                .with_ssh_backend(self.ssh.clone())
+                .with_local_time(self.local_time.clone()),
+        );
+        let schedule_service = web::Data::from(schedule_service_arc.clone());
+        let usage_service_arc = Arc::new(
+            UsageService::new(usage_repository.clone()).with_local_time(self.local_time.clone()),
+        );
+        let settings_service_arc = Arc::new(
+            SettingsService::new(settings_repository.clone())
+                .with_local_time(self.local_time.clone()),
+        );
+        let settings_service = web::Data::from(settings_service_arc.clone());
+        let dashboard_cache = web::Data::from(self.dashboard_cache.clone());
+        // Scheduler is registered but not started so tests stay deterministic
+        let scheduler = web::Data::new(
+            BackgroundScheduler::new(
+                user_service_arc,
+                usage_service_arc,
+                schedule_service_arc,
+                settings_service_arc,
+                self.dashboard_cache.clone(),
+            )
+            .with_ssh_backend(self.ssh.clone())
+            .with_user_locks(self.user_locks.clone())
+            .with_event_log(self.event_log.clone())
+            .with_local_time(self.local_time.clone()),
+        );
+        let time_service = web::Data::new(
+            TimeService::new(user_repository, usage_repository)
+                .with_ssh_backend(self.ssh.clone())
+                .with_grant_cap(settings_repository)
+                .with_user_locks(self.user_locks.clone())
+                .with_event_log(self.event_log.clone())
+                .with_local_time(self.local_time.clone()),
+        );
         let jwt_manager = web::Data::new(self.jwt_manager.clone());
 
         App::new()
             .app_data(user_service)
             .app_data(schedule_service)
             .app_data(time_service)
+            .app_data(settings_service)
+            .app_data(scheduler)
+            .app_data(dashboard_cache)
+            .app_data(web::Data::from(self.readiness.clone()))
+            .app_data(web::Data::from(self.event_log.clone()))
             .app_data(jwt_manager)
             .app_data(web::Data::new(self.pool.clone()))
+            .app_data(path_config())
+            .wrap(from_fn(negotiate_error_format))
             .route("/api/login", web::post().to(handlers::auth::login_api))
+            .route(
+                "/api/auth/readonly-token",
+                web::post().to(handlers::auth::create_readonly_token),
+            )
             .route(
                 "/api/dashboard",
                 web::get().to(handlers::dashboard::dashboard_api),
             )
+            .route(
+                "/api/dashboard/refresh",
+                web::post().to(handlers::dashboard::refresh_dashboard),
+            )
+            .route("/api/admin", web::get().to(handlers::dashboard::admin_api))
+            .route("/api/stats", web::get().to(handlers::dashboard::get_stats))
             .route(
                 "/api/users/add",
                 web::post().to(handlers::users::add_user_api),
             )
+            .route(
+                "/api/users/update/{id}",
+                web::post().to(handlers::users::update_user),
+            )
             .route(
                 "/api/users/delete/{id}",
                 web::post().to(handlers::users::delete_user),
             )
+            .route(
+                "/api/users/bulk-delete",
+                web::post().to(handlers::users::bulk_delete_users),
+            )
             .route(
                 "/api/modify-time",
                 web::post().to(handlers::time::modify_time),
             )
+            .route(
+                "/api/set-time-limit",
+                web::post().to(handlers::time::set_time_limit),
+            )
+            .route(
+                "/api/user/{id}",
+                web::get().to(handlers::users::get_user_detail),
+            )
+            .route(
+                "/api/user/{id}/hosts",
+                web::post().to(handlers::users::attach_host),
+            )
+            .route(
+                "/api/user/{id}/validation-history",
+                web::get().to(handlers::users::get_validation_history),
+            )
+            .route(
+                "/api/user/{id}/modification-history",
+                web::get().to(handlers::time::get_modification_history),
+            )
+            .route(
+                "/api/user/{id}/timekpr-path",
+                web::put().to(handlers::users::set_timekpr_path),
+            )
+            .route(
+                "/api/user/{id}/command-templates",
+                web::put().to(handlers::users::set_command_templates),
+            )
+            .route(
+                "/api/user/{id}/adjustment-preview",
+                web::get().to(handlers::time::adjustment_preview),
+            )
+            .route(
+                "/api/user/{id}/usage",
+                web::get().to(handlers::time::get_user_usage),
+            )
+            .route(
+                "/api/user/{id}/snapshot-usage",
+                web::post().to(handlers::time::snapshot_usage),
+            )
+            .route(
+                "/api/user/{id}/usage/intraday",
+                web::get().to(handlers::time::get_intraday_usage),
+            )
+            .route(
+                "/api/user/{id}/usage/aggregate",
+                web::get().to(handlers::time::get_usage_aggregate),
+            )
             .route(
                 "/api/schedule/update",
                 web::post().to(handlers::schedule::update_schedule_api),
             )
+            .route(
+                "/api/schedule/validate",
+                web::post().to(handlers::schedule::validate_schedule),
+            )
             .route(
                 "/api/schedule/{id}",
                 web::get().to(handlers::schedule::get_schedule_sync_status),
             )
+            .route(
+                "/api/intervals-sync-status/{id}",
+                web::get().to(handlers::schedule::get_intervals_sync_status),
+            )
+            .route(
+                "/api/user/{id}/sync",
+                web::post().to(handlers::schedule::sync_schedule),
+            )
+            .route(
+                "/api/user/{id}/sync/{aspect}",
+                web::post().to(handlers::schedule::sync_schedule_aspect),
+            )
+            .route(
+                "/api/user/{id}/clear-hours",
+                web::post().to(handlers::schedule::clear_allowed_hours),
+            )
+            .route(
+                "/api/user/{id}/timekpr-export",
+                web::get().to(handlers::schedule::timekpr_export),
+            )
+            .route(
+                "/api/user/{id}/extend-today",
+                web::post().to(handlers::schedule::extend_today),
+            )
+            .route(
+                "/api/user/{id}/import-timekpr",
+                web::post().to(handlers::schedule::import_timekpr),
+            )
+            .route(
+                "/api/user/{id}/block-days",
+                web::post().to(handlers::schedule::block_days),
+            )
+            .route(
+                "/api/user/{id}/allowed-days",
+                web::get().to(handlers::schedule::get_allowed_days),
+            )
+            .route(
+                "/api/user/{id}/lockout",
+                web::get().to(handlers::schedule::get_user_lockout),
+            )
+            .route(
+                "/api/user/{id}/lockout",
+                web::post().to(handlers::schedule::update_user_lockout),
+            )
+            .route(
+                "/api/user/{id}/intervals",
+                web::get().to(handlers::schedule::get_user_intervals),
+            )
+            .route(
+                "/api/user/{id}/intervals",
+                web::post().to(handlers::schedule::update_user_intervals),
+            )
+            .route(
+                "/api/settings/week-start",
+                web::post().to(handlers::settings::update_week_start),
+            )
+            .route(
+                "/api/task-status",
+                web::get().to(handlers::system::get_task_status),
+            )
+            .route(
+                "/api/system/run-scheduler-pass",
+                web::post().to(handlers::system::run_scheduler_pass),
+            )
+            .route(
+                "/api/capabilities",
+                web::get().to(handlers::system::get_capabilities),
+            )
+            .route(
+                "/api/events/recent",
+                web::get().to(handlers::system::get_recent_events),
+            )
+            .route(
+                "/api/ssh-status",
+                web::get().to(handlers::system::get_ssh_status),
+            )
+            .route(
+                "/api/ssh/key-candidates",
+                web::get().to(handlers::system::get_ssh_key_candidates),
+            )
+            .route(
+                "/api/ssh/fix-permissions",
+                web::post().to(handlers::system::fix_ssh_key_permissions),
+            )
+            .route(
+                "/api/health/live",
+                web::get().to(handlers::system::health_live),
+            )
+            .route(
+                "/api/health/ready",
+                web::get().to(handlers::system::health_ready),
+            )
+            .route(
+                "/api/health",
+                web::get().to(handlers::system::health_detailed),
+            )
+            .route(
+                "/api/settings/protect-observability",
+                web::post().to(handlers::settings::update_protect_observability),
+            )
+            .route(
+                "/api/settings/default-schedule",
+                web::post().to(handlers::settings::update_default_schedule),
+            )
+            .route(
+                "/api/settings/scheduler-window",
+                web::post().to(handlers::settings::update_scheduler_window),
+            )
+            .route(
+                "/api/settings/check-interval",
+                web::post().to(handlers::settings::update_check_interval),
+            )
+            .route(
+                "/api/settings/timezone",
+                web::post().to(handlers::settings::update_timezone),
+            )
+            .route(
+                "/api/timezones",
+                web::get().to(handlers::settings::list_timezones),
+            )
+            .route(
+                "/api/settings/grant-cap",
+                web::post().to(handlers::settings::update_grant_cap),
+            )
+            .route(
+                "/api/settings/revalidate-after-modify",
+                web::post().to(handlers::settings::update_revalidate_after_modify),
+            )
+    }
+
+    /// A scheduler over this app's database and mock SSH, for driving passes by hand
+    #[allow(dead_code)]
+    pub fn scheduler(&self) -> BackgroundScheduler {
+        let user_repository = Arc::new(SqliteUserRepository::new(self.pool.clone()));
+        let settings_repository = Arc::new(SqliteSettingsRepository::new(self.pool.clone()));
+        BackgroundScheduler::new(
+            Arc::new(
+                UserService::new(user_repository.clone())
+                    .with_settings(settings_repository.clone())
+                    .with_event_log(self.event_log.clone()),
+            ),
+            Arc::new(
+                UsageService::new(Arc::new(SqliteUsageRepository::new(self.pool.clone())))
+                    .with_local_time(self.local_time.clone()),
+            ),
+            Arc::new(
+                ScheduleService::new(
+                    Arc::new(SqliteScheduleRepository::new(self.pool.clone())),
+                    user_repository.clone(),
+                )
+                .with_ssh_backend(self.ssh.clone())
+                .with_local_time(self.local_time.clone()),
+            ),
+            Arc::new(
+                SettingsService::new(settings_repository).with_local_time(self.local_time.clone()),
+            ),
+            self.dashboard_cache.clone(),
+        )
+        .with_ssh_backend(self.ssh.clone())
+        .with_user_locks(self.user_locks.clone())
+        .with_event_log(self.event_log.clone())
+        .with_local_time(self.local_time.clone())
     }
 
+    #[allow(dead_code)]
     pub async fn login_and_get_token(&self) -> String {
         let app = test::init_service(self.create_app()).await;
 
@@ -142,6 +632,7 @@ impl TestApp {
         body["token"].as_str().unwrap().to_string()
     }
 
+    #[allow(dead_code)]
     pub async fn add_test_user(&self, token: &str) -> i64 {
         let app = test::init_service(self.create_app()).await;
 
@@ -154,16 +645,10 @@ impl TestApp {
             }))
             .to_request();
 
-        let _resp = test::call_service(&app, add_user_req).await;
-
-        // Query the database directly to get the user ID since the API doesn't return it
-        let user = sqlx::query_as::<_, ManagedUser>(
-            "SELECT * FROM managed_users WHERE username = 'testuser' AND system_ip = '192.168.1.100'"
-        )
-        .fetch_one(&self.pool)
-        .await
-        .expect("Failed to fetch created user");
-
-        user.id
+        let resp = test::call_service(&app, add_user_req).await;
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        body["user_id"]
+            .as_i64()
+            .expect("Add user response without user_id")
     }
 }
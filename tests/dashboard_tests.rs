@@ -2,6 +2,13 @@ use actix_web::{http::StatusCode, test};
 
 mod common;
 use common::TestApp;
+use std::sync::Arc;
+use timekpr_ui_rust::models::format_timestamp;
+use timekpr_ui_rust::repositories::{
+    SqliteScheduleRepository, SqliteSettingsRepository, SqliteUsageRepository, SqliteUserRepository,
+};
+use timekpr_ui_rust::scheduler::BackgroundScheduler;
+use timekpr_ui_rust::services::{ScheduleService, SettingsService, UsageService, UserService};
 
 #[actix_web::test]
 async fn test_dashboard_success() {
@@ -30,6 +37,88 @@ async fn test_dashboard_success() {
     assert!(users.is_empty()); // No valid users in test environment
 }
 
+#[actix_web::test]
+async fn test_dashboard_and_usage_cache_headers() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/dashboard")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("cache-control").unwrap(),
+        "private, max-age=30"
+    );
+    assert!(resp.headers().contains_key("etag"));
+
+    let usage = |etag: Option<&str>| {
+        let mut req = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)));
+        if let Some(etag) = etag {
+            req = req.insert_header(("If-None-Match", etag.to_string()));
+        }
+        req.to_request()
+    };
+
+    let resp = test::call_service(&app, usage(None)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(
+        resp.headers().get("cache-control").unwrap(),
+        "private, max-age=30"
+    );
+    let etag = resp
+        .headers()
+        .get("etag")
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+
+    // Unchanged: no body is sent again
+    let resp = test::call_service(&app, usage(Some(&etag))).await;
+    assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(resp.headers().get("etag").unwrap(), etag.as_str());
+    assert!(test::read_body(resp).await.is_empty());
+
+    // New usage changes the body and with it the ETag
+    UsageService::new(Arc::new(SqliteUsageRepository::new(test_app.pool.clone())))
+        .store_daily_usage(user_id, 1800)
+        .await
+        .unwrap();
+    let resp = test::call_service(&app, usage(Some(&etag))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_ne!(resp.headers().get("etag").unwrap(), etag.as_str());
+
+    // Freshness follows the configured scheduler interval
+    let req = test::TestRequest::post()
+        .uri("/api/settings/check-interval")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(serde_json::json!({ "seconds": 120 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let resp = test::call_service(&app, usage(None)).await;
+    assert_eq!(
+        resp.headers().get("cache-control").unwrap(),
+        "private, max-age=120"
+    );
+    let req = test::TestRequest::get()
+        .uri("/api/dashboard")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(
+        resp.headers().get("cache-control").unwrap(),
+        "private, max-age=120"
+    );
+}
+
 #[actix_web::test]
 async fn test_dashboard_empty_users() {
     let test_app = TestApp::new().await;
@@ -99,3 +188,286 @@ async fn test_dashboard_response_structure() {
     assert!(body.get("users").is_some());
     assert!(body["users"].is_array());
 }
+
+#[actix_web::test]
+async fn test_admin_lists_date_added() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let date_added = body["users"][0]["date_added"].as_str().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(date_added).is_ok());
+}
+
+#[actix_web::test]
+async fn test_dashboard_served_from_scheduler_cache() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let user_repository = Arc::new(SqliteUserRepository::new(test_app.pool.clone()));
+    let scheduler = BackgroundScheduler::new(
+        Arc::new(UserService::new(user_repository.clone())),
+        Arc::new(UsageService::new(Arc::new(SqliteUsageRepository::new(
+            test_app.pool.clone(),
+        )))),
+        Arc::new(ScheduleService::new(
+            Arc::new(SqliteScheduleRepository::new(test_app.pool.clone())),
+            user_repository,
+        )),
+        Arc::new(SettingsService::new(Arc::new(
+            SqliteSettingsRepository::new(test_app.pool.clone()),
+        ))),
+        test_app.dashboard_cache.clone(),
+    );
+
+    // No pass yet, so the dashboard is queried live
+    assert!(test_app.dashboard_cache.snapshot().await.is_none());
+    let dashboard = || {
+        test::TestRequest::get()
+            .uri("/api/dashboard")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, dashboard()).await).await;
+    assert_eq!(body["users"].as_array().unwrap().len(), 1);
+
+    scheduler.run_once().await;
+    let snapshot = test_app.dashboard_cache.snapshot().await.unwrap();
+    assert_eq!(snapshot.users.len(), 1);
+
+    // A user added behind the API's back only shows up after the next pass
+    sqlx::query(
+        "INSERT INTO managed_users (username, system_ip, is_valid) VALUES ('other', '10.0.0.2', 1)",
+    )
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, dashboard()).await).await;
+    assert_eq!(body["users"].as_array().unwrap().len(), 1);
+    assert_eq!(body["as_of"], format_timestamp(snapshot.as_of));
+
+    scheduler.run_once().await;
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, dashboard()).await).await;
+    assert_eq!(body["users"].as_array().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn test_timestamps_are_rfc3339_utc() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET last_checked = NULL WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+
+    // Never checked is null rather than a placeholder string
+    assert!(body["users"][0]["last_checked"].is_null());
+    let date_added = body["users"][0]["date_added"].as_str().unwrap();
+    assert!(date_added.ends_with('Z'));
+
+    let timestamp = chrono::DateTime::parse_from_rfc3339("2024-05-01T17:30:00+00:00").unwrap();
+    assert_eq!(
+        format_timestamp(timestamp.with_timezone(&chrono::Utc)),
+        "2024-05-01T17:30:00Z"
+    );
+}
+
+#[actix_web::test]
+async fn test_dashboard_status_from_last_check() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let status_after = |last_checked: Option<&'static str>, last_status: Option<&'static str>| {
+        let pool = test_app.pool.clone();
+        let app = &app;
+        let token = token.clone();
+        async move {
+            sqlx::query("UPDATE managed_users SET is_valid = 1, last_checked = ?, last_status = ? WHERE id = ?")
+                .bind(last_checked)
+                .bind(last_status)
+                .bind(user_id)
+                .execute(&pool)
+                .await
+                .unwrap();
+            let req = test::TestRequest::get()
+                .uri("/api/dashboard")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .to_request();
+            let body: serde_json::Value =
+                test::read_body_json(test::call_service(app, req).await).await;
+            body["users"][0]["status"].clone()
+        }
+    };
+
+    assert_eq!(status_after(None, None).await, "never_checked");
+    assert_eq!(
+        status_after(Some("2024-05-01 10:00:00"), Some("ok")).await,
+        "online"
+    );
+    assert_eq!(
+        status_after(Some("2024-05-01 10:00:00"), Some("offline")).await,
+        "offline"
+    );
+    assert_eq!(
+        status_after(Some("2024-05-01 10:00:00"), Some("invalid_user")).await,
+        "user_missing"
+    );
+}
+
+#[actix_web::test]
+async fn test_dashboard_refresh_checks_hosts_live() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    let refresh = || {
+        test::TestRequest::post()
+            .uri("/api/dashboard/refresh")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Offline host: the row stays, flagged as stale
+    let body: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["users"][0]["id"], user_id);
+    assert_eq!(body["users"][0]["stale"], true);
+    assert_eq!(body["users"][0]["status"], "offline");
+    assert!(body["users"][0]["time_spent_week"].is_null());
+
+    test_app.ssh.set_online(true);
+    test_app
+        .ssh
+        .set_userinfo("ACTUAL_TIME_SPENT_WEEK: 7200\nLIMIT_PER_WEEK: 36000\n");
+    let body: serde_json::Value = test::call_and_read_body_json(&app, refresh()).await;
+    assert_eq!(body["users"][0]["stale"], false);
+    assert_eq!(body["users"][0]["status"], "online");
+    assert_eq!(body["users"][0]["time_left"], "1h 0m");
+    assert_eq!(body["users"][0]["time_spent_week"], 7200);
+    assert_eq!(body["users"][0]["time_limit_week"], 36000);
+    // Not reported by this host
+    assert!(body["users"][0]["time_left_week"].is_null());
+    assert!(body["users"][0]["time_spent_month"].is_null());
+
+    // The plain dashboard now serves the refreshed rows
+    let req = test::TestRequest::get()
+        .uri("/api/dashboard")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["users"][0]["time_left"], "1h 0m");
+
+    let req = test::TestRequest::post()
+        .uri("/api/dashboard/refresh")
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn test_household_stats() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+
+    let kid = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1, last_status = 'offline' WHERE id = ?")
+        .bind(kid)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    sqlx::query("INSERT INTO managed_users (username, system_ip, is_valid, last_status) VALUES ('other', '10.0.0.2', 0, 'ok')")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    // One hour every day; 50 minutes used today is above 80%
+    sqlx::query(
+        "INSERT INTO user_weekly_schedule (user_id, monday_hours, tuesday_hours, wednesday_hours,
+         thursday_hours, friday_hours, saturday_hours, sunday_hours) VALUES (?, 1, 1, 1, 1, 1, 1, 1)",
+    )
+    .bind(kid)
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+    for (user, day, seconds) in [
+        ("testuser", "+0 days", 3000),
+        ("other", "+0 days", 600),
+        ("testuser", "-30 days", 7200),
+    ] {
+        sqlx::query(
+            "INSERT INTO user_time_usage (user_id, date, time_spent)
+             SELECT id, date('now', ?), ? FROM managed_users WHERE username = ?",
+        )
+        .bind(day)
+        .bind(seconds)
+        .bind(user)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/api/stats")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let stats = &body["stats"];
+    assert_eq!(stats["users"]["managed"], 2);
+    assert_eq!(stats["users"]["valid"], 1);
+    assert_eq!(stats["users"]["offline"], 1);
+    assert_eq!(stats["time_spent_today"], 3600);
+    // The usage from a month ago is outside the current week
+    assert_eq!(stats["time_spent_week"], 3600);
+    assert_eq!(
+        stats["busiest_day"]["date"],
+        chrono::Utc::now().date_naive().to_string()
+    );
+    assert_eq!(stats["users_near_limit"], 1);
+
+    let req = test::TestRequest::get().uri("/api/stats").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
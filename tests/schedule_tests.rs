@@ -2,7 +2,16 @@ use actix_web::{http::StatusCode, test};
 use serde_json::json;
 
 mod common;
+use chrono::NaiveDate;
 use common::TestApp;
+use std::sync::Arc;
+use timekpr_ui_rust::models::{
+    effective_limit_seconds, seconds_until_reset, DayOfWeek, EventKind, WeekStart, WeeklyHours,
+};
+use timekpr_ui_rust::repositories::{
+    ScheduleRepository, SqliteScheduleRepository, SqliteUserRepository,
+};
+use timekpr_ui_rust::services::ScheduleService;
 
 #[actix_web::test]
 async fn test_update_schedule_success() {
@@ -128,11 +137,53 @@ async fn test_update_schedule_nonexistent_user() {
         .to_request();
 
     let resp = test::call_service(&app, req).await;
-    assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["success"], false);
-    assert!(body["message"].as_str().unwrap().contains("Database error"));
+    assert_eq!(body["message"], "User not found");
+}
+
+#[actix_web::test]
+async fn test_update_schedule_warns_for_invalid_user() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    // Added while its host is unreachable, so it never validated
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 4.0,
+            "sunday": 4.0
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], true);
+    assert!(body["warning"]
+        .as_str()
+        .unwrap()
+        .starts_with("User testuser on 192.168.1.100 is not valid"));
+
+    // Saved all the same
+    let schedule = SqliteScheduleRepository::new(test_app.pool.clone())
+        .find_by_user_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(schedule.hours.saturday, 4.0);
 }
 
 #[actix_web::test]
@@ -173,9 +224,9 @@ async fn test_get_schedule_success() {
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["success"], true);
     assert!(body["schedule"].is_object());
-    assert_eq!(body["schedule"]["monday"], 2.5);
-    assert_eq!(body["schedule"]["tuesday"], 3.0);
-    assert_eq!(body["schedule"]["sunday"], 4.5);
+    assert_eq!(body["schedule"]["hours"]["monday"], 2.5);
+    assert_eq!(body["schedule"]["hours"]["tuesday"], 3.0);
+    assert_eq!(body["schedule"]["hours"]["sunday"], 4.5);
 }
 
 #[actix_web::test]
@@ -254,3 +305,2387 @@ async fn test_update_schedule_missing_day() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
+
+#[actix_web::test]
+async fn test_sunday_week_start_keeps_timekpr_day_numbers() {
+    let days = DayOfWeek::week(WeekStart::Sunday);
+    assert_eq!(days[0], DayOfWeek::Sunday);
+    assert_eq!(days[6], DayOfWeek::Saturday);
+
+    let indices: Vec<u8> = days.iter().map(|day| day.to_timekpr_index()).collect();
+    assert_eq!(indices, vec![7, 1, 2, 3, 4, 5, 6]);
+
+    // Sync order is independent of the display week start
+    let sync_indices: Vec<u8> = DayOfWeek::iter()
+        .map(|day| day.to_timekpr_index())
+        .collect();
+    assert_eq!(sync_indices, vec![1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[actix_web::test]
+async fn test_week_start_setting_changes_day_order() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["week_start"], "monday");
+    assert_eq!(body["day_order"][0], "monday");
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/week-start")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "week_start": "sunday" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["week_start"], "sunday");
+    assert_eq!(body["day_order"][0], "sunday");
+    assert_eq!(body["day_order"][6], "saturday");
+}
+
+#[actix_web::test]
+async fn test_week_start_invalid_value() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/week-start")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "week_start": "friday" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_sync_aspect_queues_when_host_unreachable() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let update_req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 4.0,
+            "sunday": 4.0
+        }))
+        .to_request();
+    test::call_service(&app, update_req).await;
+
+    // No SSH key in the test environment, so the push is queued
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/sync/hours", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["pending"], true);
+
+    // Queued as a full push for the scheduler, even after the schedule was synced
+    sqlx::query("UPDATE user_weekly_schedule SET is_synced = 1 WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/sync/limits", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::ACCEPTED
+    );
+    let is_synced: bool =
+        sqlx::query_scalar("SELECT is_synced FROM user_weekly_schedule WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert!(!is_synced);
+}
+
+#[actix_web::test]
+async fn test_sync_aspect_invalid_or_missing() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/sync/everything", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // No schedule saved yet
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/sync/limits", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/api/user/99999/sync/limits")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_effective_limit_precedence() {
+    // Weekly hours alone
+    assert_eq!(effective_limit_seconds(Some(2.0), None, None), Some(7200));
+    // Override replaces the weekly hours, bonus is added on top
+    assert_eq!(
+        effective_limit_seconds(Some(2.0), Some(3.0), Some(30)),
+        Some(12600)
+    );
+    // Bonus without any limit keeps the day unlimited
+    assert_eq!(effective_limit_seconds(None, None, Some(30)), None);
+    // Never beyond a full day
+    assert_eq!(
+        effective_limit_seconds(Some(23.5), None, Some(60)),
+        Some(86400)
+    );
+}
+
+#[actix_web::test]
+async fn test_effective_limit_with_override_and_bonus() {
+    let test_app = TestApp::new().await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let schedule_service = ScheduleService::new(
+        Arc::new(SqliteScheduleRepository::new(test_app.pool.clone())),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    schedule_service
+        .update_schedule(
+            user_id,
+            WeeklyHours {
+                monday: 2.0,
+                tuesday: 1.0,
+                wednesday: 2.0,
+                thursday: 2.0,
+                friday: 2.0,
+                saturday: 4.0,
+                sunday: 4.0,
+            },
+        )
+        .await
+        .unwrap();
+
+    let holiday_monday = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+    let next_monday = NaiveDate::from_ymd_opt(2026, 10, 26).unwrap();
+    let tuesday = NaiveDate::from_ymd_opt(2026, 10, 20).unwrap();
+    assert_eq!(DayOfWeek::from_date(holiday_monday), DayOfWeek::Monday);
+
+    sqlx::query("INSERT INTO user_limit_override (user_id, date, hours) VALUES (?, ?, 3.0)")
+        .bind(user_id)
+        .bind(holiday_monday)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        "INSERT INTO user_recurring_bonus (user_id, day_of_week, minutes) VALUES (?, 1, 30)",
+    )
+    .bind(user_id)
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+
+    // Override and bonus on the same day: 3h override + 30m bonus
+    let limit = schedule_service
+        .effective_limit_on(user_id, holiday_monday)
+        .await
+        .unwrap();
+    assert_eq!(limit, Some(3 * 3600 + 30 * 60));
+
+    // Bonus only: 2h weekly + 30m bonus
+    let limit = schedule_service
+        .effective_limit_on(user_id, next_monday)
+        .await
+        .unwrap();
+    assert_eq!(limit, Some(2 * 3600 + 30 * 60));
+
+    // Neither: weekly hours
+    let limit = schedule_service
+        .effective_limit_on(user_id, tuesday)
+        .await
+        .unwrap();
+    assert_eq!(limit, Some(3600));
+}
+
+#[actix_web::test]
+async fn test_user_detail_includes_effective_limit() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let update_req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 2.0,
+            "sunday": 2.0
+        }))
+        .to_request();
+    test::call_service(&app, update_req).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["user"]["username"], "testuser");
+    assert_eq!(body["effective_limit_today"], 7200);
+
+    let req = test::TestRequest::get()
+        .uri("/api/user/99999")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_clear_hours_resets_intervals_and_queues() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let clear = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/clear-hours", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Unreachable and nothing stored to queue the change on
+    let resp = test::call_service(&app, clear()).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let repository = SqliteScheduleRepository::new(test_app.pool.clone());
+    assert!(repository.find_by_user_id(user_id).await.unwrap().is_none());
+
+    let update_req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 4.0,
+            "sunday": 4.0,
+            "monday_start_time": "15:00",
+            "monday_end_time": "19:00"
+        }))
+        .to_request();
+    test::call_service(&app, update_req).await;
+
+    // Host is unreachable in tests, so the change is queued
+    let resp = test::call_service(&app, clear()).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["pending"], true);
+
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["is_synced"], false);
+    assert_eq!(
+        body["schedule"]["intervals"]["monday"]["start_time"],
+        "00:00"
+    );
+    assert_eq!(body["schedule"]["intervals"]["monday"]["end_time"], "23:59");
+    // Daily limits are untouched
+    assert_eq!(body["schedule"]["hours"]["saturday"], 4.0);
+
+    let req = test::TestRequest::post()
+        .uri("/api/user/99999/clear-hours")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn test_default_schedule_applied_to_new_users() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    // Without a default, new users have no schedule
+    let user_id = test_app.add_test_user(&token).await;
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert!(body["schedule"].is_null());
+
+    let hours = json!({
+        "monday": 2.0, "tuesday": 2.0, "wednesday": 2.0, "thursday": 2.0,
+        "friday": 3.0, "saturday": 4.0, "sunday": 4.0
+    });
+    let req = test::TestRequest::post()
+        .uri("/api/settings/default-schedule")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "hours": hours }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "username": "newkid", "system_ip": "192.168.1.50" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let new_id: i64 = sqlx::query_scalar("SELECT id FROM managed_users WHERE username = 'newkid'")
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", new_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["schedule"]["hours"]["saturday"], 4.0);
+    assert_eq!(body["is_synced"], false);
+
+    // Invalid hours are rejected, and null switches the default off again
+    let req = test::TestRequest::post()
+        .uri("/api/settings/default-schedule")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "hours": {
+            "monday": 25.0, "tuesday": 2.0, "wednesday": 2.0, "thursday": 2.0,
+            "friday": 3.0, "saturday": 4.0, "sunday": 4.0
+        } }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/default-schedule")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "hours": null }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let count: i64 =
+        sqlx::query_scalar("SELECT COUNT(*) FROM settings WHERE key = 'default_schedule'")
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[actix_web::test]
+async fn test_intervals_sync_status() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/intervals-sync-status/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["username"], "testuser");
+    assert_eq!(body["needs_sync"], false);
+    assert_eq!(body["enabled_intervals"], 0);
+    assert_eq!(body["total_intervals"], 7);
+
+    let update_req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 4.0,
+            "sunday": 4.0,
+            "monday_start_time": "15:00",
+            "monday_end_time": "19:00",
+            "friday_start_time": "14:00",
+            "friday_end_time": "21:00"
+        }))
+        .to_request();
+    test::call_service(&app, update_req).await;
+
+    let get_req = test::TestRequest::get()
+        .uri(&format!("/api/intervals-sync-status/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, get_req).await).await;
+    assert_eq!(body["needs_sync"], true);
+    assert_eq!(body["enabled_intervals"], 2);
+    assert!(body["last_synced"].is_null());
+
+    let get_req = test::TestRequest::get()
+        .uri("/api/intervals-sync-status/99999")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, get_req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn test_seconds_until_reset() {
+    let at = |h, m| {
+        NaiveDate::from_ymd_opt(2024, 3, 4)
+            .unwrap()
+            .and_hms_opt(h, m, 0)
+            .unwrap()
+    };
+
+    // Midnight reset
+    assert_eq!(seconds_until_reset(at(19, 48), 0), 4 * 3600 + 12 * 60);
+    assert_eq!(seconds_until_reset(at(0, 0), 0), 24 * 3600);
+
+    // Custom reset hour, before and after it on the same day
+    assert_eq!(seconds_until_reset(at(3, 30), 4), 30 * 60);
+    assert_eq!(seconds_until_reset(at(5, 0), 4), 23 * 3600);
+}
+
+#[actix_web::test]
+async fn test_user_detail_fully_synced() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let fully_synced = |body: serde_json::Value| body["fully_synced"].as_bool().unwrap();
+    let detail = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Nothing configured, nothing to push
+    let body = test::read_body_json(test::call_service(&app, detail()).await).await;
+    assert!(fully_synced(body));
+
+    let update_req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 2.0,
+            "sunday": 2.0
+        }))
+        .to_request();
+    test::call_service(&app, update_req).await;
+
+    let body = test::read_body_json(test::call_service(&app, detail()).await).await;
+    assert!(!fully_synced(body));
+
+    sqlx::query("UPDATE user_weekly_schedule SET is_synced = 1 WHERE user_id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    let body = test::read_body_json(test::call_service(&app, detail()).await).await;
+    assert!(fully_synced(body));
+
+    // A queued time adjustment also needs attention
+    sqlx::query("UPDATE managed_users SET pending_time_adjustment = 600, pending_time_operation = '+' WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    let body = test::read_body_json(test::call_service(&app, detail()).await).await;
+    assert!(!fully_synced(body));
+}
+
+#[actix_web::test]
+async fn test_allowed_hours_partial_results() {
+    use timekpr_ui_rust::models::DaySyncResult;
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let results = vec![
+        DaySyncResult::succeeded(DayOfWeek::Monday),
+        DaySyncResult::failed(DayOfWeek::Tuesday, "timeout".to_string()),
+        DaySyncResult::succeeded(DayOfWeek::Wednesday),
+    ];
+
+    let (success, message) = SSHClient::summarize_allowed_hours("kid", &results);
+    assert!(success);
+    assert_eq!(
+        message,
+        "Partially successful: 2 days configured, 1 errors: tuesday: timeout"
+    );
+
+    // Per-day results serialize with the error only where a day failed
+    let json = serde_json::to_value(&results).unwrap();
+    assert_eq!(json[0], json!({ "day": "monday", "ok": true }));
+    assert_eq!(
+        json[1],
+        json!({ "day": "tuesday", "ok": false, "error": "timeout" })
+    );
+
+    let (success, _) = SSHClient::summarize_allowed_hours(
+        "kid",
+        &[DaySyncResult::failed(DayOfWeek::Monday, "down".to_string())],
+    );
+    assert!(!success);
+}
+
+#[actix_web::test]
+async fn test_allowed_hours_keep_minutes() {
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let expand = |start: &str, end: &str| {
+        SSHClient::expand_allowed_hours(start, end)
+            .unwrap()
+            .join(";")
+    };
+
+    assert_eq!(expand("16:30", "19:45"), "16[30-59];17;18;19[0-45]");
+    assert_eq!(expand("07:00", "17:00"), "7;8;9;10;11;12;13;14;15;16");
+    assert_eq!(
+        expand("00:00", "23:59"),
+        (0..24).map(|h| h.to_string()).collect::<Vec<_>>().join(";")
+    );
+    assert_eq!(expand("16:15", "16:45"), "16[15-45]");
+    assert_eq!(expand("18:00", "09:00"), "");
+
+    assert!(SSHClient::expand_allowed_hours("25:00", "26:00").is_none());
+    assert!(SSHClient::expand_allowed_hours("7", "17:00").is_none());
+}
+
+#[actix_web::test]
+async fn test_playtime_commands() {
+    use timekpr_ui_rust::models::{PlayTimeActivity, PlayTimeSettings};
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let settings = PlayTimeSettings {
+        enabled: true,
+        hours: WeeklyHours {
+            monday: 1.0,
+            tuesday: 0.0,
+            wednesday: 1.0,
+            thursday: 0.0,
+            friday: 2.0,
+            saturday: 3.0,
+            sunday: 0.5,
+        },
+        activities: vec![
+            PlayTimeActivity {
+                process: "minecraft".to_string(),
+                description: Some("Minecraft".to_string()),
+            },
+            PlayTimeActivity {
+                process: "steam".to_string(),
+                description: None,
+            },
+        ],
+    };
+    assert!(settings.validate().is_ok());
+
+    assert_eq!(
+        SSHClient::playtime_commands("kid", &settings),
+        vec![
+            "timekpra --setplaytimeenabled kid true",
+            "timekpra --setplaytimelimitdays kid '1;3;5;6;7'",
+            "timekpra --setplaytimelimits kid '3600;3600;7200;10800;1800'",
+            "timekpra --setplaytimeactivities kid 'minecraft[Minecraft];steam[]'",
+        ]
+    );
+
+    // Values end up in single-quoted, ';'-separated arguments
+    let unsafe_settings = PlayTimeSettings {
+        activities: vec![PlayTimeActivity {
+            process: "game'; rm -rf /".to_string(),
+            description: None,
+        }],
+        ..settings
+    };
+    assert!(unsafe_settings.validate().is_err());
+}
+
+#[actix_web::test]
+async fn test_playtime_stored_with_schedule() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let schedule = |process: &str| {
+        json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 2.0,
+            "sunday": 2.0,
+            "playtime": {
+                "enabled": true,
+                "hours": {
+                    "monday": 1.0,
+                    "tuesday": 1.0,
+                    "wednesday": 1.0,
+                    "thursday": 1.0,
+                    "friday": 1.0,
+                    "saturday": 1.0,
+                    "sunday": 1.0
+                },
+                "activities": [{ "process": process, "description": "Game" }]
+            }
+        })
+    };
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(schedule("bad[process]"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(schedule("supertux2"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(
+        body["playtime"]["settings"]["activities"][0]["process"],
+        "supertux2"
+    );
+    assert_eq!(body["playtime"]["is_synced"], false);
+    assert_eq!(body["fully_synced"], false);
+
+    // A host whose userinfo has no PLAYTIME keys keeps the settings but says so
+    sqlx::query("UPDATE managed_users SET last_config = ? WHERE id = ?")
+        .bind(r#"{"USERNAME":"testuser","TIME_LEFT_DAY":3600,"raw_output":"ACTUAL_TIME_LEFT_DAY: 3600"}"#)
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(schedule("supertux2"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["success"], true);
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("does not support PlayTime"));
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["playtime"]["supported"], false);
+
+    // Once the host reports PlayTime, the scheduler pushes the settings there
+    test_app.ssh.set_userinfo("PLAYTIME_ENABLED: False");
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    let pushed = test_app.ssh.playtime.lock().unwrap().clone();
+    assert_eq!(pushed.len(), 1);
+    assert_eq!(pushed[0].0, "testuser");
+    assert_eq!(pushed[0].1.activities[0].process, "supertux2");
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["playtime"]["is_synced"], true);
+}
+
+#[actix_web::test]
+async fn test_timekpr_export() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/timekpr-export", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 0.0,
+            "wednesday": 1.5,
+            "thursday": 0.0,
+            "friday": 0.0,
+            "saturday": 0.0,
+            "sunday": 0.0,
+            "monday_start_time": "16:30",
+            "monday_end_time": "19:45"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/timekpr-export", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    let export = &body["export"];
+    assert_eq!(export["allowed_days"], "1;3");
+    assert_eq!(export["time_limits"], "7200;5400");
+    assert_eq!(export["days"][0]["day_index"], 1);
+    assert_eq!(
+        export["days"][0]["allowed_hours"],
+        "16[30-59];17;18;19[0-45]"
+    );
+    assert_eq!(export["days"][0]["time_limit"], 7200);
+    assert_eq!(export["days"][1]["time_limit"], serde_json::Value::Null);
+    // Days without an interval allow the whole day
+    assert_eq!(
+        export["days"][2]["allowed_hours"],
+        (0..24).map(|h| h.to_string()).collect::<Vec<_>>().join(";")
+    );
+    assert_eq!(
+        export["commands"][0],
+        "timekpra --setalloweddays testuser '1;3'"
+    );
+    assert_eq!(
+        export["commands"][2],
+        "timekpra --setallowedhours testuser 1 '16[30-59];17;18;19[0-45]'"
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/timekpr-export", user_id))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn test_update_intervals_per_day() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let intervals_uri = format!("/api/user/{}/intervals", user_id);
+
+    // Without a schedule there are no limits to attach the hours to
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "monday": { "start_time": "16:30", "end_time": "19:45" } }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 3.0,
+            "sunday": 3.0,
+            "tuesday_start_time": "08:00",
+            "tuesday_end_time": "20:00"
+        }))
+        .to_request();
+    test::call_service(&app, req).await;
+
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "monday": { "start_time": "19:00", "end_time": "16:00" } }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Monday interval"));
+
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "monday": { "start_time": "16:30", "end_time": "19:45" } }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["intervals"]["monday"]["start_time"], "16:30");
+    assert_eq!(body["intervals"]["monday"]["end_time"], "19:45");
+    // Days left out of the update keep their interval, and the limits are untouched
+    assert_eq!(body["intervals"]["tuesday"]["start_time"], "08:00");
+    assert_eq!(body["intervals"]["sunday"]["end_time"], "23:59");
+    assert_eq!(body["needs_sync"], true);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["schedule"]["hours"]["saturday"], 3.0);
+}
+
+#[actix_web::test]
+async fn test_intervals_reject_out_of_range_times() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let intervals_uri = format!("/api/user/{}/intervals", user_id);
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 3.0,
+            "sunday": 3.0
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Minute 60 would otherwise become the timekpr argument "8[0-60]"
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "monday": { "start_time": "07:00", "end_time": "08:60" } }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["message"],
+        "Monday interval: Invalid end time 08:60: minutes must be between 0 and 59"
+    );
+
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "friday": { "start_time": "24:00", "end_time": "23:00" } }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["message"],
+        "Friday interval: Invalid start time 24:00: hour must be between 0 and 23"
+    );
+
+    // The full schedule update takes the same path
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 3.0,
+            "sunday": 3.0,
+            "saturday_start_time": "09:00",
+            "saturday_end_time": "24:00"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("Saturday interval: Invalid end time 24:00: hour must be between 0 and 23"));
+
+    // Nothing was stored
+    let req = test::TestRequest::get()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["intervals"]["monday"]["end_time"], "23:59");
+    assert_eq!(body["intervals"]["saturday"]["end_time"], "23:59");
+}
+
+#[actix_web::test]
+async fn test_multiple_intervals_per_day() {
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let intervals_uri = format!("/api/user/{}/intervals", user_id);
+
+    // Arrays per day next to the single start/end fields
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 3.0,
+            "sunday": 3.0,
+            "monday_intervals": [
+                { "start_time": "15:00", "end_time": "18:00" },
+                { "start_time": "07:00", "end_time": "09:00" }
+            ],
+            "tuesday_start_time": "08:00",
+            "tuesday_end_time": "20:00"
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    // Stored in order of their start; `intervals` keeps the first, as before
+    assert_eq!(
+        body["intervals"]["monday"],
+        json!({ "start_time": "07:00", "end_time": "09:00" })
+    );
+    assert_eq!(
+        body["interval_lists"]["monday"],
+        json!([
+            { "start_time": "07:00", "end_time": "09:00" },
+            { "start_time": "15:00", "end_time": "18:00" }
+        ])
+    );
+    assert_eq!(
+        body["interval_lists"]["tuesday"],
+        json!([{ "start_time": "08:00", "end_time": "20:00" }])
+    );
+
+    // The ranges reach timekpr as one hour list
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    let service = ScheduleService::new(
+        repository.clone(),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    let (_, intervals_dict) = service.prepare_sync_data(&schedule);
+    assert_eq!(
+        SSHClient::allowed_hours_for_day(&intervals_dict, DayOfWeek::Monday)
+            .unwrap()
+            .join(";"),
+        "7;8;15;16;17"
+    );
+    assert_eq!(
+        SSHClient::allowed_hours_for_day(&intervals_dict, DayOfWeek::Tuesday)
+            .unwrap()
+            .join(";"),
+        "8;9;10;11;12;13;14;15;16;17;18;19"
+    );
+
+    // The per-day endpoint takes arrays too, with partial hours kept
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "wednesday": [
+                { "start_time": "06:30", "end_time": "08:15" },
+                { "start_time": "16:00", "end_time": "20:00" }
+            ]
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    let (_, intervals_dict) = service.prepare_sync_data(&schedule);
+    assert_eq!(
+        SSHClient::allowed_hours_for_day(&intervals_dict, DayOfWeek::Wednesday)
+            .unwrap()
+            .join(";"),
+        "6[30-59];7;8[0-15];16;17;18;19"
+    );
+    // Monday's ranges survive an update of another day
+    assert_eq!(schedule.intervals.monday.len(), 2);
+    // One row per interval
+    let monday_rows: Vec<(String, String)> = sqlx::query_as(
+        "SELECT start_time, end_time FROM user_daily_time_interval
+         WHERE user_id = ? AND day_of_week = 1 ORDER BY start_time",
+    )
+    .bind(user_id)
+    .fetch_all(&test_app.pool)
+    .await
+    .unwrap();
+    assert_eq!(
+        monday_rows,
+        vec![
+            ("07:00".to_string(), "09:00".to_string()),
+            ("15:00".to_string(), "18:00".to_string())
+        ]
+    );
+
+    let rejected = [
+        (
+            json!([
+                { "start_time": "07:00", "end_time": "10:00" },
+                { "start_time": "09:00", "end_time": "12:00" }
+            ]),
+            "Thursday interval: Intervals 07:00-10:00 and 09:00-12:00 overlap",
+        ),
+        // timekpr takes a single minute range per hour
+        (
+            json!([
+                { "start_time": "07:00", "end_time": "09:20" },
+                { "start_time": "09:40", "end_time": "12:00" }
+            ]),
+            "Thursday interval: Intervals 07:00-09:20 and 09:40-12:00 share hour 9",
+        ),
+        (
+            json!([]),
+            "Thursday interval: At least one interval is required",
+        ),
+    ];
+    for (intervals, message) in rejected {
+        let req = test::TestRequest::post()
+            .uri(&intervals_uri)
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "thursday": intervals }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(
+            body["message"].as_str().unwrap().starts_with(message),
+            "{}",
+            body["message"]
+        );
+    }
+
+    // Back to a single interval drops the extra ranges
+    let req = test::TestRequest::post()
+        .uri(&intervals_uri)
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "monday": { "start_time": "10:00", "end_time": "12:00" } }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert_eq!(schedule.intervals.monday.len(), 1);
+    assert_eq!(schedule.intervals.wednesday.len(), 2);
+}
+
+#[actix_web::test]
+async fn test_validate_schedule_dry_run() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/validate")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 25.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": -1.0,
+            "sunday": 2.0,
+            "monday_start_time": "19:00",
+            "monday_end_time": "16:00",
+            "friday_start_time": "8:00"
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["valid"], false);
+    let errors: Vec<&str> = body["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|error| error.as_str().unwrap())
+        .collect();
+    assert_eq!(errors.len(), 4);
+    assert!(errors[0].starts_with("Monday hours"));
+    assert!(errors[1].starts_with("Saturday hours"));
+    assert!(errors[2].starts_with("Monday interval"));
+    assert!(errors[3].starts_with("Friday interval"));
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/validate")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 4.0,
+            "sunday": 4.0,
+            "monday_start_time": "16:00",
+            "monday_end_time": "19:00"
+        }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["valid"], true);
+    assert_eq!(body["errors"].as_array().unwrap().len(), 0);
+    assert_eq!(body["warnings"].as_array().unwrap().len(), 0);
+
+    // Nothing was stored
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["schedule"].is_null());
+}
+
+#[actix_web::test]
+async fn test_update_schedule_warns_when_limit_exceeds_allowed_hours() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let schedule = json!({
+        "user_id": user_id,
+        "monday": 4.0,
+        "tuesday": 2.0,
+        "wednesday": 2.0,
+        "thursday": 2.0,
+        "friday": 2.0,
+        "saturday": 24.0,
+        "sunday": 2.0,
+        "monday_start_time": "16:00",
+        "monday_end_time": "18:00",
+        "tuesday_start_time": "16:00",
+        "tuesday_end_time": "18:00"
+    });
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/validate")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(&schedule)
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["valid"], true);
+    // Tuesday's window exactly fits its limit; Saturday's 00:00-23:59 runs to midnight
+    let warnings = body["warnings"].as_array().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0],
+        "Monday: allowed hours 16:00-18:00 give only 2h 0m, less than the daily limit of 4h 0m; the limit can never be reached"
+    );
+
+    // Saved anyway
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(&schedule)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["warnings"].as_array().unwrap().len(), 1);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/schedule/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["schedule"]["hours"]["monday"], 4.0);
+    assert_eq!(body["schedule"]["intervals"]["monday"]["end_time"], "18:00");
+}
+
+#[actix_web::test]
+async fn test_sync_retry_delay_backs_off_to_cap() {
+    use timekpr_ui_rust::services::{sync_retry_delay, SYNC_RETRY_BASE, SYNC_RETRY_MAX};
+
+    assert_eq!(sync_retry_delay(1), SYNC_RETRY_BASE);
+    assert_eq!(sync_retry_delay(2), SYNC_RETRY_BASE * 2);
+    assert_eq!(sync_retry_delay(3), SYNC_RETRY_BASE * 4);
+    assert_eq!(sync_retry_delay(20), SYNC_RETRY_MAX);
+    assert_eq!(sync_retry_delay(1000), SYNC_RETRY_MAX);
+}
+
+#[actix_web::test]
+async fn test_failed_schedule_sync_backs_off_until_changed() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let update = |monday: f64| {
+        test::TestRequest::post()
+            .uri("/api/schedule/update")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "monday": monday,
+                "tuesday": 2.0,
+                "wednesday": 2.0,
+                "thursday": 2.0,
+                "friday": 2.0,
+                "saturday": 2.0,
+                "sunday": 2.0
+            }))
+            .to_request()
+    };
+    let status = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/schedule/{}", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    assert_eq!(
+        test::call_service(&app, update(2.0)).await.status(),
+        StatusCode::OK
+    );
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], 0);
+    assert!(body["next_sync_attempt"].is_null());
+
+    // The test user has no valid host, so the background sync fails
+    let scheduler = test_app.scheduler();
+    scheduler.run_once().await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], 1);
+    let next_attempt = body["next_sync_attempt"].as_str().unwrap().to_string();
+    assert!(chrono::DateTime::parse_from_rfc3339(&next_attempt).unwrap() > chrono::Utc::now());
+
+    // Still backing off: the next pass leaves the schedule alone
+    scheduler.run_once().await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], 1);
+    assert_eq!(body["next_sync_attempt"], next_attempt);
+
+    // Changing the schedule starts over
+    assert_eq!(
+        test::call_service(&app, update(3.0)).await.status(),
+        StatusCode::OK
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], 0);
+    assert!(body["next_sync_attempt"].is_null());
+}
+
+#[actix_web::test]
+async fn test_lockout_command_and_parsing() {
+    use timekpr_ui_rust::models::{LockoutAction, LockoutSettings, TimekprConfig};
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let lock = LockoutSettings {
+        action: LockoutAction::Lock,
+        wakeup_from: None,
+        wakeup_to: None,
+    };
+    assert!(lock.validate().is_ok());
+    assert_eq!(
+        SSHClient::lockout_command("kid", &lock),
+        "timekpra --setlockouttype kid 'lock'"
+    );
+
+    let suspend_wake = LockoutSettings {
+        action: LockoutAction::SuspendWake,
+        wakeup_from: Some(7),
+        wakeup_to: Some(18),
+    };
+    assert!(suspend_wake.validate().is_ok());
+    assert_eq!(
+        SSHClient::lockout_command("kid", &suspend_wake),
+        "timekpra --setlockouttype kid 'suspendwake;7;18'"
+    );
+
+    // Wake-up hours belong to suspendwake only and must be a valid range
+    assert!(LockoutSettings {
+        wakeup_from: Some(7),
+        wakeup_to: Some(18),
+        ..lock.clone()
+    }
+    .validate()
+    .is_err());
+    assert!(LockoutSettings {
+        wakeup_to: None,
+        ..suspend_wake.clone()
+    }
+    .validate()
+    .is_err());
+    assert!(LockoutSettings {
+        wakeup_from: Some(19),
+        ..suspend_wake.clone()
+    }
+    .validate()
+    .is_err());
+    assert!(LockoutSettings {
+        wakeup_to: Some(24),
+        ..suspend_wake
+    }
+    .validate()
+    .is_err());
+
+    let config = TimekprConfig::parse(
+        "kid",
+        "LOCKOUT_TYPE: terminate\nWAKEUP_HOUR_INTERVAL: 0;23\nACTUAL_TIME_LEFT_DAY: 60\n",
+    );
+    assert_eq!(config.lockout_type.as_deref(), Some("terminate"));
+    assert_eq!(config.wakeup_hour_interval.as_deref(), Some("0;23"));
+    assert!(config.lockout_supported());
+    assert!(!TimekprConfig::parse("kid", "ACTUAL_TIME_LEFT_DAY: 60\n").lockout_supported());
+}
+
+#[actix_web::test]
+async fn test_lockout_stored_and_queued() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let update = |body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/lockout", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+    let get = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/lockout", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, get()).await;
+    assert!(body["settings"].is_null());
+
+    let resp = test::call_service(&app, update(json!({"action": "suspendwake"}))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, update(json!({"action": "reboot"}))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(
+        &app,
+        update(json!({"action": "suspendwake", "wakeup_from": 7, "wakeup_to": 18})),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, get()).await;
+    assert_eq!(body["settings"]["action"], "suspendwake");
+    assert_eq!(body["settings"]["wakeup_from"], 7);
+    assert_eq!(body["is_synced"], false);
+
+    // Pending lockout settings keep the user from counting as fully synced
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["lockout"]["settings"]["action"], "suspendwake");
+    assert_eq!(body["fully_synced"], false);
+
+    // Pushed by the scheduler once the host is back and reports lockout types
+    test_app.ssh.set_userinfo("LOCKOUT_TYPE: terminate");
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    let pushed = test_app.ssh.lockout.lock().unwrap().clone();
+    assert_eq!(pushed.len(), 1);
+    assert_eq!(pushed[0].0, "testuser");
+    assert_eq!(pushed[0].1.wakeup_to, Some(18));
+    let body: serde_json::Value = test::call_and_read_body_json(&app, get()).await;
+    assert_eq!(body["is_synced"], true);
+
+    let req = test::TestRequest::post()
+        .uri("/api/user/99999/lockout")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({"action": "lock"}))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn test_schedule_save_is_all_or_nothing() {
+    use timekpr_ui_rust::models::{TimeInterval, WeeklyTimeIntervals};
+
+    let test_app = TestApp::new().await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let schedule_service = ScheduleService::new(
+        Arc::new(SqliteScheduleRepository::new(test_app.pool.clone())),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    let hours = |value: f64| WeeklyHours {
+        monday: value,
+        tuesday: value,
+        wednesday: value,
+        thursday: value,
+        friday: value,
+        saturday: value,
+        sunday: value,
+    };
+    schedule_service
+        .update_schedule(user_id, hours(2.0))
+        .await
+        .unwrap();
+
+    // Fail the Tuesday interval write after the hours and Monday have been written
+    sqlx::query(
+        "CREATE TRIGGER fail_tuesday_interval BEFORE INSERT ON user_daily_time_interval
+         WHEN NEW.day_of_week = 2
+         BEGIN SELECT RAISE(ABORT, 'injected failure'); END",
+    )
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+
+    let intervals = WeeklyTimeIntervals {
+        monday: vec![TimeInterval::new("08:00".to_string(), "20:00".to_string()).unwrap()],
+        tuesday: vec![TimeInterval::new("09:00".to_string(), "18:00".to_string()).unwrap()],
+        ..Default::default()
+    };
+    let result = schedule_service
+        .update_schedule_with_intervals(user_id, hours(5.0), intervals)
+        .await;
+    assert!(result.is_err());
+
+    // Nothing of the failed save is visible
+    let repository = SqliteScheduleRepository::new(test_app.pool.clone());
+    let schedule = ScheduleRepository::find_by_user_id(&repository, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(schedule.hours.monday, 2.0);
+    assert_eq!(schedule.intervals.monday, vec![TimeInterval::default()]);
+    assert_eq!(schedule.intervals.tuesday, vec![TimeInterval::default()]);
+}
+
+#[actix_web::test]
+async fn test_schedule_minutes_are_exact_on_the_wire() {
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let update = |body: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/api/schedule/update")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(body)
+            .to_request()
+    };
+
+    // Minutes take precedence over hours; 50 minutes is not exact as hours
+    let resp = test::call_service(
+        &app,
+        update(json!({
+            "user_id": user_id,
+            "monday": 8.0,
+            "monday_minutes": 90,
+            "tuesday_minutes": 50,
+            "wednesday": 2.0,
+            "thursday": 0.0,
+            "friday": 0.0,
+            "saturday_minutes": 0,
+            "sunday": 0.0
+        })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    let service = ScheduleService::new(
+        repository,
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    let (schedule_dict, _) = service.prepare_sync_data(&schedule);
+    let (allowed_days, time_limits) = SSHClient::time_limit_arguments(&schedule_dict);
+    assert_eq!(allowed_days, "1;2;3");
+    assert_eq!(time_limits, "5400;3000;7200");
+
+    // Out of range minutes are rejected
+    let resp = test::call_service(
+        &app,
+        update(json!({
+            "user_id": user_id,
+            "monday": 1.0,
+            "tuesday": 1.0,
+            "wednesday": 1.0,
+            "thursday": 1.0,
+            "friday_minutes": 1441,
+            "saturday": 1.0,
+            "sunday": 1.0
+        })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["message"],
+        "Friday minutes must be between 0 and 1440, got 1441"
+    );
+}
+
+#[actix_web::test]
+async fn test_allowed_days() {
+    use timekpr_ui_rust::models::TimekprConfig;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let allowed_days = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/allowed-days", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Nothing to report before a schedule exists
+    let resp = test::call_service(&app, allowed_days()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    ScheduleService::new(
+        repository,
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    )
+    .update_schedule(
+        user_id,
+        WeeklyHours {
+            monday: 2.0,
+            tuesday: 2.0,
+            wednesday: 2.0,
+            thursday: 2.0,
+            friday: 3.0,
+            saturday: 4.0,
+            sunday: 0.0,
+        },
+    )
+    .await
+    .unwrap();
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, allowed_days()).await;
+    assert_eq!(body["blocked_days"], json!(["sunday"]));
+    assert_eq!(body["allowed_days"].as_array().unwrap().len(), 6);
+    assert_eq!(body["days"][6]["day"], "sunday");
+    assert_eq!(body["days"][6]["allowed"], false);
+    assert!(body["days"][6]["time_limit"].is_null());
+    assert!(body["matches_host"].is_null());
+
+    // The host still allows every day
+    let config = TimekprConfig::parse("testuser", "ALLOWED_WEEKDAYS: 1;2;3;4;5;6;7");
+    sqlx::query("UPDATE managed_users SET last_config = ? WHERE id = ?")
+        .bind(config.to_json())
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, allowed_days()).await;
+    assert_eq!(body["days"][6]["host_allowed"], true);
+    assert_eq!(body["days"][0]["host_allowed"], true);
+    assert_eq!(body["matches_host"], false);
+}
+
+#[actix_web::test]
+async fn test_block_days() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let block = |days: serde_json::Value| {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/block-days", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "days": days }))
+            .to_request()
+    };
+
+    // Nothing to block without a schedule
+    let resp = test::call_service(&app, block(json!(["monday"]))).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    let service = ScheduleService::new(
+        repository.clone(),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    service
+        .update_schedule(
+            user_id,
+            WeeklyHours {
+                monday: 2.0,
+                tuesday: 2.0,
+                wednesday: 2.0,
+                thursday: 2.0,
+                friday: 3.0,
+                saturday: 4.0,
+                sunday: 4.0,
+            },
+        )
+        .await
+        .unwrap();
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    let (pushed, _) = service.prepare_sync_data(&schedule);
+    service.mark_as_synced(user_id, &pushed).await.unwrap();
+
+    // A weekly bonus on Monday and an override on the next Tuesday would allow them again
+    sqlx::query(
+        "INSERT INTO user_recurring_bonus (user_id, day_of_week, minutes) VALUES (?, 1, 30)",
+    )
+    .bind(user_id)
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+    let next_tuesday = (1..=7)
+        .map(|days| service.today() + chrono::Duration::days(days))
+        .find(|date| DayOfWeek::from_date(*date) == DayOfWeek::Tuesday)
+        .unwrap();
+    repository
+        .save_limit_override(user_id, next_tuesday, 5.0)
+        .await
+        .unwrap();
+
+    let resp = test::call_service(&app, block(json!([]))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let resp = test::call_service(&app, block(json!(["someday"]))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, block(json!(["tuesday", "monday"]))).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["message"], "Blocked Monday, Tuesday for testuser");
+    assert_eq!(body["schedule"]["hours"]["monday"], 0.0);
+    assert_eq!(body["schedule"]["hours"]["tuesday"], 0.0);
+    assert_eq!(body["schedule"]["hours"]["friday"], 3.0);
+    assert_eq!(body["days"][0]["allowed"], false);
+    assert_eq!(body["days"][1]["allowed"], false);
+    assert_eq!(body["days"][2]["allowed"], true);
+    assert_eq!(body["needs_sync"], true);
+
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert!(!schedule.is_synced);
+    assert_eq!(schedule.hours.wednesday, 2.0);
+    assert_eq!(
+        repository
+            .find_recurring_bonus(user_id, DayOfWeek::Monday)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        repository
+            .find_limit_override(user_id, next_tuesday)
+            .await
+            .unwrap(),
+        None
+    );
+    assert_eq!(
+        service
+            .effective_limit_on(user_id, next_tuesday)
+            .await
+            .unwrap()
+            .unwrap_or(0),
+        0
+    );
+}
+
+#[actix_web::test]
+async fn test_extend_today_reverts_tomorrow() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let extend = |minutes: i64| {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/extend-today", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "minutes": minutes }))
+            .to_request()
+    };
+
+    // Nothing to extend without a schedule
+    let resp = test::call_service(&app, extend(60)).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    let service = ScheduleService::new(
+        repository.clone(),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    let two_hours = WeeklyHours {
+        monday: 2.0,
+        tuesday: 2.0,
+        wednesday: 2.0,
+        thursday: 2.0,
+        friday: 2.0,
+        saturday: 2.0,
+        sunday: 2.0,
+    };
+    service.update_schedule(user_id, two_hours).await.unwrap();
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    let (pushed, _) = service.prepare_sync_data(&schedule);
+    service.mark_as_synced(user_id, &pushed).await.unwrap();
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, extend(60)).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["limit_seconds"], 3 * 3600);
+    assert_eq!(body["date"], service.today().to_string());
+
+    // Extensions add up, and the schedule is pushed again with the extended limit
+    let body: serde_json::Value = test::call_and_read_body_json(&app, extend(30)).await;
+    assert_eq!(body["limit_seconds"], 3 * 3600 + 1800);
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert!(!schedule.is_synced);
+    let (schedule_dict, _) = service
+        .prepare_effective_sync_data(&schedule)
+        .await
+        .unwrap();
+    let today = DayOfWeek::from_date(service.today());
+    assert_eq!(schedule_dict[today.name()], 3.5);
+
+    // Tomorrow is back to the weekly hours
+    let tomorrow = service.today() + chrono::Duration::days(1);
+    assert_eq!(
+        service.effective_limit_on(user_id, tomorrow).await.unwrap(),
+        Some(2 * 3600)
+    );
+
+    let resp = test::call_service(&app, extend(0)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_extend_today_follows_timezone_setting() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let service = ScheduleService::new(
+        Arc::new(SqliteScheduleRepository::new(test_app.pool.clone())),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    service
+        .update_schedule(
+            user_id,
+            WeeklyHours {
+                monday: 2.0,
+                tuesday: 2.0,
+                wednesday: 2.0,
+                thursday: 2.0,
+                friday: 2.0,
+                saturday: 2.0,
+                sunday: 2.0,
+            },
+        )
+        .await
+        .unwrap();
+
+    // UTC+14 and UTC-11 are a day or more apart, so "today" differs from UTC in at least one
+    for (timezone, offset_hours) in [("Pacific/Kiritimati", 14), ("Pacific/Pago_Pago", -11)] {
+        let req = test::TestRequest::post()
+            .uri("/api/settings/timezone")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "timezone": timezone }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/api/user/{}/extend-today", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "minutes": 10 }))
+            .to_request();
+        let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+        let expected = (chrono::Utc::now() + chrono::Duration::hours(offset_hours)).date_naive();
+        assert_eq!(body["date"], expected.to_string(), "{}", timezone);
+    }
+}
+
+#[actix_web::test]
+async fn test_extended_limit_pushed_again_after_reset() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.ssh.set_online(true);
+
+    let repository = Arc::new(SqliteScheduleRepository::new(test_app.pool.clone()));
+    let service = ScheduleService::new(
+        repository.clone(),
+        Arc::new(SqliteUserRepository::new(test_app.pool.clone())),
+    );
+    let two_hours = WeeklyHours {
+        monday: 2.0,
+        tuesday: 2.0,
+        wednesday: 2.0,
+        thursday: 2.0,
+        friday: 2.0,
+        saturday: 2.0,
+        sunday: 2.0,
+    };
+    service.update_schedule(user_id, two_hours).await.unwrap();
+    let plain = (
+        "testuser".to_string(),
+        "1;2;3;4;5;6;7".to_string(),
+        "7200;7200;7200;7200;7200;7200;7200".to_string(),
+    );
+    let last_push = || test_app.ssh.time_limits.lock().unwrap().last().cloned();
+    let pushes = || test_app.ssh.time_limits.lock().unwrap().len();
+    // Move everything stored for today back a day, as if the reset had passed
+    let cross_reset = || async {
+        sqlx::query("UPDATE user_limit_override SET date = date(date, '-1 day')")
+            .execute(&test_app.pool)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE user_weekly_schedule SET synced_day = date(synced_day, '-1 day')")
+            .execute(&test_app.pool)
+            .await
+            .unwrap();
+    };
+
+    test_app.scheduler().run_once().await;
+    assert_eq!(last_push(), Some(plain.clone()));
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/extend-today", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "minutes": 60 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    test_app.scheduler().run_once().await;
+    assert_eq!(pushes(), 2);
+    assert!(last_push().unwrap().2.contains("10800"));
+
+    // Same day: nothing more to push
+    test_app.scheduler().run_once().await;
+    assert_eq!(pushes(), 2);
+
+    // After the reset the plain limit goes out again
+    cross_reset().await;
+    test_app.scheduler().run_once().await;
+    assert_eq!(pushes(), 3);
+    assert_eq!(last_push(), Some(plain.clone()));
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert!(schedule.is_synced);
+    assert_eq!(schedule.synced_day, Some(service.today()));
+
+    // A reset without overrides or bonuses needs no push
+    cross_reset().await;
+    test_app.scheduler().run_once().await;
+    assert_eq!(pushes(), 3);
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert!(schedule.is_synced);
+    assert_eq!(schedule.synced_day, Some(service.today()));
+}
+
+#[actix_web::test]
+async fn test_import_timekpr_config() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let import = |config: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/import-timekpr", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "config": config }))
+            .to_request()
+    };
+
+    let config = "\
+[DOCUMENTATION]
+# user specific configuration
+
+[USER]
+ALLOWED_HOURS_1 = 7;8;9;10;11;12;13;14;15;16;17;18;19;20;21
+ALLOWED_HOURS_2 = 15;16;17;18;19[0-30]
+ALLOWED_HOURS_3 = 8;9;17;18
+ALLOWED_HOURS_4 = 0;1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18;19;20;21;22;23
+ALLOWED_HOURS_5 = 6[30-59];7;8
+ALLOWED_HOURS_6 = 0;1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18;19;20;21;22;23
+ALLOWED_HOURS_7 = 0;1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18;19;20;21;22;23
+ALLOWED_WEEKDAYS = 1;2;3;4;5;6
+LIMITS_PER_WEEKDAYS = 7200;5400;3600;3600;10800;14400
+LIMIT_PER_WEEK = 604800
+";
+    let resp = test::call_service(&app, import(config)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["is_synced"], true);
+    let hours = &body["schedule"]["hours"];
+    assert_eq!(hours["monday"], 2.0);
+    assert_eq!(hours["tuesday"], 1.5);
+    assert_eq!(hours["saturday"], 4.0);
+    // Not in ALLOWED_WEEKDAYS
+    assert_eq!(hours["sunday"], 0.0);
+    let intervals = &body["schedule"]["interval_lists"];
+    assert_eq!(
+        intervals["monday"],
+        json!([{ "start_time": "07:00", "end_time": "22:00" }])
+    );
+    assert_eq!(
+        intervals["tuesday"],
+        json!([{ "start_time": "15:00", "end_time": "19:30" }])
+    );
+    assert_eq!(
+        intervals["thursday"],
+        json!([{ "start_time": "00:00", "end_time": "23:59" }])
+    );
+    assert_eq!(
+        intervals["friday"],
+        json!([{ "start_time": "06:30", "end_time": "09:00" }])
+    );
+    // Two separate blocks stay two intervals
+    assert_eq!(
+        intervals["wednesday"],
+        json!([
+            { "start_time": "08:00", "end_time": "10:00" },
+            { "start_time": "17:00", "end_time": "19:00" }
+        ])
+    );
+    assert_eq!(body["warnings"], json!([]));
+
+    // Stored as already applied on the host
+    let repository = SqliteScheduleRepository::new(test_app.pool.clone());
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert!(schedule.is_synced);
+    assert!(schedule.last_synced.is_some());
+    assert_eq!(schedule.hours.friday, 3.0);
+
+    // `--userinfo` output and a single daily limit are read too
+    let userinfo = "ALLOWED_WEEKDAYS: 6;7\nLIMITS_PER_WEEKDAYS: 3600\nALLOWED_HOURS_6: 10;11\n";
+    let body: serde_json::Value = test::call_and_read_body_json(&app, import(userinfo)).await;
+    assert_eq!(body["schedule"]["hours"]["saturday"], 1.0);
+    assert_eq!(body["schedule"]["hours"]["sunday"], 1.0);
+    assert_eq!(body["schedule"]["hours"]["monday"], 0.0);
+    assert_eq!(
+        body["schedule"]["intervals"]["saturday"]["end_time"],
+        "12:00"
+    );
+
+    for invalid in [
+        "LIMITS_PER_WEEKDAYS = 3600",
+        "ALLOWED_WEEKDAYS = 1;9\nLIMITS_PER_WEEKDAYS = 3600",
+        "ALLOWED_WEEKDAYS = 1;2\nLIMITS_PER_WEEKDAYS = 3600;3600\nALLOWED_HOURS_1 = 25",
+        "ALLOWED_WEEKDAYS = 1;2;3\nLIMITS_PER_WEEKDAYS = 3600;3600",
+    ] {
+        let resp = test::call_service(&app, import(invalid)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", invalid);
+    }
+    // A rejected import leaves the earlier one in place
+    let schedule = repository.find_by_user_id(user_id).await.unwrap().unwrap();
+    assert_eq!(schedule.hours.saturday, 1.0);
+}
+
+#[actix_web::test]
+async fn test_all_zero_schedule_syncs_as_blocked_week() {
+    use timekpr_ui_rust::ssh::SSHClient;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 0.0,
+            "tuesday": 0.0,
+            "wednesday": 0.0,
+            "thursday": 0.0,
+            "friday": 0.0,
+            "saturday": 0.0,
+            "sunday": 0.0
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+
+    // Every day allowed with nothing to spend, instead of a push that never succeeds
+    let repository = SqliteScheduleRepository::new(test_app.pool.clone());
+    let schedule = ScheduleRepository::find_by_user_id(&repository, user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(schedule.is_synced);
+    assert_eq!(schedule.sync_attempts, 0);
+    assert_eq!(
+        *test_app.ssh.time_limits.lock().unwrap(),
+        vec![(
+            "testuser".to_string(),
+            "1;2;3;4;5;6;7".to_string(),
+            "0;0;0;0;0;0;0".to_string()
+        )]
+    );
+
+    // A schedule without any days still pushes nothing
+    let (allowed_days, time_limits) = SSHClient::time_limit_arguments(&Default::default());
+    assert_eq!(allowed_days, "");
+    assert_eq!(time_limits, "");
+}
+
+#[actix_web::test]
+async fn test_schedule_sync_skips_host_already_matching() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let update_schedule = |hours: f64| {
+        let token = token.clone();
+        let app = &app;
+        async move {
+            let req = test::TestRequest::post()
+                .uri("/api/schedule/update")
+                .insert_header(("Authorization", format!("Bearer {}", token)))
+                .set_json(json!({
+                    "user_id": user_id,
+                    "monday": hours,
+                    "tuesday": hours,
+                    "wednesday": hours,
+                    "thursday": hours,
+                    "friday": hours,
+                    "saturday": hours,
+                    "sunday": hours
+                }))
+                .to_request();
+            assert_eq!(test::call_service(app, req).await.status(), StatusCode::OK);
+        }
+    };
+    let repository = SqliteScheduleRepository::new(test_app.pool.clone());
+    let is_synced = || async {
+        ScheduleRepository::find_by_user_id(&repository, user_id)
+            .await
+            .unwrap()
+            .unwrap()
+            .is_synced
+    };
+
+    // The host already enforces two hours a day, all day long; one day lists its last
+    // hour with explicit minutes
+    let all_day = (0..24)
+        .map(|hour| hour.to_string())
+        .collect::<Vec<_>>()
+        .join(";");
+    let mut userinfo = String::from(
+        "ALLOWED_WEEKDAYS: 1;2;3;4;5;6;7\nLIMITS_PER_WEEKDAYS: 7200;7200;7200;7200;7200;7200;7200\n",
+    );
+    for day in 1..=7 {
+        let hours = if day == 7 {
+            format!(
+                "{}[0-59]",
+                (0..23).map(|hour| format!("{};", hour)).collect::<String>() + "23"
+            )
+        } else {
+            all_day.clone()
+        };
+        userinfo.push_str(&format!("ALLOWED_HOURS_{}: {}\n", day, hours));
+    }
+    test_app.ssh.set_userinfo(&userinfo);
+    test_app.ssh.set_online(true);
+
+    update_schedule(2.0).await;
+    assert!(!is_synced().await);
+    test_app.scheduler().run_once().await;
+
+    assert!(is_synced().await);
+    assert!(test_app.ssh.time_limits.lock().unwrap().is_empty());
+    assert!(test_app.ssh.allowed_hours.lock().unwrap().is_empty());
+
+    // A different target is still pushed
+    update_schedule(3.0).await;
+    test_app.scheduler().run_once().await;
+
+    assert!(is_synced().await);
+    assert_eq!(
+        *test_app.ssh.time_limits.lock().unwrap(),
+        vec![(
+            "testuser".to_string(),
+            "1;2;3;4;5;6;7".to_string(),
+            "10800;10800;10800;10800;10800;10800;10800".to_string()
+        )]
+    );
+    assert_eq!(
+        *test_app.ssh.allowed_hours.lock().unwrap(),
+        vec!["testuser".to_string()]
+    );
+}
+
+#[actix_web::test]
+async fn test_repeated_sync_failures_wait_for_manual_sync() {
+    use timekpr_ui_rust::services::DEFAULT_MAX_SYNC_FAILURES;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 2.0,
+            "sunday": 2.0
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let status = || {
+        test::TestRequest::get()
+            .uri(&format!("/api/schedule/{}", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // One failure short of the limit, with the backoff run out
+    sqlx::query("UPDATE user_weekly_schedule SET sync_attempts = ?, next_sync_attempt = NULL WHERE user_id = ?")
+        .bind(DEFAULT_MAX_SYNC_FAILURES - 1)
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.scheduler().run_once().await;
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], DEFAULT_MAX_SYNC_FAILURES);
+    assert_eq!(body["needs_attention"], true);
+    assert!(body["next_sync_attempt"].is_null());
+    let events = test_app.event_log.recent(10);
+    assert!(events.iter().any(|event| matches!(
+        &event.kind,
+        EventKind::SyncHalted { user_id: id, failures, .. }
+            if *id == user_id && *failures == DEFAULT_MAX_SYNC_FAILURES
+    )));
+
+    let req = test::TestRequest::get()
+        .uri("/api/dashboard")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["users"][0]["needs_attention"], true);
+
+    // Not retried in the background even once the host answers
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    assert!(test_app.ssh.time_limits.lock().unwrap().is_empty());
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["is_synced"], false);
+    assert_eq!(body["needs_attention"], true);
+
+    let sync = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/sync", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // A manual sync that doesn't reach the host still re-arms it
+    test_app.ssh.set_online(false);
+    assert_eq!(
+        test::call_service(&app, sync()).await.status(),
+        StatusCode::ACCEPTED
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["sync_attempts"], 0);
+    assert_eq!(body["needs_attention"], false);
+
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["is_synced"], true);
+    assert_eq!(test_app.ssh.time_limits.lock().unwrap().len(), 1);
+
+    // One that does pushes the schedule itself and clears the flag right away
+    sqlx::query(
+        "UPDATE user_weekly_schedule SET is_synced = 0, sync_attempts = ? WHERE user_id = ?",
+    )
+    .bind(DEFAULT_MAX_SYNC_FAILURES)
+    .bind(user_id)
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["needs_attention"], true);
+    let resp = test::call_service(&app, sync()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], true);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, status()).await;
+    assert_eq!(body["is_synced"], true);
+    assert_eq!(body["sync_attempts"], 0);
+    assert_eq!(body["needs_attention"], false);
+    assert_eq!(test_app.ssh.time_limits.lock().unwrap().len(), 2);
+    assert_eq!(test_app.ssh.allowed_hours.lock().unwrap().len(), 2);
+
+    let req = test::TestRequest::post()
+        .uri("/api/user/9999/sync")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
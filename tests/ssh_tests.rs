@@ -0,0 +1,488 @@
+use async_trait::async_trait;
+use russh::server::{self, Auth, Msg, Session};
+use russh::{Channel, ChannelId, CryptoVec};
+use russh_keys::agent::client::AgentClient;
+use russh_keys::key::{KeyPair, PublicKey};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use timekpr_ui_rust::models::{TimekprConfig, UserStatus};
+use timekpr_ui_rust::ssh::{
+    is_transient_output, retry_transient, RetryPolicy, SSHClient, SshCommandError,
+};
+use timekpr_ui_rust::ssh_session::{run_command, SessionTarget};
+use tokio::net::TcpListener;
+
+const USERINFO: &str = "# timekpr-nExT user info\n\
+ALLOWED_WEEKDAYS: 1;2;3;4;5;6;7\n\
+LIMITS_PER_WEEKDAYS: 7200;7200;7200;7200;7200;10800;10800\n\
+TIME_LEFT_DAY: 4021\n\
+ACTUAL_TIME_LEFT_DAY: 3600\n\
+ACTUAL_TIME_SPENT_DAY: 1200\n\
+LOCKOUT_TYPE: terminate\n";
+
+/// Answers `timekpra --userinfo kid` and `--setallowedhours` like a host running timekpr,
+/// and fails anything else
+#[derive(Clone)]
+struct FakeHost {
+    authorized: PublicKey,
+    connections: Arc<AtomicUsize>,
+    commands: Arc<Mutex<Vec<String>>>,
+}
+
+#[async_trait]
+impl server::Handler for FakeHost {
+    type Error = russh::Error;
+
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> Result<Auth, Self::Error> {
+        if user == "timekpr-remote" && *public_key == self.authorized {
+            Ok(Auth::Accept)
+        } else {
+            Ok(Auth::Reject {
+                proceed_with_methods: None,
+            })
+        }
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        _channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> Result<bool, Self::Error> {
+        Ok(true)
+    }
+
+    async fn exec_request(
+        &mut self,
+        channel: ChannelId,
+        data: &[u8],
+        session: &mut Session,
+    ) -> Result<(), Self::Error> {
+        let command = String::from_utf8_lossy(data).into_owned();
+        self.commands.lock().unwrap().push(command.clone());
+        let exit_status = if command.starts_with("timekpra --setallowedhours kid ") {
+            0
+        } else if command == "timekpra --userinfo kid" {
+            session.data(channel, CryptoVec::from_slice(USERINFO.as_bytes()));
+            session.extended_data(
+                channel,
+                1,
+                CryptoVec::from_slice(b"DeprecationWarning: --userinfo is deprecated\n"),
+            );
+            0
+        } else {
+            session.extended_data(
+                channel,
+                1,
+                CryptoVec::from_slice(b"User \"nobody\" is not found\n"),
+            );
+            1
+        };
+        session.exit_status_request(channel, exit_status);
+        session.eof(channel);
+        session.close(channel);
+        Ok(())
+    }
+}
+
+/// Serve SSH on a local port
+async fn start_fake_host(authorized: PublicKey) -> (u16, FakeHost) {
+    let config = Arc::new(server::Config {
+        keys: vec![KeyPair::generate_ed25519().unwrap()],
+        auth_rejection_time: Duration::from_millis(10),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let host = FakeHost {
+        authorized,
+        connections: Arc::new(AtomicUsize::new(0)),
+        commands: Arc::new(Mutex::new(Vec::new())),
+    };
+    let handler = host.clone();
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            handler.connections.fetch_add(1, Ordering::SeqCst);
+            let config = config.clone();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                if let Ok(session) = server::run_stream(config, socket, handler).await {
+                    let _ = session.await;
+                }
+            });
+        }
+    });
+    (port, host)
+}
+
+fn write_key(dir: &tempfile::TempDir, name: &str, key: &KeyPair) -> String {
+    let path = dir.path().join(name);
+    russh_keys::encode_pkcs8_pem(key, std::fs::File::create(&path).unwrap()).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+#[actix_web::test]
+async fn test_russh_transport_runs_timekpr_commands() {
+    let key = KeyPair::generate_ed25519().unwrap();
+    let (port, _) = start_fake_host(key.clone_public_key().unwrap()).await;
+    let dir = tempfile::tempdir().unwrap();
+    let key_path = write_key(&dir, "id_ed25519", &key);
+    let target = SessionTarget {
+        host: "127.0.0.1",
+        port,
+        user: "timekpr-remote",
+        key_path: Some(&key_path),
+        passphrase: None,
+        agent_socket: None,
+    };
+    let timeout = Duration::from_secs(5);
+
+    let output = run_command(&target, timeout, timeout, "timekpra --userinfo kid")
+        .await
+        .unwrap();
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("DeprecationWarning"));
+    let config = TimekprConfig::parse("kid", &String::from_utf8_lossy(&output.stdout));
+    assert_eq!(config.time_left_day, Some(3600));
+    assert_eq!(config.time_spent_day, Some(1200));
+    assert_eq!(config.lockout_type.as_deref(), Some("terminate"));
+    assert_eq!(config.allowed_weekdays().map(|days| days.len()), Some(7));
+
+    // A failing command keeps its exit code and stderr
+    let output = run_command(&target, timeout, timeout, "timekpra --userinfo nobody")
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("is not found"));
+
+    // A key the host doesn't know is rejected the way `ssh` words it
+    let stranger = write_key(&dir, "stranger", &KeyPair::generate_ed25519().unwrap());
+    let error = run_command(
+        &SessionTarget {
+            key_path: Some(&stranger),
+            ..target
+        },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(error, SshCommandError::Session(_)));
+    assert!(error.to_string().contains("Permission denied (publickey)"));
+
+    // Nothing listening: a connection error rather than a command result
+    let error = run_command(
+        &SessionTarget { port: 1, ..target },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().starts_with("SSH connection failed"));
+}
+
+/// Serve an ssh-agent holding `keys` on a socket in `dir`
+async fn start_agent(dir: &tempfile::TempDir, keys: &[&KeyPair]) -> String {
+    let path = dir.path().join("agent.sock");
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+    let connections = futures::stream::unfold(listener, |listener| async move {
+        let connection = listener.accept().await.map(|(stream, _)| stream);
+        Some((connection, listener))
+    });
+    tokio::spawn(russh_keys::agent::server::serve(Box::pin(connections), ()));
+
+    let mut agent = AgentClient::connect_uds(&path).await.unwrap();
+    for key in keys {
+        agent.add_identity(key, &[]).await.unwrap();
+    }
+    path.to_string_lossy().into_owned()
+}
+
+#[actix_web::test]
+async fn test_russh_transport_authenticates_with_agent_or_passphrase() {
+    let key = KeyPair::generate_ed25519().unwrap();
+    let (port, _) = start_fake_host(key.clone_public_key().unwrap()).await;
+    let dir = tempfile::tempdir().unwrap();
+    let timeout = Duration::from_secs(5);
+    let target = SessionTarget {
+        host: "127.0.0.1",
+        port,
+        user: "timekpr-remote",
+        key_path: None,
+        passphrase: None,
+        agent_socket: None,
+    };
+
+    // Agent only: no key file at all
+    let agent_socket = start_agent(&dir, &[&KeyPair::generate_ed25519().unwrap(), &key]).await;
+    let output = run_command(
+        &SessionTarget {
+            agent_socket: Some(&agent_socket),
+            ..target
+        },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap();
+    assert!(output.status.success());
+    let client = SSHClient::new("127.0.0.1")
+        .with_port(port)
+        .with_agent_socket(Some(&agent_socket));
+    let outputs = client
+        .run_batch(&["timekpra --userinfo kid".to_string()])
+        .await;
+    assert_eq!(outputs[0].0, 0);
+    let (status, _, config) = client.validate_user("kid").await;
+    assert_eq!(status, UserStatus::Ok);
+    assert_eq!(config.and_then(|config| config.time_left_day), Some(3600));
+
+    // A passphrase-protected key file, decrypted with the configured passphrase
+    let encrypted = dir.path().join("id_encrypted");
+    russh_keys::encode_pkcs8_pem_encrypted(
+        &key,
+        b"correct horse",
+        16,
+        std::fs::File::create(&encrypted).unwrap(),
+    )
+    .unwrap();
+    let encrypted = encrypted.to_string_lossy().into_owned();
+    let output = run_command(
+        &SessionTarget {
+            key_path: Some(&encrypted),
+            passphrase: Some("correct horse"),
+            ..target
+        },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap();
+    assert!(output.status.success());
+    let outputs = SSHClient::new("127.0.0.1")
+        .with_port(port)
+        .with_key_path(&encrypted)
+        .with_key_passphrase(Some("correct horse"))
+        .with_agent_socket(None)
+        .run_batch(&["timekpra --userinfo kid".to_string()])
+        .await;
+    assert_eq!(outputs[0].0, 0);
+
+    // Without the passphrase the key can't be loaded
+    let error = run_command(
+        &SessionTarget {
+            key_path: Some(&encrypted),
+            ..target
+        },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("Could not load SSH key"));
+
+    // An agent without the host's key falls back to the key file, as does a dead socket
+    let stranger_dir = tempfile::tempdir().unwrap();
+    let stranger_agent = start_agent(&stranger_dir, &[&KeyPair::generate_ed25519().unwrap()]).await;
+    let key_path = write_key(&dir, "id_ed25519", &key);
+    for socket in [stranger_agent.as_str(), "/nonexistent/agent.sock"] {
+        let output = run_command(
+            &SessionTarget {
+                key_path: Some(&key_path),
+                agent_socket: Some(socket),
+                ..target
+            },
+            timeout,
+            timeout,
+            "timekpra --userinfo kid",
+        )
+        .await
+        .unwrap();
+        assert!(output.status.success());
+    }
+
+    // Neither an accepted agent key nor a key file
+    let error = run_command(
+        &SessionTarget {
+            agent_socket: Some(&stranger_agent),
+            ..target
+        },
+        timeout,
+        timeout,
+        "timekpra --userinfo kid",
+    )
+    .await
+    .unwrap_err();
+    assert!(error.to_string().contains("Permission denied (publickey)"));
+}
+
+#[actix_web::test]
+async fn test_allowed_hours_share_one_connection() {
+    let key = KeyPair::generate_ed25519().unwrap();
+    let (port, host) = start_fake_host(key.clone_public_key().unwrap()).await;
+    let dir = tempfile::tempdir().unwrap();
+    let key_path = write_key(&dir, "id_ed25519", &key);
+    let client = SSHClient::new("127.0.0.1")
+        .with_port(port)
+        .with_key_path(&key_path);
+
+    let mut intervals: HashMap<String, Vec<(String, String)>> = [
+        "monday",
+        "tuesday",
+        "wednesday",
+        "thursday",
+        "friday",
+        "saturday",
+        "sunday",
+    ]
+    .iter()
+    .map(|day| {
+        (
+            day.to_string(),
+            vec![("16:30".to_string(), "19:45".to_string())],
+        )
+    })
+    .collect();
+    // Several ranges of a day go out as one hour list
+    intervals.insert(
+        "tuesday".to_string(),
+        vec![
+            ("07:00".to_string(), "09:00".to_string()),
+            ("15:00".to_string(), "18:30".to_string()),
+        ],
+    );
+
+    let results = client
+        .set_weekly_allowed_hours_per_day("kid", &intervals)
+        .await;
+    assert_eq!(results.len(), 7);
+    assert!(results.iter().all(|result| result.ok));
+    assert_eq!(host.connections.load(Ordering::SeqCst), 1);
+    let commands = host.commands.lock().unwrap().clone();
+    assert_eq!(commands.len(), 7);
+    assert_eq!(
+        commands[0],
+        "timekpra --setallowedhours kid 1 '16[30-59];17;18;19[0-45]'"
+    );
+    assert_eq!(
+        commands[1],
+        "timekpra --setallowedhours kid 2 '7;8;15;16;17;18[0-30]'"
+    );
+
+    // Exit code, stdout and stderr per command, in order
+    let outputs = client
+        .run_batch(&[
+            "timekpra --userinfo kid".to_string(),
+            "timekpra --userinfo nobody".to_string(),
+        ])
+        .await;
+    assert_eq!(host.connections.load(Ordering::SeqCst), 2);
+    assert_eq!(outputs.len(), 2);
+    assert_eq!(outputs[0].0, 0);
+    assert!(outputs[0].1.contains("ACTUAL_TIME_LEFT_DAY: 3600"));
+    assert_eq!(outputs[1].0, 1);
+    assert!(outputs[1].2.contains("is not found"));
+
+    // Every command fails alike when no session can be set up
+    let outputs = SSHClient::new("127.0.0.1")
+        .with_port(1)
+        .with_key_path(&key_path)
+        .with_retry_policy(RetryPolicy::none())
+        .run_batch(&["true".to_string(), "true".to_string()])
+        .await;
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs
+        .iter()
+        .all(|(code, _, stderr)| *code == 255 && stderr.starts_with("SSH connection failed")));
+}
+
+#[actix_web::test]
+async fn test_unreachable_host_is_retried_with_backoff() {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::{ExitStatus, Output};
+
+    let policy = RetryPolicy::default();
+    assert_eq!(policy.max_attempts, 3);
+    assert_eq!(policy.delay_after(1), Duration::from_millis(500));
+    assert_eq!(policy.delay_after(2), Duration::from_secs(1));
+    assert_eq!(policy.delay_after(3), Duration::from_secs(2));
+
+    let output = |code: i32, stderr: &str| Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: Vec::new(),
+        stderr: stderr.as_bytes().to_vec(),
+    };
+    let fast = RetryPolicy {
+        max_attempts: 3,
+        base_delay: Duration::from_millis(1),
+    };
+
+    // Fails to connect twice, then the third attempt goes through
+    let attempts = AtomicUsize::new(0);
+    let result = retry_transient(&fast, is_transient_output, || {
+        let attempt = attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        async move {
+            if attempt < 3 {
+                Err(SshCommandError::Connect("Connection refused".to_string()))
+            } else {
+                Ok(output(0, ""))
+            }
+        }
+    })
+    .await;
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert!(result.unwrap().status.success());
+
+    // A command timekpr rejected ran on the host and is not repeated
+    let attempts = AtomicUsize::new(0);
+    let result = retry_transient(&fast, is_transient_output, || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        async { Ok(output(1, "User \"nobody\" is not found")) }
+    })
+    .await;
+    assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    assert_eq!(result.unwrap().status.code(), Some(1));
+
+    // Neither is one that timed out after connecting, nor a rejected key
+    for error in [
+        SshCommandError::CommandTimeout(Duration::from_secs(1)),
+        SshCommandError::Session("Permission denied (publickey)".to_string()),
+    ] {
+        let attempts = AtomicUsize::new(0);
+        let error = std::sync::Mutex::new(Some(error));
+        let result = retry_transient(&fast, is_transient_output, || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let error = error.lock().unwrap().take();
+            async move { Err(error.unwrap()) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    // `ssh` reports an unreachable host through exit code 255
+    let attempts = AtomicUsize::new(0);
+    let result = retry_transient(&fast, is_transient_output, || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        async {
+            Ok(output(
+                255,
+                "ssh: connect to host 10.0.0.9 port 22: No route to host",
+            ))
+        }
+    })
+    .await;
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(result.unwrap().status.code(), Some(255));
+}
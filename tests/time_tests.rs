@@ -3,6 +3,7 @@ use serde_json::json;
 
 mod common;
 use common::TestApp;
+use timekpr_ui_rust::models::TimekprConfig;
 
 #[actix_web::test]
 async fn test_modify_time_add_success() {
@@ -23,7 +24,7 @@ async fn test_modify_time_add_success() {
         .to_request();
 
     let resp = test::call_service(&app, req).await;
-    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
 
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["success"], true);
@@ -50,7 +51,7 @@ async fn test_modify_time_subtract_success() {
         .to_request();
 
     let resp = test::call_service(&app, req).await;
-    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
 
     let body: serde_json::Value = test::read_body_json(resp).await;
     assert_eq!(body["success"], true);
@@ -202,3 +203,1217 @@ async fn test_modify_time_missing_fields() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 }
+
+#[actix_web::test]
+async fn test_get_usage_negative_days() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/usage?days=-5", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Clamped to just today
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["days"], 1);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_get_usage_days_clamped_to_maximum() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/usage?days=99999", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["days"], 365);
+    assert_eq!(body["data"].as_array().unwrap().len(), 365);
+}
+
+#[actix_web::test]
+async fn test_get_usage_day_ranges() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    for (days_ago, seconds) in [(0, 1800), (20, 7200), (40, 3600)] {
+        sqlx::query(
+            "INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, date('now', ?), ?)",
+        )
+        .bind(user_id)
+        .bind(format!("-{} days", days_ago))
+        .bind(seconds)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    }
+    let usage = |days: i32| {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage?days={}", user_id, days))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Just today
+    let body: serde_json::Value = test::call_and_read_body_json(&app, usage(1)).await;
+    assert_eq!(body["days"], 1);
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 1);
+    assert_eq!(data[0]["hours"], 0.5);
+
+    // A month, oldest first, with zero-hour days filling the gaps; day 40 is outside it
+    let body: serde_json::Value = test::call_and_read_body_json(&app, usage(30)).await;
+    assert_eq!(body["days"], 30);
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 30);
+    let hours: Vec<f64> = data
+        .iter()
+        .map(|day| day["hours"].as_f64().unwrap())
+        .collect();
+    assert_eq!(hours[29], 0.5);
+    assert_eq!(hours[9], 2.0);
+    assert_eq!(hours.iter().filter(|hours| **hours == 0.0).count(), 28);
+    let dates: Vec<&str> = data
+        .iter()
+        .map(|day| day["date"].as_str().unwrap())
+        .collect();
+    assert!(dates.windows(2).all(|pair| pair[0] < pair[1]));
+
+    // No days at all still returns today
+    let body: serde_json::Value = test::call_and_read_body_json(&app, usage(0)).await;
+    assert_eq!(body["days"], 1);
+}
+
+#[actix_web::test]
+async fn test_get_usage_aggregate() {
+    use chrono::{Datelike, Duration, Utc};
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    // Either side of the start of this month, plus today
+    let today = Utc::now().date_naive();
+    let first_of_month = today.with_day(1).unwrap();
+    let end_of_last_month = first_of_month - Duration::days(1);
+    for (date, seconds) in [
+        (end_of_last_month, 3600),
+        (first_of_month, 1800),
+        (today, 900),
+    ] {
+        sqlx::query(
+            "INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, ?, ?)
+             ON CONFLICT(user_id, date) DO UPDATE SET time_spent = time_spent + excluded.time_spent",
+        )
+        .bind(user_id)
+        .bind(date)
+        .bind(seconds)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    }
+    let aggregate = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage/aggregate?{}", user_id, query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // The last day of a month and the first of the next land in different months
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, aggregate("by=monthly&periods=2")).await;
+    assert_eq!(body["by"], "monthly");
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 2);
+    assert_eq!(
+        data[0]["period"],
+        end_of_last_month.format("%Y-%m").to_string()
+    );
+    assert_eq!(
+        data[0]["start"],
+        end_of_last_month.with_day(1).unwrap().to_string()
+    );
+    assert_eq!(data[0]["end"], end_of_last_month.to_string());
+    assert_eq!(data[0]["hours"], 1.0);
+    assert_eq!(data[1]["period"], today.format("%Y-%m").to_string());
+    assert_eq!(data[1]["start"], first_of_month.to_string());
+    assert_eq!(data[1]["hours"], 0.75);
+
+    // ISO weeks run Monday to Sunday, whatever month they fall in
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, aggregate("by=weekly&periods=10")).await;
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 10);
+    let mut expected = std::collections::BTreeMap::new();
+    for (date, seconds) in [
+        (end_of_last_month, 3600),
+        (first_of_month, 1800),
+        (today, 900),
+    ] {
+        let week = date.iso_week();
+        *expected
+            .entry(format!("{}-W{:02}", week.year(), week.week()))
+            .or_insert(0.0) += seconds as f64 / 3600.0;
+    }
+    for bucket in data {
+        let start =
+            chrono::NaiveDate::parse_from_str(bucket["start"].as_str().unwrap(), "%Y-%m-%d")
+                .unwrap();
+        assert_eq!(start.weekday(), chrono::Weekday::Mon);
+        assert_eq!(bucket["end"], (start + Duration::days(6)).to_string());
+        let hours = expected
+            .get(bucket["period"].as_str().unwrap())
+            .copied()
+            .unwrap_or(0.0);
+        assert_eq!(bucket["hours"].as_f64().unwrap(), hours, "{}", bucket);
+    }
+    let total: f64 = data
+        .iter()
+        .map(|bucket| bucket["hours"].as_f64().unwrap())
+        .sum();
+    assert_eq!(total, 1.75);
+
+    // Daily by default, a week of days with zero for days without usage
+    let body: serde_json::Value = test::call_and_read_body_json(&app, aggregate("")).await;
+    assert_eq!(body["by"], "daily");
+    let data = body["data"].as_array().unwrap();
+    assert_eq!(data.len(), 7);
+    assert_eq!(data[6]["period"], today.to_string());
+    assert_eq!(data[6]["start"], data[6]["end"]);
+
+    // Capped at the usage range, roughly a year
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, aggregate("by=monthly&periods=1000")).await;
+    assert_eq!(body["data"].as_array().unwrap().len(), 11);
+
+    for query in ["by=yearly", "periods=0", "by=weekly&periods=-1"] {
+        let resp = test::call_service(&app, aggregate(query)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", query);
+    }
+}
+
+#[actix_web::test]
+async fn test_get_usage_in_minutes() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    sqlx::query(
+        "INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, date('now'), 90)",
+    )
+    .bind(user_id)
+    .execute(&test_app.pool)
+    .await
+    .unwrap();
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/usage?unit=minutes", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["unit"], "minutes");
+    assert_eq!(body["data"][6]["value"], 1.5);
+    assert_eq!(body["data"][6]["hours"], 0.025);
+
+    // Default unit stays hours for existing clients
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/usage", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["unit"], "hours");
+    assert_eq!(body["data"][6]["value"], 0.025);
+}
+
+#[actix_web::test]
+async fn test_get_usage_formats() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    for (days_ago, seconds) in [(0, 1800), (2, 3600)] {
+        sqlx::query(
+            "INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, date('now', ?), ?)",
+        )
+        .bind(user_id)
+        .bind(format!("-{} days", days_ago))
+        .bind(seconds)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    }
+    let usage = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage?days=4{}", user_id, query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Every day of the range, zero where nothing was recorded
+    let objects: serde_json::Value = test::call_and_read_body_json(&app, usage("")).await;
+    assert_eq!(objects["format"], "objects");
+    let data = objects["data"].as_array().unwrap();
+    assert_eq!(data.len(), 4);
+    let hours: Vec<f64> = data
+        .iter()
+        .map(|day| day["hours"].as_f64().unwrap())
+        .collect();
+    assert_eq!(hours, vec![0.0, 1.0, 0.0, 0.5]);
+
+    let arrays: serde_json::Value =
+        test::call_and_read_body_json(&app, usage("&format=arrays&unit=minutes")).await;
+    assert_eq!(arrays["format"], "arrays");
+    assert_eq!(arrays["data"]["values"], json!([0.0, 60.0, 0.0, 30.0]));
+    assert_eq!(arrays["data"]["hours"], json!([0.0, 1.0, 0.0, 0.5]));
+    let labels: Vec<&serde_json::Value> = data.iter().map(|day| &day["date"]).collect();
+    assert_eq!(arrays["data"]["labels"], json!(labels));
+
+    let resp = test::call_service(&app, usage("&format=csv")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_get_usage_invalid_unit() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/usage?unit=weeks", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_modify_time_operation_aliases() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    for (operation, expected) in [("add", "+"), (" - ", "-")] {
+        let req = test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": operation,
+                "seconds": 600
+            }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+        // Queued in canonical form since the host is unreachable in tests
+        let stored: String =
+            sqlx::query_scalar("SELECT pending_time_operation FROM managed_users WHERE id = ?")
+                .bind(user_id)
+                .fetch_one(&test_app.pool)
+                .await
+                .unwrap();
+        assert_eq!(stored, expected);
+    }
+}
+
+#[actix_web::test]
+async fn test_modify_time_reason() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "operation": "+",
+            "seconds": 1800,
+            "reason": "  homework\nfinished  "
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["reason"], "homework finished");
+
+    // Stored with the queued adjustment and shown in the user detail
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["user"]["pending_time_reason"], "homework finished");
+
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "operation": "+",
+            "seconds": 1800,
+            "reason": "x".repeat(201)
+        }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn test_pending_adjustment_applied_when_host_returns() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    // Host offline: the adjustment is queued on the user
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "operation": "+",
+            "seconds": 900
+        }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["pending"], true);
+
+    let pending = || async {
+        sqlx::query_as::<_, (Option<i64>, Option<String>)>(
+            "SELECT pending_time_adjustment, pending_time_operation FROM managed_users WHERE id = ?",
+        )
+        .bind(user_id)
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap()
+    };
+    assert_eq!(pending().await, (Some(900), Some("+".to_string())));
+
+    // A pass while still offline leaves it queued
+    let scheduler = test_app.scheduler();
+    scheduler.run_once().await;
+    assert_eq!(pending().await, (Some(900), Some("+".to_string())));
+
+    // Back online: the next pass applies it and clears the queue
+    test_app.ssh.set_online(true);
+    scheduler.run_once().await;
+    assert_eq!(pending().await, (None, None));
+    assert_eq!(
+        *test_app.ssh.applied.lock().unwrap(),
+        vec![("testuser".to_string(), "+".to_string(), 900)]
+    );
+}
+
+#[actix_web::test]
+async fn test_modify_time_applied_when_online() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    test_app.ssh.set_online(true);
+
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "operation": "-",
+            "seconds": 300
+        }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["pending"], false);
+
+    let stored: Option<i64> =
+        sqlx::query_scalar("SELECT pending_time_adjustment FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(stored, None);
+    assert_eq!(test_app.ssh.applied.lock().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn test_daily_grant_cap() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    test_app.ssh.set_online(true);
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/grant-cap")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "max_daily_grant_seconds": 3600 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let modify = |operation: &str, seconds: i64| {
+        test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": operation,
+                "seconds": seconds
+            }))
+            .to_request()
+    };
+
+    assert_eq!(
+        test::call_service(&app, modify("+", 2400)).await.status(),
+        StatusCode::OK
+    );
+
+    // Over the cap: rejected with what is left
+    let resp = test::call_service(&app, modify("+", 1800)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("1200s can still be granted today"));
+
+    assert_eq!(
+        test::call_service(&app, modify("+", 1200)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        test::call_service(&app, modify("+", 1)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Taking time away is never capped
+    assert_eq!(
+        test::call_service(&app, modify("-", 600)).await.status(),
+        StatusCode::OK
+    );
+
+    // Grants from previous days don't count
+    sqlx::query("UPDATE user_daily_grant SET date = date(date, '-1 day')")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        test::call_service(&app, modify("+", 3600)).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[actix_web::test]
+async fn test_daily_grant_cap_counts_only_the_queued_adjustment() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let scheduler = test_app.scheduler();
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/grant-cap")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "max_daily_grant_seconds": 3600 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let modify = |operation: &str, seconds: i64| {
+        test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": operation,
+                "seconds": seconds
+            }))
+            .to_request()
+    };
+    let granted = || async {
+        sqlx::query_scalar::<_, i64>("SELECT COALESCE(SUM(seconds), 0) FROM user_daily_grant")
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap()
+    };
+
+    // Queued again, the second adjustment replaces the first and only it counts
+    assert_eq!(
+        test::call_service(&app, modify("+", 2400)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    assert_eq!(
+        test::call_service(&app, modify("+", 3000)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    assert_eq!(granted().await, 3000);
+    let resp = test::call_service(&app, modify("+", 3601)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("3600s can still be granted today"));
+
+    // Replaced by taking time away, nothing is granted
+    assert_eq!(
+        test::call_service(&app, modify("-", 600)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    assert_eq!(granted().await, 0);
+
+    // An adjustment applied right away supersedes the queued one as well
+    assert_eq!(
+        test::call_service(&app, modify("+", 1800)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    test_app.ssh.set_online(true);
+    assert_eq!(
+        test::call_service(&app, modify("+", 1200)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(granted().await, 1200);
+    scheduler.run_once().await;
+    assert_eq!(
+        *test_app.ssh.applied.lock().unwrap(),
+        vec![("testuser".to_string(), "+".to_string(), 1200)]
+    );
+    assert_eq!(
+        test::call_service(&app, modify("+", 2400)).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        test::call_service(&app, modify("+", 1)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn test_daily_grant_cap_counts_absolute_sets() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/grant-cap")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "max_daily_grant_seconds": 3600 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let set_limit = |seconds: i64| {
+        test::TestRequest::post()
+            .uri("/api/set-time-limit")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "user_id": user_id, "seconds": seconds }))
+            .to_request()
+    };
+
+    // Time left unknown: the whole value counts
+    assert_eq!(
+        test::call_service(&app, set_limit(86400)).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    // Only what is added on top of the time left counts
+    let config = TimekprConfig::parse("testuser", "ACTUAL_TIME_LEFT_DAY: 3000").to_json();
+    sqlx::query("UPDATE managed_users SET last_config = ? WHERE id = ?")
+        .bind(config)
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        test::call_service(&app, set_limit(1000)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    assert_eq!(
+        test::call_service(&app, set_limit(5400)).await.status(),
+        StatusCode::ACCEPTED
+    );
+    // Each set replaces the queued one, so its 2400s are given back before checking
+    let resp = test::call_service(&app, set_limit(6700)).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("3600s can still be granted today"));
+    assert_eq!(
+        test::call_service(&app, set_limit(6600)).await.status(),
+        StatusCode::ACCEPTED
+    );
+}
+
+#[actix_web::test]
+async fn test_snapshot_usage() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let snapshot = || {
+        test::TestRequest::post()
+            .uri(&format!("/api/user/{}/snapshot-usage", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let stored = || async {
+        sqlx::query_as::<_, (i64,)>("SELECT time_spent FROM user_time_usage WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&test_app.pool)
+            .await
+            .unwrap()
+    };
+
+    // Offline: nothing to store
+    let body: serde_json::Value = test::call_and_read_body_json(&app, snapshot()).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["status"], "offline");
+    assert!(body["time_spent"].is_null());
+    assert!(stored().await.is_empty());
+
+    test_app.ssh.set_online(true);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, snapshot()).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["time_spent"], 1200);
+    assert_eq!(stored().await, vec![(1200,)]);
+
+    let req = test::TestRequest::post()
+        .uri("/api/user/99999/snapshot-usage")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn test_intraday_usage_samples() {
+    use std::sync::Arc;
+    use timekpr_ui_rust::repositories::usage_repository::SqliteUsageRepository;
+    use timekpr_ui_rust::services::usage_service::UsageService;
+
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let intraday = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage/intraday{}", user_id, query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let repository = Arc::new(SqliteUsageRepository::new(test_app.pool.clone()));
+
+    // Sampling is off by default
+    let disabled = UsageService::new(repository.clone()).with_sample_retention(None);
+    disabled.record_sample(user_id, 600).await.unwrap();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, intraday("")).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["samples"].as_array().unwrap().len(), 0);
+
+    let enabled = UsageService::new(repository).with_sample_retention(Some(7));
+    enabled.record_sample(user_id, 600).await.unwrap();
+    enabled.record_sample(user_id, 1200).await.unwrap();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, intraday("")).await;
+    let samples = body["samples"].as_array().unwrap();
+    assert_eq!(samples.len(), 2);
+    assert_eq!(samples[0]["time_spent"], 600);
+    assert_eq!(samples[1]["time_spent"], 1200);
+    assert_eq!(samples[0]["host_id"], user_id);
+
+    // Another day has no samples
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, intraday("?date=2000-01-01")).await;
+    assert_eq!(body["date"], "2000-01-01");
+    assert_eq!(body["samples"].as_array().unwrap().len(), 0);
+
+    // Samples past the retention period are pruned
+    sqlx::query("UPDATE user_usage_sample SET sampled_at = datetime('now', '-8 days') WHERE time_spent = 600")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(enabled.prune_samples().await.unwrap(), 1);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, intraday("")).await;
+    assert_eq!(body["samples"].as_array().unwrap().len(), 1);
+
+    assert_eq!(
+        test::call_service(&app, intraday("?date=yesterday"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn test_adjustment_preview() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let preview = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!(
+                "/api/user/{}/adjustment-preview{}",
+                user_id, query
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    // Nothing known about the host yet
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, preview("?operation=%2B&seconds=1800")).await;
+    assert_eq!(body["success"], true);
+    assert!(body["current_time_left"].is_null());
+    assert!(body["projected_time_left"].is_null());
+
+    // A scheduler pass stores the host's config with an hour left
+    sqlx::query("UPDATE managed_users SET is_valid = 1")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    test_app.ssh.set_online(false);
+
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, preview("?operation=%2B&seconds=1800")).await;
+    assert_eq!(body["estimate"], true);
+    assert_eq!(body["current_time_left"], 3600);
+    assert_eq!(body["projected_time_left"], 5400);
+    assert_eq!(body["projected_formatted"], "1h 30m");
+    assert!(body["as_of"].is_string());
+
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, preview("?operation=-&seconds=7200")).await;
+    assert_eq!(body["projected_time_left"], 0);
+
+    // Previewing never queues anything
+    let pending: (Option<i64>,) =
+        sqlx::query_as("SELECT pending_time_adjustment FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(pending, (None,));
+
+    assert_eq!(
+        test::call_service(&app, preview("?operation=*&seconds=60"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        test::call_service(&app, preview("?operation=%2B&seconds=0"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+}
+
+#[actix_web::test]
+async fn test_usage_before_reset_hour_counts_towards_previous_day() {
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use std::sync::Arc;
+    use timekpr_ui_rust::models::usage_day;
+    use timekpr_ui_rust::repositories::usage_repository::SqliteUsageRepository;
+    use timekpr_ui_rust::services::usage_service::UsageService;
+
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+    let at = |d, h, m| day(d).and_hms_opt(h, m, 0).unwrap();
+
+    // Midnight reset by default
+    assert_eq!(usage_day(at(5, 0, 30), 0), day(5));
+    // With the day starting at 02:00, 00:30 still belongs to the day before
+    assert_eq!(usage_day(at(5, 0, 30), 2), day(4));
+    assert_eq!(usage_day(at(5, 2, 0), 2), day(5));
+    assert_eq!(
+        usage_day(at(1, 1, 59), 2),
+        NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+    );
+
+    let test_app = TestApp::new().await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let usage = UsageService::new(Arc::new(SqliteUsageRepository::new(test_app.pool.clone())))
+        .with_reset_hour(2);
+
+    let sampled_at = Utc.from_utc_datetime(&at(5, 0, 30));
+    usage
+        .store_daily_usage_at(user_id, sampled_at, 5400)
+        .await
+        .unwrap();
+
+    let (date,): (String,) = sqlx::query_as("SELECT date FROM user_time_usage WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(date, "2024-03-04");
+}
+
+#[actix_web::test]
+async fn test_usage_range_ends_on_the_given_day() {
+    use chrono::NaiveDate;
+    use timekpr_ui_rust::repositories::usage_repository::{SqliteUsageRepository, UsageRepository};
+
+    let test_app = TestApp::new().await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let repository = SqliteUsageRepository::new(test_app.pool.clone());
+
+    let day = |d| NaiveDate::from_ymd_opt(2024, 3, d).unwrap();
+    for d in 1..=10 {
+        repository
+            .store_daily_usage(user_id, day(d), d as i64 * 60)
+            .await
+            .unwrap();
+    }
+
+    // The range follows the caller's local day, not the database clock
+    let usage = repository.get_usage_data(user_id, day(8), 3).await.unwrap();
+    assert_eq!(
+        usage,
+        vec![(day(5), 300), (day(6), 360), (day(7), 420), (day(8), 480)]
+    );
+}
+
+#[actix_web::test]
+async fn test_modify_time_and_scheduler_do_not_double_apply() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let modify = |seconds: i64| {
+        test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": "+",
+                "seconds": seconds
+            }))
+            .to_request()
+    };
+
+    // Queued while offline
+    let body: serde_json::Value = test::call_and_read_body_json(&app, modify(900)).await;
+    assert_eq!(body["pending"], true);
+
+    // The host is back; a direct modification is in flight when the scheduler pass starts.
+    // The direct one clears the queue, so the pass must not apply the stale adjustment.
+    test_app.ssh.set_online(true);
+    test_app
+        .ssh
+        .set_adjust_delay(std::time::Duration::from_millis(300));
+    let scheduler = test_app.scheduler();
+    let (resp, _) = tokio::join!(test::call_service(&app, modify(300)), async {
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        scheduler.run_once().await;
+    });
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    assert_eq!(
+        *test_app.ssh.applied.lock().unwrap(),
+        vec![("testuser".to_string(), "+".to_string(), 300)]
+    );
+    let (pending,): (Option<i64>,) =
+        sqlx::query_as("SELECT pending_time_adjustment FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(pending, None);
+}
+
+#[actix_web::test]
+async fn test_revalidate_after_modify_setting() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    test_app.ssh.set_online(true);
+
+    let modify = || {
+        test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": "+",
+                "seconds": 600
+            }))
+            .to_request()
+    };
+    let has_config = || async {
+        sqlx::query_scalar::<_, bool>("SELECT last_config_gz IS NOT NULL OR last_config IS NOT NULL FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap()
+    };
+
+    // On by default: the host's config is read again after the adjustment
+    assert_eq!(
+        test::call_service(&app, modify()).await.status(),
+        StatusCode::OK
+    );
+    assert!(has_config().await);
+
+    let req = test::TestRequest::post()
+        .uri("/api/settings/revalidate-after-modify")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "enabled": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    // Off: the adjustment is applied, the config is left for the scheduler to refresh.
+    // An adjustment queued earlier is superseded all the same.
+    sqlx::query("UPDATE managed_users SET last_config = NULL, last_config_gz = NULL, last_checked = NULL, pending_time_adjustment = 1800, pending_time_operation = '+', pending_time_reason = 'homework' WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(
+        test::call_service(&app, modify()).await.status(),
+        StatusCode::OK
+    );
+    assert_eq!(test_app.ssh.applied.lock().unwrap().len(), 2);
+    assert!(!has_config().await);
+    let last_checked: Option<String> =
+        sqlx::query_scalar("SELECT last_checked FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert!(last_checked.is_some());
+    let pending: (Option<i64>, Option<String>, Option<String>) = sqlx::query_as(
+        "SELECT pending_time_adjustment, pending_time_operation, pending_time_reason FROM managed_users WHERE id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(&test_app.pool)
+    .await
+    .unwrap();
+    assert_eq!(pending, (None, None, None));
+
+    // Nothing left for the scheduler to apply on top
+    test_app.scheduler().run_once().await;
+    assert_eq!(test_app.ssh.applied.lock().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn test_set_time_limit_rejects_invalid_seconds() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    for (seconds, expected) in [
+        (0, "must be positive"),
+        (-3600, "must be positive"),
+        (86_401, "at most 86400"),
+    ] {
+        let req = test::TestRequest::post()
+            .uri("/api/set-time-limit")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "user_id": user_id, "seconds": seconds }))
+            .to_request();
+
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["success"], false);
+        assert!(body["message"].as_str().unwrap().contains(expected));
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/api/set-time-limit")
+        .set_json(json!({ "user_id": user_id, "seconds": 7200 }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn test_set_time_limit_queued_until_host_returns() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    // Host offline: the absolute value is queued like an adjustment
+    let req = test::TestRequest::post()
+        .uri("/api/set-time-limit")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "user_id": user_id, "seconds": 7200 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["pending"], true);
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("Setting time left to 7200s"));
+
+    // Stored within the '+'/'-' CHECK, flagged as absolute
+    let pending: (Option<i64>, Option<String>, bool) = sqlx::query_as(
+        "SELECT pending_time_adjustment, pending_time_operation, pending_time_absolute FROM managed_users WHERE id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(&test_app.pool)
+    .await
+    .unwrap();
+    assert_eq!(pending, (Some(7200), Some("+".to_string()), true));
+
+    // The scheduler applies it with the absolute form once the host answers
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    assert_eq!(
+        *test_app.ssh.applied.lock().unwrap(),
+        vec![("testuser".to_string(), "=".to_string(), 7200)]
+    );
+
+    // Online: applied straight away
+    let req = test::TestRequest::post()
+        .uri("/api/set-time-limit")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "user_id": user_id, "seconds": 3600 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(test_app.ssh.applied.lock().unwrap().len(), 2);
+}
+
+#[actix_web::test]
+async fn test_modification_history() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    // Queued while the host is offline, then applied once it answers
+    let modify = |operation: &str, seconds: i64, reason: &str| {
+        test::TestRequest::post()
+            .uri("/api/modify-time")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "user_id": user_id,
+                "operation": operation,
+                "seconds": seconds,
+                "reason": reason
+            }))
+            .to_request()
+    };
+    assert_eq!(
+        test::call_service(&app, modify("+", 900, "homework done"))
+            .await
+            .status(),
+        StatusCode::ACCEPTED
+    );
+    test_app.ssh.set_online(true);
+    assert_eq!(
+        test::call_service(&app, modify("-", 300, "late for dinner"))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+
+    let history = |query: &str| {
+        test::TestRequest::get()
+            .uri(&format!(
+                "/api/user/{}/modification-history{}",
+                user_id, query
+            ))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+    let resp = test::call_service(&app, history("")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["username"], "testuser");
+    let entries = body["entries"].as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["operation"], "-");
+    assert_eq!(entries[0]["seconds"], 300);
+    assert_eq!(entries[0]["reason"], "late for dinner");
+    assert_eq!(entries[0]["applied"], true);
+    assert_eq!(entries[1]["operation"], "+");
+    assert_eq!(entries[1]["seconds"], 900);
+    assert_eq!(entries[1]["applied"], false);
+    assert!(entries[1]["recorded_at"].as_str().is_some());
+
+    let body: serde_json::Value =
+        test::read_body_json(test::call_service(&app, history("?limit=1")).await).await;
+    assert_eq!(body["entries"].as_array().unwrap().len(), 1);
+    assert_eq!(body["entries"][0]["operation"], "-");
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get()
+            .uri("/api/user/9999/modification-history")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
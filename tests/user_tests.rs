@@ -3,6 +3,9 @@ use serde_json::json;
 
 mod common;
 use common::TestApp;
+use timekpr_ui_rust::models::{ManagedUser, TimekprConfig, UserStatus};
+use timekpr_ui_rust::repositories::{decompress_config, SqliteUserRepository, UserRepository};
+use timekpr_ui_rust::ssh::{command_warning, SSHClient};
 
 #[actix_web::test]
 async fn test_add_user_success() {
@@ -28,6 +31,24 @@ async fn test_add_user_success() {
     assert_eq!(body["success"], true);
     assert!(body["message"].as_str().unwrap().contains("testuser"));
     // SSH validation will fail in test environment, but user creation should succeed
+
+    // The response carries the new id, which the other user endpoints take
+    let user_id = body["user_id"].as_i64().unwrap();
+    let stored: (String, String) =
+        sqlx::query_as("SELECT username, system_ip FROM managed_users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&test_app.pool)
+            .await
+            .unwrap();
+    assert_eq!(
+        stored,
+        ("testuser".to_string(), "192.168.1.100".to_string())
+    );
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
 }
 
 #[actix_web::test]
@@ -56,25 +77,51 @@ async fn test_add_user_invalid_ip() {
 
     let token = test_app.login_and_get_token().await;
 
-    let req = test::TestRequest::post()
-        .uri("/api/users/add")
-        .insert_header(("Authorization", format!("Bearer {}", token)))
-        .set_json(json!({
-            "username": "testuser",
-            "system_ip": "invalid_ip"
-        }))
-        .to_request();
+    let add = |system_ip: &str| {
+        test::TestRequest::post()
+            .uri("/api/users/add")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({
+                "username": "testuser",
+                "system_ip": system_ip
+            }))
+            .to_request()
+    };
 
-    let resp = test::call_service(&app, req).await;
-    assert_eq!(resp.status(), StatusCode::OK);
+    // Malformed addresses are rejected before any SSH attempt
+    for system_ip in [
+        "invalid_ip",
+        "300.1.1.1",
+        "192.168.1",
+        "-laptop.lan",
+        "kids laptop",
+        "fe80::1::2",
+    ] {
+        let resp = test::call_service(&app, add(system_ip)).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", system_ip);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["success"], false);
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("not a valid IP address or hostname"));
+    }
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM managed_users")
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
 
-    let body: serde_json::Value = test::read_body_json(resp).await;
-    assert_eq!(body["success"], true);
-    // Invalid IP will cause SSH validation to fail but user will still be created
-    assert!(body["message"]
-        .as_str()
-        .unwrap()
-        .contains("validation failed"));
+    // IPv6 literals and DNS names are accepted; SSH validation still fails in tests
+    for system_ip in ["::1", "fd00::1a2b", "kids-laptop.lan", "desktop"] {
+        let resp = test::call_service(&app, add(system_ip)).await;
+        assert_eq!(resp.status(), StatusCode::OK, "{}", system_ip);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert!(body["message"]
+            .as_str()
+            .unwrap()
+            .contains("validation failed"));
+    }
 }
 
 #[actix_web::test]
@@ -136,6 +183,47 @@ async fn test_remove_user_success() {
     assert!(body["message"].as_str().unwrap().contains("deleted"));
 }
 
+#[actix_web::test]
+async fn test_remove_user_with_pending_adjustment() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    // The host is offline, so the adjustment is queued
+    let req = test::TestRequest::post()
+        .uri("/api/modify-time")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({"user_id": user_id, "operation": "+", "seconds": 3600}))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::ACCEPTED
+    );
+
+    let delete = |query: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/users/delete/{}{}", user_id, query))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, delete("?on_pending=block")).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"].as_str().unwrap().contains("+60 minutes"));
+
+    // The default deletes anyway and reports what was dropped
+    let resp = test::call_service(&app, delete("")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["discarded"][0],
+        "+60 minutes for testuser on 192.168.1.100"
+    );
+}
+
 #[actix_web::test]
 async fn test_remove_nonexistent_user() {
     let test_app = TestApp::new().await;
@@ -181,3 +269,1038 @@ async fn test_user_operations_without_auth() {
     let resp = test::call_service(&app, req).await;
     assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
 }
+
+#[actix_web::test]
+async fn test_remove_user_non_numeric_id() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/delete/abc")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["success"], false);
+    assert_eq!(body["code"], "INVALID_PATH_PARAM");
+    assert_eq!(body["message"], "id must be an integer");
+}
+
+#[actix_web::test]
+async fn test_last_config_round_trips_through_compression() {
+    let test_app = TestApp::new().await;
+    let repository = SqliteUserRepository::with_config_compression(test_app.pool.clone(), true);
+
+    let config =
+        r#"{"TIME_LEFT_DAY":3600,"TIME_SPENT_DAY":1800,"LIMITS_PER_WEEKDAYS":[1,2,3,4,5,6,7]}"#;
+    let user = ManagedUser {
+        id: 0,
+        username: "compressed".to_string(),
+        system_ip: "192.168.1.50".to_string(),
+        is_valid: true,
+        date_added: None,
+        last_checked: None,
+        last_config: Some(config.to_string()),
+        pending_time_adjustment: None,
+        pending_time_operation: None,
+        pending_time_reason: None,
+        last_status: None,
+        consecutive_failures: 0,
+        host_group_id: None,
+        timekpr_path: None,
+        validate_command_template: None,
+        modify_command_template: None,
+    };
+    repository.save(&user).await.unwrap();
+
+    // Stored only as gzip bytes
+    let (plain, compressed): (Option<String>, Option<Vec<u8>>) = sqlx::query_as(
+        "SELECT last_config, last_config_gz FROM managed_users WHERE username = 'compressed'",
+    )
+    .fetch_one(&test_app.pool)
+    .await
+    .unwrap();
+    assert!(plain.is_none());
+    assert_eq!(
+        decompress_config(&compressed.unwrap()).as_deref(),
+        Some(config)
+    );
+
+    // Read back transparently, also through a repository with compression disabled
+    let users = repository.find_all().await.unwrap();
+    assert_eq!(users[0].last_config.as_deref(), Some(config));
+
+    let plain_repository =
+        SqliteUserRepository::with_config_compression(test_app.pool.clone(), false);
+    let users = plain_repository.find_all().await.unwrap();
+    assert_eq!(users[0].last_config.as_deref(), Some(config));
+}
+
+#[actix_web::test]
+async fn test_timekpr_config_parses_userinfo_output() {
+    let output =
+        "ALLOWED_HOURS_1: 0;1;2\nACTUAL_TIME_LEFT_DAY: 5400\nACTUAL_TIME_SPENT_DAY: 1234\n";
+    let config = TimekprConfig::parse("kid", output);
+
+    assert_eq!(config.username, "kid");
+    assert_eq!(config.time_left_day, Some(5400));
+    assert_eq!(config.time_spent_day, Some(1234));
+
+    // Stored JSON keeps the keys used by earlier versions
+    let json: serde_json::Value = serde_json::from_str(&config.to_json()).unwrap();
+    assert_eq!(json["TIME_LEFT_DAY"], 5400);
+    assert_eq!(json["TIME_SPENT_DAY"], 1234);
+
+    let restored: TimekprConfig = config.to_json().parse().unwrap();
+    assert_eq!(restored, config);
+
+    // Configs stored without time fields still load
+    let partial: TimekprConfig = r#"{"USERNAME":"kid"}"#.parse().unwrap();
+    assert_eq!(partial.time_left_day, None);
+}
+
+#[actix_web::test]
+async fn test_timekpr_config_parses_weekly_and_monthly_fields() {
+    // As printed by `timekpra --userinfo` on timekpr-nExT 0.5
+    let output = "# timekpr-nExT user info
+ALLOWED_HOURS_1: 0;1;2;3;4;5;6;7;8;9;10;11;12;13;14;15;16;17;18;19;20;21;22;23
+ALLOWED_WEEKDAYS: 1;2;3;4;5;6;7
+LIMITS_PER_WEEKDAYS: 7200;7200;7200;7200;7200;10800;10800
+LIMIT_PER_WEEK: 36000
+LIMIT_PER_MONTH: 144000
+TIME_SPENT_BALANCE: -300
+TIME_SPENT_DAY: 1500
+TIME_SPENT_WEEK: 9000
+TIME_SPENT_MONTH: 30000
+TIME_LEFT_WEEK: 27000
+TIME_LEFT_MONTH: 114000
+ACTUAL_TIME_SPENT_SESSION: 600
+ACTUAL_TIME_LEFT_DAY: 5400
+ACTUAL_TIME_SPENT_DAY: 1800
+ACTUAL_TIME_SPENT_WEEK: 9300
+ACTUAL_TIME_SPENT_MONTH: 30300
+";
+    let config = TimekprConfig::parse("kid", output);
+
+    assert_eq!(config.time_left_day, Some(5400));
+    // Current values win over the ones timekpr saved last; the saved time left is reduced
+    // by what was spent since, so left and spent still add up to the limit
+    assert_eq!(config.time_spent_week, Some(9300));
+    assert_eq!(config.time_spent_month, Some(30300));
+    assert_eq!(config.time_left_week, Some(26700));
+    assert_eq!(config.time_left_month, Some(113700));
+    assert_eq!(config.time_limit_week, Some(36000));
+    assert_eq!(config.time_limit_month, Some(144000));
+
+    let restored: TimekprConfig = config.to_json().parse().unwrap();
+    assert_eq!(restored.time_limit_week, Some(36000));
+
+    // Both current values are taken together
+    let config = TimekprConfig::parse(
+        "kid",
+        "TIME_SPENT_WEEK: 9000\nTIME_LEFT_WEEK: 27000\nACTUAL_TIME_SPENT_WEEK: 9300\nACTUAL_TIME_LEFT_WEEK: 26500\n",
+    );
+    assert_eq!(config.time_spent_week, Some(9300));
+    assert_eq!(config.time_left_week, Some(26500));
+
+    // Hosts that don't report them leave the fields empty
+    let config = TimekprConfig::parse("kid", "ACTUAL_TIME_LEFT_DAY: 5400\n");
+    assert_eq!(config.time_left_week, None);
+    assert_eq!(config.time_spent_week, None);
+    assert_eq!(config.time_limit_week, None);
+    assert_eq!(config.time_left_month, None);
+    assert_eq!(config.time_spent_month, None);
+    assert_eq!(config.time_limit_month, None);
+}
+
+#[actix_web::test]
+async fn test_validation_failures_are_classified() {
+    assert_eq!(
+        SSHClient::classify_failure(Some(127), "bash: timekpra: command not found"),
+        UserStatus::TimekprMissing
+    );
+    assert_eq!(
+        SSHClient::classify_failure(
+            Some(127),
+            "bash: /opt/timekpr/timekpra: No such file or directory"
+        ),
+        UserStatus::TimekprMissing
+    );
+    assert_eq!(
+        SSHClient::classify_failure(
+            Some(255),
+            "ssh: connect to host 10.0.0.9 port 22: Connection refused"
+        ),
+        UserStatus::Offline
+    );
+    assert_eq!(
+        SSHClient::classify_failure(Some(1), "User \"nobody\" is not configured"),
+        UserStatus::InvalidUser
+    );
+    assert_eq!(UserStatus::TimekprMissing.label(), "timekpra not found");
+}
+
+#[actix_web::test]
+async fn test_stuck_command_is_killed_at_the_command_timeout() {
+    use std::time::{Duration, Instant};
+    use timekpr_ui_rust::ssh::{output_within, SshCommandError};
+    use tokio::process::Command;
+
+    let mut quick = Command::new("sh");
+    quick.args(["-c", "echo done"]);
+    let output = output_within(quick, Duration::from_secs(5)).await.unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout).trim(), "done");
+
+    let started = Instant::now();
+    let mut stuck = Command::new("sleep");
+    stuck.arg("30");
+    let error = output_within(stuck, Duration::from_millis(200))
+        .await
+        .unwrap_err();
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(matches!(error, SshCommandError::CommandTimeout(_)));
+    assert!(error.to_string().starts_with("command_timeout"));
+
+    // Reported apart from an unreachable host
+    assert_eq!(UserStatus::CommandTimeout.as_str(), "command_timeout");
+    assert_eq!(
+        "command_timeout".parse::<UserStatus>(),
+        Ok(UserStatus::CommandTimeout)
+    );
+}
+
+#[actix_web::test]
+async fn test_timekpr_path_override() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/user/{}/timekpr-path", user_id))
+        .set_json(json!({ "timekpr_path": "/usr/local/bin/timekpra" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Ends up in a remote shell command, so only path characters are allowed
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/user/{}/timekpr-path", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "timekpr_path": "timekpra; reboot" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let req = test::TestRequest::put()
+        .uri("/api/user/9999/timekpr-path")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "timekpr_path": "/usr/local/bin/timekpra" }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/user/{}/timekpr-path", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "timekpr_path": " /usr/local/bin/timekpra " }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["user"]["timekpr_path"], "/usr/local/bin/timekpra");
+
+    // Commands for the user run the configured binary
+    let user = SqliteUserRepository::new(test_app.pool.clone())
+        .find_by_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let client = SSHClient::for_user(&user).with_login_shell(false);
+    assert_eq!(
+        client.remote_command("timekpra --userinfo testuser"),
+        "/usr/local/bin/timekpra --userinfo testuser"
+    );
+    // A login shell gets the whole command as one quoted argument
+    assert_eq!(
+        client
+            .with_login_shell(true)
+            .remote_command("timekpra --setalloweddays testuser '1;2'"),
+        "bash -lc '/usr/local/bin/timekpra --setalloweddays testuser '\\''1;2'\\'''"
+    );
+
+    // An empty path goes back to plain timekpra
+    let req = test::TestRequest::put()
+        .uri(&format!("/api/user/{}/timekpr-path", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "timekpr_path": "" }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(body["timekpr_path"], serde_json::Value::Null);
+    assert_eq!(body["message"], "Commands for testuser will run timekpra");
+}
+
+#[actix_web::test]
+async fn test_command_templates() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let set_templates = |templates: serde_json::Value| {
+        test::TestRequest::put()
+            .uri(&format!("/api/user/{}/command-templates", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(templates)
+            .to_request()
+    };
+
+    // Every placeholder is required, and only known ones are accepted
+    let resp = test::call_service(
+        &app,
+        set_templates(json!({ "modify_command_template": "tk-adjust {username} {op}" })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"].as_str().unwrap().contains("{seconds}"));
+
+    let resp = test::call_service(
+        &app,
+        set_templates(json!({ "validate_command_template": "tk-info {user}" })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let resp = test::call_service(
+        &app,
+        set_templates(json!({
+            "validate_command_template": " sudo tk-info --user {username} ",
+            "modify_command_template": "sudo tk-adjust {username} {op}{seconds}"
+        })),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    assert_eq!(
+        body["user"]["validate_command_template"],
+        "sudo tk-info --user {username}"
+    );
+
+    let user = SqliteUserRepository::new(test_app.pool.clone())
+        .find_by_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let client = SSHClient::for_user(&user).with_login_shell(false);
+    assert_eq!(
+        client.validate_command("testuser"),
+        "sudo tk-info --user testuser"
+    );
+    assert_eq!(
+        client.modify_command("testuser", "+", 600),
+        "sudo tk-adjust testuser +600"
+    );
+
+    // Null goes back to the built-in commands
+    let resp = test::call_service(&app, set_templates(json!({}))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let user = SqliteUserRepository::new(test_app.pool.clone())
+        .find_by_id(user_id)
+        .await
+        .unwrap()
+        .unwrap();
+    let client = SSHClient::for_user(&user).with_login_shell(false);
+    assert_eq!(
+        client.validate_command("testuser"),
+        "timekpra --userinfo testuser"
+    );
+    assert_eq!(
+        client.modify_command("testuser", "-", 300),
+        "timekpra --settimeleft testuser - 300"
+    );
+}
+
+#[actix_web::test]
+async fn test_admin_view_shows_validation_status() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::get()
+        .uri("/api/admin")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // No SSH key in the test environment, so the host counts as unreachable
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["users"][0]["status"], "offline");
+    assert_eq!(body["users"][0]["status_label"], "Offline");
+}
+
+#[actix_web::test]
+async fn test_attach_host_aggregates_usage() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/hosts", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "system_ip": "192.168.1.101" }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let host_id: i64 = sqlx::query_scalar(
+        "SELECT id FROM managed_users WHERE system_ip = '192.168.1.101' AND host_group_id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(&test_app.pool)
+    .await
+    .unwrap();
+
+    // Attaching the same endpoint twice is rejected
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/hosts", host_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "system_ip": "192.168.1.101" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    for (id, seconds) in [(user_id, 1800), (host_id, 900)] {
+        sqlx::query(
+            "INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, date('now'), ?)",
+        )
+        .bind(id)
+        .bind(seconds)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    }
+
+    // Usage is summed per day across the hosts, whichever member is asked
+    for id in [user_id, host_id] {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/user/{}/usage?days=1&unit=seconds", id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+
+        let body: serde_json::Value =
+            test::read_body_json(test::call_service(&app, req).await).await;
+        assert_eq!(body["data"].as_array().unwrap().len(), 1);
+        assert_eq!(body["data"][0]["value"], 2700.0);
+    }
+
+    // Deleting the primary removes its additional hosts
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/users/delete/{}", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM managed_users")
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, 0);
+}
+
+#[actix_web::test]
+async fn test_bulk_delete_users() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/bulk-delete")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "user_ids": [user_id, 9999, user_id] }))
+        .to_request();
+
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["deleted"], 1);
+    assert_eq!(body["not_found"], 1);
+    assert_eq!(body["results"].as_array().unwrap().len(), 2);
+    assert_eq!(body["results"][0]["user_id"], user_id);
+    assert_eq!(body["results"][0]["status"], "deleted");
+    assert_eq!(body["results"][1]["status"], "not_found");
+
+    // Empty requests and unauthenticated callers are rejected
+    let req = test::TestRequest::post()
+        .uri("/api/users/bulk-delete")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "user_ids": [] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::BAD_REQUEST
+    );
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/bulk-delete")
+        .set_json(json!({ "user_ids": [1] }))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn test_add_user_without_validation() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "username": "quickadd",
+            "system_ip": "192.168.1.120",
+            "validate": false
+        }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("next background check"));
+    assert!(body["user_id"].as_i64().is_some());
+    assert!(body["validation"].is_null());
+
+    let state = || async {
+        sqlx::query_as::<_, (bool, Option<String>)>(
+            "SELECT is_valid, last_status FROM managed_users WHERE username = 'quickadd'",
+        )
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap()
+    };
+    assert_eq!(state().await, (false, None));
+
+    // The next scheduler pass validates it
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    assert_eq!(state().await, (true, Some("ok".to_string())));
+}
+
+#[actix_web::test]
+async fn test_offline_only_after_consecutive_failures() {
+    use std::sync::Arc;
+    use timekpr_ui_rust::services::UserService;
+
+    let test_app = TestApp::new().await;
+    let repository = Arc::new(SqliteUserRepository::new(test_app.pool.clone()));
+    let service = UserService::new(repository.clone()).with_offline_after_failures(3);
+
+    let user = ManagedUser {
+        id: 0,
+        username: "flaky".to_string(),
+        system_ip: "192.168.1.60".to_string(),
+        is_valid: true,
+        date_added: None,
+        last_checked: None,
+        last_config: None,
+        pending_time_adjustment: None,
+        pending_time_operation: None,
+        pending_time_reason: None,
+        last_status: Some(UserStatus::Ok.as_str().to_string()),
+        consecutive_failures: 0,
+        host_group_id: None,
+        timekpr_path: None,
+        validate_command_template: None,
+        modify_command_template: None,
+    };
+    repository.save(&user).await.unwrap();
+    let user_id = repository.find_all().await.unwrap()[0].id;
+
+    let admin_row = || async {
+        service
+            .get_admin_users()
+            .await
+            .unwrap()
+            .into_iter()
+            .find(|user| user.id == user_id)
+            .unwrap()
+    };
+
+    // Two dropped checks stay within the grace period
+    for failures in 1..=2 {
+        service
+            .update_last_checked(user_id, UserStatus::Offline)
+            .await
+            .unwrap();
+        let row = admin_row().await;
+        assert_eq!(row.status, Some(UserStatus::Ok));
+        assert_eq!(row.consecutive_failures, failures);
+    }
+
+    service
+        .update_last_checked(user_id, UserStatus::Offline)
+        .await
+        .unwrap();
+    let row = admin_row().await;
+    assert_eq!(row.status, Some(UserStatus::Offline));
+    assert_eq!(row.consecutive_failures, 3);
+
+    // One successful check resets the counter
+    service.update_background_data(user_id, None).await.unwrap();
+    let row = admin_row().await;
+    assert_eq!(row.status, Some(UserStatus::Ok));
+    assert_eq!(row.consecutive_failures, 0);
+}
+
+#[actix_web::test]
+async fn test_validation_history() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "username": "flaky",
+            "system_ip": "192.168.1.121",
+            "validate": false
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let user_id: i64 = sqlx::query_scalar("SELECT id FROM managed_users WHERE username = 'flaky'")
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+
+    // One pass while the host answers, one while it doesn't
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    test_app.ssh.set_online(false);
+    test_app.scheduler().run_once().await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/validation-history", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["username"], "flaky");
+    let results = body["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    // Newest first
+    assert_eq!(results[0]["status"], "offline");
+    assert_eq!(results[0]["reachable"], false);
+    assert_eq!(results[0]["user_missing"], false);
+    assert!(results[0]["error"].is_string());
+    assert_eq!(results[1]["status"], "ok");
+    assert_eq!(results[1]["reachable"], true);
+    assert!(results[1]["error"].is_null());
+
+    // Only the newest results are kept per host
+    let repository = SqliteUserRepository::new(test_app.pool.clone());
+    for _ in 0..3 {
+        repository
+            .store_validation_result(
+                user_id,
+                chrono::Utc::now(),
+                "invalid_user",
+                Some("No such user"),
+                2,
+            )
+            .await
+            .unwrap();
+    }
+    let kept = repository
+        .get_validation_results(user_id, chrono::Utc::now() - chrono::Duration::hours(1))
+        .await
+        .unwrap();
+    assert_eq!(kept.len(), 2);
+    assert!(kept.iter().all(|(_, status, _)| status == "invalid_user"));
+
+    let req = test::TestRequest::get()
+        .uri("/api/user/9999/validation-history")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::NOT_FOUND
+    );
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/api/user/{}/validation-history", user_id))
+        .to_request();
+    assert_eq!(
+        test::call_service(&app, req).await.status(),
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[actix_web::test]
+async fn test_add_user_with_unreachable_host() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let add = |username: &str, on_unreachable: Option<&str>| {
+        let mut form = json!({ "username": username, "system_ip": "192.168.1.122" });
+        if let Some(on_unreachable) = on_unreachable {
+            form["on_unreachable"] = json!(on_unreachable);
+        }
+        test::TestRequest::post()
+            .uri("/api/users/add")
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(form)
+            .to_request()
+    };
+    let state = |username: &'static str| {
+        let pool = test_app.pool.clone();
+        async move {
+            sqlx::query_as::<_, (bool, bool)>(
+                "SELECT is_valid, last_checked IS NOT NULL FROM managed_users WHERE username = ?",
+            )
+            .bind(username)
+            .fetch_optional(&pool)
+            .await
+            .unwrap()
+        }
+    };
+
+    // Rejected: nothing is saved
+    let resp = test::call_service(&app, add("rejected", Some("reject"))).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"].as_str().unwrap().contains("unreachable"));
+    assert_eq!(state("rejected").await, None);
+
+    // Pending: saved unchecked, so the scheduler validates it later
+    let resp = test::call_service(&app, add("pending", Some("pending"))).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["validation"]["status"], "offline");
+    assert_eq!(body["validation"]["reachable"], false);
+    assert_eq!(body["validation"]["user_missing"], false);
+    assert_eq!(state("pending").await, Some((false, false)));
+
+    // Still offline on the first pass: checked, and tried again on a later one
+    test_app.scheduler().run_once().await;
+    assert_eq!(state("pending").await, Some((false, true)));
+
+    // Back online by the next tick
+    test_app.ssh.set_online(true);
+    sqlx::query("UPDATE managed_users SET last_checked = datetime(last_checked, '-30 seconds') WHERE username = 'pending'")
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.scheduler().run_once().await;
+    assert_eq!(state("pending").await, Some((true, true)));
+
+    // Default: saved as checked and not valid, with the reason reported
+    let resp = test::call_service(&app, add("invalid", None)).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["validation"]["reachable"], false);
+    assert_eq!(state("invalid").await, Some((false, true)));
+}
+
+#[actix_web::test]
+async fn test_command_warning_is_recorded_without_failing() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "username": "noisy",
+            "system_ip": "192.168.1.122",
+            "validate": false
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let user_id: i64 = sqlx::query_scalar("SELECT id FROM managed_users WHERE username = 'noisy'")
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+
+    let detail = || async {
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/user/{}", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        test::read_body_json::<serde_json::Value, _>(resp).await
+    };
+    assert!(detail().await["command_warning"].is_null());
+
+    test_app.ssh.set_online(true);
+    test_app
+        .ssh
+        .set_warning(Some("DeprecationWarning: --userinfo will be removed"));
+    test_app.scheduler().run_once().await;
+
+    let body = detail().await;
+    assert_eq!(
+        body["command_warning"]["message"],
+        "DeprecationWarning: --userinfo will be removed"
+    );
+    assert!(body["command_warning"]["recorded_at"].is_string());
+    // The command still counts as a success
+    assert_eq!(body["user"]["is_valid"], true);
+
+    // Only the host-key notice ssh prints itself is not a warning
+    assert_eq!(
+        command_warning(
+            "Warning: Permanently added '10.0.0.1' (ED25519) to the list of known hosts.\n"
+        ),
+        None
+    );
+    assert_eq!(
+        command_warning("Warning: Permanently added '10.0.0.1'\nnotice: config moved\n").as_deref(),
+        Some("notice: config moved")
+    );
+}
+
+#[actix_web::test]
+async fn test_update_user_keeps_usage() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    sqlx::query("INSERT INTO user_time_usage (user_id, date, time_spent) VALUES (?, date('now', '-1 day'), 5400)")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    sqlx::query("UPDATE managed_users SET last_checked = NULL WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    // Everything already pushed to the old host
+    let req = test::TestRequest::post()
+        .uri("/api/schedule/update")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({
+            "user_id": user_id,
+            "monday": 2.0,
+            "tuesday": 2.0,
+            "wednesday": 2.0,
+            "thursday": 2.0,
+            "friday": 2.0,
+            "saturday": 2.0,
+            "sunday": 2.0
+        }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    for query in [
+        "UPDATE user_weekly_schedule SET is_synced = 1 WHERE user_id = ?",
+        "INSERT INTO user_playtime (user_id, settings, is_synced) VALUES (?, '{}', 1)",
+        "INSERT INTO user_lockout (user_id, settings, is_synced) VALUES (?, '{}', 1)",
+    ] {
+        sqlx::query(query)
+            .bind(user_id)
+            .execute(&test_app.pool)
+            .await
+            .unwrap();
+    }
+    let synced = || async {
+        sqlx::query_as::<_, (bool, bool, bool)>(
+            "SELECT s.is_synced, p.is_synced, l.is_synced FROM user_weekly_schedule s
+             JOIN user_playtime p ON p.user_id = s.user_id
+             JOIN user_lockout l ON l.user_id = s.user_id
+             WHERE s.user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap()
+    };
+
+    let update = |id: i64, username: &str, system_ip: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/users/update/{}", id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "username": username, "system_ip": system_ip }))
+            .to_request()
+    };
+
+    // New DHCP lease and a renamed account
+    let resp = test::call_service(&app, update(user_id, "kid", " 192.168.1.150 ")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert!(body["message"]
+        .as_str()
+        .unwrap()
+        .contains("User kid updated"));
+
+    let row: (String, String, Option<String>, bool) = sqlx::query_as(
+        "SELECT username, system_ip, last_status, last_checked IS NOT NULL FROM managed_users WHERE id = ?",
+    )
+    .bind(user_id)
+    .fetch_one(&test_app.pool)
+    .await
+    .unwrap();
+    assert_eq!(row.0, "kid");
+    assert_eq!(row.1, "192.168.1.150");
+    // Validated again against the new address
+    assert!(row.2.is_some());
+    assert!(row.3);
+
+    let usage: i64 = sqlx::query_scalar("SELECT time_spent FROM user_time_usage WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_one(&test_app.pool)
+        .await
+        .unwrap();
+    assert_eq!(usage, 5400);
+
+    // Queued for the new host, and pushed there on the next pass
+    assert_eq!(synced().await, (false, false, false));
+    sqlx::query("UPDATE managed_users SET is_valid = 1 WHERE id = ?")
+        .bind(user_id)
+        .execute(&test_app.pool)
+        .await
+        .unwrap();
+    test_app.ssh.set_online(true);
+    test_app.scheduler().run_once().await;
+    let pushed = test_app.ssh.time_limits.lock().unwrap().clone();
+    assert_eq!(pushed.len(), 1);
+    assert_eq!(pushed[0].0, "kid");
+    assert!(synced().await.0);
+
+    // Saving the same username and address pushes nothing again
+    let resp = test::call_service(&app, update(user_id, "kid", "192.168.1.150")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(synced().await.0);
+
+    // Another user can't be moved onto the same username and address
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "username": "sibling", "system_ip": "192.168.1.151" }))
+        .to_request();
+    let body: serde_json::Value = test::read_body_json(test::call_service(&app, req).await).await;
+    let sibling_id = body["user_id"].as_i64().unwrap();
+    let resp = test::call_service(&app, update(sibling_id, "kid", "192.168.1.150")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    assert_eq!(
+        test::call_service(&app, update(user_id, "", "192.168.1.150"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        test::call_service(&app, update(9999, "kid", "192.168.1.160"))
+            .await
+            .status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+#[actix_web::test]
+async fn test_update_user_renames_host_group() {
+    let test_app = TestApp::new().await;
+    let app = test::init_service(test_app.create_app()).await;
+
+    let token = test_app.login_and_get_token().await;
+    let user_id = test_app.add_test_user(&token).await;
+    let req = test::TestRequest::post()
+        .uri(&format!("/api/user/{}/hosts", user_id))
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "system_ip": "192.168.1.101" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::post()
+        .uri("/api/users/add")
+        .insert_header(("Authorization", format!("Bearer {}", token)))
+        .set_json(json!({ "username": "other", "system_ip": "192.168.1.101" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let update = |username: &str, system_ip: &str| {
+        test::TestRequest::post()
+            .uri(&format!("/api/users/update/{}", user_id))
+            .insert_header(("Authorization", format!("Bearer {}", token)))
+            .set_json(json!({ "username": username, "system_ip": system_ip }))
+            .to_request()
+    };
+    let rows = || async {
+        sqlx::query_as::<_, (String, String)>(
+            "SELECT username, system_ip FROM managed_users WHERE id = ? OR host_group_id = ? ORDER BY id",
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(&test_app.pool)
+        .await
+        .unwrap()
+    };
+
+    // The attached host's row would clash with the other user's
+    assert_eq!(
+        test::call_service(&app, update("other", "192.168.1.100"))
+            .await
+            .status(),
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(rows().await[1].0, "testuser");
+
+    // The account is renamed on every host, and only the edited row moves
+    assert_eq!(
+        test::call_service(&app, update("kid", "192.168.1.150"))
+            .await
+            .status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        rows().await,
+        vec![
+            ("kid".to_string(), "192.168.1.150".to_string()),
+            ("kid".to_string(), "192.168.1.101".to_string()),
+        ]
+    );
+}